@@ -1,3 +1,4 @@
+use std::collections::HashMap;
 use std::time::Instant;
 
 use graph::prelude::*;
@@ -167,6 +168,7 @@ impl WriteContext {
                                 stopwatch,
                                 Vec::new(),
                                 Vec::new(),
+                                HashMap::new(),
                             )
                             .map_err(|e| e.into())
                             .map(move |_| {
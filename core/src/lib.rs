@@ -1,8 +1,10 @@
 mod link_resolver;
 mod metrics;
+mod notifier;
 mod subgraph;
 pub mod three_box;
 
 pub use crate::link_resolver::LinkResolver;
 pub use crate::metrics::MetricsRegistry;
+pub use crate::notifier::{HealthNotifier, NotificationKind, WebhookSink};
 pub use crate::subgraph::{SubgraphAssignmentProvider, SubgraphInstanceManager, SubgraphRegistrar};
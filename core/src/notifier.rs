@@ -0,0 +1,580 @@
+//! Notify webhooks when deployments fail, become unhealthy, fall behind the
+//! chain head or finish syncing.
+//!
+//! The notifier periodically loads the status of the deployments that are
+//! assigned to this node and only sends a notification when a condition
+//! starts to hold, so that a deployment that stays failed is only reported
+//! once. When graph-node starts, the current state of all deployments is
+//! taken as the baseline without sending notifications. Notifications are
+//! delivered in the background so that a slow webhook does not hold up
+//! checking deployments.
+use std::collections::HashMap;
+use std::fmt;
+use std::str::FromStr;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use graph::components::store::{StatusStore, SubgraphStore};
+use graph::data::subgraph::schema::SubgraphHealth;
+use graph::data::subgraph::status;
+use graph::prelude::*;
+
+/// How long to wait before the first retry of a failed delivery. The delay
+/// doubles with every further retry
+const RETRY_BASE_DELAY: Duration = Duration::from_secs(1);
+
+/// How long to wait for a webhook to respond to a delivery
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// The conditions of a deployment that we send notifications about
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum NotificationKind {
+    /// The deployment failed
+    Failed,
+    /// The deployment has non-fatal errors
+    Unhealthy,
+    /// The deployment was synced, but is now too far behind the chain head
+    FallingBehind,
+    /// The deployment reached the chain head for the first time
+    Synced,
+}
+
+impl NotificationKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            NotificationKind::Failed => "failed",
+            NotificationKind::Unhealthy => "unhealthy",
+            NotificationKind::FallingBehind => "falling-behind",
+            NotificationKind::Synced => "synced",
+        }
+    }
+}
+
+impl FromStr for NotificationKind {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<NotificationKind, Error> {
+        match s {
+            "failed" => Ok(NotificationKind::Failed),
+            "unhealthy" => Ok(NotificationKind::Unhealthy),
+            "falling-behind" => Ok(NotificationKind::FallingBehind),
+            "synced" => Ok(NotificationKind::Synced),
+            _ => Err(anyhow!(
+                "unknown notification `{}`, must be one of failed, unhealthy, falling-behind or synced",
+                s
+            )),
+        }
+    }
+}
+
+impl fmt::Display for NotificationKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.as_str())
+    }
+}
+
+/// A notification about one deployment
+#[derive(Clone, Debug, PartialEq)]
+pub struct Notification {
+    pub deployment: String,
+    pub kind: NotificationKind,
+    pub health: SubgraphHealth,
+    pub message: Option<String>,
+    pub network: Option<String>,
+    pub latest_block: Option<BlockNumber>,
+    pub chain_head_block: Option<BlockNumber>,
+    /// When the condition was detected, in seconds since the epoch
+    pub at: u64,
+}
+
+impl Notification {
+    /// The JSON body that is posted to webhooks. Deliveries that are
+    /// retried have the same `id` so that receivers can deduplicate them
+    pub fn to_json(&self) -> serde_json::Value {
+        serde_json::json!({
+            "id": format!("{}-{}-{}", self.deployment, self.kind, self.at),
+            "deployment": self.deployment,
+            "kind": self.kind.as_str(),
+            "health": self.health.as_str(),
+            "message": self.message,
+            "network": self.network,
+            "latestBlock": self.latest_block,
+            "chainHeadBlock": self.chain_head_block,
+            "at": self.at,
+        })
+    }
+}
+
+/// A webhook that notifications are posted to as JSON
+pub struct WebhookSink {
+    name: String,
+    url: String,
+    /// The kinds of notifications this sink receives; all if empty
+    kinds: Vec<NotificationKind>,
+    retries: usize,
+    client: reqwest::Client,
+}
+
+impl WebhookSink {
+    pub fn new(name: String, url: String, kinds: Vec<NotificationKind>, retries: usize) -> Self {
+        WebhookSink {
+            name,
+            url,
+            kinds,
+            retries,
+            client: reqwest::Client::builder()
+                .timeout(REQUEST_TIMEOUT)
+                .build()
+                .expect("failed to create the webhook client"),
+        }
+    }
+
+    fn accepts(&self, kind: NotificationKind) -> bool {
+        self.kinds.is_empty() || self.kinds.contains(&kind)
+    }
+
+    /// Post `notification` to the webhook, retrying with exponential
+    /// backoff if that fails
+    pub async fn send(&self, notification: &Notification) -> Result<(), Error> {
+        let body = notification.to_json().to_string();
+        let mut delay = RETRY_BASE_DELAY;
+        let mut attempt = 0;
+        loop {
+            let res = self
+                .client
+                .post(&self.url)
+                .header("Content-Type", "application/json")
+                .body(body.clone())
+                .send()
+                .await
+                .and_then(|response| response.error_for_status());
+            match res {
+                Ok(_) => return Ok(()),
+                Err(e) if attempt >= self.retries => {
+                    return Err(anyhow!(
+                        "failed to notify webhook {} after {} attempts: {}",
+                        self.name,
+                        attempt + 1,
+                        e
+                    ))
+                }
+                Err(_) => {
+                    tokio::time::delay_for(delay).await;
+                    delay *= 2;
+                    attempt += 1;
+                }
+            }
+        }
+    }
+}
+
+/// The conditions that held for a deployment when we last checked it
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+struct Conditions {
+    failed: bool,
+    unhealthy: bool,
+    behind: bool,
+    synced: bool,
+}
+
+/// Tracks the conditions of deployments across checks and turns changes
+/// into notifications
+struct Tracker {
+    node_id: NodeId,
+    lag_threshold: BlockNumber,
+    conditions: HashMap<String, Conditions>,
+    initialized: bool,
+}
+
+impl Tracker {
+    fn new(node_id: NodeId, lag_threshold: BlockNumber) -> Self {
+        Tracker {
+            node_id,
+            lag_threshold,
+            conditions: HashMap::new(),
+            initialized: false,
+        }
+    }
+
+    /// Compare `infos` with what we saw during the last check and return
+    /// notifications for the conditions that started to hold since then
+    fn check(&mut self, infos: Vec<status::Info>, at: u64) -> Vec<Notification> {
+        let mut notifications = Vec::new();
+        let mut conditions = HashMap::new();
+
+        for info in infos {
+            if info.node.as_deref() != Some(self.node_id.as_str()) {
+                continue;
+            }
+            let prev = self.conditions.get(&info.subgraph).copied();
+            let chain = info.chains.first();
+            let latest_block =
+                chain.and_then(|chain| chain.latest_block.as_ref().map(|b| b.number()));
+            let chain_head_block =
+                chain.and_then(|chain| chain.chain_head_block.as_ref().map(|b| b.number()));
+
+            // Once a deployment is behind, it has to catch up to within
+            // half the threshold before it is not considered behind anymore
+            // so that we do not send a notification each time the lag
+            // crosses the threshold
+            let was_behind = prev.map_or(false, |prev| prev.behind);
+            let behind = match (latest_block, chain_head_block) {
+                (Some(latest), Some(head)) if info.synced => {
+                    let threshold = if was_behind {
+                        self.lag_threshold / 2
+                    } else {
+                        self.lag_threshold
+                    };
+                    head - latest > threshold
+                }
+                _ => was_behind,
+            };
+            let current = Conditions {
+                failed: info.health == SubgraphHealth::Failed,
+                unhealthy: info.health == SubgraphHealth::Unhealthy,
+                behind,
+                synced: info.synced,
+            };
+
+            // Deployments we see for the first time after startup only set
+            // the baseline
+            if let (true, Some(prev)) = (self.initialized, prev) {
+                let started = |now: bool, before: bool| now && !before;
+                let mut notify = |kind, message: Option<String>| {
+                    notifications.push(Notification {
+                        deployment: info.subgraph.clone(),
+                        kind,
+                        health: info.health,
+                        message,
+                        network: chain.map(|chain| chain.network.clone()),
+                        latest_block,
+                        chain_head_block,
+                        at,
+                    })
+                };
+                if started(current.failed, prev.failed) {
+                    let message = info.fatal_error.as_ref().map(|e| e.message.clone());
+                    notify(NotificationKind::Failed, message);
+                }
+                if started(current.unhealthy, prev.unhealthy) {
+                    let message = info.non_fatal_errors.first().map(|e| e.message.clone());
+                    notify(NotificationKind::Unhealthy, message);
+                }
+                if started(current.behind, prev.behind) {
+                    let lag = chain_head_block.unwrap_or(0) - latest_block.unwrap_or(0);
+                    notify(
+                        NotificationKind::FallingBehind,
+                        Some(format!("{} blocks behind the chain head", lag)),
+                    );
+                }
+                if started(current.synced, prev.synced) {
+                    notify(NotificationKind::Synced, None);
+                }
+            }
+            conditions.insert(info.subgraph, current);
+        }
+
+        // Forget deployments that are no longer assigned to this node
+        self.conditions = conditions;
+        self.initialized = true;
+        notifications
+    }
+}
+
+/// Periodically checks the status of the deployments assigned to this
+/// node and sends notifications to webhooks
+pub struct HealthNotifier<S, SS> {
+    logger: Logger,
+    store: Arc<S>,
+    subgraph_store: Arc<SS>,
+    node_id: NodeId,
+    sinks: Vec<Arc<WebhookSink>>,
+    interval: Duration,
+    tracker: Tracker,
+}
+
+impl<S: StatusStore, SS: SubgraphStore> HealthNotifier<S, SS> {
+    /// Create a notifier that checks deployments every `interval` and
+    /// reports deployments that are more than `lag_threshold` blocks behind
+    /// the chain head
+    pub fn new(
+        logger: &Logger,
+        store: Arc<S>,
+        subgraph_store: Arc<SS>,
+        node_id: NodeId,
+        sinks: Vec<WebhookSink>,
+        interval: Duration,
+        lag_threshold: BlockNumber,
+    ) -> Self {
+        HealthNotifier {
+            logger: logger.new(o!("component" => "HealthNotifier")),
+            store,
+            subgraph_store,
+            node_id: node_id.clone(),
+            sinks: sinks.into_iter().map(Arc::new).collect(),
+            interval,
+            tracker: Tracker::new(node_id, lag_threshold),
+        }
+    }
+
+    pub async fn run(mut self) {
+        loop {
+            if let Err(e) = self.check().await {
+                warn!(self.logger, "Failed to check deployment health"; "error" => e.to_string());
+            }
+            tokio::time::delay_for(self.interval).await;
+        }
+    }
+
+    /// Load the status of the deployments that are assigned to this node.
+    /// Loading the status of all deployments would also load deployments
+    /// that other nodes index, which can be many more
+    async fn status(&self) -> Result<Vec<status::Info>, Error> {
+        let store = self.store.cheap_clone();
+        let subgraph_store = self.subgraph_store.cheap_clone();
+        let node_id = self.node_id.clone();
+
+        // The status queries are blocking database calls
+        graph::spawn_blocking_allow_panic(move || -> Result<_, Error> {
+            let ids: Vec<_> = subgraph_store
+                .assignments(&node_id)?
+                .into_iter()
+                .map(|id| id.to_string())
+                .collect();
+            if ids.is_empty() {
+                return Ok(vec![]);
+            }
+            Ok(store.status(status::Filter::Deployments(ids))?)
+        })
+        .await?
+    }
+
+    async fn check(&mut self) -> Result<(), Error> {
+        let infos = self.status().await?;
+        let at = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|now| now.as_secs())
+            .unwrap_or(0);
+        let notifications = self.tracker.check(infos, at);
+
+        for notification in notifications {
+            info!(self.logger, "Sending notification";
+                  "deployment" => &notification.deployment,
+                  "kind" => notification.kind.as_str());
+            let notification = Arc::new(notification);
+            for sink in self
+                .sinks
+                .iter()
+                .filter(|sink| sink.accepts(notification.kind))
+            {
+                let sink = sink.cheap_clone();
+                let notification = notification.cheap_clone();
+                let logger = self.logger.cheap_clone();
+                graph::spawn(async move {
+                    if let Err(e) = sink.send(&notification).await {
+                        error!(logger, "Failed to send notification"; "error" => e.to_string());
+                    }
+                });
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{Read, Write};
+    use std::net::TcpListener;
+    use std::sync::Mutex;
+    use std::thread;
+
+    use graph::data::subgraph::schema::SubgraphError;
+    use graph::data::subgraph::status::{ChainInfo, EthereumBlock, Info};
+    use graph::prelude::web3::types::H256;
+
+    use super::*;
+
+    const DEPLOYMENT: &str = "QmDeployment";
+
+    fn info(health: SubgraphHealth, synced: bool, latest: u64, head: u64) -> Info {
+        let fatal_error = if health == SubgraphHealth::Failed {
+            Some(SubgraphError {
+                subgraph_id: SubgraphDeploymentId::new(DEPLOYMENT).unwrap(),
+                message: "boom".to_string(),
+                block_ptr: None,
+                handler: None,
+                deterministic: true,
+            })
+        } else {
+            None
+        };
+        Info {
+            subgraph: DEPLOYMENT.to_string(),
+            synced,
+            health,
+            fatal_error,
+            non_fatal_errors: vec![],
+            chains: vec![ChainInfo {
+                network: "mainnet".to_string(),
+                chain_head_block: Some(EthereumBlock::new(H256::zero(), head)),
+                earliest_block: None,
+                latest_block: Some(EthereumBlock::new(H256::zero(), latest)),
+            }],
+            entity_count: 0,
+            node: Some("node".to_string()),
+            paused: Some(false),
+            retry: None,
+        }
+    }
+
+    fn kinds(notifications: Vec<Notification>) -> Vec<NotificationKind> {
+        notifications.into_iter().map(|n| n.kind).collect()
+    }
+
+    #[test]
+    fn notifications_on_transitions() {
+        use NotificationKind::*;
+        use SubgraphHealth::*;
+
+        let mut tracker = Tracker::new(NodeId::new("node").unwrap(), 100);
+
+        // The first check only sets the baseline
+        assert!(tracker
+            .check(vec![info(Healthy, false, 10, 1000)], 0)
+            .is_empty());
+
+        assert_eq!(
+            vec![Synced],
+            kinds(tracker.check(vec![info(Healthy, true, 1000, 1000)], 1))
+        );
+        // Conditions that persist are only reported once
+        assert!(tracker
+            .check(vec![info(Healthy, true, 1000, 1000)], 2)
+            .is_empty());
+
+        assert_eq!(
+            vec![FallingBehind],
+            kinds(tracker.check(vec![info(Healthy, true, 1000, 1200)], 3))
+        );
+        // Still behind until the lag is less than half the threshold
+        assert!(tracker
+            .check(vec![info(Healthy, true, 1130, 1200)], 4)
+            .is_empty());
+        assert!(tracker
+            .check(vec![info(Healthy, true, 1180, 1200)], 5)
+            .is_empty());
+        assert_eq!(
+            vec![FallingBehind],
+            kinds(tracker.check(vec![info(Healthy, true, 1180, 1400)], 6))
+        );
+
+        let notifications = tracker.check(vec![info(Failed, true, 1400, 1400)], 7);
+        assert_eq!(1, notifications.len());
+        assert_eq!(Failed, notifications[0].kind);
+        assert_eq!(Some("boom"), notifications[0].message.as_deref());
+        assert!(tracker
+            .check(vec![info(Failed, true, 1400, 1400)], 8)
+            .is_empty());
+
+        // Deployments that are assigned to other nodes are ignored
+        let mut other = info(Unhealthy, true, 1400, 1400);
+        other.node = Some("other".to_string());
+        assert!(tracker.check(vec![other], 9).is_empty());
+    }
+
+    /// A stand-in for a webhook that answers requests with the given
+    /// status codes in order and records the bodies it received
+    fn webhook(statuses: Vec<u16>) -> (String, Arc<Mutex<Vec<String>>>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/hook", listener.local_addr().unwrap());
+        let bodies = Arc::new(Mutex::new(Vec::new()));
+        let received = bodies.clone();
+        thread::spawn(move || {
+            for status in statuses {
+                let (mut stream, _) = listener.accept().unwrap();
+                let mut request = Vec::new();
+                let mut buf = [0u8; 4096];
+                // Read until we have the headers and the whole body
+                loop {
+                    let n = stream.read(&mut buf).unwrap();
+                    request.extend_from_slice(&buf[..n]);
+                    let text = String::from_utf8_lossy(&request);
+                    if let Some(end) = text.find("\r\n\r\n") {
+                        let length = text[..end]
+                            .lines()
+                            .find_map(|line| {
+                                let line = line.to_lowercase();
+                                line.strip_prefix("content-length:")
+                                    .map(|len| len.trim().parse::<usize>().unwrap())
+                            })
+                            .unwrap_or(0);
+                        if request.len() >= end + 4 + length {
+                            received
+                                .lock()
+                                .unwrap()
+                                .push(text[end + 4..end + 4 + length].to_string());
+                            break;
+                        }
+                    }
+                    if n == 0 {
+                        break;
+                    }
+                }
+                let response = format!(
+                    "HTTP/1.1 {} X\r\nContent-Length: 0\r\nConnection: close\r\n\r\n",
+                    status
+                );
+                stream.write_all(response.as_bytes()).unwrap();
+            }
+        });
+        (url, bodies)
+    }
+
+    fn notification() -> Notification {
+        Notification {
+            deployment: DEPLOYMENT.to_string(),
+            kind: NotificationKind::Failed,
+            health: SubgraphHealth::Failed,
+            message: Some("boom".to_string()),
+            network: Some("mainnet".to_string()),
+            latest_block: Some(10),
+            chain_head_block: Some(12),
+            at: 1,
+        }
+    }
+
+    #[tokio::test]
+    async fn webhook_retries() {
+        let (url, bodies) = webhook(vec![500, 200]);
+        let sink = WebhookSink::new("test".to_string(), url, vec![], 3);
+        sink.send(&notification()).await.unwrap();
+
+        // Both deliveries carry the same id
+        let bodies = bodies.lock().unwrap();
+        assert_eq!(2, bodies.len());
+        assert_eq!(bodies[0], bodies[1]);
+        let body: serde_json::Value = serde_json::from_str(&bodies[0]).unwrap();
+        assert_eq!(DEPLOYMENT, body["deployment"]);
+        assert_eq!("failed", body["kind"]);
+        assert_eq!(format!("{}-failed-1", DEPLOYMENT), body["id"]);
+    }
+
+    #[tokio::test]
+    async fn webhook_gives_up() {
+        let (url, bodies) = webhook(vec![500, 503]);
+        let sink = WebhookSink::new("test".to_string(), url, vec![], 1);
+        assert!(sink.send(&notification()).await.is_err());
+        assert_eq!(2, bodies.lock().unwrap().len());
+    }
+
+    #[test]
+    fn sinks_filter_by_kind() {
+        let sink = WebhookSink::new(
+            "test".to_string(),
+            "http://localhost".to_string(),
+            vec![NotificationKind::Failed],
+            0,
+        );
+        assert!(sink.accepts(NotificationKind::Failed));
+        assert!(!sink.accepts(NotificationKind::Synced));
+    }
+}
@@ -6,6 +6,7 @@ use std::collections::HashMap;
 use std::env;
 use std::str::FromStr;
 
+use graph::components::store::DynamicDataSourceKey;
 use graph::components::subgraph::{MappingError, SharedProofOfIndexing};
 use graph::prelude::{SubgraphInstance as SubgraphInstanceTrait, *};
 use web3::types::{Log, H256};
@@ -98,6 +99,7 @@ where
             } else {
                 let sender = T::spawn_mapping(
                     module_bytes.clone(),
+                    data_source.mapping.mapping_language()?,
                     logger,
                     self.subgraph_id.clone(),
                     host_metrics.clone(),
@@ -191,6 +193,7 @@ where
                             proof_of_indexing.cheap_clone(),
                         )
                         .await?;
+                    Self::count_trigger(&mut state, host);
                 }
             }
             EthereumTrigger::Call(call) => {
@@ -226,6 +229,7 @@ where
                             proof_of_indexing.cheap_clone(),
                         )
                         .await?;
+                    Self::count_trigger(&mut state, host);
                 }
             }
             EthereumTrigger::Block(ptr, trigger_type) => {
@@ -255,12 +259,28 @@ where
                             proof_of_indexing.cheap_clone(),
                         )
                         .await?;
+                    Self::count_trigger(&mut state, host);
                 }
             }
         }
         Ok(state)
     }
 
+    /// Count the trigger that `host` handled if its data source was created
+    /// from a template
+    fn count_trigger(state: &mut BlockState, host: &T::Host) {
+        if let (Some(creation_block), Some(address)) =
+            (host.creation_block_number(), host.data_source_address())
+        {
+            let key = DynamicDataSourceKey {
+                name: host.data_source_name().to_owned(),
+                address,
+                creation_block,
+            };
+            *state.trigger_counts.entry(key).or_default() += 1;
+        }
+    }
+
     fn add_dynamic_data_source(
         &mut self,
         logger: &Logger,
@@ -1,15 +1,17 @@
 use atomic_refcell::AtomicRefCell;
 use fail::fail_point;
 use lazy_static::lazy_static;
-use std::collections::{BTreeSet, HashMap, HashSet};
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
 use std::sync::{Arc, RwLock};
-use std::time::Instant;
+use std::time::{Duration, Instant};
 
 use graph::components::ethereum::{triggers_in_block, EthereumNetworks};
 use graph::components::store::{BlockStore, ModificationsAndCache};
-use graph::components::subgraph::{MappingError, ProofOfIndexing, SharedProofOfIndexing};
+use graph::components::subgraph::{
+    DeploymentProgress, MappingError, ProofOfIndexing, SharedProofOfIndexing,
+};
 use graph::data::store::scalar::Bytes;
-use graph::data::subgraph::schema::{SubgraphError, POI_OBJECT};
+use graph::data::subgraph::schema::{SubgraphError, SubgraphRestart, POI_OBJECT};
 use graph::data::subgraph::SubgraphFeature;
 use graph::prelude::{
     SubgraphInstance as SubgraphInstanceTrait,
@@ -34,6 +36,58 @@ lazy_static! {
     // Used for testing Graph Node itself.
     pub static ref DISABLE_FAIL_FAST: bool =
         std::env::var("GRAPH_DISABLE_FAIL_FAST").is_ok();
+
+    /// How many blocks a subgraph needs to be behind the chain head for
+    /// the store to write the changes from several blocks in one go. Closer
+    /// to the head, every block is written as soon as it has been processed
+    pub static ref WRITE_BATCH_DISTANCE: BlockNumber =
+        std::env::var("GRAPH_STORE_WRITE_BATCH_DISTANCE")
+            .unwrap_or("250".into())
+            .parse::<BlockNumber>()
+            .expect("invalid GRAPH_STORE_WRITE_BATCH_DISTANCE");
+
+    /// How often a subgraph that fails with a non-deterministic error is
+    /// restarted before it is left in the failed state. The count is reset
+    /// whenever the subgraph processes a block successfully
+    static ref SUBGRAPH_ERROR_RETRY_LIMIT: i32 =
+        std::env::var("GRAPH_SUBGRAPH_ERROR_RETRY_LIMIT")
+            .unwrap_or("10".into())
+            .parse::<i32>()
+            .expect("invalid GRAPH_SUBGRAPH_ERROR_RETRY_LIMIT");
+
+    /// How long to wait before the first restart, in seconds. The delay
+    /// doubles with every further restart
+    static ref SUBGRAPH_ERROR_RETRY_BASE_DELAY: Duration = Duration::from_secs(
+        std::env::var("GRAPH_SUBGRAPH_ERROR_RETRY_BASE_DELAY")
+            .unwrap_or("30".into())
+            .parse::<u64>()
+            .expect("invalid GRAPH_SUBGRAPH_ERROR_RETRY_BASE_DELAY"));
+
+    /// The longest we wait before restarting a subgraph, in seconds
+    static ref SUBGRAPH_ERROR_RETRY_MAX_DELAY: Duration = Duration::from_secs(
+        std::env::var("GRAPH_SUBGRAPH_ERROR_RETRY_MAX_DELAY")
+            .unwrap_or("1800".into())
+            .parse::<u64>()
+            .expect("invalid GRAPH_SUBGRAPH_ERROR_RETRY_MAX_DELAY"));
+}
+
+/// How long to wait before restarting a subgraph for the `attempt`-th time,
+/// or `None` if the subgraph should not be restarted anymore
+fn restart_delay(attempt: i32) -> Option<Duration> {
+    if attempt > *SUBGRAPH_ERROR_RETRY_LIMIT {
+        return None;
+    }
+    let factor = 2u32.saturating_pow((attempt - 1).max(0) as u32);
+    let delay = SUBGRAPH_ERROR_RETRY_BASE_DELAY
+        .checked_mul(factor)
+        .unwrap_or(*SUBGRAPH_ERROR_RETRY_MAX_DELAY);
+    Some(delay.min(*SUBGRAPH_ERROR_RETRY_MAX_DELAY))
+}
+
+/// Returned by `run_subgraph` when the subgraph failed with a
+/// non-deterministic error and should be started again from scratch
+struct Restart {
+    attempt: i32,
 }
 
 type SharedInstanceKeepAliveMap = Arc<RwLock<HashMap<SubgraphDeploymentId, CancelGuard>>>;
@@ -49,6 +103,9 @@ struct IndexingInputs<B, S, C> {
     stream_builder: B,
     include_calls_in_blocks: bool,
     templates: Arc<Vec<DataSourceTemplate>>,
+    /// The block for which to record the proof of indexing events, if
+    /// that was requested
+    record_poi_events: Option<BlockNumber>,
 }
 
 struct IndexingState<T: RuntimeHostBuilder> {
@@ -59,6 +116,48 @@ struct IndexingState<T: RuntimeHostBuilder> {
     call_filter: EthereumCallFilter,
     block_filter: EthereumBlockFilter,
     entity_lfu_cache: LfuCache<EntityKey, Option<Entity>>,
+    chain_head: ChainHead,
+}
+
+/// How long we use a chain head that we looked up before looking it up again
+const CHAIN_HEAD_REFRESH_INTERVAL: Duration = Duration::from_secs(5);
+
+/// The chain head as of the last time we looked it up. Looking it up for
+/// every block would put unnecessary load on the database
+#[derive(Default)]
+struct ChainHead {
+    number: Option<BlockNumber>,
+    updated: Option<Instant>,
+}
+
+impl ChainHead {
+    /// The number of the chain head. It is looked up again if what we have
+    /// is too old or behind `block`. If looking it up fails, we keep using
+    /// what we have since the chain head only serves as a hint
+    fn number(
+        &mut self,
+        logger: &Logger,
+        chain_store: &impl ChainStore,
+        block: BlockNumber,
+    ) -> Option<BlockNumber> {
+        let stale = match self.updated {
+            Some(updated) => {
+                updated.elapsed() >= CHAIN_HEAD_REFRESH_INTERVAL
+                    || self.number.map_or(false, |head| head < block)
+            }
+            None => true,
+        };
+        if stale {
+            match chain_store.chain_head_ptr() {
+                Ok(ptr) => self.number = ptr.map(|ptr| ptr.number),
+                Err(e) => {
+                    warn!(logger, "Failed to look up the chain head"; "error" => e.to_string())
+                }
+            }
+            self.updated = Some(Instant::now());
+        }
+        self.number
+    }
 }
 
 struct IndexingContext<B, T: RuntimeHostBuilder, S, C> {
@@ -91,6 +190,9 @@ pub struct SubgraphInstanceManager<B, S, BS, M, H, L> {
     manager_metrics: SubgraphInstanceManagerMetrics,
     instances: SharedInstanceKeepAliveMap,
     link_resolver: Arc<L>,
+    mapping_profiler: Arc<MappingProfiler>,
+    indexing_progress: Arc<IndexingProgress>,
+    node_id: NodeId,
 }
 
 struct SubgraphInstanceManagerMetrics {
@@ -130,12 +232,23 @@ struct SubgraphInstanceMetrics {
     pub block_trigger_count: Box<Histogram>,
     pub block_processing_duration: Box<Histogram>,
     pub block_ops_transaction_duration: Box<Histogram>,
+    pub entity_cache_hits: Box<Counter>,
+    pub entity_cache_misses: Box<Counter>,
+    pub entity_cache_weight: Box<Gauge>,
 
     trigger_processing_duration: Box<HistogramVec>,
+    blocks_per_second: Box<Gauge>,
+    triggers_per_second: Box<Gauge>,
+    estimated_time_to_sync: Box<Gauge>,
+    progress: Arc<DeploymentProgress>,
 }
 
 impl SubgraphInstanceMetrics {
-    pub fn new(registry: Arc<impl MetricsRegistry>, subgraph_hash: &str) -> Self {
+    pub fn new(
+        registry: Arc<impl MetricsRegistry>,
+        subgraph_hash: &str,
+        progress: Arc<DeploymentProgress>,
+    ) -> Self {
         let block_trigger_count = registry
             .new_deployment_histogram(
                 "deployment_block_trigger_count",
@@ -169,12 +282,78 @@ impl SubgraphInstanceMetrics {
                 vec![0.01, 0.05, 0.1, 0.3, 0.7, 2.0],
             )
             .expect("failed to create `deployment_transact_block_operations_duration_{}");
+        let entity_cache_hits = registry
+            .new_deployment_counter(
+                "deployment_entity_cache_hits",
+                "Counts entity lookups during indexing that were answered from the entity cache",
+                subgraph_hash,
+            )
+            .expect("failed to create `deployment_entity_cache_hits` counter");
+        let entity_cache_misses = registry
+            .new_deployment_counter(
+                "deployment_entity_cache_misses",
+                "Counts entity lookups during indexing that had to be answered by the store",
+                subgraph_hash,
+            )
+            .expect("failed to create `deployment_entity_cache_misses` counter");
+        let entity_cache_weight = registry
+            .new_deployment_gauge(
+                "deployment_entity_cache_weight",
+                "The approximate size of the entity cache for a subgraph deployment, in bytes",
+                subgraph_hash,
+            )
+            .expect("failed to create `deployment_entity_cache_weight` gauge");
+        let blocks_per_second = registry
+            .new_deployment_gauge(
+                "deployment_blocks_per_second",
+                "The number of blocks a subgraph deployment processed per second, averaged over GRAPH_INDEXING_RATE_WINDOW",
+                subgraph_hash,
+            )
+            .expect("failed to create `deployment_blocks_per_second` gauge");
+        let triggers_per_second = registry
+            .new_deployment_gauge(
+                "deployment_triggers_per_second",
+                "The number of triggers a subgraph deployment processed per second, averaged over GRAPH_INDEXING_RATE_WINDOW",
+                subgraph_hash,
+            )
+            .expect("failed to create `deployment_triggers_per_second` gauge");
+        let estimated_time_to_sync = registry
+            .new_deployment_gauge(
+                "deployment_estimated_time_to_sync",
+                "The estimated time until a subgraph deployment reaches the chain head at its current rate, in seconds",
+                subgraph_hash,
+            )
+            .expect("failed to create `deployment_estimated_time_to_sync` gauge");
 
         Self {
             block_trigger_count,
             block_processing_duration,
             trigger_processing_duration,
             block_ops_transaction_duration,
+            entity_cache_hits,
+            entity_cache_misses,
+            entity_cache_weight,
+            blocks_per_second,
+            triggers_per_second,
+            estimated_time_to_sync,
+            progress,
+        }
+    }
+
+    /// Record that `block`, which had `triggers` triggers, was processed
+    /// and update the indexing rate metrics
+    pub fn observe_block(
+        &self,
+        block: BlockNumber,
+        triggers: usize,
+        chain_head: Option<BlockNumber>,
+    ) {
+        self.progress.record(block, triggers);
+        let rate = self.progress.rate();
+        self.blocks_per_second.set(rate.blocks_per_second);
+        self.triggers_per_second.set(rate.triggers_per_second);
+        if let Some(eta) = chain_head.and_then(|head| rate.time_to_process(head - block)) {
+            self.estimated_time_to_sync.set(eta.as_secs_f64());
         }
     }
 
@@ -189,6 +368,12 @@ impl SubgraphInstanceMetrics {
         registry.unregister(self.block_trigger_count.clone());
         registry.unregister(self.trigger_processing_duration.clone());
         registry.unregister(self.block_ops_transaction_duration.clone());
+        registry.unregister(self.entity_cache_hits.clone());
+        registry.unregister(self.entity_cache_misses.clone());
+        registry.unregister(self.entity_cache_weight.clone());
+        registry.unregister(self.blocks_per_second.clone());
+        registry.unregister(self.triggers_per_second.clone());
+        registry.unregister(self.estimated_time_to_sync.clone());
     }
 }
 
@@ -209,23 +394,17 @@ where
     ) {
         let logger = self.logger_factory.subgraph_logger(&id);
 
-        // Blocking due to store interactions. Won't be blocking after #905.
-        match graph::spawn_blocking(Self::start_subgraph_inner(
-            logger.clone(),
-            self.instances.clone(),
-            self.host_builder.clone(),
-            self.block_stream_builder.clone(),
-            self.subgraph_store.cheap_clone(),
-            self.block_store.cheap_clone(),
-            self.eth_networks.clone(),
-            id,
-            manifest,
-            self.metrics_registry.cheap_clone(),
-            self.link_resolver.cheap_clone(),
-        ))
-        .await
-        .map_err(Error::from)
-        .and_then(|e| e)
+        // The deployment might have been paused after the start was
+        // requested
+        if let Ok(Some((_, true))) = self.subgraph_store.assignment_status(&id) {
+            info!(logger, "Not starting subgraph since it is paused");
+            return;
+        }
+
+        match self
+            .cheap_clone()
+            .launch_subgraph(logger.clone(), id, manifest, 0, None)
+            .await
         {
             Ok(()) => self.manager_metrics.subgraph_count.inc(),
             Err(err) => error!(
@@ -244,6 +423,7 @@ where
         // Drop the cancel guard to shut down the subgraph now
         let mut instances = self.instances.write().unwrap();
         instances.remove(&id);
+        self.indexing_progress.stop(&id);
 
         self.manager_metrics.subgraph_count.dec();
     }
@@ -267,10 +447,20 @@ where
         block_stream_builder: B,
         metrics_registry: Arc<M>,
         link_resolver: Arc<L>,
+        mapping_profiler: Arc<MappingProfiler>,
+        indexing_progress: Arc<IndexingProgress>,
+        node_id: NodeId,
     ) -> Self {
         let logger = logger_factory.component_logger("SubgraphInstanceManager", None);
         let logger_factory = logger_factory.with_parent(logger.clone());
 
+        // Nothing runs yet, even if the node did not get to record that its
+        // instances stopped when it shut down
+        if let Err(e) = subgraph_store.clear_running_instances(&node_id) {
+            error!(logger, "Failed to clear the subgraph instances of this node";
+                "error" => e.to_string());
+        }
+
         let link_resolver = Arc::new(
             link_resolver
                 .as_ref()
@@ -290,9 +480,144 @@ where
             metrics_registry,
             instances: SharedInstanceKeepAliveMap::default(),
             link_resolver,
+            mapping_profiler,
+            indexing_progress,
+            node_id,
         }
     }
 
+    /// Record that this node no longer runs an instance of `id` and will
+    /// not write to it anymore
+    fn instance_stopped(&self, id: &SubgraphDeploymentId) {
+        if let Err(e) = self
+            .subgraph_store
+            .set_instance_running(id, &self.node_id, false)
+        {
+            error!(self.logger_factory.subgraph_logger(id),
+                "Failed to record that the subgraph stopped";
+                "error" => e.to_string());
+        }
+    }
+
+    async fn launch_subgraph(
+        self: Arc<Self>,
+        logger: Logger,
+        id: SubgraphDeploymentId,
+        manifest: serde_yaml::Mapping,
+        attempt: i32,
+        restart_handle: Option<CancelHandle>,
+    ) -> Result<(), Error> {
+        // Blocking due to store interactions. Won't be blocking after #905.
+        graph::spawn_blocking(Self::start_subgraph_inner(
+            logger,
+            self.instances.clone(),
+            self.host_builder.clone(),
+            self.block_stream_builder.clone(),
+            self.subgraph_store.cheap_clone(),
+            self.block_store.cheap_clone(),
+            self.eth_networks.clone(),
+            id,
+            manifest,
+            self.metrics_registry.cheap_clone(),
+            self.link_resolver.cheap_clone(),
+            self.mapping_profiler.cheap_clone(),
+            self.indexing_progress.cheap_clone(),
+            self.cheap_clone(),
+            attempt,
+            restart_handle,
+        ))
+        .await
+        .map_err(Error::from)
+        .and_then(|e| e)
+    }
+
+    /// Start the subgraph again after it failed with a non-deterministic
+    /// error, unless it was stopped in the meantime. Failing to start it
+    /// counts as another failed attempt and is retried with backoff
+    fn restart_subgraph(
+        self: Arc<Self>,
+        id: SubgraphDeploymentId,
+        manifest: serde_yaml::Mapping,
+        mut attempt: i32,
+    ) {
+        graph::spawn(async move {
+            let logger = self.logger_factory.subgraph_logger(&id);
+
+            // `stop_subgraph` removes the cancel guard of the failed run.
+            // Replace that guard with one for the restart, so that stopping
+            // the subgraph at any point until the new run has started is
+            // noticed by the new run
+            let restart_handle = {
+                let mut instances = self.instances.write().unwrap();
+                if !instances.contains_key(&id) {
+                    self.instance_stopped(&id);
+                    return;
+                }
+                let guard = CancelGuard::new();
+                let handle = guard.handle();
+                instances.insert(id.clone(), guard);
+                handle
+            };
+
+            loop {
+                info!(logger, "Restarting subgraph"; "attempt" => attempt);
+                let err = match self
+                    .cheap_clone()
+                    .launch_subgraph(
+                        logger.clone(),
+                        id.clone(),
+                        manifest.clone(),
+                        attempt,
+                        Some(restart_handle.clone()),
+                    )
+                    .await
+                {
+                    Ok(()) => return,
+                    Err(err) => err,
+                };
+
+                let message = format!("{:#}", err).replace("\n", "\t");
+                error!(
+                    logger,
+                    "Failed to restart subgraph";
+                    "error" => &message,
+                    "code" => LogCode::SubgraphStartFailure
+                );
+
+                attempt += 1;
+                let delay = restart_delay(attempt);
+                let restart = SubgraphRestart {
+                    attempt,
+                    message,
+                    block_ptr: None,
+                    delay,
+                };
+                if let Err(e) = self.subgraph_store.record_subgraph_restart(&id, restart) {
+                    error!(logger, "Failed to record subgraph restart: {}", e);
+                }
+
+                let delay = match delay {
+                    Some(delay) => delay,
+                    None => {
+                        error!(
+                            logger,
+                            "Subgraph failed too often, not restarting it";
+                            "attempts" => attempt - 1,
+                        );
+                        self.instance_stopped(&id);
+                        return;
+                    }
+                };
+                tokio::time::delay_for(delay).await;
+
+                if restart_handle.is_canceled() {
+                    self.instance_stopped(&id);
+                    return;
+                }
+            }
+        });
+    }
+
     async fn start_subgraph_inner(
         logger: Logger,
         instances: SharedInstanceKeepAliveMap,
@@ -305,7 +630,13 @@ where
         manifest: serde_yaml::Mapping,
         registry: Arc<M>,
         link_resolver: Arc<L>,
+        mapping_profiler: Arc<MappingProfiler>,
+        indexing_progress: Arc<IndexingProgress>,
+        manager: Arc<Self>,
+        attempt: i32,
+        restart_handle: Option<CancelHandle>,
     ) -> Result<(), Error> {
+        let raw_manifest = manifest.clone();
         let manifest = {
             info!(logger, "Resolve subgraph files using IPFS");
 
@@ -355,6 +686,10 @@ where
                 &required_capabilities, e))?.clone();
 
         store.start_subgraph_deployment(&logger, &manifest.id)?;
+        let record_poi_events = store.poi_events_block(&manifest.id)?;
+        if let Some(block) = record_poi_events {
+            info!(logger, "Proof of indexing events will be recorded"; "block" => block);
+        }
 
         // Clone the deployment ID for later
         let deployment_id = manifest.id.clone();
@@ -380,12 +715,14 @@ where
         let subgraph_metrics = Arc::new(SubgraphInstanceMetrics::new(
             registry.clone(),
             deployment_id.as_str(),
+            indexing_progress.start(&deployment_id),
         ));
         let subgraph_metrics_unregister = subgraph_metrics.clone();
         let host_metrics = Arc::new(HostMetrics::new(
             registry.clone(),
             deployment_id.as_str(),
             stopwatch_metrics.clone(),
+            mapping_profiler.start(&deployment_id),
         ));
         let ethrpc_metrics = Arc::new(SubgraphEthRpcMetrics::new(registry.clone(), &deployment_id));
         let block_stream_metrics = Arc::new(BlockStreamMetrics::new(
@@ -411,6 +748,7 @@ where
                 stream_builder,
                 include_calls_in_blocks,
                 templates,
+                record_poi_events,
             },
             state: IndexingState {
                 logger,
@@ -420,6 +758,7 @@ where
                 call_filter,
                 block_filter,
                 entity_lfu_cache: LfuCache::new(),
+                chain_head: ChainHead::default(),
             },
             subgraph_metrics,
             host_metrics,
@@ -434,17 +773,39 @@ where
         // forward; this is easier than updating the existing block stream.
         //
         // This task has many calls to the store, so mark it as `blocking`.
+        //
+        // Until the instance is done for good, this node counts as running
+        // it, including while it waits to be restarted, so that nobody
+        // else changes the deployment while this node might still write it
+        manager
+            .subgraph_store
+            .set_instance_running(&deployment_id, &manager.node_id, true)?;
         graph::spawn_blocking(async move {
-            let res = run_subgraph(ctx).await;
+            let res = run_subgraph(ctx, attempt, restart_handle).await;
             subgraph_metrics_unregister.unregister(registry);
-            res
+            match res {
+                Ok(Restart { attempt }) => {
+                    manager.restart_subgraph(deployment_id, raw_manifest, attempt)
+                }
+                Err(()) => manager.instance_stopped(&deployment_id),
+            }
         });
 
         Ok(())
     }
 }
 
-async fn run_subgraph<B, T, S, C>(mut ctx: IndexingContext<B, T, S, C>) -> Result<(), ()>
+/// Process blocks until the subgraph is stopped or fails. That always ends
+/// in an error, except when the subgraph failed with a non-deterministic
+/// error and should be restarted. `attempt` is the number of restarts since
+/// the subgraph last processed a block successfully. For a restart,
+/// `restart_handle` is canceled if the subgraph was stopped before this run
+/// took over
+async fn run_subgraph<B, T, S, C>(
+    mut ctx: IndexingContext<B, T, S, C>,
+    mut attempt: i32,
+    mut restart_handle: Option<CancelHandle>,
+) -> Result<Restart, ()>
 where
     B: BlockStreamBuilder,
     T: RuntimeHostBuilder,
@@ -482,12 +843,20 @@ where
             .compat();
 
         // Keep the stream's cancel guard around to be able to shut it down
-        // when the subgraph deployment is unassigned
-        ctx.state
-            .instances
-            .write()
-            .unwrap()
-            .insert(ctx.inputs.deployment_id.clone(), block_stream_canceler);
+        // when the subgraph deployment is unassigned. Checking whether the
+        // subgraph was stopped while it was being restarted has to happen
+        // under the same lock that `stop_subgraph` takes
+        {
+            let mut instances = ctx.state.instances.write().unwrap();
+            if restart_handle
+                .take()
+                .map_or(false, |handle| handle.is_canceled())
+            {
+                info!(logger, "Subgraph was stopped while it was restarted");
+                return Err(());
+            }
+            instances.insert(ctx.inputs.deployment_id.clone(), block_stream_canceler);
+        }
 
         debug!(logger, "Starting block stream");
 
@@ -558,6 +927,7 @@ where
                     // match any data sources.
                     ctx.state.instance.revert_data_sources(subgraph_ptr.number);
                     ctx.state.entity_lfu_cache = LfuCache::new();
+                    ctx.subgraph_metrics.entity_cache_weight.set(0.0);
                     continue;
                 }
                 // Log and drop the errors from the block_stream
@@ -598,6 +968,7 @@ where
             match res {
                 Ok((c, needs_restart)) => {
                     ctx = c;
+                    attempt = 0;
 
                     // Unfail the subgraph if it was previously failed.
                     // As an optimization we check this only on the first run.
@@ -628,6 +999,13 @@ where
                         "Subgraph block stream shut down cleanly";
                         "id" => id_for_err.to_string(),
                     );
+                    if let Err(e) = store_for_err.flush(&id_for_err) {
+                        error!(
+                            &logger,
+                            "Failed to write buffered changes: {}", e;
+                            "id" => id_for_err.to_string(),
+                        );
+                    }
                     return Err(());
                 }
 
@@ -643,8 +1021,8 @@ where
 
                     let error = SubgraphError {
                         subgraph_id: id_for_err.clone(),
-                        message,
-                        block_ptr: Some(block_ptr),
+                        message: message.clone(),
+                        block_ptr: Some(block_ptr.clone()),
                         handler: None,
                         deterministic: e.is_deterministic(),
                     };
@@ -657,7 +1035,56 @@ where
                             "code" => LogCode::SubgraphSyncingFailureNotRecorded
                         );
                     }
-                    return Err(());
+
+                    if e.is_deterministic() {
+                        return Err(());
+                    }
+
+                    // Non-deterministic errors might go away, for example
+                    // when an Ethereum node recovers, so we restart the
+                    // subgraph with exponential backoff
+                    attempt += 1;
+                    let delay = restart_delay(attempt);
+                    let restart = SubgraphRestart {
+                        attempt,
+                        message,
+                        block_ptr: Some(block_ptr),
+                        delay,
+                    };
+                    if let Err(e) = store_for_err.record_subgraph_restart(&id_for_err, restart) {
+                        error!(
+                            &logger,
+                            "Failed to record subgraph restart: {}", e;
+                            "id" => id_for_err.to_string(),
+                        );
+                    }
+
+                    let delay = match delay {
+                        Some(delay) => delay,
+                        None => {
+                            error!(
+                                &logger,
+                                "Subgraph failed too often, not restarting it";
+                                "id" => id_for_err.to_string(),
+                                "attempts" => attempt - 1,
+                            );
+                            return Err(());
+                        }
+                    };
+                    info!(
+                        &logger,
+                        "Restarting subgraph after non-deterministic error";
+                        "id" => id_for_err.to_string(),
+                        "attempt" => attempt,
+                        "delay_s" => delay.as_secs(),
+                    );
+                    tokio::time::delay_for(delay).await;
+
+                    // The subgraph was stopped while we were waiting
+                    if block_stream_cancel_handle.is_canceled() {
+                        return Err(());
+                    }
+                    return Ok(Restart { attempt });
                 }
             }
         }
@@ -707,6 +1134,7 @@ where
     C: ChainStore,
 {
     let triggers = block.triggers;
+    let trigger_count = triggers.len();
     let block = block.ethereum_block;
 
     let block_ptr = EthereumBlockPointer::from(&block);
@@ -738,9 +1166,12 @@ where
         .supports_proof_of_indexing(&ctx.inputs.deployment_id)
         .await?
     {
-        Some(Arc::new(AtomicRefCell::new(ProofOfIndexing::new(
-            block_ptr.number,
-        ))))
+        let proof_of_indexing = if ctx.inputs.record_poi_events == Some(block_ptr.number) {
+            ProofOfIndexing::recording(block_ptr.number)
+        } else {
+            ProofOfIndexing::new(block_ptr.number)
+        };
+        Some(Arc::new(AtomicRefCell::new(proof_of_indexing)))
     } else {
         None
     };
@@ -869,6 +1300,20 @@ where
         }
     }
 
+    // Store what the mappings logged, even if the block fails, so that the messages that led up
+    // to a failure can be looked at. Messages are only kept for troubleshooting, and failing to
+    // store them does not fail the block.
+    let mapping_logs = std::mem::replace(&mut block_state.mapping_logs, Vec::new());
+    if !mapping_logs.is_empty() {
+        if let Err(e) = ctx
+            .inputs
+            .store
+            .write_mapping_logs(&ctx.inputs.deployment_id, mapping_logs)
+        {
+            warn!(logger, "Failed to store mapping logs"; "error" => e.to_string());
+        }
+    }
+
     // The triggers were processed but some were skipped due to deterministic errors, if the
     // `nonFatalErrors` feature is not present, return early with an error.
     let has_errors = block_state.has_errors();
@@ -893,13 +1338,26 @@ where
 
     if let Some(proof_of_indexing) = proof_of_indexing {
         let proof_of_indexing = Arc::try_unwrap(proof_of_indexing).unwrap().into_inner();
-        update_proof_of_indexing(
+        let events = update_proof_of_indexing(
             proof_of_indexing,
             &ctx.host_metrics.stopwatch,
             &ctx.inputs.deployment_id,
             &mut block_state.entity_cache,
         )
         .await?;
+
+        // Like mapping logs, recorded events are only for troubleshooting
+        if let Some(events) = events {
+            match ctx.inputs.store.write_poi_events(
+                &ctx.inputs.deployment_id,
+                block_ptr.number,
+                events,
+            ) {
+                Ok(()) => info!(logger, "Recorded proof of indexing events"),
+                Err(e) => warn!(logger, "Failed to store proof of indexing events";
+                                "error" => e.to_string()),
+            }
+        }
     }
 
     let section = ctx.host_metrics.stopwatch.start_section("as_modifications");
@@ -907,6 +1365,8 @@ where
         modifications: mods,
         data_sources,
         entity_lfu_cache: mut cache,
+        cache_hits,
+        cache_misses,
     } = block_state
         .entity_cache
         .as_modifications(ctx.inputs.store.as_ref())
@@ -920,6 +1380,10 @@ where
     cache.evict(*ENTITY_CACHE_SIZE);
     section.end();
 
+    metrics.entity_cache_hits.inc_by(cache_hits as f64);
+    metrics.entity_cache_misses.inc_by(cache_misses as f64);
+    metrics.entity_cache_weight.set(cache.total_weight() as f64);
+
     // Put the cache back in the ctx, asserting that the placeholder cache was not used.
     assert!(ctx.state.entity_lfu_cache.is_empty());
     ctx.state.entity_lfu_cache = cache;
@@ -953,17 +1417,50 @@ where
                 .map_err(BlockProcessingError::Unknown)?)
     };
 
-    match ctx.inputs.store.transact_block_operations(
-        subgraph_id.cheap_clone(),
-        block_ptr_after,
-        mods,
-        stopwatch,
-        data_sources,
-        block_state.deterministic_errors,
-    ) {
+    // While we are far behind the chain head, let the store hold on to the
+    // changes so it can write several blocks at once. Blocks with errors or
+    // new data sources are written right away since they change the health
+    // of the subgraph or lead to a restart that reloads data sources
+    let chain_head = ctx.state.chain_head.number(
+        &logger,
+        ctx.inputs.chain_store.as_ref(),
+        block_ptr_after.number,
+    );
+    let write_now = has_errors
+        || needs_restart
+        || !data_sources.is_empty()
+        || match chain_head {
+            Some(head) => head - block_ptr_after.number <= *WRITE_BATCH_DISTANCE,
+            None => true,
+        };
+
+    let res = if write_now {
+        ctx.inputs.store.transact_block_operations(
+            subgraph_id.cheap_clone(),
+            block_ptr_after,
+            mods,
+            stopwatch,
+            data_sources,
+            block_state.deterministic_errors,
+            block_state.trigger_counts,
+        )
+    } else {
+        ctx.inputs.store.buffer_block_operations(
+            subgraph_id.cheap_clone(),
+            block_ptr_after,
+            mods,
+            stopwatch,
+            data_sources,
+            block_state.deterministic_errors,
+            block_state.trigger_counts,
+        )
+    };
+
+    match res {
         Ok(_) => {
             let elapsed = start.elapsed().as_secs_f64();
             metrics.block_ops_transaction_duration.observe(elapsed);
+            metrics.observe_block(block_ptr_after.number, trigger_count, chain_head);
 
             // To prevent a buggy pending version from replacing a current version, if errors are
             // present the subgraph will be unassigned.
@@ -985,18 +1482,28 @@ where
 }
 
 /// Transform the proof of indexing changes into entity updates that will be
-/// inserted when as_modifications is called.
+/// inserted when as_modifications is called. If `proof_of_indexing` records
+/// events, return them by causality region.
 async fn update_proof_of_indexing(
     proof_of_indexing: ProofOfIndexing,
     stopwatch: &StopwatchMetrics,
     deployment_id: &SubgraphDeploymentId,
     entity_cache: &mut EntityCache,
-) -> Result<(), Error> {
+) -> Result<Option<BTreeMap<String, Vec<String>>>, Error> {
     let _section_guard = stopwatch.start_section("update_proof_of_indexing");
 
+    let mut events = if proof_of_indexing.is_recording() {
+        Some(BTreeMap::new())
+    } else {
+        None
+    };
     let mut proof_of_indexing = proof_of_indexing.take();
 
-    for (causality_region, stream) in proof_of_indexing.drain() {
+    for (causality_region, mut stream) in proof_of_indexing.drain() {
+        if let (Some(events), Some(recorded)) = (events.as_mut(), stream.take_events()) {
+            events.insert(causality_region.clone(), recorded);
+        }
+
         // Create the special POI entity key specific to this causality_region
         let entity_key = EntityKey {
             subgraph_id: deployment_id.clone(),
@@ -1028,7 +1535,7 @@ async fn update_proof_of_indexing(
         entity_cache.set(entity_key, new_poi_entity);
     }
 
-    Ok(())
+    Ok(events)
 }
 
 async fn process_triggers(
@@ -1172,3 +1679,27 @@ fn persist_dynamic_data_sources<B, T: RuntimeHostBuilder, S, C>(
         .block_filter
         .extend(EthereumBlockFilter::from_data_sources(&data_sources));
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn restart_delay_grows_and_is_capped() {
+        let base = *SUBGRAPH_ERROR_RETRY_BASE_DELAY;
+        let max = *SUBGRAPH_ERROR_RETRY_MAX_DELAY;
+        let limit = *SUBGRAPH_ERROR_RETRY_LIMIT;
+
+        assert_eq!(Some(base.min(max)), restart_delay(1));
+        assert_eq!(Some((base * 2).min(max)), restart_delay(2));
+        assert_eq!(Some((base * 4).min(max)), restart_delay(3));
+
+        let delays: Vec<_> = (1..=limit).map(restart_delay).collect();
+        assert!(delays.iter().all(|delay| delay.is_some()));
+        assert!(delays.windows(2).all(|pair| pair[0] <= pair[1]));
+        assert!(delays.iter().all(|delay| delay.unwrap() <= max));
+
+        assert_eq!(None, restart_delay(limit + 1));
+        assert_eq!(None, restart_delay(i32::MAX));
+    }
+}
@@ -6,7 +6,10 @@ use async_trait::async_trait;
 use lazy_static::lazy_static;
 
 use graph::components::store::SubscriptionManager;
-use graph::components::{ethereum::EthereumNetworks, store::BlockStore};
+use graph::components::{
+    ethereum::EthereumNetworks,
+    store::{BlockStore, ChainStore},
+};
 use graph::data::subgraph::schema::SubgraphDeploymentEntity;
 use graph::prelude::{
     CreateSubgraphResult, SubgraphAssignmentProvider as SubgraphAssignmentProviderTrait,
@@ -23,6 +26,13 @@ lazy_static! {
     );
 }
 
+/// How long a rewind waits for the node that indexes the deployment to
+/// stop writing to it after it was paused or unassigned
+const INSTANCE_STOP_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// How often a rewind checks whether the deployment is still being indexed
+const INSTANCE_STOP_POLL_INTERVAL: Duration = Duration::from_secs(1);
+
 pub struct SubgraphRegistrar<L, P, S, BS, SM> {
     logger: Logger,
     logger_factory: LoggerFactory,
@@ -184,20 +194,21 @@ where
                     match operation {
                         EntityChangeOperation::Set => {
                             store
-                                .assigned_node(&subgraph_hash)
+                                .assignment_status(&subgraph_hash)
                                 .map_err(|e| {
                                     anyhow!("Failed to get subgraph assignment entity: {}", e)
                                 })
                                 .map(|assigned| -> Box<dyn Stream<Item = _, Error = _> + Send> {
-                                    if let Some(assigned) = assigned {
-                                        if assigned == node_id {
+                                    if let Some((assigned, paused)) = assigned {
+                                        if assigned == node_id && !paused {
                                             // Start subgraph on this node
                                             Box::new(stream::once(Ok(AssignmentEvent::Add {
                                                 subgraph_id: subgraph_hash,
                                                 node_id: node_id.clone(),
                                             })))
                                         } else {
-                                            // Ensure it is removed from this node
+                                            // Ensure it is removed from this node, either
+                                            // because it is paused or assigned elsewhere
                                             Box::new(stream::once(Ok(AssignmentEvent::Remove {
                                                 subgraph_id: subgraph_hash,
                                                 node_id: node_id.clone(),
@@ -357,6 +368,113 @@ where
 
         Ok(())
     }
+
+    async fn pause_subgraph(&self, id: SubgraphDeploymentId) -> Result<(), SubgraphRegistrarError> {
+        self.store.pause_subgraph(&id)?;
+
+        debug!(self.logger, "Paused subgraph"; "subgraph_id" => id.to_string());
+
+        Ok(())
+    }
+
+    async fn resume_subgraph(
+        &self,
+        id: SubgraphDeploymentId,
+    ) -> Result<(), SubgraphRegistrarError> {
+        self.store.resume_subgraph(&id)?;
+
+        debug!(self.logger, "Resumed subgraph"; "subgraph_id" => id.to_string());
+
+        Ok(())
+    }
+
+    async fn unassign_subgraph(
+        &self,
+        id: SubgraphDeploymentId,
+    ) -> Result<(), SubgraphRegistrarError> {
+        self.store.unassign_subgraph(&id)?;
+
+        debug!(self.logger, "Unassigned subgraph"; "subgraph_id" => id.to_string());
+
+        Ok(())
+    }
+
+    async fn unfail_subgraph(
+        &self,
+        id: SubgraphDeploymentId,
+    ) -> Result<(), SubgraphRegistrarError> {
+        self.store.unfail(&id)?;
+
+        debug!(self.logger, "Unfailed subgraph"; "subgraph_id" => id.to_string());
+
+        Ok(())
+    }
+
+    async fn rewind_subgraph(
+        &self,
+        id: SubgraphDeploymentId,
+        block_ptr: EthereumBlockPointer,
+    ) -> Result<(), SubgraphRegistrarError> {
+        let network = self.store.network_name(&id)?;
+        let chain_store = self
+            .block_store
+            .chain_store(&network)
+            .ok_or_else(|| SubgraphRegistrarError::NetworkNotSupported(network.clone()))?;
+        check_block_ptr(chain_store.as_ref(), &network, &block_ptr)?;
+
+        if let Some((node, false)) = self.store.assignment_status(&id)? {
+            return Err(SubgraphRegistrarError::RewindError(format!(
+                "deployment {} is being indexed by node {}; pause it before rewinding it",
+                id, node
+            )));
+        }
+
+        // Pausing or unassigning the deployment only asks the node that
+        // indexes it to stop; it might still be writing a block
+        let start = Instant::now();
+        while let Some(node) = self.store.running_instance(&id)? {
+            if start.elapsed() >= INSTANCE_STOP_TIMEOUT {
+                return Err(SubgraphRegistrarError::RewindError(format!(
+                    "node {} has not stopped indexing deployment {} yet; try again later",
+                    node, id
+                )));
+            }
+            tokio::time::delay_for(INSTANCE_STOP_POLL_INTERVAL).await;
+        }
+
+        self.store.rewind(&id, block_ptr.clone())?;
+
+        debug!(self.logger, "Rewound subgraph";
+            "subgraph_id" => id.to_string(),
+            "block" => block_ptr.to_string());
+
+        Ok(())
+    }
+}
+
+/// Check that `block_ptr` is a block of `network`, so that deployments are
+/// only ever rewound to actual blocks
+fn check_block_ptr(
+    chain_store: &impl ChainStore,
+    network: &str,
+    block_ptr: &EthereumBlockPointer,
+) -> Result<(), SubgraphRegistrarError> {
+    let hashes = chain_store
+        .block_hashes_by_block_number(block_ptr.number)
+        .map_err(SubgraphRegistrarError::Unknown)?;
+    if hashes.is_empty() {
+        return Err(SubgraphRegistrarError::RewindError(format!(
+            "block {} of network {} is not in the block cache, so its hash can not be checked",
+            block_ptr.number, network
+        )));
+    }
+    if !hashes.contains(&block_ptr.hash_as_h256()) {
+        return Err(SubgraphRegistrarError::RewindError(format!(
+            "block {} of network {} does not have hash {}",
+            block_ptr.number, network, block_ptr.hash
+        )));
+    }
+    Ok(())
 }
 
 async fn handle_assignment_event(
@@ -553,3 +671,32 @@ fn create_subgraph_version(
             })
     )
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use graph::prelude::web3::types::H256;
+    use graph_mock::MockStore;
+
+    #[test]
+    fn check_block_ptr_rejects_bogus_blocks() {
+        let hash = H256::from_low_u64_be(1);
+        let mut chain_store = MockStore::new();
+        chain_store
+            .expect_block_hashes_by_block_number()
+            .returning(move |number| match number {
+                1 => Ok(vec![hash]),
+                _ => Ok(vec![]),
+            });
+        let ptr = |hash: H256, number: BlockNumber| EthereumBlockPointer::from((hash, number));
+
+        assert!(check_block_ptr(&chain_store, "mainnet", &ptr(hash, 1)).is_ok());
+
+        let bogus = H256::from_low_u64_be(2);
+        let err = check_block_ptr(&chain_store, "mainnet", &ptr(bogus, 1)).unwrap_err();
+        assert!(err.to_string().contains("does not have hash"));
+
+        let err = check_block_ptr(&chain_store, "mainnet", &ptr(hash, 2)).unwrap_err();
+        assert!(err.to_string().contains("is not in the block cache"));
+    }
+}
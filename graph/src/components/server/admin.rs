@@ -1,8 +1,14 @@
+use serde::{Deserialize, Serialize};
+use std::fmt;
 use std::io;
+use std::net::IpAddr;
+use std::str::FromStr;
 use std::sync::Arc;
 
+use crate::components::store::StatusStore;
 use crate::prelude::Logger;
 use crate::prelude::NodeId;
+use crate::prelude::SubgraphStore;
 
 /// Common trait for JSON-RPC admin server implementations.
 pub trait JsonRpcServer<P> {
@@ -13,7 +19,211 @@ pub trait JsonRpcServer<P> {
         http_port: u16,
         ws_port: u16,
         provider: Arc<P>,
+        store: Arc<dyn SubgraphStore>,
+        status_store: Arc<dyn StatusStore>,
+        auth: AdminAuth,
         node_id: NodeId,
         logger: Logger,
     ) -> Result<Self::Server, io::Error>;
 }
+
+/// What a caller of the admin API is allowed to do. Roles are ordered such
+/// that each role can do everything the roles before it can do
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Deserialize, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum AdminRole {
+    /// Can only look at deployments
+    ReadOnly,
+    /// Can create, deploy and remove subgraphs
+    Deployer,
+    /// Can also change how deployments are indexed, e.g., by reassigning,
+    /// pausing or rewinding them
+    Operator,
+}
+
+impl AdminRole {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            AdminRole::ReadOnly => "read-only",
+            AdminRole::Deployer => "deployer",
+            AdminRole::Operator => "operator",
+        }
+    }
+
+    /// Whether this role is allowed to do things that require `required`
+    pub fn allows(&self, required: AdminRole) -> bool {
+        *self >= required
+    }
+}
+
+impl FromStr for AdminRole {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "read-only" => Ok(AdminRole::ReadOnly),
+            "deployer" => Ok(AdminRole::Deployer),
+            "operator" => Ok(AdminRole::Operator),
+            _ => Err(anyhow::anyhow!("unknown admin role `{}`", s)),
+        }
+    }
+}
+
+impl fmt::Display for AdminRole {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.as_str())
+    }
+}
+
+/// A user of the admin API. Users identify themselves either with a bearer
+/// token or with the subject of their client certificate
+#[derive(Clone, Debug)]
+pub struct AdminUser {
+    pub name: String,
+    pub role: AdminRole,
+    pub token: Option<String>,
+    pub subject: Option<String>,
+}
+
+/// How callers of the admin API are authenticated. With no users, the
+/// admin API is open to anybody who can reach it
+#[derive(Clone, Debug, Default)]
+pub struct AdminAuth {
+    pub users: Vec<AdminUser>,
+    /// The request header in which a proxy that terminates TLS passes the
+    /// subject of the verified client certificate
+    pub client_cert_header: Option<String>,
+    /// The addresses of the proxies that may connect to the admin API when
+    /// `client_cert_header` is set. Since anybody who can connect could set
+    /// the header, nobody else can connect then; without any trusted
+    /// proxies, the admin API only listens on the loopback interface
+    pub trusted_proxies: Vec<IpAddr>,
+}
+
+impl AdminAuth {
+    pub fn is_enabled(&self) -> bool {
+        !self.users.is_empty()
+    }
+
+    /// Find the user that the `token` or certificate `subject` from a
+    /// request belong to
+    pub fn authenticate(&self, token: Option<&str>, subject: Option<&str>) -> Option<&AdminUser> {
+        self.users.iter().find(|user| {
+            let by_token = match (&user.token, token) {
+                (Some(expected), Some(token)) => constant_time_eq(expected, token),
+                _ => false,
+            };
+            let by_subject = match (&user.subject, subject) {
+                (Some(expected), Some(subject)) => expected == subject,
+                _ => false,
+            };
+            by_token || by_subject
+        })
+    }
+}
+
+/// Compare two strings in time that only depends on their length so that
+/// tokens can't be guessed by timing failed attempts
+fn constant_time_eq(a: &str, b: &str) -> bool {
+    a.len() == b.len()
+        && a.bytes()
+            .zip(b.bytes())
+            .fold(0u8, |acc, (x, y)| acc | (x ^ y))
+            == 0
+}
+
+/// How a call to the admin API ended
+#[derive(Clone, Debug, PartialEq)]
+pub enum AdminOutcome {
+    Success,
+    /// The caller was not allowed to make the call
+    Denied,
+    Error(String),
+}
+
+impl AdminOutcome {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            AdminOutcome::Success => "success",
+            AdminOutcome::Denied => "denied",
+            AdminOutcome::Error(_) => "error",
+        }
+    }
+}
+
+/// An entry in the audit log of the admin API
+#[derive(Clone, Debug)]
+pub struct AdminAction {
+    /// The name of the user that made the call; `None` if authentication
+    /// is not enabled or the caller could not be authenticated
+    pub caller: Option<String>,
+    pub role: Option<AdminRole>,
+    pub method: String,
+    /// The subgraph name or deployment the call was about
+    pub subgraph: Option<String>,
+    pub params: String,
+    pub outcome: AdminOutcome,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn auth() -> AdminAuth {
+        AdminAuth {
+            users: vec![
+                AdminUser {
+                    name: "ci".to_string(),
+                    role: AdminRole::Deployer,
+                    token: Some("s3cr3t".to_string()),
+                    subject: None,
+                },
+                AdminUser {
+                    name: "ops".to_string(),
+                    role: AdminRole::Operator,
+                    token: None,
+                    subject: Some("CN=ops,O=Example".to_string()),
+                },
+            ],
+            client_cert_header: Some("X-Client-Cert-Subject".to_string()),
+            trusted_proxies: vec![],
+        }
+    }
+
+    #[test]
+    fn authenticate() {
+        let auth = auth();
+        let name = |user: Option<&AdminUser>| user.map(|user| user.name.clone());
+
+        assert_eq!(
+            Some("ci".to_string()),
+            name(auth.authenticate(Some("s3cr3t"), None))
+        );
+        assert_eq!(None, name(auth.authenticate(Some("s3cr3"), None)));
+        assert_eq!(None, name(auth.authenticate(Some("s3cr3u"), None)));
+        assert_eq!(
+            Some("ops".to_string()),
+            name(auth.authenticate(None, Some("CN=ops,O=Example")))
+        );
+        assert_eq!(None, name(auth.authenticate(None, Some("CN=ci"))));
+        assert_eq!(None, name(auth.authenticate(None, None)));
+
+        assert!(auth.is_enabled());
+        assert!(!AdminAuth::default().is_enabled());
+    }
+
+    #[test]
+    fn roles() {
+        use AdminRole::*;
+
+        assert!(Operator.allows(Deployer));
+        assert!(Operator.allows(ReadOnly));
+        assert!(Deployer.allows(Deployer));
+        assert!(!Deployer.allows(Operator));
+        assert!(!ReadOnly.allows(Deployer));
+
+        for role in &[ReadOnly, Deployer, Operator] {
+            assert_eq!(*role, AdminRole::from_str(role.as_str()).unwrap());
+        }
+    }
+}
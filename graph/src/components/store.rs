@@ -6,7 +6,7 @@ use mockall::predicate::*;
 use mockall::*;
 use serde::{Deserialize, Serialize};
 use stable_hash::prelude::*;
-use std::collections::{BTreeMap, HashMap, HashSet};
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
 use std::env;
 use std::fmt;
 use std::str::FromStr;
@@ -334,6 +334,40 @@ pub type BlockNumber = i32;
 
 pub const BLOCK_NUMBER_MAX: BlockNumber = std::i32::MAX;
 
+/// The entities of a derived field of the entity with id `value`, i.e., the
+/// entities of `entity_type` whose `entity_field` references it.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DerivedEntityQuery {
+    pub entity_type: EntityType,
+    pub entity_field: String,
+    pub value: String,
+}
+
+impl DerivedEntityQuery {
+    /// Whether `entity` references the entity with id `value`.
+    pub fn matches(&self, entity: &Entity) -> bool {
+        match entity.get(&self.entity_field) {
+            Some(Value::String(id)) => id == &self.value,
+            Some(Value::List(values)) => values.iter().any(|value| match value {
+                Value::String(id) => id == &self.value,
+                _ => false,
+            }),
+            _ => false,
+        }
+    }
+}
+
+/// How an entity changed in a block. `before` is the version that was
+/// current before the block and `after` the version the block wrote; an
+/// inserted entity has no `before` and a removed entity no `after`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct EntityVersionChange {
+    pub entity_type: EntityType,
+    pub entity_id: String,
+    pub before: Option<Entity>,
+    pub after: Option<Entity>,
+}
+
 /// A query for entities in a store.
 ///
 /// Details of how query generation for `EntityQuery` works can be found
@@ -817,6 +851,15 @@ pub struct StoredDynamicDataSource {
     pub creation_block: Option<BlockNumber>,
 }
 
+/// Identifies a dynamic data source by the template it was created from,
+/// its address and the block in which it was created
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct DynamicDataSourceKey {
+    pub name: String,
+    pub address: Address,
+    pub creation_block: BlockNumber,
+}
+
 impl From<&DataSource> for StoredDynamicDataSource {
     fn from(ds: &DataSource) -> Self {
         Self {
@@ -875,6 +918,22 @@ pub trait SubgraphStore: Send + Sync + 'static {
         ids_for_type: BTreeMap<&EntityType, Vec<&str>>,
     ) -> Result<BTreeMap<EntityType, Vec<Entity>>, StoreError>;
 
+    /// Looks up an entity using the given store key as it was at the end of
+    /// `block`.
+    fn get_at_block(
+        &self,
+        key: EntityKey,
+        block: BlockNumber,
+    ) -> Result<Option<Entity>, QueryExecutionError>;
+
+    /// Look up the entities that make up a derived field as of the latest
+    /// block, ordered by their id.
+    fn get_derived(
+        &self,
+        subgraph_id: &SubgraphDeploymentId,
+        query: &DerivedEntityQuery,
+    ) -> Result<Vec<Entity>, StoreError>;
+
     /// Queries the store for entities that match the store query.
     fn find(&self, query: EntityQuery) -> Result<Vec<Entity>, QueryExecutionError>;
 
@@ -886,7 +945,10 @@ pub trait SubgraphStore: Send + Sync + 'static {
     fn find_ens_name(&self, _hash: &str) -> Result<Option<String>, QueryExecutionError>;
 
     /// Transact the entity changes from a single block atomically into the store, and update the
-    /// subgraph block pointer to `block_ptr_to`.
+    /// subgraph block pointer to `block_ptr_to`. Changes that are still buffered from earlier
+    /// calls to `buffer_block_operations` are written in the same transaction.
+    /// `trigger_counts` is the number of triggers each dynamic data source handled in the block;
+    /// like all other changes, they are undone when the block is reverted.
     ///
     /// `block_ptr_to` must point to a child block of the current subgraph block pointer.
     fn transact_block_operations(
@@ -897,8 +959,29 @@ pub trait SubgraphStore: Send + Sync + 'static {
         stopwatch: StopwatchMetrics,
         data_sources: Vec<StoredDynamicDataSource>,
         deterministic_errors: Vec<SubgraphError>,
+        trigger_counts: HashMap<DynamicDataSourceKey, i64>,
     ) -> Result<(), StoreError>;
 
+    /// Like `transact_block_operations`, but the store may hold on to the changes and write them
+    /// together with the changes from later blocks. Until they are written, buffered changes are
+    /// visible through `get`, `get_many` and `block_ptr`, but not to GraphQL queries.
+    ///
+    /// This should only be used while the subgraph is far behind the chain head.
+    fn buffer_block_operations(
+        &self,
+        subgraph_id: SubgraphDeploymentId,
+        block_ptr_to: EthereumBlockPointer,
+        mods: Vec<EntityModification>,
+        stopwatch: StopwatchMetrics,
+        data_sources: Vec<StoredDynamicDataSource>,
+        deterministic_errors: Vec<SubgraphError>,
+        trigger_counts: HashMap<DynamicDataSourceKey, i64>,
+    ) -> Result<(), StoreError>;
+
+    /// Write all changes for the subgraph that are still buffered from calls to
+    /// `buffer_block_operations`.
+    fn flush(&self, subgraph_id: &SubgraphDeploymentId) -> Result<(), StoreError>;
+
     /// Revert the entity changes from a single block atomically in the store, and update the
     /// subgraph block pointer to `block_ptr_to`.
     ///
@@ -909,6 +992,32 @@ pub trait SubgraphStore: Send + Sync + 'static {
         block_ptr_to: EthereumBlockPointer,
     ) -> Result<(), StoreError>;
 
+    /// Store the messages that mappings logged, replacing messages that were
+    /// stored earlier for the same or later blocks. Only the most recent
+    /// messages of each subgraph are kept.
+    fn write_mapping_logs(
+        &self,
+        subgraph_id: &SubgraphDeploymentId,
+        logs: Vec<MappingLog>,
+    ) -> Result<(), StoreError>;
+
+    /// The block for which the proof of indexing events of the subgraph
+    /// should be recorded when it is processed, if that was requested.
+    fn poi_events_block(
+        &self,
+        subgraph_id: &SubgraphDeploymentId,
+    ) -> Result<Option<BlockNumber>, StoreError>;
+
+    /// Store the proof of indexing events that were recorded for `block`,
+    /// by causality region, replacing events that were stored for it
+    /// earlier. This fulfills the request to record them.
+    fn write_poi_events(
+        &self,
+        subgraph_id: &SubgraphDeploymentId,
+        block: BlockNumber,
+        events: BTreeMap<String, Vec<String>>,
+    ) -> Result<(), StoreError>;
+
     /// Find the deployment for the current version of subgraph `name` and
     /// return details about it needed for executing queries
     async fn deployment_state_from_name(
@@ -980,6 +1089,16 @@ pub trait SubgraphStore: Send + Sync + 'static {
 
     fn unassign_subgraph(&self, id: &SubgraphDeploymentId) -> Result<(), StoreError>;
 
+    /// Add `action` to the audit log of the admin API
+    fn record_admin_action(&self, action: AdminAction) -> Result<(), StoreError>;
+
+    /// Stop indexing the subgraph with `id` while keeping its assignment.
+    /// Paused subgraphs are not started until they are resumed
+    fn pause_subgraph(&self, id: &SubgraphDeploymentId) -> Result<(), StoreError>;
+
+    /// Resume indexing a subgraph that was paused with `pause_subgraph`
+    fn resume_subgraph(&self, id: &SubgraphDeploymentId) -> Result<(), StoreError>;
+
     /// Start an existing subgraph deployment.
     fn start_subgraph_deployment(
         &self,
@@ -990,6 +1109,23 @@ pub trait SubgraphStore: Send + Sync + 'static {
     /// Remove the fatal error from a subgraph and check if it is healthy or unhealthy.
     fn unfail(&self, subgraph_id: &SubgraphDeploymentId) -> Result<(), StoreError>;
 
+    /// Revert all changes the subgraph made after `block_ptr_to` and move
+    /// its block pointer back to it. The subgraph must be paused or
+    /// unassigned
+    fn rewind(
+        &self,
+        subgraph_id: &SubgraphDeploymentId,
+        block_ptr_to: EthereumBlockPointer,
+    ) -> Result<(), StoreError>;
+
+    /// Record that the subgraph failed with a non-deterministic error and
+    /// is being restarted
+    fn record_subgraph_restart(
+        &self,
+        subgraph_id: &SubgraphDeploymentId,
+        restart: SubgraphRestart,
+    ) -> Result<(), StoreError>;
+
     /// Load the dynamic data sources for the given deployment
     async fn load_dynamic_data_sources(
         &self,
@@ -1001,8 +1137,34 @@ pub trait SubgraphStore: Send + Sync + 'static {
         subgraph_id: &SubgraphDeploymentId,
     ) -> Result<Option<NodeId>, StoreError>;
 
+    /// The node the subgraph is assigned to and whether indexing it is
+    /// paused, or `None` if the subgraph is not assigned
+    fn assignment_status(
+        &self,
+        subgraph_id: &SubgraphDeploymentId,
+    ) -> Result<Option<(NodeId, bool)>, StoreError>;
+
     fn assignments(&self, node: &NodeId) -> Result<Vec<SubgraphDeploymentId>, StoreError>;
 
+    /// Record whether `node` runs an instance of the subgraph, i.e.,
+    /// whether it might still write to the subgraph
+    fn set_instance_running(
+        &self,
+        subgraph_id: &SubgraphDeploymentId,
+        node: &NodeId,
+        running: bool,
+    ) -> Result<(), StoreError>;
+
+    /// The node that runs an instance of the subgraph, if any
+    fn running_instance(
+        &self,
+        subgraph_id: &SubgraphDeploymentId,
+    ) -> Result<Option<NodeId>, StoreError>;
+
+    /// Forget about all instances that `node` ran. Nodes call this when
+    /// they start, since they can't run any instances at that point
+    fn clear_running_instances(&self, node: &NodeId) -> Result<(), StoreError>;
+
     /// Return `true` if a subgraph `name` exists, regardless of whether the
     /// subgraph has any deployments attached to it
     fn subgraph_exists(&self, name: &SubgraphName) -> Result<bool, StoreError>;
@@ -1044,6 +1206,12 @@ mock! {
             _subgraph_id: &SubgraphDeploymentId,
             _ids_for_type: BTreeMap<&'a EntityType, Vec<&'a str>>,
         ) -> Result<BTreeMap<EntityType, Vec<Entity>>, StoreError>;
+
+        fn get_derived_mock(
+            &self,
+            _subgraph_id: &SubgraphDeploymentId,
+            _query: &DerivedEntityQuery,
+        ) -> Result<Vec<Entity>, StoreError>;
     }
 }
 
@@ -1089,6 +1257,22 @@ impl SubgraphStore for MockStore {
         self.get_many_mock(subgraph_id, ids_for_type)
     }
 
+    fn get_at_block(
+        &self,
+        _key: EntityKey,
+        _block: BlockNumber,
+    ) -> Result<Option<Entity>, QueryExecutionError> {
+        unimplemented!()
+    }
+
+    fn get_derived(
+        &self,
+        subgraph_id: &SubgraphDeploymentId,
+        query: &DerivedEntityQuery,
+    ) -> Result<Vec<Entity>, StoreError> {
+        self.get_derived_mock(subgraph_id, query)
+    }
+
     fn find(&self, _query: EntityQuery) -> Result<Vec<Entity>, QueryExecutionError> {
         unimplemented!()
     }
@@ -1109,10 +1293,28 @@ impl SubgraphStore for MockStore {
         _stopwatch: StopwatchMetrics,
         _data_sources: Vec<StoredDynamicDataSource>,
         _deterministic_errors: Vec<SubgraphError>,
+        _trigger_counts: HashMap<DynamicDataSourceKey, i64>,
+    ) -> Result<(), StoreError> {
+        unimplemented!()
+    }
+
+    fn buffer_block_operations(
+        &self,
+        _subgraph_id: SubgraphDeploymentId,
+        _block_ptr_to: EthereumBlockPointer,
+        _mods: Vec<EntityModification>,
+        _stopwatch: StopwatchMetrics,
+        _data_sources: Vec<StoredDynamicDataSource>,
+        _deterministic_errors: Vec<SubgraphError>,
+        _trigger_counts: HashMap<DynamicDataSourceKey, i64>,
     ) -> Result<(), StoreError> {
         unimplemented!()
     }
 
+    fn flush(&self, _subgraph_id: &SubgraphDeploymentId) -> Result<(), StoreError> {
+        unimplemented!()
+    }
+
     fn revert_block_operations(
         &self,
         _subgraph_id: SubgraphDeploymentId,
@@ -1121,6 +1323,30 @@ impl SubgraphStore for MockStore {
         unimplemented!()
     }
 
+    fn write_mapping_logs(
+        &self,
+        _subgraph_id: &SubgraphDeploymentId,
+        _logs: Vec<MappingLog>,
+    ) -> Result<(), StoreError> {
+        unimplemented!()
+    }
+
+    fn poi_events_block(
+        &self,
+        _subgraph_id: &SubgraphDeploymentId,
+    ) -> Result<Option<BlockNumber>, StoreError> {
+        unimplemented!()
+    }
+
+    fn write_poi_events(
+        &self,
+        _subgraph_id: &SubgraphDeploymentId,
+        _block: BlockNumber,
+        _events: BTreeMap<String, Vec<String>>,
+    ) -> Result<(), StoreError> {
+        unimplemented!()
+    }
+
     async fn deployment_state_from_name(
         &self,
         _: SubgraphName,
@@ -1171,6 +1397,18 @@ impl SubgraphStore for MockStore {
         unimplemented!()
     }
 
+    fn record_admin_action(&self, _: AdminAction) -> Result<(), StoreError> {
+        unimplemented!()
+    }
+
+    fn pause_subgraph(&self, _: &SubgraphDeploymentId) -> Result<(), StoreError> {
+        unimplemented!()
+    }
+
+    fn resume_subgraph(&self, _: &SubgraphDeploymentId) -> Result<(), StoreError> {
+        unimplemented!()
+    }
+
     fn start_subgraph_deployment(
         &self,
         _logger: &Logger,
@@ -1183,6 +1421,18 @@ impl SubgraphStore for MockStore {
         unimplemented!()
     }
 
+    fn rewind(&self, _: &SubgraphDeploymentId, _: EthereumBlockPointer) -> Result<(), StoreError> {
+        unimplemented!()
+    }
+
+    fn record_subgraph_restart(
+        &self,
+        _: &SubgraphDeploymentId,
+        _: SubgraphRestart,
+    ) -> Result<(), StoreError> {
+        unimplemented!()
+    }
+
     fn is_deployment_synced(&self, _: &SubgraphDeploymentId) -> Result<bool, Error> {
         unimplemented!()
     }
@@ -1202,10 +1452,34 @@ impl SubgraphStore for MockStore {
         unimplemented!()
     }
 
+    fn assignment_status(
+        &self,
+        _: &SubgraphDeploymentId,
+    ) -> Result<Option<(NodeId, bool)>, StoreError> {
+        unimplemented!()
+    }
+
     fn assignments(&self, _: &NodeId) -> Result<Vec<SubgraphDeploymentId>, StoreError> {
         unimplemented!()
     }
 
+    fn set_instance_running(
+        &self,
+        _: &SubgraphDeploymentId,
+        _: &NodeId,
+        _: bool,
+    ) -> Result<(), StoreError> {
+        unimplemented!()
+    }
+
+    fn running_instance(&self, _: &SubgraphDeploymentId) -> Result<Option<NodeId>, StoreError> {
+        unimplemented!()
+    }
+
+    fn clear_running_instances(&self, _: &NodeId) -> Result<(), StoreError> {
+        unimplemented!()
+    }
+
     fn subgraph_exists(&self, _: &SubgraphName) -> Result<bool, StoreError> {
         unimplemented!()
     }
@@ -1406,6 +1680,40 @@ pub trait StatusStore: Send + Sync + 'static {
         indexer: &'a Option<Address>,
         block: EthereumBlockPointer,
     ) -> DynTryFuture<'a, Option<[u8; 32]>>;
+
+    /// The stored messages of the mappings of the subgraph that are at least
+    /// as severe as `level`, optionally limited to a range of blocks, in the
+    /// order in which they were logged.
+    fn mapping_logs(
+        &self,
+        subgraph_id: &SubgraphDeploymentId,
+        level: MappingLogLevel,
+        from_block: Option<BlockNumber>,
+        to_block: Option<BlockNumber>,
+    ) -> Result<Vec<MappingLog>, StoreError>;
+
+    /// The data sources that were created from templates, in the order in
+    /// which they were created.
+    fn dynamic_data_sources(
+        &self,
+        subgraph_id: &SubgraphDeploymentId,
+    ) -> Result<Vec<status::DataSource>, StoreError>;
+
+    /// The entities that were inserted, updated or removed in `block`,
+    /// ordered by entity type and id.
+    fn entity_changes_in_block(
+        &self,
+        subgraph_id: &SubgraphDeploymentId,
+        block: BlockNumber,
+    ) -> Result<Vec<EntityVersionChange>, StoreError>;
+
+    /// The latest `first` entries in the health history of the subgraph,
+    /// from oldest to newest.
+    fn health_history(
+        &self,
+        subgraph_id: &SubgraphDeploymentId,
+        first: i64,
+    ) -> Result<Vec<HealthEvent>, StoreError>;
 }
 
 /// An entity operation that can be transacted into the store; as opposed to
@@ -1501,6 +1809,11 @@ pub struct EntityCache {
 
     data_sources: Vec<StoredDynamicDataSource>,
 
+    /// How many lookups were answered from `current`, and how many had to
+    /// go to the store
+    hits: usize,
+    misses: usize,
+
     /// The store is only used to read entities.
     pub store: Arc<dyn SubgraphStore>,
 }
@@ -1518,6 +1831,10 @@ pub struct ModificationsAndCache {
     pub modifications: Vec<EntityModification>,
     pub data_sources: Vec<StoredDynamicDataSource>,
     pub entity_lfu_cache: LfuCache<EntityKey, Option<Entity>>,
+    /// The number of entity lookups that were answered from the cache
+    pub cache_hits: usize,
+    /// The number of entity lookups that had to go to the store
+    pub cache_misses: usize,
 }
 
 impl EntityCache {
@@ -1528,6 +1845,8 @@ impl EntityCache {
             handler_updates: HashMap::new(),
             in_handler: false,
             data_sources: vec![],
+            hits: 0,
+            misses: 0,
             store,
         }
     }
@@ -1542,10 +1861,18 @@ impl EntityCache {
             handler_updates: HashMap::new(),
             in_handler: false,
             data_sources: vec![],
+            hits: 0,
+            misses: 0,
             store,
         }
     }
 
+    /// The number of lookups so far that were answered from the cache, and
+    /// the number that had to go to the store
+    pub fn cache_stats(&self) -> (usize, usize) {
+        (self.hits, self.misses)
+    }
+
     pub(crate) fn enter_handler(&mut self) {
         assert!(!self.in_handler);
         self.in_handler = true;
@@ -1570,6 +1897,11 @@ impl EntityCache {
 
     pub fn get(&mut self, key: &EntityKey) -> Result<Option<Entity>, QueryExecutionError> {
         // Get the current entity, apply any updates from `updates`, then from `handler_updates`.
+        if self.current.contains_key(key) {
+            self.hits += 1;
+        } else {
+            self.misses += 1;
+        }
         let mut entity = self.current.get_entity(&*self.store, &key)?;
         if let Some(op) = self.updates.get(&key).cloned() {
             entity = op.apply_to(entity)
@@ -1580,6 +1912,51 @@ impl EntityCache {
         Ok(entity)
     }
 
+    /// Load the entities of a derived field with the changes in this cache
+    /// applied, ordered by their id.
+    pub fn load_related(
+        &mut self,
+        subgraph_id: &SubgraphDeploymentId,
+        query: &DerivedEntityQuery,
+    ) -> Result<Vec<Entity>, QueryExecutionError> {
+        let mut keys = BTreeSet::new();
+        self.misses += 1;
+        for mut entity in self.store.get_derived(subgraph_id, query)? {
+            // `__typename` is for queries not for mappings.
+            entity.remove("__typename");
+            let key = EntityKey {
+                subgraph_id: subgraph_id.clone(),
+                entity_type: query.entity_type.clone(),
+                entity_id: entity
+                    .id()
+                    .map_err(|e| QueryExecutionError::StoreError(e.into()))?,
+            };
+            self.current.insert(key.clone(), Some(entity));
+            keys.insert(key);
+        }
+
+        // Entities that were changed since may reference the entity now, or
+        // not anymore.
+        keys.extend(
+            self.updates
+                .keys()
+                .chain(self.handler_updates.keys())
+                .filter(|key| {
+                    &key.subgraph_id == subgraph_id && key.entity_type == query.entity_type
+                })
+                .cloned(),
+        );
+
+        let mut entities = Vec::new();
+        for key in keys {
+            match self.get(&key)? {
+                Some(entity) if query.matches(&entity) => entities.push(entity),
+                _ => (),
+            }
+        }
+        Ok(entities)
+    }
+
     pub fn remove(&mut self, key: EntityKey) {
         self.entity_op(key, EntityOp::Remove);
     }
@@ -1628,6 +2005,8 @@ impl EntityCache {
         assert!(!other.in_handler);
 
         self.current.extend(other.current);
+        self.hits += other.hits;
+        self.misses += other.misses;
         for (key, op) in other.updates {
             self.entity_op(key, op);
         }
@@ -1646,6 +2025,8 @@ impl EntityCache {
 
         // The first step is to make sure all entities being set are in `self.current`.
         // For each subgraph, we need a map of entity type to missing entity ids.
+        // Loading them does not count towards the cache hits and misses,
+        // which only track lookups the mappings make
         let missing = self
             .updates
             .keys()
@@ -1724,6 +2105,8 @@ impl EntityCache {
             modifications: mods,
             data_sources: self.data_sources,
             entity_lfu_cache: self.current,
+            cache_hits: self.hits,
+            cache_misses: self.misses,
         })
     }
 }
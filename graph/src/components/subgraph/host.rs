@@ -8,9 +8,9 @@ use async_trait::async_trait;
 use futures::sync::mpsc;
 
 use crate::components::metrics::HistogramVec;
-use crate::components::subgraph::SharedProofOfIndexing;
+use crate::components::subgraph::{DeploymentProfile, SharedProofOfIndexing};
 use crate::prelude::*;
-use web3::types::{Log, Transaction};
+use web3::types::{Address, Log, Transaction};
 
 #[derive(Debug)]
 pub enum MappingError {
@@ -82,12 +82,22 @@ pub trait RuntimeHost: Send + Sync + Debug + 'static {
     /// Block number in which this host was created.
     /// Returns `None` for static data sources.
     fn creation_block_number(&self) -> Option<BlockNumber>;
+
+    /// The name of the data source, or of the template it was created from.
+    fn data_source_name(&self) -> &str;
+
+    /// The address of the contract the data source handles triggers for.
+    fn data_source_address(&self) -> Option<Address>;
 }
 
 pub struct HostMetrics {
     handler_execution_time: Box<HistogramVec>,
     host_fn_execution_time: Box<HistogramVec>,
+    handler_gas_used: Box<HistogramVec>,
     pub stopwatch: StopwatchMetrics,
+
+    /// Set if the deployment is profiled, see `MappingProfiler`
+    pub profile: Option<Arc<DeploymentProfile>>,
 }
 
 impl fmt::Debug for HostMetrics {
@@ -102,6 +112,7 @@ impl HostMetrics {
         registry: Arc<impl MetricsRegistry>,
         subgraph: &str,
         stopwatch: StopwatchMetrics,
+        profile: Option<Arc<DeploymentProfile>>,
     ) -> Self {
         let handler_execution_time = registry
             .new_deployment_histogram_vec(
@@ -121,10 +132,21 @@ impl HostMetrics {
                 vec![0.025, 0.05, 0.2, 2.0, 8.0, 20.0],
             )
             .expect("failed to create `deployment_host_fn_execution_time` histogram");
+        let handler_gas_used = registry
+            .new_deployment_histogram_vec(
+                "deployment_handler_gas_used",
+                "Measures the gas used by handlers",
+                subgraph,
+                vec![String::from("handler")],
+                vec![1e4, 1e5, 1e6, 1e7, 1e8, 1e9, 1e10],
+            )
+            .expect("failed to create `deployment_handler_gas_used` histogram");
         Self {
             handler_execution_time,
             host_fn_execution_time,
+            handler_gas_used,
             stopwatch,
+            profile,
         }
     }
 
@@ -134,6 +156,12 @@ impl HostMetrics {
             .observe(duration);
     }
 
+    pub fn observe_handler_gas_used(&self, gas: u64, handler: &str) {
+        self.handler_gas_used
+            .with_label_values(&[handler][..])
+            .observe(gas as f64);
+    }
+
     pub fn observe_host_fn_execution_time(&self, duration: f64, fn_name: &str) {
         self.host_fn_execution_time
             .with_label_values(&[fn_name][..])
@@ -186,6 +214,7 @@ pub trait RuntimeHostBuilder: Clone + Send + Sync + 'static {
     /// cached and shared among mappings that use the same wasm file.
     fn spawn_mapping(
         raw_module: Vec<u8>,
+        language: MappingLanguage,
         logger: Logger,
         subgraph_id: SubgraphDeploymentId,
         metrics: Arc<HostMetrics>,
@@ -1,11 +1,14 @@
 use async_trait::async_trait;
 use web3::types::Log;
 
+use std::collections::HashMap;
+
 use crate::prelude::*;
 use crate::util::lfu_cache::LfuCache;
 use crate::{
+    components::store::DynamicDataSourceKey,
     components::subgraph::{MappingError, SharedProofOfIndexing},
-    data::subgraph::schema::SubgraphError,
+    data::subgraph::schema::{MappingLog, SubgraphError},
 };
 
 #[derive(Clone, Debug)]
@@ -21,6 +24,12 @@ pub struct DataSourceTemplateInfo {
 pub struct BlockState {
     pub entity_cache: EntityCache,
     pub deterministic_errors: Vec<SubgraphError>,
+
+    // Messages that handlers logged, including those of handlers that failed.
+    pub mapping_logs: Vec<MappingLog>,
+
+    // How many triggers each dynamic data source handled.
+    pub trigger_counts: HashMap<DynamicDataSourceKey, i64>,
     created_data_sources: Vec<DataSourceTemplateInfo>,
 
     // Data sources created in the current handler.
@@ -38,6 +47,8 @@ impl BlockState {
         BlockState {
             entity_cache: EntityCache::with_current(store, lfu_cache),
             deterministic_errors: Vec::new(),
+            mapping_logs: Vec::new(),
+            trigger_counts: HashMap::new(),
             created_data_sources: Vec::new(),
             handler_created_data_sources: Vec::new(),
             in_handler: false,
@@ -50,6 +61,8 @@ impl BlockState {
         let BlockState {
             entity_cache,
             deterministic_errors,
+            mapping_logs,
+            trigger_counts,
             created_data_sources,
             handler_created_data_sources,
             in_handler,
@@ -60,6 +73,10 @@ impl BlockState {
             false => created_data_sources.extend(other.created_data_sources),
         }
         deterministic_errors.extend(other.deterministic_errors);
+        mapping_logs.extend(other.mapping_logs);
+        for (key, count) in other.trigger_counts {
+            *trigger_counts.entry(key).or_default() += count;
+        }
         entity_cache.extend(other.entity_cache);
     }
 
@@ -1,6 +1,8 @@
 mod host;
 mod instance;
 mod instance_manager;
+mod profiler;
+mod progress;
 mod proof_of_indexing;
 mod provider;
 mod registrar;
@@ -10,6 +12,10 @@ pub use crate::prelude::Entity;
 pub use self::host::{HostMetrics, MappingError, RuntimeHost, RuntimeHostBuilder};
 pub use self::instance::{BlockState, DataSourceTemplateInfo, SubgraphInstance};
 pub use self::instance_manager::SubgraphInstanceManager;
+pub use self::profiler::{
+    DeploymentProfile, HandlerProfile, HostFnProfile, MappingProfiler, Profile,
+};
+pub use self::progress::{DeploymentProgress, IndexingProgress, IndexingRate};
 pub use self::proof_of_indexing::{
     BlockEventStream, ProofOfIndexing, ProofOfIndexingEvent, ProofOfIndexingFinisher,
     SharedProofOfIndexing,
@@ -0,0 +1,216 @@
+//! Opt-in profiling of mapping execution, broken down by handler and by the host functions
+//! each handler calls.
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::env;
+use std::fmt::Write;
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::Duration;
+
+use crate::data::graphql::{object, IntoValue};
+use crate::prelude::{lazy_static, q, SubgraphDeploymentId};
+
+lazy_static! {
+    /// The deployments for which mapping execution is profiled, as a comma-separated list of
+    /// deployment ids
+    static ref PROFILED_DEPLOYMENTS: HashSet<String> = env::var("GRAPH_MAPPING_PROFILE")
+        .map(|s| {
+            s.split(',')
+                .map(str::trim)
+                .filter(|id| !id.is_empty())
+                .map(str::to_string)
+                .collect()
+        })
+        .unwrap_or_default();
+}
+
+/// Collects execution profiles of the mappings of deployments that opted into profiling.
+/// Profiles are kept in memory and start out empty whenever the node starts.
+pub struct MappingProfiler {
+    deployments: HashSet<String>,
+    profiles: RwLock<HashMap<SubgraphDeploymentId, Arc<DeploymentProfile>>>,
+}
+
+impl MappingProfiler {
+    /// Profile the deployments listed in `GRAPH_MAPPING_PROFILE`.
+    pub fn from_env() -> Self {
+        Self::new(PROFILED_DEPLOYMENTS.clone())
+    }
+
+    pub fn new(deployments: HashSet<String>) -> Self {
+        MappingProfiler {
+            deployments,
+            profiles: RwLock::new(HashMap::new()),
+        }
+    }
+
+    /// The profile that the mappings of `deployment` should record into, or `None` if the
+    /// deployment is not profiled.
+    pub fn start(&self, deployment: &SubgraphDeploymentId) -> Option<Arc<DeploymentProfile>> {
+        if !self.deployments.contains(deployment.as_str()) {
+            return None;
+        }
+        let mut profiles = self.profiles.write().unwrap();
+        Some(profiles.entry(deployment.clone()).or_default().clone())
+    }
+
+    /// The profile recorded so far for `deployment`.
+    pub fn profile(&self, deployment: &SubgraphDeploymentId) -> Option<Profile> {
+        let profiles = self.profiles.read().unwrap();
+        profiles.get(deployment).map(|profile| Profile {
+            deployment: deployment.clone(),
+            handlers: profile.handlers.lock().unwrap().clone(),
+        })
+    }
+}
+
+/// The profiles of all handlers of a deployment.
+#[derive(Default)]
+pub struct DeploymentProfile {
+    handlers: Mutex<BTreeMap<String, HandlerProfile>>,
+}
+
+impl DeploymentProfile {
+    /// Add the profile of one or more calls of `handler`.
+    pub fn record(&self, handler: &str, profile: HandlerProfile) {
+        let mut handlers = self.handlers.lock().unwrap();
+        handlers
+            .entry(handler.to_string())
+            .or_default()
+            .merge(profile);
+    }
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct HandlerProfile {
+    pub calls: u64,
+    pub wall_time: Duration,
+    pub host_fns: BTreeMap<String, HostFnProfile>,
+    pub entity_cache_hits: u64,
+    pub entity_cache_misses: u64,
+}
+
+impl HandlerProfile {
+    pub fn record_host_fn(&mut self, name: &str, duration: Duration) {
+        let host_fn = self.host_fns.entry(name.to_string()).or_default();
+        host_fn.calls += 1;
+        host_fn.time += duration;
+    }
+
+    /// The time spent in the handler outside of host functions.
+    pub fn self_time(&self) -> Duration {
+        let host_fn_time: Duration = self.host_fns.values().map(|host_fn| host_fn.time).sum();
+        self.wall_time.checked_sub(host_fn_time).unwrap_or_default()
+    }
+
+    fn merge(&mut self, other: HandlerProfile) {
+        self.calls += other.calls;
+        self.wall_time += other.wall_time;
+        self.entity_cache_hits += other.entity_cache_hits;
+        self.entity_cache_misses += other.entity_cache_misses;
+        for (name, host_fn) in other.host_fns {
+            let entry = self.host_fns.entry(name).or_default();
+            entry.calls += host_fn.calls;
+            entry.time += host_fn.time;
+        }
+    }
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct HostFnProfile {
+    pub calls: u64,
+    pub time: Duration,
+}
+
+/// A snapshot of the profile of a deployment.
+#[derive(Clone, Debug)]
+pub struct Profile {
+    pub deployment: SubgraphDeploymentId,
+    pub handlers: BTreeMap<String, HandlerProfile>,
+}
+
+impl Profile {
+    /// The profile in the folded stacks format that `flamegraph.pl` and `inferno` read, with one
+    /// line per handler and per host function called from it. Times are in microseconds.
+    pub fn folded_stacks(&self) -> String {
+        let mut out = String::new();
+        for (handler, profile) in &self.handlers {
+            writeln!(out, "{} {}", handler, profile.self_time().as_micros()).unwrap();
+            for (name, host_fn) in &profile.host_fns {
+                writeln!(out, "{};{} {}", handler, name, host_fn.time.as_micros()).unwrap();
+            }
+        }
+        out
+    }
+}
+
+impl IntoValue for Profile {
+    fn into_value(self) -> q::Value {
+        let flamegraph = self.folded_stacks();
+        let Profile {
+            deployment,
+            handlers,
+        } = self;
+        object! {
+            __typename: "MappingProfile",
+            subgraph: deployment.to_string(),
+            handlers: handlers
+                .into_iter()
+                .map(|(handler, profile)| object! {
+                    __typename: "HandlerProfile",
+                    handler: handler,
+                    calls: format!("{}", profile.calls),
+                    wallTimeMicros: format!("{}", profile.wall_time.as_micros()),
+                    entityCacheHits: format!("{}", profile.entity_cache_hits),
+                    entityCacheMisses: format!("{}", profile.entity_cache_misses),
+                    hostFunctions: profile
+                        .host_fns
+                        .into_iter()
+                        .map(|(name, host_fn)| object! {
+                            __typename: "HostFunctionProfile",
+                            name: name,
+                            calls: format!("{}", host_fn.calls),
+                            timeMicros: format!("{}", host_fn.time.as_micros()),
+                        })
+                        .collect::<Vec<_>>(),
+                })
+                .collect::<Vec<_>>(),
+            flamegraph: flamegraph,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn folded_stacks() {
+        let profiler = MappingProfiler::new(vec!["Qmprofiled".to_string()].into_iter().collect());
+        let profiled = SubgraphDeploymentId::new("Qmprofiled").unwrap();
+        let other = SubgraphDeploymentId::new("Qmother").unwrap();
+        assert!(profiler.start(&other).is_none());
+
+        let profile = profiler.start(&profiled).unwrap();
+        for _ in 0..2 {
+            let mut handler = HandlerProfile {
+                calls: 1,
+                wall_time: Duration::from_micros(100),
+                entity_cache_hits: 1,
+                ..Default::default()
+            };
+            handler.record_host_fn("store.get", Duration::from_micros(30));
+            profile.record("handleTransfer", handler);
+        }
+
+        let profile = profiler.profile(&profiled).unwrap();
+        let handler = &profile.handlers["handleTransfer"];
+        assert_eq!(handler.calls, 2);
+        assert_eq!(handler.entity_cache_hits, 2);
+        assert_eq!(handler.host_fns["store.get"].calls, 2);
+        assert_eq!(
+            profile.folded_stacks(),
+            "handleTransfer 140\nhandleTransfer;store.get 60\n"
+        );
+        assert!(profiler.profile(&other).is_none());
+    }
+}
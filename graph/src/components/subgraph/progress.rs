@@ -0,0 +1,221 @@
+//! Rolling estimates of how fast the deployments that this node indexes are
+//! making progress.
+use std::collections::{HashMap, VecDeque};
+use std::env;
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::{Duration, Instant};
+
+use crate::data::graphql::{object, IntoValue};
+use crate::prelude::{lazy_static, q, BlockNumber, SubgraphDeploymentId};
+
+lazy_static! {
+    /// The window, in seconds, over which indexing rates are averaged
+    static ref INDEXING_RATE_WINDOW: Duration = env::var("GRAPH_INDEXING_RATE_WINDOW")
+        .ok()
+        .map(|s| {
+            s.parse::<u64>().unwrap_or_else(|_| {
+                panic!(
+                    "GRAPH_INDEXING_RATE_WINDOW must be a number of seconds, but is `{}`",
+                    s
+                )
+            })
+        })
+        .map(Duration::from_secs)
+        .unwrap_or(Duration::from_secs(300));
+}
+
+/// Samples that are closer together than this are merged into one
+const SAMPLE_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Tracks the indexing progress of the deployments that this node indexes.
+/// Progress is kept in memory and starts out empty whenever a deployment is
+/// started.
+#[derive(Default)]
+pub struct IndexingProgress {
+    deployments: RwLock<HashMap<SubgraphDeploymentId, Arc<DeploymentProgress>>>,
+}
+
+impl IndexingProgress {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Start tracking `deployment`, forgetting any progress recorded
+    /// earlier.
+    pub fn start(&self, deployment: &SubgraphDeploymentId) -> Arc<DeploymentProgress> {
+        let progress = Arc::new(DeploymentProgress::new(*INDEXING_RATE_WINDOW));
+        let mut deployments = self.deployments.write().unwrap();
+        deployments.insert(deployment.clone(), progress.clone());
+        progress
+    }
+
+    /// Stop tracking `deployment`.
+    pub fn stop(&self, deployment: &SubgraphDeploymentId) {
+        let mut deployments = self.deployments.write().unwrap();
+        deployments.remove(deployment);
+    }
+
+    /// The current indexing rate of `deployment`, or `None` if this node
+    /// does not index it.
+    pub fn rate(&self, deployment: &SubgraphDeploymentId) -> Option<IndexingRate> {
+        let deployments = self.deployments.read().unwrap();
+        deployments.get(deployment).map(|progress| progress.rate())
+    }
+}
+
+struct Sample {
+    at: Instant,
+    /// The last block processed in this sample
+    block: BlockNumber,
+    /// The number of triggers processed in this sample
+    triggers: u64,
+}
+
+/// The blocks that one deployment processed recently.
+pub struct DeploymentProgress {
+    window: Duration,
+    samples: Mutex<VecDeque<Sample>>,
+}
+
+impl DeploymentProgress {
+    fn new(window: Duration) -> Self {
+        DeploymentProgress {
+            window,
+            samples: Mutex::new(VecDeque::new()),
+        }
+    }
+
+    /// Record that the deployment processed all blocks up to and including
+    /// `block`, and that `block` had `triggers` triggers.
+    pub fn record(&self, block: BlockNumber, triggers: usize) {
+        self.record_at(Instant::now(), block, triggers)
+    }
+
+    fn record_at(&self, at: Instant, block: BlockNumber, triggers: usize) {
+        let mut samples = self.samples.lock().unwrap();
+        match samples.back_mut() {
+            Some(last) if at.duration_since(last.at) < SAMPLE_INTERVAL => {
+                last.block = block;
+                last.triggers += triggers as u64;
+            }
+            _ => samples.push_back(Sample {
+                at,
+                block,
+                triggers: triggers as u64,
+            }),
+        }
+        Self::prune(&mut samples, at, self.window);
+    }
+
+    /// Remove samples that are outside the window, but keep the last one
+    /// before the window so we know where the window starts
+    fn prune(samples: &mut VecDeque<Sample>, now: Instant, window: Duration) {
+        while samples.len() > 1 && now.duration_since(samples[1].at) > window {
+            samples.pop_front();
+        }
+    }
+
+    /// The indexing rate over the window. If the deployment stops making
+    /// progress, the rate goes down to zero over the window.
+    pub fn rate(&self) -> IndexingRate {
+        self.rate_at(Instant::now())
+    }
+
+    fn rate_at(&self, now: Instant) -> IndexingRate {
+        let mut samples = self.samples.lock().unwrap();
+        Self::prune(&mut samples, now, self.window);
+
+        let (first, last) = match (samples.front(), samples.back()) {
+            (Some(first), Some(last)) => (first, last),
+            _ => return IndexingRate::default(),
+        };
+        let secs = now.duration_since(first.at).as_secs_f64();
+        if secs <= 0.0 {
+            return IndexingRate::default();
+        }
+        // The triggers of the first sample were processed before the
+        // window starts
+        let triggers: u64 = samples.iter().skip(1).map(|sample| sample.triggers).sum();
+        IndexingRate {
+            blocks_per_second: (last.block - first.block).max(0) as f64 / secs,
+            triggers_per_second: triggers as f64 / secs,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct IndexingRate {
+    pub blocks_per_second: f64,
+    pub triggers_per_second: f64,
+}
+
+impl IndexingRate {
+    /// How long it will take to process `blocks` more blocks at this rate,
+    /// or `None` if the deployment is not making progress.
+    pub fn time_to_process(&self, blocks: BlockNumber) -> Option<Duration> {
+        if blocks <= 0 {
+            Some(Duration::from_secs(0))
+        } else if self.blocks_per_second > 0.0 {
+            Some(Duration::from_secs_f64(
+                blocks as f64 / self.blocks_per_second,
+            ))
+        } else {
+            None
+        }
+    }
+}
+
+impl IntoValue for IndexingRate {
+    fn into_value(self) -> q::Value {
+        object! {
+            __typename: "IndexingRate",
+            blocksPerSecond: self.blocks_per_second,
+            triggersPerSecond: self.triggers_per_second,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rate_over_window() {
+        let start = Instant::now();
+        let secs = |n| start + Duration::from_secs(n);
+        let progress = DeploymentProgress::new(Duration::from_secs(10));
+
+        assert_eq!(IndexingRate::default(), progress.rate_at(start));
+
+        progress.record_at(start, 100, 7);
+        for i in 1..=10 {
+            progress.record_at(secs(i), 100 + 5 * i as BlockNumber, 2);
+        }
+        let rate = progress.rate_at(secs(10));
+        assert_eq!(5.0, rate.blocks_per_second);
+        assert_eq!(2.0, rate.triggers_per_second);
+        assert_eq!(Some(Duration::from_secs(20)), rate.time_to_process(100));
+
+        // Samples that fall out of the window are forgotten, and the rate
+        // goes down when no more blocks are processed
+        progress.record_at(secs(20), 200, 20);
+        let rate = progress.rate_at(secs(20));
+        assert_eq!(5.0, rate.blocks_per_second);
+        assert_eq!(2.0, rate.triggers_per_second);
+
+        let rate = progress.rate_at(secs(100));
+        assert_eq!(IndexingRate::default(), rate);
+        assert_eq!(None, rate.time_to_process(100));
+        assert_eq!(Some(Duration::from_secs(0)), rate.time_to_process(0));
+    }
+
+    #[test]
+    fn samples_are_merged() {
+        let start = Instant::now();
+        let progress = DeploymentProgress::new(Duration::from_secs(10));
+        for i in 0..100 {
+            progress.record_at(start + Duration::from_millis(10 * i), i as BlockNumber, 1);
+        }
+        assert_eq!(1, progress.samples.lock().unwrap().len());
+    }
+}
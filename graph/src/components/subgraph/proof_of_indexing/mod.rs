@@ -261,4 +261,61 @@ mod tests {
             }
         }
     }
+
+    /// Recording events must not change the proof of indexing
+    #[test]
+    fn recording() {
+        let logger = Logger::root(Discard, o!());
+        let data = hashmap! {
+            "val".to_owned() => Value::Int(1)
+        };
+        let events = vec![
+            (
+                "eth",
+                ProofOfIndexingEvent::RemoveEntity {
+                    entity_type: "t",
+                    id: "a",
+                },
+            ),
+            (
+                "ipfs",
+                ProofOfIndexingEvent::SetEntity {
+                    entity_type: "t",
+                    id: "b",
+                    data: &data,
+                },
+            ),
+            (
+                "eth",
+                ProofOfIndexingEvent::SetEntity {
+                    entity_type: "t",
+                    id: "c",
+                    data: &data,
+                },
+            ),
+        ];
+
+        let mut plain = ProofOfIndexing::new(3);
+        let mut recording = ProofOfIndexing::recording(3);
+        assert!(!plain.is_recording());
+        assert!(recording.is_recording());
+        for (region, event) in &events {
+            plain.write(&logger, region, event);
+            recording.write(&logger, region, event);
+        }
+
+        let mut plain = plain.take();
+        let mut recording = recording.take();
+        for region in &["eth", "ipfs"] {
+            let plain = plain.remove(*region).unwrap();
+            let mut recording = recording.remove(*region).unwrap();
+            let expected: Vec<_> = events
+                .iter()
+                .filter(|(name, _)| name == region)
+                .map(|(_, event)| format!("{:?}", event))
+                .collect();
+            assert_eq!(Some(expected), recording.take_events());
+            assert_eq!(plain.pause(None), recording.pause(None));
+        }
+    }
 }
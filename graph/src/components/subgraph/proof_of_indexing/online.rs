@@ -24,6 +24,8 @@ pub struct BlockEventStream {
     vec_length: usize,
     seq_no: Blake3SeqNo,
     digest: SetHasher,
+    /// The events that were written to the stream, if it records them
+    events: Option<Vec<String>>,
 }
 
 /// Go directly to a SequenceNumber identifying a field within a struct.
@@ -65,7 +67,7 @@ fn traverse_seq_no(counts: &[usize]) -> Blake3SeqNo {
 }
 
 impl BlockEventStream {
-    fn new(block_number: BlockNumber) -> Self {
+    fn new(block_number: BlockNumber, record: bool) -> Self {
         let events = traverse_seq_no(&[
             1,                                // kvp -> v
             0,                                // CausalityRegion.blocks: Vec<Block>
@@ -76,9 +78,16 @@ impl BlockEventStream {
             vec_length: 0,
             seq_no: events,
             digest: SetHasher::new(),
+            events: if record { Some(Vec::new()) } else { None },
         }
     }
 
+    /// The events that were written to the stream, formatted like they are
+    /// logged with `GRAPH_LOG_POI_EVENTS`, if the stream records them
+    pub fn take_events(&mut self) -> Option<Vec<String>> {
+        self.events.take()
+    }
+
     /// Finishes the current block and returns the serialized hash function to
     /// be resumed later. Cases in which the hash function is resumed include
     /// when asking for the final PoI, or when combining with the next modified
@@ -94,6 +103,9 @@ impl BlockEventStream {
     }
 
     fn write(&mut self, event: &ProofOfIndexingEvent<'_>) {
+        if let Some(events) = &mut self.events {
+            events.push(format!("{:?}", event));
+        }
         self.vec_length += 1;
         event.stable_hash(self.seq_no.next_child(), &mut self.digest);
     }
@@ -107,6 +119,9 @@ pub struct ProofOfIndexing {
     /// state with other data sources. This may also give us some freedom to change
     /// the order of triggers in the future.
     per_causality_region: HashMap<String, BlockEventStream>,
+    /// Whether to keep the events for each causality region, so that they
+    /// can be compared with those of another indexer
+    record: bool,
 }
 
 impl fmt::Debug for ProofOfIndexing {
@@ -120,8 +135,22 @@ impl ProofOfIndexing {
         Self {
             block_number,
             per_causality_region: HashMap::new(),
+            record: false,
         }
     }
+
+    /// Like `new`, but keep the events that are written for each causality
+    /// region; see `BlockEventStream::take_events`
+    pub fn recording(block_number: BlockNumber) -> Self {
+        Self {
+            record: true,
+            ..Self::new(block_number)
+        }
+    }
+
+    pub fn is_recording(&self) -> bool {
+        self.record
+    }
     /// Adds an event to the digest of the ProofOfIndexingStream local to the causality region
     pub fn write(
         &mut self,
@@ -142,7 +171,7 @@ impl ProofOfIndexing {
         if let Some(causality_region) = self.per_causality_region.get_mut(causality_region) {
             causality_region.write(event);
         } else {
-            let mut entry = BlockEventStream::new(self.block_number);
+            let mut entry = BlockEventStream::new(self.block_number, self.record);
             entry.write(event);
             self.per_causality_region
                 .insert(causality_region.to_owned(), entry);
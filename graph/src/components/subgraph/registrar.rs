@@ -40,4 +40,37 @@ pub trait SubgraphRegistrar: Send + Sync + 'static {
         hash: SubgraphDeploymentId,
         node_id: NodeId,
     ) -> Result<(), SubgraphRegistrarError>;
+
+    /// Stop indexing the deployment `hash` on the node it is assigned to
+    /// without unassigning it
+    async fn pause_subgraph(
+        &self,
+        hash: SubgraphDeploymentId,
+    ) -> Result<(), SubgraphRegistrarError>;
+
+    /// Continue indexing the deployment `hash` after it was paused
+    async fn resume_subgraph(
+        &self,
+        hash: SubgraphDeploymentId,
+    ) -> Result<(), SubgraphRegistrarError>;
+
+    /// Stop indexing the deployment `hash` by removing its assignment
+    async fn unassign_subgraph(
+        &self,
+        hash: SubgraphDeploymentId,
+    ) -> Result<(), SubgraphRegistrarError>;
+
+    /// Clear the fatal error of the deployment `hash`
+    async fn unfail_subgraph(
+        &self,
+        hash: SubgraphDeploymentId,
+    ) -> Result<(), SubgraphRegistrarError>;
+
+    /// Revert the deployment `hash` to the block `block_ptr`. The
+    /// deployment must be paused or unassigned
+    async fn rewind_subgraph(
+        &self,
+        hash: SubgraphDeploymentId,
+        block_ptr: EthereumBlockPointer,
+    ) -> Result<(), SubgraphRegistrarError>;
 }
@@ -184,6 +184,7 @@ impl DocumentExt for Document {
 
 pub trait TypeExt {
     fn get_base_type(&self) -> &String;
+    fn is_list(&self) -> bool;
 }
 
 impl TypeExt for Type {
@@ -194,6 +195,14 @@ impl TypeExt for Type {
             Type::ListType(inner) => Self::get_base_type(&inner),
         }
     }
+
+    fn is_list(&self) -> bool {
+        match self {
+            Type::NamedType(_) => false,
+            Type::NonNullType(inner) => inner.is_list(),
+            Type::ListType(_) => true,
+        }
+    }
 }
 
 pub trait DirectiveExt {
@@ -101,6 +101,10 @@ pub enum SchemaValidationError {
     FulltextIncludedFieldMissingRequiredProperty,
     #[error("Fulltext entity field, {0}, not found or not a string")]
     FulltextIncludedFieldInvalid(String),
+    #[error("Type `{0}` has an invalid @index directive: {1}")]
+    InvalidIndexDirective(String, String), // (type, reason)
+    #[error("Field `{1}` in type `{0}` has invalid @noIndex: {2}")]
+    InvalidNoIndex(String, String, String), // (type, field, reason)
 }
 
 #[derive(Clone, Debug, PartialEq)]
@@ -254,6 +258,44 @@ impl From<&s::Directive> for FulltextDefinition {
         }
     }
 }
+/// A composite index over several attributes of an entity type, declared
+/// with `@index(fields: ["a", "b"])` on the type
+#[derive(Clone, Debug, PartialEq)]
+pub struct IndexDefinition {
+    /// The GraphQL names of the fields in the index, in index order
+    pub fields: Vec<String>,
+}
+
+impl IndexDefinition {
+    /// Read the `@index` directive `directive` on `entity`. Schemas are
+    /// validated before we get here, but we still report a malformed
+    /// directive as a validation error rather than panicking
+    fn from_directive(
+        entity: &ObjectType,
+        directive: &Directive,
+    ) -> Result<Self, SchemaValidationError> {
+        let invalid = |reason: &str| {
+            SchemaValidationError::InvalidIndexDirective(entity.name.to_owned(), reason.to_owned())
+        };
+        let fields = match directive.argument("fields") {
+            Some(Value::List(fields)) if !fields.is_empty() => fields,
+            _ => {
+                return Err(invalid(
+                    "the `fields` argument must be a non-empty list of field names",
+                ))
+            }
+        };
+        let fields = fields
+            .iter()
+            .map(|field| match field {
+                Value::String(name) => Ok(name.clone()),
+                _ => Err(invalid("the `fields` argument must only contain strings")),
+            })
+            .collect::<Result<_, _>>()?;
+        Ok(IndexDefinition { fields })
+    }
+}
+
 #[derive(Debug, Error, PartialEq, Eq, Clone)]
 pub enum SchemaImportError {
     #[error("Schema for imported subgraph `{0}` was not found")]
@@ -658,6 +700,7 @@ impl Schema {
         errors.append(&mut self.validate_fields());
         errors.append(&mut self.validate_import_directives());
         errors.append(&mut self.validate_fulltext_directives());
+        errors.append(&mut self.validate_index_directives());
         errors.append(&mut self.validate_imported_types(schemas));
         if errors.is_empty() {
             Ok(())
@@ -809,6 +852,111 @@ impl Schema {
             })
     }
 
+    /// Check the `@index` directives on entity types and the `@noIndex`
+    /// directives on their fields. An `@index` must list at least one
+    /// field, and all fields must be stored attributes of the type that
+    /// are neither lists nor marked `@noIndex`. `@noIndex` can not be used
+    /// on the `id` or on derived fields since neither of them gets an
+    /// attribute index
+    fn validate_index_directives(&self) -> Vec<SchemaValidationError> {
+        fn invalid_index(object_type: &ObjectType, reason: String) -> SchemaValidationError {
+            SchemaValidationError::InvalidIndexDirective(object_type.name.to_owned(), reason)
+        }
+
+        let mut errors = vec![];
+        for object_type in self
+            .document
+            .get_object_type_definitions()
+            .into_iter()
+            .filter(|object_type| !object_type.name.eq(SCHEMA_TYPE_NAME))
+        {
+            for index in object_type
+                .directives
+                .iter()
+                .filter(|directive| directive.name.eq("index"))
+            {
+                let fields = match index.argument("fields") {
+                    Some(Value::List(fields)) if !fields.is_empty() => fields,
+                    _ => {
+                        errors.push(invalid_index(
+                            object_type,
+                            "the `fields` argument must be a non-empty list of field names"
+                                .to_owned(),
+                        ));
+                        continue;
+                    }
+                };
+                let mut seen = HashSet::new();
+                for field in fields {
+                    let name = match field {
+                        Value::String(name) => name,
+                        _ => {
+                            errors.push(invalid_index(
+                                object_type,
+                                "the `fields` argument must only contain strings".to_owned(),
+                            ));
+                            continue;
+                        }
+                    };
+                    if !seen.insert(name) {
+                        errors.push(invalid_index(
+                            object_type,
+                            format!("field `{}` is listed more than once", name),
+                        ));
+                        continue;
+                    }
+                    match object_type.fields.iter().find(|f| f.name.eq(name)) {
+                        None => errors.push(invalid_index(
+                            object_type,
+                            format!("field `{}` does not exist", name),
+                        )),
+                        Some(f) if f.find_directive(String::from("derivedFrom")).is_some() => {
+                            errors.push(invalid_index(
+                                object_type,
+                                format!("field `{}` is derived and can not be indexed", name),
+                            ))
+                        }
+                        Some(f) if f.field_type.is_list() => errors.push(invalid_index(
+                            object_type,
+                            format!("field `{}` is a list and can not be indexed", name),
+                        )),
+                        Some(f) if f.find_directive(String::from("noIndex")).is_some() => errors
+                            .push(invalid_index(
+                                object_type,
+                                format!(
+                                    "field `{}` is marked @noIndex and can not be indexed",
+                                    name
+                                ),
+                            )),
+                        Some(_) => { /* ok */ }
+                    }
+                }
+            }
+
+            for field in object_type
+                .fields
+                .iter()
+                .filter(|field| field.find_directive(String::from("noIndex")).is_some())
+            {
+                let reason = if field.name.eq("id") {
+                    Some("the `id` field is always indexed")
+                } else if field.find_directive(String::from("derivedFrom")).is_some() {
+                    Some("derived fields are never indexed")
+                } else {
+                    None
+                };
+                if let Some(reason) = reason {
+                    errors.push(SchemaValidationError::InvalidNoIndex(
+                        object_type.name.to_owned(),
+                        field.name.to_owned(),
+                        reason.to_owned(),
+                    ));
+                }
+            }
+        }
+        errors
+    }
+
     fn validate_fulltext_directive_name(&self, fulltext: &Directive) -> Vec<SchemaValidationError> {
         let name = match fulltext.argument("name") {
             Some(Value::String(name)) => name,
@@ -1292,6 +1440,19 @@ impl Schema {
             .map(|directive| FulltextDefinition::from(directive))
             .collect())
     }
+
+    /// Return the composite indexes declared with `@index` on the
+    /// `entity` type
+    pub fn entity_index_definitions(
+        entity: &ObjectType,
+    ) -> Result<Vec<IndexDefinition>, SchemaValidationError> {
+        entity
+            .directives
+            .iter()
+            .filter(|directive| directive.name.eq("index"))
+            .map(|directive| IndexDefinition::from_directive(entity, directive))
+            .collect()
+    }
 }
 
 #[test]
@@ -1565,3 +1726,84 @@ type Gravatar @entity {
 
     assert_eq!(schema.validate_fulltext_directives(), vec![]);
 }
+
+#[test]
+fn test_index_directive_validation() {
+    const SCHEMA: &str = r#"
+type Transfer @entity @index(fields: ["owner", "timestamp"]) {
+  id: ID!
+  owner: Bytes!
+  timestamp: BigInt!
+  memo: String @noIndex
+  tags: [String!]!
+}"#;
+
+    let document = graphql_parser::parse_schema(SCHEMA).expect("Failed to parse schema");
+    let schema = Schema::new(SubgraphDeploymentId::new("id1").unwrap(), document);
+    assert_eq!(schema.validate_index_directives(), vec![]);
+
+    let object_type = schema
+        .document
+        .get_object_type_definitions()
+        .into_iter()
+        .find(|object_type| object_type.name == "Transfer")
+        .unwrap();
+    assert_eq!(
+        Schema::entity_index_definitions(object_type),
+        Ok(vec![IndexDefinition {
+            fields: vec!["owner".to_owned(), "timestamp".to_owned()]
+        }])
+    );
+
+    const BAD_SCHEMA: &str = r#"
+type Transfer @entity @index(fields: ["owner", "missing", "tags", "memo"]) @index(fields: []) {
+  id: ID! @noIndex
+  owner: Bytes!
+  memo: String @noIndex
+  tags: [String!]!
+}"#;
+
+    let document = graphql_parser::parse_schema(BAD_SCHEMA).expect("Failed to parse schema");
+    let schema = Schema::new(SubgraphDeploymentId::new("id1").unwrap(), document);
+    assert_eq!(
+        schema.validate_index_directives(),
+        vec![
+            SchemaValidationError::InvalidIndexDirective(
+                "Transfer".to_owned(),
+                "field `missing` does not exist".to_owned()
+            ),
+            SchemaValidationError::InvalidIndexDirective(
+                "Transfer".to_owned(),
+                "field `tags` is a list and can not be indexed".to_owned()
+            ),
+            SchemaValidationError::InvalidIndexDirective(
+                "Transfer".to_owned(),
+                "field `memo` is marked @noIndex and can not be indexed".to_owned()
+            ),
+            SchemaValidationError::InvalidIndexDirective(
+                "Transfer".to_owned(),
+                "the `fields` argument must be a non-empty list of field names".to_owned()
+            ),
+            SchemaValidationError::InvalidNoIndex(
+                "Transfer".to_owned(),
+                "id".to_owned(),
+                "the `id` field is always indexed".to_owned()
+            ),
+        ]
+    );
+
+    // Malformed directives are reported as errors, not panics
+    let object_type = schema
+        .document
+        .get_object_type_definitions()
+        .into_iter()
+        .find(|object_type| object_type.name == "Transfer")
+        .unwrap();
+    assert_eq!(
+        Schema::entity_index_definitions(object_type),
+        Err(SchemaValidationError::InvalidIndexDirective(
+            "Transfer".to_owned(),
+            "the `fields` argument must be a non-empty list of field names".to_owned()
+        ))
+    );
+}
@@ -302,6 +302,8 @@ pub enum SubgraphRegistrarError {
     ManifestValidationError(Vec<SubgraphManifestValidationError>),
     #[error("subgraph deployment error: {0}")]
     SubgraphDeploymentError(StoreError),
+    #[error("can not rewind deployment: {0}")]
+    RewindError(String),
     #[error("subgraph registrar error: {0}")]
     Unknown(anyhow::Error),
 }
@@ -384,6 +386,8 @@ pub enum SubgraphManifestValidationError {
     SchemaValidationError(Vec<SchemaValidationError>),
     #[error("the graft base is invalid: {0}")]
     GraftBaseInvalid(String),
+    #[error("mapping language `{0}` is not supported")]
+    UnsupportedMappingLanguage(String),
 }
 
 #[derive(Error, Debug)]
@@ -593,6 +597,12 @@ impl Mapping {
             })
     }
 
+    /// The language the mapping is written in. Manifests with unsupported languages do not pass
+    /// validation.
+    pub fn mapping_language(&self) -> Result<MappingLanguage, Error> {
+        self.language.parse()
+    }
+
     pub fn required_capabilities(&self) -> NodeCapabilities {
         NodeCapabilities {
             traces: self.has_block_handler_with_call_filter() || self.has_call_handler(),
@@ -911,6 +921,25 @@ impl UnvalidatedSubgraphManifest {
             errors.push(SubgraphManifestValidationError::DataSourceBlockHandlerLimitExceeded)
         }
 
+        let languages = self
+            .0
+            .data_sources
+            .iter()
+            .map(|data_source| &data_source.mapping.language)
+            .chain(
+                self.0
+                    .templates
+                    .iter()
+                    .map(|template| &template.mapping.language),
+            );
+        for language in languages {
+            if language.parse::<MappingLanguage>().is_err() {
+                errors.push(SubgraphManifestValidationError::UnsupportedMappingLanguage(
+                    language.clone(),
+                ));
+            }
+        }
+
         let mut networks = self
             .0
             .data_sources
@@ -1169,6 +1198,35 @@ impl FromStr for SubgraphFeature {
     }
 }
 
+/// The languages that mappings can be written in, which determine the ABI between the mapping and
+/// the host.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum MappingLanguage {
+    AssemblyScript,
+    Rust,
+}
+
+impl fmt::Display for MappingLanguage {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MappingLanguage::AssemblyScript => write!(f, "wasm/assemblyscript"),
+            MappingLanguage::Rust => write!(f, "wasm/rust"),
+        }
+    }
+}
+
+impl FromStr for MappingLanguage {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> anyhow::Result<Self> {
+        match s {
+            "wasm/assemblyscript" => Ok(MappingLanguage::AssemblyScript),
+            "wasm/rust" => Ok(MappingLanguage::Rust),
+            _ => Err(anyhow::anyhow!("invalid mapping language {}", s)),
+        }
+    }
+}
+
 fn display_vector(input: &Vec<impl std::fmt::Display>) -> impl std::fmt::Display {
     let formatted_errors = input
         .iter()
@@ -7,13 +7,15 @@ use rand::rngs::OsRng;
 use rand::Rng;
 use stable_hash::{SequenceNumber, StableHash, StableHasher};
 use std::str::FromStr;
+use std::time::Duration;
 use std::{fmt, fmt::Display};
 
 use super::SubgraphDeploymentId;
 use crate::components::{ethereum::EthereumBlockPointer, store::EntityType};
-use crate::data::graphql::TryFromValue;
+use crate::data::graphql::{object, IntoValue, TryFromValue};
 use crate::data::store::Value;
 use crate::data::subgraph::SubgraphManifest;
+use crate::prelude::web3::types::H256;
 use crate::prelude::*;
 
 pub const POI_TABLE: &str = "poi2$";
@@ -184,6 +186,20 @@ pub struct SubgraphError {
     pub deterministic: bool,
 }
 
+/// An automatic restart of a subgraph that failed with a non-deterministic
+/// error
+#[derive(Clone, Debug)]
+pub struct SubgraphRestart {
+    /// The number of restarts since the subgraph last processed a block
+    /// successfully, starting at 1
+    pub attempt: i32,
+    pub message: String,
+    pub block_ptr: Option<EthereumBlockPointer>,
+    /// How long until the subgraph is restarted, or `None` if it failed too
+    /// often and will not be restarted automatically
+    pub delay: Option<Duration>,
+}
+
 impl Display for SubgraphError {
     fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
         write!(f, "{}", self.message)?;
@@ -214,6 +230,209 @@ impl StableHash for SubgraphError {
     }
 }
 
+/// The level of a message that a mapping logged, from most to least severe.
+#[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Debug)]
+pub enum MappingLogLevel {
+    Critical,
+    Error,
+    Warning,
+    Info,
+    Debug,
+}
+
+impl MappingLogLevel {
+    pub const ALL: [MappingLogLevel; 5] = [
+        MappingLogLevel::Critical,
+        MappingLogLevel::Error,
+        MappingLogLevel::Warning,
+        MappingLogLevel::Info,
+        MappingLogLevel::Debug,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            MappingLogLevel::Critical => "critical",
+            MappingLogLevel::Error => "error",
+            MappingLogLevel::Warning => "warning",
+            MappingLogLevel::Info => "info",
+            MappingLogLevel::Debug => "debug",
+        }
+    }
+
+    /// The levels that are at least as severe as `self`.
+    pub fn and_above(self) -> impl Iterator<Item = MappingLogLevel> {
+        Self::ALL
+            .iter()
+            .copied()
+            .filter(move |level| *level <= self)
+    }
+}
+
+impl FromStr for MappingLogLevel {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<MappingLogLevel, Error> {
+        match s {
+            "critical" => Ok(MappingLogLevel::Critical),
+            "error" => Ok(MappingLogLevel::Error),
+            "warning" => Ok(MappingLogLevel::Warning),
+            "info" => Ok(MappingLogLevel::Info),
+            "debug" => Ok(MappingLogLevel::Debug),
+            _ => Err(anyhow!("failed to parse `{}` as MappingLogLevel", s)),
+        }
+    }
+}
+
+impl From<MappingLogLevel> for slog::Level {
+    fn from(level: MappingLogLevel) -> slog::Level {
+        match level {
+            MappingLogLevel::Critical => slog::Level::Critical,
+            MappingLogLevel::Error => slog::Level::Error,
+            MappingLogLevel::Warning => slog::Level::Warning,
+            MappingLogLevel::Info => slog::Level::Info,
+            MappingLogLevel::Debug => slog::Level::Debug,
+        }
+    }
+}
+
+impl From<MappingLogLevel> for q::Value {
+    fn from(level: MappingLogLevel) -> q::Value {
+        q::Value::Enum(level.as_str().to_string())
+    }
+}
+
+impl TryFromValue for MappingLogLevel {
+    fn try_from_value(value: &q::Value) -> Result<MappingLogLevel, Error> {
+        match value {
+            q::Value::Enum(level) => MappingLogLevel::from_str(level),
+            _ => Err(anyhow!(
+                "cannot parse value as MappingLogLevel: `{:?}`",
+                value
+            )),
+        }
+    }
+}
+
+/// A message that a mapping logged with `log.log` while processing a block.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct MappingLog {
+    pub block_number: BlockNumber,
+    pub data_source: String,
+    pub handler: Option<String>,
+    pub level: MappingLogLevel,
+    pub message: String,
+}
+
+impl IntoValue for MappingLog {
+    fn into_value(self) -> q::Value {
+        let MappingLog {
+            block_number,
+            data_source,
+            handler,
+            level,
+            message,
+        } = self;
+
+        object! {
+            __typename: "SubgraphLog",
+            blockNumber: block_number,
+            dataSource: data_source,
+            handler: handler,
+            level: q::Value::from(level),
+            message: message,
+        }
+    }
+}
+
+/// What happened to a deployment in an entry of its health history
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum HealthEventKind {
+    /// The health of the deployment changed
+    HealthChange,
+    /// The deployment failed with a non-deterministic error and was
+    /// restarted
+    Restart,
+    /// The fatal error of the deployment was cleared
+    Unfail,
+    /// The deployment was assigned to a different node, or unassigned
+    Reassignment,
+    /// The deployment became the current version of a subgraph
+    VersionSwitch,
+    /// The deployment was rewound to an earlier block
+    Rewind,
+}
+
+impl HealthEventKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            HealthEventKind::HealthChange => "healthChange",
+            HealthEventKind::Restart => "restart",
+            HealthEventKind::Unfail => "unfail",
+            HealthEventKind::Reassignment => "reassignment",
+            HealthEventKind::VersionSwitch => "versionSwitch",
+            HealthEventKind::Rewind => "rewind",
+        }
+    }
+}
+
+impl FromStr for HealthEventKind {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<HealthEventKind, Error> {
+        match s {
+            "healthChange" => Ok(HealthEventKind::HealthChange),
+            "restart" => Ok(HealthEventKind::Restart),
+            "unfail" => Ok(HealthEventKind::Unfail),
+            "reassignment" => Ok(HealthEventKind::Reassignment),
+            "versionSwitch" => Ok(HealthEventKind::VersionSwitch),
+            "rewind" => Ok(HealthEventKind::Rewind),
+            _ => Err(anyhow!("failed to parse `{}` as HealthEventKind", s)),
+        }
+    }
+}
+
+impl From<HealthEventKind> for q::Value {
+    fn from(kind: HealthEventKind) -> q::Value {
+        q::Value::Enum(kind.as_str().to_string())
+    }
+}
+
+/// An entry in the health history of a deployment
+#[derive(Clone, Debug)]
+pub struct HealthEvent {
+    pub kind: HealthEventKind,
+    /// The health of the deployment after the event, if it changed
+    pub health: Option<SubgraphHealth>,
+    pub message: Option<String>,
+    pub block_number: Option<BlockNumber>,
+    pub block_hash: Option<H256>,
+    /// When the event happened, in seconds since the epoch
+    pub at: i64,
+}
+
+impl IntoValue for HealthEvent {
+    fn into_value(self) -> q::Value {
+        let HealthEvent {
+            kind,
+            health,
+            message,
+            block_number,
+            block_hash,
+            at,
+        } = self;
+
+        object! {
+            __typename: "HealthEvent",
+            kind: q::Value::from(kind),
+            health: health.map(q::Value::from),
+            message: message,
+            blockNumber: block_number,
+            blockHash: block_hash.map(|hash| format!("0x{}", hex::encode(hash.as_bytes()))),
+            at: at.to_string(),
+        }
+    }
+}
+
 pub fn generate_entity_id() -> String {
     // Fast crypto RNG from operating system
     let mut rng = OsRng::new().unwrap();
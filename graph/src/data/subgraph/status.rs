@@ -2,7 +2,11 @@
 
 use super::schema::{SubgraphError, SubgraphHealth};
 use crate::data::graphql::{object, IntoValue};
-use crate::prelude::{q, web3::types::H256, EthereumBlockPointer, Value};
+use crate::prelude::{
+    q,
+    web3::types::{Address, H256},
+    BlockNumber, EthereumBlockPointer, Value,
+};
 
 pub enum Filter {
     /// Get all versions for the named subgraph
@@ -101,6 +105,14 @@ pub struct Info {
 
     /// ID of the Graph Node that the subgraph is indexed by.
     pub node: Option<String>,
+
+    /// Whether indexing the subgraph is paused. `None` if the subgraph is
+    /// not assigned to a node.
+    pub paused: Option<bool>,
+
+    /// Automatic restarts after non-deterministic errors since the subgraph
+    /// last processed a block successfully.
+    pub retry: Option<RetryState>,
 }
 
 impl IntoValue for Info {
@@ -114,6 +126,8 @@ impl IntoValue for Info {
             node,
             non_fatal_errors,
             synced,
+            paused,
+            retry,
         } = self;
 
         fn subgraph_error_to_value(subgraph_error: SubgraphError) -> q::Value {
@@ -155,6 +169,77 @@ impl IntoValue for Info {
             chains: chains.into_iter().map(|chain| chain.into_value()).collect::<Vec<_>>(),
             entityCount: format!("{}", entity_count),
             node: node,
+            paused: paused,
+            retryState: retry,
+        }
+    }
+}
+
+/// How often a subgraph was restarted automatically after failing with a
+/// non-deterministic error
+#[derive(Debug)]
+pub struct RetryState {
+    pub attempt: i32,
+    /// The error that caused the last restart
+    pub message: String,
+    pub block: Option<EthereumBlock>,
+    /// When the subgraph failed, in seconds since the epoch
+    pub failed_at: i64,
+    /// When the subgraph will be restarted, in seconds since the epoch, or
+    /// `None` if it will not be restarted automatically
+    pub next_attempt_at: Option<i64>,
+}
+
+impl IntoValue for RetryState {
+    fn into_value(self) -> q::Value {
+        let RetryState {
+            attempt,
+            message,
+            block,
+            failed_at,
+            next_attempt_at,
+        } = self;
+        object! {
+            __typename: "RetryState",
+            attempt: attempt,
+            message: message,
+            block: block,
+            failedAt: failed_at.to_string(),
+            nextAttemptAt: next_attempt_at.map(|at| at.to_string()),
+        }
+    }
+}
+
+/// A data source that was created from a template
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DataSource {
+    /// The name of the template
+    pub template: String,
+    pub address: Option<Address>,
+    pub creation_block: Option<BlockNumber>,
+    pub context: Option<String>,
+    /// How many triggers the data source has handled. Triggers in blocks
+    /// that were reverted and processed again are counted twice
+    pub trigger_count: i64,
+}
+
+impl IntoValue for DataSource {
+    fn into_value(self) -> q::Value {
+        let DataSource {
+            template,
+            address,
+            creation_block,
+            context,
+            trigger_count,
+        } = self;
+
+        object! {
+            __typename: "DataSource",
+            template: template,
+            address: address.map(|address| format!("0x{}", hex::encode(address.as_bytes()))),
+            creationBlock: creation_block,
+            context: context,
+            triggerCount: format!("{}", trigger_count),
         }
     }
 }
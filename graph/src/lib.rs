@@ -94,13 +94,15 @@ pub mod prelude {
         GaugeVec, Histogram, HistogramOpts, HistogramVec, MetricsRegistry, Opts, PrometheusError,
         Registry,
     };
-    pub use crate::components::server::admin::JsonRpcServer;
+    pub use crate::components::server::admin::{
+        AdminAction, AdminAuth, AdminOutcome, AdminRole, AdminUser, JsonRpcServer,
+    };
     pub use crate::components::server::index_node::IndexNodeServer;
     pub use crate::components::server::metrics::MetricsServer;
     pub use crate::components::server::query::GraphQLServer;
     pub use crate::components::server::subscription::SubscriptionServer;
     pub use crate::components::store::{
-        BlockNumber, ChainStore, ChildMultiplicity, EntityCache, EntityChange,
+        BlockNumber, ChainStore, ChildMultiplicity, DerivedEntityQuery, EntityCache, EntityChange,
         EntityChangeOperation, EntityCollection, EntityFilter, EntityKey, EntityLink,
         EntityModification, EntityOperation, EntityOrder, EntityQuery, EntityRange, EntityWindow,
         EthereumCallCache, ParentLink, PoolWaitStats, QueryStore, QueryStoreManager, StoreError,
@@ -108,9 +110,9 @@ pub mod prelude {
         BLOCK_NUMBER_MAX, SUBSCRIPTION_THROTTLE_INTERVAL,
     };
     pub use crate::components::subgraph::{
-        BlockState, DataSourceTemplateInfo, HostMetrics, RuntimeHost, RuntimeHostBuilder,
-        SubgraphAssignmentProvider, SubgraphInstance, SubgraphInstanceManager, SubgraphRegistrar,
-        SubgraphVersionSwitchingMode,
+        BlockState, DataSourceTemplateInfo, HostMetrics, IndexingProgress, MappingProfiler,
+        RuntimeHost, RuntimeHostBuilder, SubgraphAssignmentProvider, SubgraphInstance,
+        SubgraphInstanceManager, SubgraphRegistrar, SubgraphVersionSwitchingMode,
     };
     pub use crate::components::{EventConsumer, EventProducer};
 
@@ -132,7 +134,7 @@ pub mod prelude {
     pub use crate::data::subgraph::{
         BlockHandlerFilter, CreateSubgraphResult, DataSource, DataSourceContext,
         DataSourceTemplate, DeploymentState, Link, MappingABI, MappingBlockHandler,
-        MappingCallHandler, MappingEventHandler, SubgraphAssignmentProviderError,
+        MappingCallHandler, MappingEventHandler, MappingLanguage, SubgraphAssignmentProviderError,
         SubgraphDeploymentId, SubgraphManifest, SubgraphManifestResolveError,
         SubgraphManifestValidationError, SubgraphName, SubgraphRegistrarError,
         UnvalidatedSubgraphManifest,
@@ -163,6 +163,11 @@ impl<K: Clone + Ord + Eq + Hash + Debug + CacheWeight, V: CacheWeight + Default>
         self.queue.len()
     }
 
+    /// The approximate size of all entries in the cache, in bytes
+    pub fn total_weight(&self) -> usize {
+        self.total_weight
+    }
+
     /// Same as `evict_with_period(max_weight, STALE_PERIOD)`
     pub fn evict(&mut self, max_weight: usize) -> Option<(usize, usize, usize)> {
         self.evict_with_period(max_weight, STALE_PERIOD)
@@ -2,8 +2,10 @@ use std::collections::BTreeMap;
 use std::sync::Arc;
 
 use graph::prelude::{
-    Entity, EntityCache, EntityKey, EntityModification, SubgraphDeploymentId, Value,
+    DerivedEntityQuery, Entity, EntityCache, EntityKey, EntityModification, SubgraphDeploymentId,
+    Value,
 };
+use graph::util::lfu_cache::LfuCache;
 use graph::{components::store::EntityType, mock::MockStore};
 
 fn make_band(id: &'static str, data: Vec<(&str, Value)>) -> (EntityKey, Entity) {
@@ -199,3 +201,96 @@ fn consecutive_modifications() {
         },])
     );
 }
+
+#[test]
+fn cache_is_reused_across_blocks() {
+    let mut store = MockStore::new();
+
+    // Only the first block may go to the store; after that, the entity must
+    // come from the cache that is carried over from the previous block.
+    store
+        .expect_get_many_mock()
+        .times(1)
+        .returning(|_, _| Ok(BTreeMap::new()));
+
+    let store = Arc::new(store);
+
+    // Block 1: create the entity
+    let mut cache = EntityCache::new(store.clone());
+    let (mogwai_key, mogwai_data) = make_band(
+        "mogwai",
+        vec![("id", "mogwai".into()), ("name", "Mogwai".into())],
+    );
+    cache.set(mogwai_key.clone(), mogwai_data.clone());
+    let result = cache.as_modifications(&*store).unwrap();
+    assert_eq!((0, 0), (result.cache_hits, result.cache_misses));
+
+    // Block 2: read and update the entity
+    let mut cache = EntityCache::with_current(store.clone(), result.entity_lfu_cache);
+    assert_eq!(Some(mogwai_data), cache.get(&mogwai_key).unwrap());
+    let (_, update_data) = make_band(
+        "mogwai",
+        vec![("id", "mogwai".into()), ("founded", 1995.into())],
+    );
+    cache.set(mogwai_key.clone(), update_data);
+    let result = cache.as_modifications(&*store).unwrap();
+    assert_eq!((1, 0), (result.cache_hits, result.cache_misses));
+    assert_eq!(
+        result.modifications,
+        vec![EntityModification::Overwrite {
+            key: mogwai_key,
+            data: Entity::from(vec![
+                ("id", "mogwai".into()),
+                ("name", "Mogwai".into()),
+                ("founded", 1995.into()),
+            ]),
+        }]
+    );
+}
+
+#[test]
+fn load_related_applies_changes() {
+    fn make_member(id: &'static str, band: &'static str) -> (EntityKey, Entity) {
+        let subgraph_id = SubgraphDeploymentId::new("entity_cache").unwrap();
+
+        (
+            EntityKey::data(subgraph_id, "Member".to_string(), id.into()),
+            Entity::from(vec![("id", id.into()), ("band", band.into())]),
+        )
+    }
+
+    let mut store = MockStore::new();
+
+    // The store knows of two members of the band
+    store.expect_get_derived_mock().times(1).returning(|_, _| {
+        Ok(vec![
+            make_member("braithwaite", "mogwai").1,
+            make_member("burns", "mogwai").1,
+        ])
+    });
+
+    let store = Arc::new(store);
+
+    // `bulloch` is new and does not need to be loaded from the store
+    let (bulloch_key, bulloch_data) = make_member("bulloch", "mogwai");
+    let mut current = LfuCache::new();
+    current.insert(bulloch_key.clone(), None);
+    let mut cache = EntityCache::with_current(store.clone(), current);
+
+    // One member joins the band, and one leaves it
+    cache.set(bulloch_key, bulloch_data.clone());
+    let (burns_key, burns_data) = make_member("burns", "other");
+    cache.set(burns_key, burns_data);
+
+    let query = DerivedEntityQuery {
+        entity_type: EntityType::from("Member"),
+        entity_field: "band".to_string(),
+        value: "mogwai".to_string(),
+    };
+    let subgraph_id = SubgraphDeploymentId::new("entity_cache").unwrap();
+    let members = cache.load_related(&subgraph_id, &query).unwrap();
+    assert_eq!(
+        members,
+        vec![make_member("braithwaite", "mogwai").1, bulloch_data]
+    );
+}
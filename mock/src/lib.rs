@@ -6,9 +6,11 @@ extern crate rand;
 
 mod block_stream;
 
+mod memory_store;
 mod metrics_registry;
 mod store;
 
 pub use self::block_stream::{MockBlockStream, MockBlockStreamBuilder};
+pub use self::memory_store::MemoryStore;
 pub use self::metrics_registry::MockMetricsRegistry;
 pub use self::store::MockStore;
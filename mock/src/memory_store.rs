@@ -0,0 +1,383 @@
+use std::collections::{BTreeMap, HashMap};
+use std::sync::Mutex;
+
+use graph::components::store::EntityType;
+use graph::components::store::{DynamicDataSourceKey, StoredDynamicDataSource};
+use graph::data::subgraph::schema::{MappingLog, SubgraphError, SubgraphRestart};
+use graph::prelude::*;
+use web3::types::Address;
+
+/// A store that keeps the entities of a single subgraph in memory. It only supports what
+/// mappings need, i.e., loading entities and the input schema, and recording admin actions;
+/// everything else fails with an error.
+pub struct MemoryStore {
+    schema: Arc<Schema>,
+    entities: Mutex<BTreeMap<EntityKey, Entity>>,
+    admin_actions: Mutex<Vec<AdminAction>>,
+}
+
+impl MemoryStore {
+    pub fn new(schema: Arc<Schema>) -> Self {
+        MemoryStore {
+            schema,
+            entities: Mutex::new(BTreeMap::new()),
+            admin_actions: Mutex::new(Vec::new()),
+        }
+    }
+
+    /// Insert or replace an entity.
+    pub fn set(&self, key: EntityKey, entity: Entity) {
+        self.entities.lock().unwrap().insert(key, entity);
+    }
+
+    /// Apply the changes that handlers made to the entity cache.
+    pub fn apply(&self, mods: Vec<EntityModification>) {
+        let mut entities = self.entities.lock().unwrap();
+        for modification in mods {
+            match modification {
+                EntityModification::Insert { key, data }
+                | EntityModification::Overwrite { key, data } => {
+                    entities.insert(key, data);
+                }
+                EntityModification::Remove { key } => {
+                    entities.remove(&key);
+                }
+            }
+        }
+    }
+
+    /// All entities of `entity_type`, ordered by id.
+    pub fn entities(&self, entity_type: &EntityType) -> Vec<Entity> {
+        self.entities
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|(key, _)| &key.entity_type == entity_type)
+            .map(|(_, entity)| entity.clone())
+            .collect()
+    }
+
+    /// The calls to the admin API that were recorded, in order.
+    pub fn admin_actions(&self) -> Vec<AdminAction> {
+        self.admin_actions.lock().unwrap().clone()
+    }
+}
+
+fn unsupported(operation: &str) -> StoreError {
+    StoreError::Unknown(anyhow!("the memory store does not support `{}`", operation))
+}
+
+#[async_trait]
+impl SubgraphStore for MemoryStore {
+    fn block_ptr(
+        &self,
+        _subgraph_id: &SubgraphDeploymentId,
+    ) -> Result<Option<EthereumBlockPointer>, Error> {
+        Err(unsupported("block_ptr").into())
+    }
+
+    fn get(&self, key: EntityKey) -> Result<Option<Entity>, QueryExecutionError> {
+        Ok(self.entities.lock().unwrap().get(&key).cloned())
+    }
+
+    fn get_many(
+        &self,
+        subgraph_id: &SubgraphDeploymentId,
+        ids_for_type: BTreeMap<&EntityType, Vec<&str>>,
+    ) -> Result<BTreeMap<EntityType, Vec<Entity>>, StoreError> {
+        let entities = self.entities.lock().unwrap();
+        let mut result = BTreeMap::new();
+        for (entity_type, ids) in ids_for_type {
+            let found: Vec<_> = ids
+                .into_iter()
+                .filter_map(|id| {
+                    entities
+                        .get(&EntityKey {
+                            subgraph_id: subgraph_id.clone(),
+                            entity_type: entity_type.clone(),
+                            entity_id: id.to_owned(),
+                        })
+                        .cloned()
+                })
+                .collect();
+            if !found.is_empty() {
+                result.insert(entity_type.clone(), found);
+            }
+        }
+        Ok(result)
+    }
+
+    /// The store only keeps the latest version of entities.
+    fn get_at_block(
+        &self,
+        _key: EntityKey,
+        _block: BlockNumber,
+    ) -> Result<Option<Entity>, QueryExecutionError> {
+        Err(QueryExecutionError::StoreError(
+            anyhow!("the memory store does not keep previous versions of entities").into(),
+        ))
+    }
+
+    fn get_derived(
+        &self,
+        subgraph_id: &SubgraphDeploymentId,
+        query: &DerivedEntityQuery,
+    ) -> Result<Vec<Entity>, StoreError> {
+        Ok(self
+            .entities
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|(key, entity)| {
+                &key.subgraph_id == subgraph_id
+                    && key.entity_type == query.entity_type
+                    && query.matches(entity)
+            })
+            .map(|(_, entity)| entity.clone())
+            .collect())
+    }
+
+    fn supports_proof_of_indexing<'a>(
+        self: Arc<Self>,
+        _subgraph_id: &'a SubgraphDeploymentId,
+    ) -> DynTryFuture<'a, bool> {
+        Box::pin(futures03::future::err(Error::from(unsupported(
+            "supports_proof_of_indexing",
+        ))))
+    }
+
+    fn get_proof_of_indexing<'a>(
+        self: Arc<Self>,
+        _subgraph_id: &'a SubgraphDeploymentId,
+        _indexer: &'a Option<Address>,
+        _block: EthereumBlockPointer,
+    ) -> DynTryFuture<'a, Option<[u8; 32]>> {
+        Box::pin(futures03::future::err(Error::from(unsupported(
+            "get_proof_of_indexing",
+        ))))
+    }
+
+    fn find(&self, _query: EntityQuery) -> Result<Vec<Entity>, QueryExecutionError> {
+        Err(unsupported("find").into())
+    }
+
+    fn find_one(&self, _query: EntityQuery) -> Result<Option<Entity>, QueryExecutionError> {
+        Err(unsupported("find_one").into())
+    }
+
+    fn find_ens_name(&self, _hash: &str) -> Result<Option<String>, QueryExecutionError> {
+        Ok(None)
+    }
+
+    fn transact_block_operations(
+        &self,
+        _subgraph_id: SubgraphDeploymentId,
+        _block_ptr_to: EthereumBlockPointer,
+        _mods: Vec<EntityModification>,
+        _stopwatch: StopwatchMetrics,
+        _data_sources: Vec<StoredDynamicDataSource>,
+        _deterministic_errors: Vec<SubgraphError>,
+        _trigger_counts: HashMap<DynamicDataSourceKey, i64>,
+    ) -> Result<(), StoreError> {
+        Err(unsupported("transact_block_operations"))
+    }
+
+    fn buffer_block_operations(
+        &self,
+        _subgraph_id: SubgraphDeploymentId,
+        _block_ptr_to: EthereumBlockPointer,
+        _mods: Vec<EntityModification>,
+        _stopwatch: StopwatchMetrics,
+        _data_sources: Vec<StoredDynamicDataSource>,
+        _deterministic_errors: Vec<SubgraphError>,
+        _trigger_counts: HashMap<DynamicDataSourceKey, i64>,
+    ) -> Result<(), StoreError> {
+        Err(unsupported("buffer_block_operations"))
+    }
+
+    fn flush(&self, _subgraph_id: &SubgraphDeploymentId) -> Result<(), StoreError> {
+        Err(unsupported("flush"))
+    }
+
+    fn revert_block_operations(
+        &self,
+        _subgraph_id: SubgraphDeploymentId,
+        _block_ptr_to: EthereumBlockPointer,
+    ) -> Result<(), StoreError> {
+        Err(unsupported("revert_block_operations"))
+    }
+
+    fn write_mapping_logs(
+        &self,
+        _subgraph_id: &SubgraphDeploymentId,
+        _logs: Vec<MappingLog>,
+    ) -> Result<(), StoreError> {
+        Err(unsupported("write_mapping_logs"))
+    }
+
+    fn poi_events_block(
+        &self,
+        _subgraph_id: &SubgraphDeploymentId,
+    ) -> Result<Option<BlockNumber>, StoreError> {
+        Ok(None)
+    }
+
+    fn write_poi_events(
+        &self,
+        _subgraph_id: &SubgraphDeploymentId,
+        _block: BlockNumber,
+        _events: BTreeMap<String, Vec<String>>,
+    ) -> Result<(), StoreError> {
+        Err(unsupported("write_poi_events"))
+    }
+
+    async fn deployment_state_from_name(
+        &self,
+        _: SubgraphName,
+    ) -> Result<DeploymentState, StoreError> {
+        Err(unsupported("deployment_state_from_name"))
+    }
+
+    async fn deployment_state_from_id(
+        &self,
+        _: SubgraphDeploymentId,
+    ) -> Result<DeploymentState, StoreError> {
+        Err(unsupported("deployment_state_from_id"))
+    }
+
+    async fn fail_subgraph(
+        &self,
+        _: SubgraphDeploymentId,
+        _: SubgraphError,
+    ) -> Result<(), StoreError> {
+        Err(unsupported("fail_subgraph"))
+    }
+
+    fn create_subgraph_deployment(
+        &self,
+        _: SubgraphName,
+        _: &Schema,
+        _: SubgraphDeploymentEntity,
+        _: NodeId,
+        _: String,
+        _: SubgraphVersionSwitchingMode,
+    ) -> Result<(), StoreError> {
+        Err(unsupported("create_subgraph_deployment"))
+    }
+
+    fn create_subgraph(&self, _: SubgraphName) -> Result<String, StoreError> {
+        Err(unsupported("create_subgraph"))
+    }
+
+    fn remove_subgraph(&self, _: SubgraphName) -> Result<(), StoreError> {
+        Err(unsupported("remove_subgraph"))
+    }
+
+    fn reassign_subgraph(&self, _: &SubgraphDeploymentId, _: &NodeId) -> Result<(), StoreError> {
+        Err(unsupported("reassign_subgraph"))
+    }
+
+    fn unassign_subgraph(&self, _: &SubgraphDeploymentId) -> Result<(), StoreError> {
+        Err(unsupported("unassign_subgraph"))
+    }
+
+    fn record_admin_action(&self, action: AdminAction) -> Result<(), StoreError> {
+        self.admin_actions.lock().unwrap().push(action);
+        Ok(())
+    }
+
+    fn pause_subgraph(&self, _: &SubgraphDeploymentId) -> Result<(), StoreError> {
+        Err(unsupported("pause_subgraph"))
+    }
+
+    fn resume_subgraph(&self, _: &SubgraphDeploymentId) -> Result<(), StoreError> {
+        Err(unsupported("resume_subgraph"))
+    }
+
+    fn start_subgraph_deployment(
+        &self,
+        _logger: &Logger,
+        _subgraph_id: &SubgraphDeploymentId,
+    ) -> Result<(), StoreError> {
+        Err(unsupported("start_subgraph_deployment"))
+    }
+
+    fn unfail(&self, _: &SubgraphDeploymentId) -> Result<(), StoreError> {
+        Err(unsupported("unfail"))
+    }
+
+    fn rewind(&self, _: &SubgraphDeploymentId, _: EthereumBlockPointer) -> Result<(), StoreError> {
+        Err(unsupported("rewind"))
+    }
+
+    fn record_subgraph_restart(
+        &self,
+        _: &SubgraphDeploymentId,
+        _: SubgraphRestart,
+    ) -> Result<(), StoreError> {
+        Err(unsupported("record_subgraph_restart"))
+    }
+
+    fn is_deployment_synced(&self, _: &SubgraphDeploymentId) -> Result<bool, Error> {
+        Err(unsupported("is_deployment_synced").into())
+    }
+
+    fn deployment_synced(&self, _: &SubgraphDeploymentId) -> Result<(), Error> {
+        Err(unsupported("deployment_synced").into())
+    }
+
+    async fn load_dynamic_data_sources(
+        &self,
+        _: SubgraphDeploymentId,
+    ) -> Result<Vec<StoredDynamicDataSource>, StoreError> {
+        Err(unsupported("load_dynamic_data_sources"))
+    }
+
+    fn assigned_node(&self, _: &SubgraphDeploymentId) -> Result<Option<NodeId>, StoreError> {
+        Err(unsupported("assigned_node"))
+    }
+
+    fn assignment_status(
+        &self,
+        _: &SubgraphDeploymentId,
+    ) -> Result<Option<(NodeId, bool)>, StoreError> {
+        Err(unsupported("assignment_status"))
+    }
+
+    fn assignments(&self, _: &NodeId) -> Result<Vec<SubgraphDeploymentId>, StoreError> {
+        Err(unsupported("assignments"))
+    }
+
+    fn set_instance_running(
+        &self,
+        _: &SubgraphDeploymentId,
+        _: &NodeId,
+        _: bool,
+    ) -> Result<(), StoreError> {
+        Err(unsupported("set_instance_running"))
+    }
+
+    fn running_instance(&self, _: &SubgraphDeploymentId) -> Result<Option<NodeId>, StoreError> {
+        Err(unsupported("running_instance"))
+    }
+
+    fn clear_running_instances(&self, _: &NodeId) -> Result<(), StoreError> {
+        Err(unsupported("clear_running_instances"))
+    }
+
+    fn subgraph_exists(&self, _: &SubgraphName) -> Result<bool, StoreError> {
+        Err(unsupported("subgraph_exists"))
+    }
+
+    fn input_schema(&self, _: &SubgraphDeploymentId) -> Result<Arc<Schema>, StoreError> {
+        Ok(self.schema.clone())
+    }
+
+    fn api_schema(&self, _: &SubgraphDeploymentId) -> Result<Arc<ApiSchema>, StoreError> {
+        Err(unsupported("api_schema"))
+    }
+
+    fn network_name(&self, _: &SubgraphDeploymentId) -> Result<String, StoreError> {
+        Err(unsupported("network_name"))
+    }
+}
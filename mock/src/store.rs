@@ -1,10 +1,10 @@
 use mockall::predicate::*;
 use mockall::*;
-use std::collections::BTreeMap;
+use std::collections::{BTreeMap, HashMap};
 
 use graph::components::store::EntityType;
-use graph::components::store::StoredDynamicDataSource;
-use graph::data::subgraph::schema::SubgraphError;
+use graph::components::store::{DynamicDataSourceKey, StoredDynamicDataSource};
+use graph::data::subgraph::schema::{MappingLog, SubgraphError, SubgraphRestart};
 use graph::prelude::*;
 use web3::types::{Address, H256};
 
@@ -75,6 +75,22 @@ impl SubgraphStore for MockStore {
         unimplemented!()
     }
 
+    fn get_at_block(
+        &self,
+        _key: EntityKey,
+        _block: BlockNumber,
+    ) -> Result<Option<Entity>, QueryExecutionError> {
+        unimplemented!()
+    }
+
+    fn get_derived(
+        &self,
+        _subgraph_id: &SubgraphDeploymentId,
+        _query: &DerivedEntityQuery,
+    ) -> Result<Vec<Entity>, StoreError> {
+        unimplemented!()
+    }
+
     fn supports_proof_of_indexing<'a>(
         self: Arc<Self>,
         _subgraph_id: &'a SubgraphDeploymentId,
@@ -111,10 +127,28 @@ impl SubgraphStore for MockStore {
         _stopwatch: StopwatchMetrics,
         _data_sources: Vec<StoredDynamicDataSource>,
         _deterministic_errors: Vec<SubgraphError>,
+        _trigger_counts: HashMap<DynamicDataSourceKey, i64>,
+    ) -> Result<(), StoreError> {
+        unimplemented!()
+    }
+
+    fn buffer_block_operations(
+        &self,
+        _subgraph_id: SubgraphDeploymentId,
+        _block_ptr_to: EthereumBlockPointer,
+        _mods: Vec<EntityModification>,
+        _stopwatch: StopwatchMetrics,
+        _data_sources: Vec<StoredDynamicDataSource>,
+        _deterministic_errors: Vec<SubgraphError>,
+        _trigger_counts: HashMap<DynamicDataSourceKey, i64>,
     ) -> Result<(), StoreError> {
         unimplemented!()
     }
 
+    fn flush(&self, _subgraph_id: &SubgraphDeploymentId) -> Result<(), StoreError> {
+        unimplemented!()
+    }
+
     fn revert_block_operations(
         &self,
         _subgraph_id: SubgraphDeploymentId,
@@ -123,6 +157,30 @@ impl SubgraphStore for MockStore {
         unimplemented!()
     }
 
+    fn write_mapping_logs(
+        &self,
+        _subgraph_id: &SubgraphDeploymentId,
+        _logs: Vec<MappingLog>,
+    ) -> Result<(), StoreError> {
+        unimplemented!()
+    }
+
+    fn poi_events_block(
+        &self,
+        _subgraph_id: &SubgraphDeploymentId,
+    ) -> Result<Option<BlockNumber>, StoreError> {
+        unimplemented!()
+    }
+
+    fn write_poi_events(
+        &self,
+        _subgraph_id: &SubgraphDeploymentId,
+        _block: BlockNumber,
+        _events: BTreeMap<String, Vec<String>>,
+    ) -> Result<(), StoreError> {
+        unimplemented!()
+    }
+
     async fn deployment_state_from_name(
         &self,
         _: SubgraphName,
@@ -178,6 +236,18 @@ impl SubgraphStore for MockStore {
         unimplemented!()
     }
 
+    fn record_admin_action(&self, _: AdminAction) -> Result<(), StoreError> {
+        unimplemented!()
+    }
+
+    fn pause_subgraph(&self, _: &SubgraphDeploymentId) -> Result<(), StoreError> {
+        unimplemented!()
+    }
+
+    fn resume_subgraph(&self, _: &SubgraphDeploymentId) -> Result<(), StoreError> {
+        unimplemented!()
+    }
+
     fn start_subgraph_deployment(
         &self,
         _logger: &Logger,
@@ -190,6 +260,18 @@ impl SubgraphStore for MockStore {
         unimplemented!()
     }
 
+    fn rewind(&self, _: &SubgraphDeploymentId, _: EthereumBlockPointer) -> Result<(), StoreError> {
+        unimplemented!()
+    }
+
+    fn record_subgraph_restart(
+        &self,
+        _: &SubgraphDeploymentId,
+        _: SubgraphRestart,
+    ) -> Result<(), StoreError> {
+        unimplemented!()
+    }
+
     fn is_deployment_synced(&self, _: &SubgraphDeploymentId) -> Result<bool, Error> {
         unimplemented!()
     }
@@ -209,10 +291,34 @@ impl SubgraphStore for MockStore {
         unimplemented!()
     }
 
+    fn assignment_status(
+        &self,
+        _: &SubgraphDeploymentId,
+    ) -> Result<Option<(NodeId, bool)>, StoreError> {
+        unimplemented!()
+    }
+
     fn assignments(&self, _: &NodeId) -> Result<Vec<SubgraphDeploymentId>, StoreError> {
         unimplemented!()
     }
 
+    fn set_instance_running(
+        &self,
+        _: &SubgraphDeploymentId,
+        _: &NodeId,
+        _: bool,
+    ) -> Result<(), StoreError> {
+        unimplemented!()
+    }
+
+    fn running_instance(&self, _: &SubgraphDeploymentId) -> Result<Option<NodeId>, StoreError> {
+        unimplemented!()
+    }
+
+    fn clear_running_instances(&self, _: &NodeId) -> Result<(), StoreError> {
+        unimplemented!()
+    }
+
     fn subgraph_exists(&self, _: &SubgraphName) -> Result<bool, StoreError> {
         unimplemented!()
     }
@@ -8,12 +8,12 @@ use structopt::StructOpt;
 
 use graph::{
     log::logger,
-    prelude::{info, o, slog, tokio, Logger, NodeId},
+    prelude::{info, o, slog, tokio, BlockNumber, Logger, NodeId},
 };
 use graph_node::config;
 use graph_node::store_builder::StoreBuilder;
 use graph_store_postgres::{
-    connection_pool::ConnectionPool, SubgraphStore, SubscriptionManager, PRIMARY_SHARD,
+    connection_pool::ConnectionPool, Store, SubgraphStore, SubscriptionManager, PRIMARY_SHARD,
 };
 
 use crate::config::Config as Cfg;
@@ -80,6 +80,9 @@ pub enum Command {
         /// List only used (current and pending) versions
         #[structopt(long, short)]
         used: bool,
+        /// Also print the health history of each deployment
+        #[structopt(long)]
+        history: bool,
     },
     /// Manage unused deployments
     ///
@@ -103,6 +106,19 @@ pub enum Command {
         /// The id of the deployment to unassign
         id: String,
     },
+    /// Pause indexing a deployment
+    ///
+    /// The deployment stays assigned to its node, but is not indexed
+    /// until it is resumed
+    Pause {
+        /// The id of the deployment to pause
+        id: String,
+    },
+    /// Resume indexing a paused deployment
+    Resume {
+        /// The id of the deployment to resume
+        id: String,
+    },
     /// Check and interrogate the configuration
     ///
     /// Print information about a configuration file without
@@ -110,6 +126,34 @@ pub enum Command {
     Config(ConfigCommand),
     /// Listen for store events and print them
     Listen(ListenCommand),
+    /// Manage the indexes of a deployment
+    ///
+    /// Indexes are created and dropped concurrently, i.e., without
+    /// blocking indexing or queries for the deployment
+    Index(IndexCommand),
+    /// Print storage statistics for a deployment
+    ///
+    /// Shows how many entity versions each table has, how much space
+    /// tables and indexes use, and how often indexes are used, and
+    /// suggests indexes that could be dropped and tables that could be
+    /// pruned or vacuumed
+    Stats {
+        /// The id of the deployment
+        id: String,
+    },
+    /// Compare proofs of indexing with a reference
+    Poi(PoiCommand),
+    /// List the data sources a deployment created from templates
+    ///
+    /// Shows the address, creation block, context and number of handled
+    /// triggers for each data source
+    DataSources {
+        /// The id of the deployment
+        id: String,
+        /// Only list data sources created from this template
+        #[structopt(long, short)]
+        template: Option<String>,
+    },
 }
 
 #[derive(Clone, Debug, StructOpt)]
@@ -161,6 +205,75 @@ pub enum ConfigCommand {
     },
 }
 
+#[derive(Clone, Debug, StructOpt)]
+pub enum IndexCommand {
+    /// Create a btree index on one or more attributes of an entity type
+    Create {
+        /// The id of the deployment
+        id: String,
+        /// The GraphQL name of the entity type
+        entity: String,
+        /// The GraphQL names of the attributes to index, in index order
+        #[structopt(required = true)]
+        fields: Vec<String>,
+    },
+    /// List the indexes on the table for an entity type
+    List {
+        /// The id of the deployment
+        id: String,
+        /// The GraphQL name of the entity type
+        entity: String,
+    },
+    /// Drop an index
+    Drop {
+        /// The id of the deployment
+        id: String,
+        /// The name of the index as shown by `list`
+        name: String,
+    },
+}
+
+#[derive(Clone, Debug, StructOpt)]
+pub enum PoiCommand {
+    /// Find the first block at which the proof of indexing of a deployment
+    /// differs from a reference
+    ///
+    /// The reference is either a file in which each line lists a block
+    /// number, the block hash and the proof of indexing for that block,
+    /// or the URL of the GraphQL endpoint of another index node. For an
+    /// index node, block hashes are taken from the local chain store. At
+    /// the first block that differs, print the digest of each causality
+    /// region and which of them changed in that block
+    Bisect {
+        /// The id of the deployment
+        id: String,
+        /// A file with proofs of indexing or the URL of an index node
+        #[structopt(long, short)]
+        reference: String,
+        /// The indexer address to use for proofs of indexing
+        #[structopt(long, short)]
+        indexer: Option<String>,
+        /// The first block to compare (default: 0)
+        #[structopt(long)]
+        from: Option<BlockNumber>,
+        /// The last block to compare (default: the latest block of the deployment)
+        #[structopt(long)]
+        to: Option<BlockNumber>,
+    },
+    /// Record the proof of indexing events of a deployment for a block
+    ///
+    /// Rewinds the deployment to the block before the given block so that
+    /// it processes the block again once it is resumed, and records the
+    /// events that go into its proof of indexing for that block. The
+    /// deployment must be paused. `bisect` prints the recorded events
+    Record {
+        /// The id of the deployment
+        id: String,
+        /// The block for which to record events
+        block: BlockNumber,
+    },
+}
+
 #[derive(Clone, Debug, StructOpt)]
 pub enum ListenCommand {
     Assignments,
@@ -202,6 +315,12 @@ fn make_store(logger: &Logger, node_id: &NodeId, config: &Cfg) -> Arc<SubgraphSt
     StoreBuilder::make_sharded_store(logger, node_id, config, make_registry(logger))
 }
 
+async fn make_network_store(logger: &Logger, node_id: &NodeId, config: &Cfg) -> Arc<Store> {
+    StoreBuilder::new(logger, node_id, config, make_registry(logger))
+        .await
+        .network_store(vec![])
+}
+
 fn make_subscription_manager(logger: &Logger, config: &Cfg) -> Arc<SubscriptionManager> {
     let primary = config.primary_store();
     Arc::new(SubscriptionManager::new(
@@ -255,9 +374,11 @@ async fn main() {
             current,
             pending,
             used,
+            history,
         } => {
             let pool = make_main_pool();
-            commands::info::run(pool, name, current, pending, used)
+            let store = if history { Some(make_store()) } else { None };
+            commands::info::run(pool, store, name, current, pending, used)
         }
         Unused(cmd) => {
             let store = make_store();
@@ -295,6 +416,14 @@ async fn main() {
             let store = make_store();
             commands::assign::reassign(store, id, node)
         }
+        Pause { id } => {
+            let store = make_store();
+            commands::assign::pause(store, id)
+        }
+        Resume { id } => {
+            let store = make_store();
+            commands::assign::resume(store, id)
+        }
         Listen(cmd) => {
             use ListenCommand::*;
             match cmd {
@@ -311,6 +440,42 @@ async fn main() {
                 }
             }
         }
+        Index(cmd) => {
+            use IndexCommand::*;
+            let store = make_store();
+            match cmd {
+                Create { id, entity, fields } => commands::index::create(store, id, entity, fields),
+                List { id, entity } => commands::index::list(store, id, entity),
+                Drop { id, name } => commands::index::drop(store, id, name),
+            }
+        }
+        Stats { id } => {
+            let store = make_store();
+            commands::stats::run(store, id)
+        }
+        Poi(cmd) => {
+            use PoiCommand::*;
+            match cmd {
+                Bisect {
+                    id,
+                    reference,
+                    indexer,
+                    from,
+                    to,
+                } => {
+                    let store = make_network_store(&logger, &node, &config).await;
+                    commands::poi::bisect(store, id, reference, indexer, from, to).await
+                }
+                Record { id, block } => {
+                    let store = make_network_store(&logger, &node, &config).await;
+                    commands::poi::record(store, id, block)
+                }
+            }
+        }
+        DataSources { id, template } => {
+            let store = make_store();
+            commands::data_sources::list(store, id, template)
+        }
     };
     if let Err(e) = result {
         die!("error: {}", e)
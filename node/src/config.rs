@@ -2,16 +2,19 @@ use graph::{
     components::ethereum::NodeCapabilities,
     prelude::{
         anyhow::{anyhow, bail, Context, Result},
-        info, serde_json, Logger, NodeId,
+        info, serde_json, AdminAuth, AdminRole, AdminUser, Logger, NodeId,
     },
 };
 use graph_chain_ethereum::CLEANUP_BLOCKS;
+use graph_core::NotificationKind;
 use graph_store_postgres::{DeploymentPlacer, Shard as ShardName, PRIMARY_SHARD};
 
 use regex::Regex;
 use serde::{Deserialize, Serialize};
 use std::collections::BTreeMap;
 use std::fs::read_to_string;
+use std::net::IpAddr;
+use std::str::FromStr;
 use url::Url;
 
 const ANY_NAME: &str = ".*";
@@ -58,6 +61,8 @@ pub struct Config {
     pub stores: BTreeMap<String, Shard>,
     pub chains: ChainSection,
     pub deployment: Deployment,
+    pub notifications: Option<NotificationSection>,
+    pub admin: Option<AdminSection>,
 }
 
 fn validate_name(s: &str) -> Result<()> {
@@ -132,6 +137,14 @@ impl Config {
 
         self.chains.validate()?;
 
+        if let Some(notifications) = &self.notifications {
+            notifications.validate()?;
+        }
+
+        if let Some(admin) = &mut self.admin {
+            admin.validate()?;
+        }
+
         Ok(())
     }
 
@@ -163,6 +176,8 @@ impl Config {
             stores,
             chains,
             deployment,
+            notifications: None,
+            admin: None,
         })
     }
 
@@ -182,6 +197,15 @@ impl Config {
             .expect("a validated config has a primary store")
     }
 
+    /// How callers of the admin API are authenticated. Without an `admin`
+    /// section, the admin API is open
+    pub fn admin_auth(&self) -> AdminAuth {
+        self.admin
+            .as_ref()
+            .map(AdminSection::auth)
+            .unwrap_or_default()
+    }
+
     pub fn query_only(&self, node: &NodeId) -> bool {
         self.general
             .as_ref()
@@ -532,6 +556,157 @@ impl std::fmt::Display for Transport {
     }
 }
 
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct NotificationSection {
+    /// How often to check the health of deployments, in seconds
+    #[serde(default = "notification_interval")]
+    pub interval: u64,
+    /// How many blocks a synced deployment can fall behind the chain head
+    /// before we send a notification
+    #[serde(default = "notification_lag")]
+    pub lag_threshold: i32,
+    #[serde(rename = "webhook", default)]
+    pub webhooks: Vec<Webhook>,
+}
+
+impl NotificationSection {
+    fn validate(&self) -> Result<()> {
+        if self.interval == 0 {
+            return Err(anyhow!(
+                "the notification interval must be at least 1 second"
+            ));
+        }
+        if self.lag_threshold <= 0 {
+            return Err(anyhow!(
+                "the notification lag_threshold must be positive, but is {}",
+                self.lag_threshold
+            ));
+        }
+        for webhook in &self.webhooks {
+            webhook.validate()?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct Webhook {
+    pub name: String,
+    pub url: String,
+    /// The notifications to send; all notifications if empty
+    #[serde(default)]
+    pub events: Vec<String>,
+    #[serde(default = "webhook_retries")]
+    pub retries: usize,
+}
+
+impl Webhook {
+    fn validate(&self) -> Result<()> {
+        validate_name(&self.name).context("illegal webhook name")?;
+        Url::parse(&self.url).map_err(|e| {
+            anyhow!(
+                "the url `{}` for webhook {} is not a legal URL: {}",
+                self.url,
+                self.name,
+                e
+            )
+        })?;
+        self.kinds()
+            .with_context(|| format!("illegal events for webhook {}", self.name))?;
+        Ok(())
+    }
+
+    pub fn kinds(&self) -> Result<Vec<NotificationKind>> {
+        self.events
+            .iter()
+            .map(|event| NotificationKind::from_str(event))
+            .collect()
+    }
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct AdminSection {
+    /// The request header in which the proxy that terminates TLS for the
+    /// admin API passes the subject of the verified client certificate
+    pub client_cert_header: Option<String>,
+    /// The addresses of the proxies that pass client certificates on
+    #[serde(default)]
+    pub trusted_proxies: Vec<IpAddr>,
+    #[serde(rename = "user", default)]
+    pub users: Vec<AdminUserConfig>,
+}
+
+impl AdminSection {
+    fn validate(&mut self) -> Result<()> {
+        if !self.trusted_proxies.is_empty() && self.client_cert_header.is_none() {
+            return Err(anyhow!(
+                "`trusted_proxies` can only be used together with `client_cert_header`"
+            ));
+        }
+        let mut names = std::collections::HashSet::new();
+        for user in self.users.iter_mut() {
+            user.validate(self.client_cert_header.is_some())?;
+            if !names.insert(user.name.clone()) {
+                return Err(anyhow!("duplicate admin user {}", user.name));
+            }
+        }
+        Ok(())
+    }
+
+    fn auth(&self) -> AdminAuth {
+        AdminAuth {
+            users: self
+                .users
+                .iter()
+                .map(|user| AdminUser {
+                    name: user.name.clone(),
+                    role: user.role,
+                    token: user.token.clone(),
+                    subject: user.subject.clone(),
+                })
+                .collect(),
+            client_cert_header: self.client_cert_header.clone(),
+            trusted_proxies: self.trusted_proxies.clone(),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct AdminUserConfig {
+    pub name: String,
+    pub role: AdminRole,
+    /// The bearer token the user authenticates with; can reference
+    /// environment variables
+    #[serde(skip_serializing)]
+    pub token: Option<String>,
+    /// The subject of the client certificate the user authenticates with
+    pub subject: Option<String>,
+}
+
+impl AdminUserConfig {
+    fn validate(&mut self, has_cert_header: bool) -> Result<()> {
+        validate_name(&self.name).context("illegal admin user name")?;
+        if let Some(token) = &self.token {
+            let token = shellexpand::env(token)?.into_owned();
+            if token.is_empty() {
+                return Err(anyhow!("the token for admin user {} is empty", self.name));
+            }
+            self.token = Some(token);
+        }
+        match (&self.token, &self.subject) {
+            (None, None) => Err(anyhow!(
+                "admin user {} needs a token or a certificate subject",
+                self.name
+            )),
+            (_, Some(_)) if !has_cert_header => Err(anyhow!(
+                "admin user {} uses a certificate subject, but `client_cert_header` is not set",
+                self.name
+            )),
+            _ => Ok(()),
+        }
+    }
+}
+
 #[derive(Clone, Debug, Deserialize, Serialize)]
 pub struct Deployment {
     #[serde(rename = "rule")]
@@ -715,3 +890,15 @@ fn primary_store() -> String {
 fn one() -> usize {
     1
 }
+
+fn notification_interval() -> u64 {
+    60
+}
+
+fn notification_lag() -> i32 {
+    1000
+}
+
+fn webhook_retries() -> usize {
+    3
+}
@@ -21,9 +21,9 @@ use graph::util::security::SafeDisplay;
 use graph_chain_arweave::adapter::ArweaveAdapter;
 use graph_chain_ethereum::{network_indexer, BlockIngestor, BlockStreamBuilder, Transport};
 use graph_core::{
-    three_box::ThreeBoxAdapter, LinkResolver, MetricsRegistry,
+    three_box::ThreeBoxAdapter, HealthNotifier, LinkResolver, MetricsRegistry,
     SubgraphAssignmentProvider as IpfsSubgraphAssignmentProvider, SubgraphInstanceManager,
-    SubgraphRegistrar as IpfsSubgraphRegistrar,
+    SubgraphRegistrar as IpfsSubgraphRegistrar, WebhookSink,
 };
 use graph_graphql::prelude::GraphQlRunner;
 use graph_runtime_wasm::RuntimeHostBuilder as WASMRuntimeHostBuilder;
@@ -130,6 +130,9 @@ async fn main() {
     let node_id =
         NodeId::new(opt.node_id.clone()).expect("Node ID must contain only a-z, A-Z, 0-9, and '_'");
     let query_only = config.query_only(&node_id);
+    // Query nodes do not index deployments and therefore do not send
+    // notifications about them
+    let notifications = config.notifications.clone().filter(|_| !query_only);
 
     // Obtain subgraph related command-line arguments
     let subgraph = opt.subgraph.clone();
@@ -263,10 +266,14 @@ async fn main() {
                 network_store.subgraph_store(),
             );
 
+            let mapping_profiler = Arc::new(MappingProfiler::from_env());
+            let indexing_progress = Arc::new(IndexingProgress::new());
             let mut index_node_server = IndexNodeServer::new(
                 &logger_factory,
                 graphql_runner.clone(),
                 network_store.clone(),
+                mapping_profiler.clone(),
+                indexing_progress.clone(),
             );
 
             // Spawn Ethereum network indexers for all networks that are to be indexed
@@ -323,6 +330,33 @@ async fn main() {
                 graph::spawn_blocking(job_runner.start());
             }
 
+            // Notify webhooks about health changes of the deployments that
+            // this node indexes
+            if let Some(notifications) = notifications {
+                let sinks = notifications
+                    .webhooks
+                    .iter()
+                    .map(|webhook| {
+                        WebhookSink::new(
+                            webhook.name.clone(),
+                            webhook.url.clone(),
+                            webhook.kinds().expect("webhook events have been validated"),
+                            webhook.retries,
+                        )
+                    })
+                    .collect();
+                let notifier = HealthNotifier::new(
+                    &logger,
+                    network_store.clone(),
+                    network_store.subgraph_store(),
+                    node_id.clone(),
+                    sinks,
+                    Duration::from_secs(notifications.interval),
+                    notifications.lag_threshold,
+                );
+                graph::spawn(notifier.run());
+            }
+
             let block_stream_builder = BlockStreamBuilder::new(
                 network_store.subgraph_store(),
                 network_store.block_store(),
@@ -349,6 +383,9 @@ async fn main() {
                 block_stream_builder,
                 metrics_registry.clone(),
                 link_resolver.cheap_clone(),
+                mapping_profiler,
+                indexing_progress,
+                node_id.clone(),
             );
 
             // Create IPFS-based subgraph provider
@@ -391,6 +428,9 @@ async fn main() {
                 http_port,
                 ws_port,
                 subgraph_registrar.clone(),
+                network_store.subgraph_store(),
+                network_store.clone(),
+                config.admin_auth(),
                 node_id.clone(),
                 logger.clone(),
             )
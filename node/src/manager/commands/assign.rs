@@ -23,3 +23,23 @@ pub fn reassign(store: Arc<SubgraphStore>, id: String, node: String) -> Result<(
 
     Ok(())
 }
+
+pub fn pause(store: Arc<SubgraphStore>, id: String) -> Result<(), Error> {
+    let id =
+        SubgraphDeploymentId::new(id).map_err(|id| anyhow!("illegal deployment id `{}`", id))?;
+
+    println!("pausing {}", id.as_str());
+    store.pause_subgraph(&id)?;
+
+    Ok(())
+}
+
+pub fn resume(store: Arc<SubgraphStore>, id: String) -> Result<(), Error> {
+    let id =
+        SubgraphDeploymentId::new(id).map_err(|id| anyhow!("illegal deployment id `{}`", id))?;
+
+    println!("resuming {}", id.as_str());
+    store.resume_subgraph(&id)?;
+
+    Ok(())
+}
@@ -0,0 +1,39 @@
+use std::sync::Arc;
+
+use graph::prelude::{anyhow, Error, SubgraphDeploymentId};
+use graph_store_postgres::SubgraphStore;
+
+/// List the data sources that deployment `id` created from templates,
+/// optionally only those created from `template`
+pub fn list(store: Arc<SubgraphStore>, id: String, template: Option<String>) -> Result<(), Error> {
+    let id =
+        SubgraphDeploymentId::new(id).map_err(|id| anyhow!("illegal deployment id `{}`", id))?;
+
+    let data_sources: Vec<_> = store
+        .dynamic_data_sources(&id)?
+        .into_iter()
+        .filter(|ds| template.as_ref().map_or(true, |t| &ds.template == t))
+        .collect();
+
+    println!(
+        "{:<24} {:<42} {:>10} {:>10}  {}",
+        "template", "address", "created", "triggers", "context"
+    );
+    println!("{:-<120}", "");
+    for ds in &data_sources {
+        println!(
+            "{:<24} {:<42} {:>10} {:>10}  {}",
+            ds.template,
+            ds.address
+                .map(|address| format!("{:?}", address))
+                .unwrap_or_default(),
+            ds.creation_block
+                .map(|block| block.to_string())
+                .unwrap_or_default(),
+            ds.trigger_count,
+            ds.context.as_deref().unwrap_or("")
+        );
+    }
+    println!("\n{} data sources", data_sources.len());
+    Ok(())
+}
@@ -0,0 +1,59 @@
+use std::{sync::Arc, time::Instant};
+
+use graph::{
+    components::store::EntityType,
+    prelude::{anyhow, Error, SubgraphDeploymentId},
+};
+use graph_store_postgres::SubgraphStore;
+
+fn deployment_id(id: String) -> Result<SubgraphDeploymentId, Error> {
+    SubgraphDeploymentId::new(id).map_err(|id| anyhow!("illegal deployment id `{}`", id))
+}
+
+pub fn create(
+    store: Arc<SubgraphStore>,
+    id: String,
+    entity: String,
+    fields: Vec<String>,
+) -> Result<(), Error> {
+    let id = deployment_id(id)?;
+    let entity = EntityType::new(entity);
+
+    println!(
+        "creating index on {}({}) for {}. This might take a while.",
+        entity,
+        fields.join(", "),
+        id.as_str()
+    );
+    let start = Instant::now();
+    let name = store.create_manual_index(&id, &entity, fields)?;
+    println!(
+        "created index {} in {:.1}s",
+        name,
+        start.elapsed().as_millis() as f64 / 1000.0
+    );
+    Ok(())
+}
+
+pub fn list(store: Arc<SubgraphStore>, id: String, entity: String) -> Result<(), Error> {
+    let id = deployment_id(id)?;
+    let entity = EntityType::new(entity);
+
+    let indexes = store.indexes_for_entity(&id, &entity)?;
+    if indexes.is_empty() {
+        println!("no indexes for {}", entity);
+    }
+    for (name, definition) in indexes {
+        println!("{}", name);
+        println!("  {}", definition);
+    }
+    Ok(())
+}
+
+pub fn drop(store: Arc<SubgraphStore>, id: String, name: String) -> Result<(), Error> {
+    let id = deployment_id(id)?;
+
+    println!("dropping index {} from {}", name, id.as_str());
+    store.drop_index(&id, &name)?;
+    Ok(())
+}
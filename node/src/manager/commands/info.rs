@@ -1,10 +1,17 @@
-use graph::prelude::anyhow;
-use graph_store_postgres::connection_pool::ConnectionPool;
+use std::sync::Arc;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use graph::prelude::{anyhow, SubgraphDeploymentId};
+use graph_store_postgres::{connection_pool::ConnectionPool, SubgraphStore};
 
 use crate::manager::deployment::Deployment;
 
+/// The number of health history entries printed per deployment
+const HISTORY_LENGTH: i64 = 50;
+
 pub fn run(
     pool: ConnectionPool,
+    store: Option<Arc<SubgraphStore>>,
     name: String,
     current: bool,
     pending: bool,
@@ -29,8 +36,74 @@ pub fn run(
 
     if deployments.is_empty() {
         println!("No matches");
-    } else {
-        Deployment::print_table(deployments);
+        return Ok(());
+    }
+
+    let ids: Vec<_> = deployments
+        .iter()
+        .map(|deployment| deployment.deployment.clone())
+        .collect();
+    Deployment::print_table(deployments);
+
+    if let Some(store) = store {
+        let mut printed = Vec::new();
+        for id in ids {
+            // The same deployment can be listed for several subgraphs
+            if printed.contains(&id) {
+                continue;
+            }
+            print_history(&store, &id)?;
+            printed.push(id);
+        }
     }
     Ok(())
 }
+
+fn print_history(store: &SubgraphStore, id: &str) -> Result<(), anyhow::Error> {
+    let id = SubgraphDeploymentId::new(id)
+        .map_err(|id| anyhow::anyhow!("illegal deployment id `{}`", id))?;
+    let events = store.health_history(&id, HISTORY_LENGTH)?;
+
+    println!("\nhealth history of {}", id);
+    println!(
+        "{:<20} {:<14} {:<10} {:>10}  {}",
+        "at", "event", "health", "block", "message"
+    );
+    println!("{:-<120}", "");
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|now| now.as_secs() as i64)
+        .unwrap_or(0);
+    for event in &events {
+        println!(
+            "{:<20} {:<14} {:<10} {:>10}  {}",
+            ago(now - event.at),
+            event.kind.as_str(),
+            event.health.map(|health| health.as_str()).unwrap_or(""),
+            event
+                .block_number
+                .map(|number| number.to_string())
+                .unwrap_or_default(),
+            event.message.as_deref().unwrap_or("")
+        );
+    }
+    if events.is_empty() {
+        println!("no events recorded");
+    }
+    Ok(())
+}
+
+/// Format `secs` seconds as a rough age like `3d 4h ago`
+fn ago(secs: i64) -> String {
+    let secs = secs.max(0);
+    let (days, hours, mins) = (secs / 86400, secs % 86400 / 3600, secs % 3600 / 60);
+    if days > 0 {
+        format!("{}d {}h ago", days, hours)
+    } else if hours > 0 {
+        format!("{}h {}m ago", hours, mins)
+    } else if mins > 0 {
+        format!("{}m {}s ago", mins, secs % 60)
+    } else {
+        format!("{}s ago", secs)
+    }
+}
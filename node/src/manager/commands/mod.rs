@@ -1,7 +1,11 @@
 pub mod assign;
 pub mod config;
+pub mod data_sources;
+pub mod index;
 pub mod info;
 pub mod listen;
+pub mod poi;
 pub mod remove;
+pub mod stats;
 pub mod txn_speed;
 pub mod unused_deployments;
@@ -0,0 +1,610 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::future::Future;
+use std::str::FromStr;
+use std::sync::Arc;
+
+use graph::{
+    components::store::{BlockStore as _, ChainStore as _},
+    prelude::{
+        anyhow, hex, reqwest, serde_json,
+        web3::types::{Address, H256},
+        BlockNumber, Error, EthereumBlockPointer, SubgraphDeploymentId, SubgraphStore as _,
+    },
+};
+use graph_store_postgres::{ChainStore, Store};
+
+/// Where the proofs of indexing that we compare against come from
+enum Reference {
+    /// Proofs of indexing listed in a file, by block number
+    File(BTreeMap<BlockNumber, (H256, String)>),
+    /// An index node that we query for proofs of indexing. Block hashes are
+    /// taken from our chain store
+    IndexNode {
+        url: String,
+        client: reqwest::Client,
+        chain_store: Arc<ChainStore>,
+    },
+}
+
+impl Reference {
+    /// Read the file at `path`. Each line contains a block number, the
+    /// block hash and the proof of indexing for that block, separated by
+    /// whitespace. Empty lines and lines starting with `#` are ignored
+    fn from_file(path: &str) -> Result<Self, Error> {
+        let contents = fs::read_to_string(path)
+            .map_err(|e| anyhow!("could not read reference file `{}`: {}", path, e))?;
+        let mut pois = BTreeMap::new();
+        for (lineno, line) in contents.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let invalid = || {
+                anyhow!(
+                    "{}:{}: expected `<block number> <block hash> <proof of indexing>`",
+                    path,
+                    lineno + 1
+                )
+            };
+            let fields: Vec<_> = line.split_whitespace().collect();
+            if fields.len() != 3 {
+                return Err(invalid());
+            }
+            let number = BlockNumber::from_str(fields[0]).map_err(|_| invalid())?;
+            let hash = H256::from_str(fields[1].trim_start_matches("0x")).map_err(|_| invalid())?;
+            pois.insert(number, (hash, normalize(fields[2])));
+        }
+        Ok(Reference::File(pois))
+    }
+
+    fn block_hash(&self, number: BlockNumber) -> Result<H256, Error> {
+        match self {
+            Reference::File(pois) => Ok(pois[&number].0),
+            Reference::IndexNode { chain_store, .. } => block_hash(chain_store, number),
+        }
+    }
+
+    async fn poi(
+        &self,
+        id: &SubgraphDeploymentId,
+        indexer: &Option<Address>,
+        block: &EthereumBlockPointer,
+    ) -> Result<Option<String>, Error> {
+        match self {
+            Reference::File(pois) => Ok(Some(pois[&block.number].1.clone())),
+            Reference::IndexNode { url, client, .. } => {
+                let query = serde_json::json!({
+                    "query": "query poi($subgraph: String!, $number: Int!, $hash: Bytes!, $indexer: Bytes) {
+                        proofOfIndexing(subgraph: $subgraph, blockNumber: $number,
+                                        blockHash: $hash, indexer: $indexer)
+                    }",
+                    "variables": {
+                        "subgraph": id.as_str(),
+                        "number": block.number,
+                        "hash": format!("0x{}", block.hash_hex()),
+                        "indexer": indexer.map(|indexer| format!("{:?}", indexer)),
+                    }
+                });
+                let body = client
+                    .post(url)
+                    .header("Content-Type", "application/json")
+                    .body(query.to_string())
+                    .send()
+                    .await?
+                    .error_for_status()?
+                    .text()
+                    .await?;
+                let response: serde_json::Value = serde_json::from_str(&body)?;
+                if let Some(errors) = response.get("errors") {
+                    return Err(anyhow!("the index node at {} failed: {}", url, errors));
+                }
+                match &response["data"]["proofOfIndexing"] {
+                    serde_json::Value::String(poi) => Ok(Some(normalize(poi))),
+                    serde_json::Value::Null => Ok(None),
+                    other => Err(anyhow!(
+                        "the index node at {} returned an invalid proof of indexing: {}",
+                        url,
+                        other
+                    )),
+                }
+            }
+        }
+    }
+}
+
+/// The block numbers that we bisect over
+enum Candidates {
+    Blocks(Vec<BlockNumber>),
+    Range(BlockNumber, BlockNumber),
+}
+
+impl Candidates {
+    fn len(&self) -> usize {
+        match self {
+            Candidates::Blocks(blocks) => blocks.len(),
+            Candidates::Range(from, to) => (to - from + 1).max(0) as usize,
+        }
+    }
+
+    fn get(&self, idx: usize) -> BlockNumber {
+        match self {
+            Candidates::Blocks(blocks) => blocks[idx],
+            Candidates::Range(from, _) => from + idx as BlockNumber,
+        }
+    }
+}
+
+/// Where the proofs of indexing first differ from the reference
+#[derive(Debug, PartialEq)]
+enum Divergence {
+    /// The proofs of indexing agree at the last candidate
+    None(BlockNumber),
+    /// The proofs of indexing already differ at the first candidate
+    First(BlockNumber),
+    /// The proofs of indexing agree at the first block and differ at the
+    /// second block. There are no candidates between them
+    Between(BlockNumber, BlockNumber),
+}
+
+/// Bisect over `candidates` to find where the proofs of indexing start to
+/// differ; `agrees` compares them for one block. Since proofs of indexing
+/// accumulate, they differ at every block after the first block at which
+/// they differ
+async fn find_divergence<F, Fut>(
+    candidates: &Candidates,
+    mut agrees: F,
+) -> Result<Divergence, Error>
+where
+    F: FnMut(BlockNumber) -> Fut,
+    Fut: Future<Output = Result<bool, Error>>,
+{
+    if candidates.len() == 0 {
+        return Err(anyhow!("there are no blocks to compare"));
+    }
+
+    let last = candidates.get(candidates.len() - 1);
+    if agrees(last).await? {
+        return Ok(Divergence::None(last));
+    }
+
+    let first = candidates.get(0);
+    if !agrees(first).await? {
+        return Ok(Divergence::First(first));
+    }
+
+    // The proofs of indexing agree at `lo` and differ at `hi`
+    let (mut lo, mut hi) = (0, candidates.len() - 1);
+    while hi - lo > 1 {
+        let mid = lo + (hi - lo) / 2;
+        if agrees(candidates.get(mid)).await? {
+            lo = mid;
+        } else {
+            hi = mid;
+        }
+    }
+    Ok(Divergence::Between(candidates.get(lo), candidates.get(hi)))
+}
+
+/// The hash of the block with `number` in `chain_store`
+fn block_hash(chain_store: &ChainStore, number: BlockNumber) -> Result<H256, Error> {
+    let hashes = chain_store.block_hashes_by_block_number(number)?;
+    match hashes.as_slice() {
+        [hash] => Ok(*hash),
+        [] => Err(anyhow!("the chain store has no block {}", number)),
+        _ => Err(anyhow!(
+            "the chain store has {} blocks with number {}",
+            hashes.len(),
+            number
+        )),
+    }
+}
+
+fn normalize(poi: &str) -> String {
+    poi.trim_start_matches("0x").to_lowercase()
+}
+
+/// Compare our proof of indexing for `number` with the reference and
+/// return `true` if they are the same
+async fn agrees(
+    store: &Store,
+    reference: &Reference,
+    id: &SubgraphDeploymentId,
+    indexer: &Option<Address>,
+    number: BlockNumber,
+) -> Result<bool, Error> {
+    let hash = reference.block_hash(number)?;
+    let block = EthereumBlockPointer::from((hash, number as u64));
+
+    let ours = store
+        .subgraph_store()
+        .get_proof_of_indexing(id, indexer, block.clone())
+        .await?
+        .map(|poi| hex::encode(&poi))
+        .ok_or_else(|| anyhow!("we have no proof of indexing for block {}", number))?;
+    let theirs = reference.poi(id, indexer, &block).await?.ok_or_else(|| {
+        anyhow!(
+            "the reference has no proof of indexing for block {}",
+            number
+        )
+    })?;
+
+    let agrees = ours == theirs;
+    println!(
+        "block {:>10}: ours 0x{} reference 0x{} {}",
+        number,
+        ours,
+        theirs,
+        if agrees { "agree" } else { "differ" }
+    );
+    Ok(agrees)
+}
+
+/// Print the digest for each causality region at `block`, mark the regions
+/// whose digest changed in `block`, and print the events that the
+/// deployment recorded for `block` if there are any
+fn dump_causality_regions(
+    store: &Store,
+    id: &SubgraphDeploymentId,
+    block: BlockNumber,
+) -> Result<(), Error> {
+    let subgraph_store = store.subgraph_store();
+    let digests = subgraph_store
+        .poi_digests(id, block)?
+        .ok_or_else(|| anyhow!("the deployment does not support proofs of indexing"))?;
+    let previous = if block > 0 {
+        subgraph_store
+            .poi_digests(id, block - 1)?
+            .unwrap_or_default()
+    } else {
+        BTreeMap::new()
+    };
+
+    println!("\nproof of indexing digests at block {}", block);
+    println!("{:<40} {:<8} {}", "causality region", "changed", "digest");
+    println!("{:-<120}", "");
+    for (region, digest) in &digests {
+        let changed = previous.get(region) != Some(digest);
+        println!(
+            "{:<40} {:<8} {}",
+            region,
+            if changed { "yes" } else { "" },
+            hex::encode(digest)
+        );
+    }
+
+    match subgraph_store.poi_events(id, block)? {
+        Some(events) => {
+            for (region, events) in &events {
+                println!(
+                    "\nproof of indexing events for {} at block {}",
+                    region, block
+                );
+                println!("{:-<120}", "");
+                for event in events {
+                    println!("{}", event);
+                }
+            }
+        }
+        None if subgraph_store.poi_events_block(id)? == Some(block) => println!(
+            "\nThe events for block {} will be recorded once the deployment \
+             processes that block again. Resume the deployment and run this \
+             command again once it has processed block {}",
+            block, block
+        ),
+        None => println!(
+            "\nNo events were recorded for block {}. To record them, pause the \
+             deployment and run `graphman poi record {} {}`",
+            block, id, block
+        ),
+    }
+    Ok(())
+}
+
+/// Have the deployment record its proof of indexing events for `block` by
+/// rewinding it to the block before `block`. The deployment must be paused,
+/// and records the events once it is resumed and processes `block` again
+pub fn record(store: Arc<Store>, id: String, block: BlockNumber) -> Result<(), Error> {
+    let id =
+        SubgraphDeploymentId::new(id).map_err(|id| anyhow!("illegal deployment id `{}`", id))?;
+    if block <= 0 {
+        return Err(anyhow!(
+            "can not record events for block {} since the deployment can not be rewound to the block before it",
+            block
+        ));
+    }
+
+    let subgraph_store = store.subgraph_store();
+    let latest = subgraph_store
+        .block_ptr(&id)?
+        .ok_or_else(|| anyhow!("deployment {} has not indexed any blocks", id))?
+        .number;
+    if block > latest {
+        // The deployment has not processed `block` yet, no need to rewind
+        subgraph_store.request_poi_events(&id, block)?;
+        println!(
+            "{} will record its proof of indexing events for block {} when it processes it",
+            id, block
+        );
+        return Ok(());
+    }
+
+    let network = subgraph_store.network_name(&id)?;
+    let chain_store = store
+        .block_store()
+        .chain_store(&network)
+        .ok_or_else(|| anyhow!("there is no chain store for network {}", network))?;
+    let hash = block_hash(&chain_store, block - 1)?;
+
+    // Rewinding fails unless the deployment is paused, and nothing can
+    // process the block before the request is recorded
+    subgraph_store.rewind(&id, EthereumBlockPointer::from((hash, block - 1)))?;
+    subgraph_store.request_poi_events(&id, block)?;
+    println!(
+        "rewound {} to block {}; resume it with `graphman resume {}` and it will \
+         record its proof of indexing events for block {}",
+        id,
+        block - 1,
+        id,
+        block
+    );
+    Ok(())
+}
+
+/// Find the first block at which our proof of indexing for a deployment
+/// differs from a reference, by bisecting over block numbers. The reference
+/// is either a file or the URL of another index node
+pub async fn bisect(
+    store: Arc<Store>,
+    id: String,
+    reference: String,
+    indexer: Option<String>,
+    from: Option<BlockNumber>,
+    to: Option<BlockNumber>,
+) -> Result<(), Error> {
+    let id =
+        SubgraphDeploymentId::new(id).map_err(|id| anyhow!("illegal deployment id `{}`", id))?;
+    let indexer = indexer
+        .map(|indexer| {
+            Address::from_str(indexer.trim_start_matches("0x"))
+                .map_err(|_| anyhow!("illegal indexer address `{}`", indexer))
+        })
+        .transpose()?;
+
+    let subgraph_store = store.subgraph_store();
+    let latest = subgraph_store
+        .block_ptr(&id)?
+        .ok_or_else(|| anyhow!("deployment {} has not indexed any blocks", id))?
+        .number;
+    let from = from.unwrap_or(0);
+    let to = to.unwrap_or(latest).min(latest);
+
+    let (reference, candidates) =
+        if reference.starts_with("http://") || reference.starts_with("https://") {
+            let network = subgraph_store.network_name(&id)?;
+            let chain_store = store
+                .block_store()
+                .chain_store(&network)
+                .ok_or_else(|| anyhow!("there is no chain store for network {}", network))?;
+            let reference = Reference::IndexNode {
+                url: reference,
+                client: reqwest::Client::new(),
+                chain_store,
+            };
+            (reference, Candidates::Range(from, to))
+        } else {
+            let reference = Reference::from_file(&reference)?;
+            let blocks = match &reference {
+                Reference::File(pois) => pois.range(from..=to).map(|(number, _)| *number).collect(),
+                Reference::IndexNode { .. } => unreachable!("the reference is a file"),
+            };
+            (reference, Candidates::Blocks(blocks))
+        };
+
+    if candidates.len() == 0 {
+        return Err(anyhow!(
+            "there are no blocks between {} and {} to compare",
+            from,
+            to
+        ));
+    }
+
+    let divergence = find_divergence(&candidates, |number| {
+        agrees(&store, &reference, &id, &indexer, number)
+    })
+    .await?;
+    let divergent = match divergence {
+        Divergence::None(last) => {
+            println!("\nthe proofs of indexing agree at block {}", last);
+            return Ok(());
+        }
+        Divergence::First(first) => {
+            println!(
+                "\nthe proofs of indexing already differ at block {}, the first block that was compared",
+                first
+            );
+            first
+        }
+        Divergence::Between(agreeing, divergent) if divergent - agreeing > 1 => {
+            println!(
+                "\nthe proofs of indexing agree at block {} and first differ at block {}; \
+                 the reference has no proofs of indexing for the blocks in between",
+                agreeing, divergent
+            );
+            divergent
+        }
+        Divergence::Between(_, divergent) => {
+            println!(
+                "\nthe proofs of indexing first differ at block {}",
+                divergent
+            );
+            divergent
+        }
+    };
+
+    dump_causality_regions(&store, &id, divergent)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use graph::prelude::{futures03::future, tokio};
+    use std::cell::RefCell;
+    use std::env;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    fn write_file(contents: &str) -> String {
+        static COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+        let path = env::temp_dir().join(format!(
+            "poi-reference-{}-{}",
+            std::process::id(),
+            COUNTER.fetch_add(1, Ordering::SeqCst)
+        ));
+        fs::write(&path, contents).unwrap();
+        path.to_str().unwrap().to_owned()
+    }
+
+    fn hash(n: u64) -> H256 {
+        H256::from_low_u64_be(n)
+    }
+
+    #[test]
+    fn reference_from_file() {
+        let path = write_file(&format!(
+            "# block hash poi\n\n  1 0x{:x} 0xABCD  \n3\t{:x}\tef01\n",
+            hash(1),
+            hash(3)
+        ));
+        let pois = match Reference::from_file(&path).unwrap() {
+            Reference::File(pois) => pois,
+            Reference::IndexNode { .. } => panic!("expected a file reference"),
+        };
+        fs::remove_file(&path).unwrap();
+
+        let expected: BTreeMap<_, _> = vec![
+            (1, (hash(1), "abcd".to_owned())),
+            (3, (hash(3), "ef01".to_owned())),
+        ]
+        .into_iter()
+        .collect();
+        assert_eq!(expected, pois);
+    }
+
+    #[test]
+    fn reference_from_file_errors() {
+        for (contents, line) in &[
+            ("1 0x01\n", 1),
+            ("# comment\n\nx 0x01 0xab\n", 3),
+            ("1 0xnothex 0xab\n", 1),
+            ("1 0x01 0xab extra\n", 1),
+        ] {
+            let path = write_file(contents);
+            let err = Reference::from_file(&path).err().unwrap();
+            fs::remove_file(&path).unwrap();
+            assert!(
+                err.to_string().starts_with(&format!("{}:{}: ", path, line)),
+                "unexpected error for {:?}: {}",
+                contents,
+                err
+            );
+        }
+
+        let err = Reference::from_file("/nonexistent/poi-reference")
+            .err()
+            .unwrap();
+        assert!(err.to_string().contains("could not read reference file"));
+    }
+
+    #[test]
+    fn candidates() {
+        let range = Candidates::Range(10, 14);
+        assert_eq!(5, range.len());
+        assert_eq!(
+            vec![10, 11, 12, 13, 14],
+            (0..5).map(|i| range.get(i)).collect::<Vec<_>>()
+        );
+        assert_eq!(0, Candidates::Range(10, 9).len());
+
+        let blocks = Candidates::Blocks(vec![3, 7, 20]);
+        assert_eq!(3, blocks.len());
+        assert_eq!(7, blocks.get(1));
+    }
+
+    /// Bisect over `candidates` against a deployment whose proofs of
+    /// indexing first differ at `divergent`, and return the result together
+    /// with the blocks that were compared
+    async fn bisect(
+        candidates: Candidates,
+        divergent: BlockNumber,
+    ) -> (Divergence, Vec<BlockNumber>) {
+        let compared = RefCell::new(Vec::new());
+        let divergence = find_divergence(&candidates, |number| {
+            compared.borrow_mut().push(number);
+            future::ready(Ok(number < divergent))
+        })
+        .await
+        .unwrap();
+        (divergence, compared.into_inner())
+    }
+
+    #[tokio::test]
+    async fn find_divergence_in_range() {
+        let (divergence, compared) = bisect(Candidates::Range(0, 100), 37).await;
+        assert_eq!(Divergence::Between(36, 37), divergence);
+        assert_eq!(vec![100, 0], compared[0..2].to_vec());
+        // Bisection only needs a logarithmic number of comparisons
+        assert!(compared.len() <= 2 + 7);
+
+        // Agree at the lower bound, differ right after it
+        let (divergence, _) = bisect(Candidates::Range(5, 100), 6).await;
+        assert_eq!(Divergence::Between(5, 6), divergence);
+
+        // Agree everywhere but at the upper bound
+        let (divergence, _) = bisect(Candidates::Range(5, 100), 100).await;
+        assert_eq!(Divergence::Between(99, 100), divergence);
+    }
+
+    #[tokio::test]
+    async fn find_divergence_at_ends() {
+        let (divergence, compared) = bisect(Candidates::Range(5, 100), 200).await;
+        assert_eq!(Divergence::None(100), divergence);
+        assert_eq!(vec![100], compared);
+
+        let (divergence, compared) = bisect(Candidates::Range(5, 100), 5).await;
+        assert_eq!(Divergence::First(5), divergence);
+        assert_eq!(vec![100, 5], compared);
+
+        let (divergence, _) = bisect(Candidates::Range(5, 5), 200).await;
+        assert_eq!(Divergence::None(5), divergence);
+
+        assert!(
+            find_divergence(&Candidates::Blocks(vec![]), |_| { future::ready(Ok(true)) })
+                .await
+                .is_err()
+        );
+    }
+
+    #[tokio::test]
+    async fn find_divergence_with_gaps() {
+        let blocks = || Candidates::Blocks(vec![1, 10, 20, 30, 50, 80]);
+
+        // The proofs of indexing first differ at a block that is not a
+        // candidate; we only know that they differ somewhere after 30
+        let (divergence, compared) = bisect(blocks(), 42).await;
+        assert_eq!(Divergence::Between(30, 50), divergence);
+        assert!(compared
+            .iter()
+            .all(|block| blocks_contain(&blocks(), *block)));
+
+        let (divergence, _) = bisect(blocks(), 20).await;
+        assert_eq!(Divergence::Between(10, 20), divergence);
+
+        let (divergence, _) = bisect(blocks(), 1).await;
+        assert_eq!(Divergence::First(1), divergence);
+    }
+
+    fn blocks_contain(candidates: &Candidates, block: BlockNumber) -> bool {
+        (0..candidates.len()).any(|i| candidates.get(i) == block)
+    }
+}
@@ -0,0 +1,124 @@
+use std::sync::Arc;
+
+use graph::prelude::{anyhow, Error, SubgraphDeploymentId};
+use graph_store_postgres::command_support::{TableStats, VersionCount};
+use graph_store_postgres::SubgraphStore;
+
+/// Tables with fewer rows than this are never suggested for pruning
+const PRUNE_MIN_VERSIONS: i64 = 10_000;
+/// Suggest pruning when more than this fraction of all versions in a table
+/// are historical
+const PRUNE_HISTORY_RATIO: f64 = 0.5;
+/// Suggest a vacuum when the number of dead tuples in a table is more than
+/// this fraction of the number of rows
+const VACUUM_DEAD_RATIO: f64 = 0.2;
+
+fn human_bytes(bytes: i64) -> String {
+    const UNITS: [&str; 5] = ["B", "kB", "MB", "GB", "TB"];
+
+    let mut size = bytes as f64;
+    let mut unit = 0;
+    while size >= 1024.0 && unit < UNITS.len() - 1 {
+        size /= 1024.0;
+        unit += 1;
+    }
+    if unit == 0 {
+        format!("{} {}", bytes, UNITS[unit])
+    } else {
+        format!("{:.1} {}", size, UNITS[unit])
+    }
+}
+
+pub fn run(store: Arc<SubgraphStore>, id: String) -> Result<(), Error> {
+    let id =
+        SubgraphDeploymentId::new(id).map_err(|id| anyhow!("illegal deployment id `{}`", id))?;
+
+    println!(
+        "counting entity versions for {}. This might take a while.",
+        id.as_str()
+    );
+    let (counts, stats) = store.stats(&id)?;
+    let find_stats = |count: &VersionCount| -> Option<&TableStats> {
+        stats
+            .iter()
+            .find(|stats| stats.name == count.table.as_str())
+    };
+
+    println!(
+        "\n{:<30} {:>12} {:>12} {:>12} {:>10} {:>10} {:>10}",
+        "entity", "versions", "current", "historical", "table", "indexes", "dead"
+    );
+    println!("{:-<102}", "");
+    for count in &counts {
+        let (table_bytes, index_bytes, dead) = find_stats(count)
+            .map(|stats| (stats.table_bytes, stats.index_bytes, stats.dead_tuples))
+            .unwrap_or((0, 0, 0));
+        println!(
+            "{:<30} {:>12} {:>12} {:>12} {:>10} {:>10} {:>10}",
+            count.entity.as_str(),
+            count.versions,
+            count.current,
+            count.versions - count.current,
+            human_bytes(table_bytes),
+            human_bytes(index_bytes),
+            dead
+        );
+    }
+
+    println!("\n{:<60} {:>12} {:>10}", "index", "scans", "size");
+    println!("{:-<84}", "");
+    for table in &stats {
+        for index in &table.indexes {
+            println!(
+                "{:<60} {:>12} {:>10}",
+                index.name,
+                index.scans,
+                human_bytes(index.bytes)
+            );
+        }
+    }
+
+    let mut suggestions = Vec::new();
+    for table in &stats {
+        for index in &table.indexes {
+            // The BRIN indexes on block ranges are needed for reverts even
+            // if queries never use them
+            if index.scans == 0 && !index.is_constraint && !index.name.starts_with("brin_") {
+                suggestions.push(format!(
+                    "index {} on {} has never been used and could be dropped to save {}",
+                    index.name,
+                    table.name,
+                    human_bytes(index.bytes)
+                ));
+            }
+        }
+    }
+    for count in &counts {
+        let historical = count.versions - count.current;
+        if count.versions >= PRUNE_MIN_VERSIONS
+            && historical as f64 > PRUNE_HISTORY_RATIO * count.versions as f64
+        {
+            suggestions.push(format!(
+                "{} versions of {} are historical; pruning history would shrink table {}",
+                historical, count.entity, count.table
+            ));
+        }
+        if let Some(stats) = find_stats(count) {
+            if stats.dead_tuples as f64 > VACUUM_DEAD_RATIO * count.versions as f64 {
+                suggestions.push(format!(
+                    "table {} has {} dead rows and should be vacuumed",
+                    count.table, stats.dead_tuples
+                ));
+            }
+        }
+    }
+
+    if !suggestions.is_empty() {
+        println!("\nSuggestions:");
+        for suggestion in suggestions {
+            println!("  - {}", suggestion);
+        }
+    }
+    println!("\nIndex usage is counted since Postgres statistics were last reset");
+    Ok(())
+}
@@ -13,6 +13,7 @@ pub struct Deployment {
     pub deployment: String,
     pub namespace: String,
     pub node_id: Option<String>,
+    pub paused: Option<bool>,
     pub shard: String,
     pub chain: String,
 }
@@ -39,6 +40,7 @@ impl Deployment {
                 v::deployment,
                 ds::name,
                 a::node_id.nullable(),
+                a::paused.nullable(),
                 ds::shard,
                 ds::network,
             ));
@@ -74,7 +76,11 @@ impl Deployment {
                 deployment.namespace,
                 deployment.shard,
                 deployment.chain,
-                deployment.node_id.unwrap_or("---".to_string()),
+                match (deployment.node_id, deployment.paused) {
+                    (Some(node_id), Some(true)) => format!("{} (paused)", node_id),
+                    (Some(node_id), _) => node_id,
+                    (None, _) => "---".to_string(),
+                },
             ]);
         }
 
@@ -7,7 +7,7 @@ use graph::{
     prelude::{info, CheapClone, EthereumNetworkIdentifier, Logger},
     util::security::SafeDisplay,
 };
-use graph_store_postgres::connection_pool::ConnectionPool;
+use graph_store_postgres::connection_pool::{ConnectionPool, PoolName};
 use graph_store_postgres::{
     BlockStore as DieselBlockStore, ChainHeadUpdateListener as PostgresChainHeadUpdateListener,
     Shard as ShardName, Store as DieselStore, SubgraphStore, SubscriptionManager, PRIMARY_SHARD,
@@ -148,7 +148,7 @@ impl StoreBuilder {
         );
         ConnectionPool::create(
             name,
-            "main",
+            PoolName::Main,
             shard.connection.to_owned(),
             pool_size,
             &logger,
@@ -186,7 +186,7 @@ impl StoreBuilder {
                     ));
                     ConnectionPool::create(
                         name,
-                        pool,
+                        PoolName::Replica(pool.clone()),
                         replica.connection.clone(),
                         pool_size,
                         &logger,
@@ -0,0 +1,511 @@
+//! The encoding of values that cross the boundary between a mapping and
+//! graph-node. See `docs/rust-mappings.md` for the specification; the host
+//! side lives in `runtime/wasm/src/rust_abi.rs`.
+use std::collections::BTreeMap;
+use std::fmt;
+
+use crate::types::*;
+
+/// A buffer from the host could not be decoded.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DecodeError(pub String);
+
+impl fmt::Display for DecodeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "decode error: {}", self.0)
+    }
+}
+
+impl std::error::Error for DecodeError {}
+
+#[derive(Default)]
+pub struct Encoder {
+    bytes: Vec<u8>,
+}
+
+impl Encoder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn u8(&mut self, value: u8) {
+        self.bytes.push(value);
+    }
+
+    pub fn u32(&mut self, value: u32) {
+        self.bytes.extend_from_slice(&value.to_le_bytes());
+    }
+
+    pub fn u64(&mut self, value: u64) {
+        self.bytes.extend_from_slice(&value.to_le_bytes());
+    }
+
+    pub fn bool(&mut self, value: bool) {
+        self.u8(value as u8);
+    }
+
+    /// Bytes of a size that is known from the context, like addresses and hashes.
+    pub fn fixed(&mut self, bytes: &[u8]) {
+        self.bytes.extend_from_slice(bytes);
+    }
+
+    /// Bytes prefixed with their length.
+    pub fn bytes(&mut self, bytes: &[u8]) {
+        self.u32(bytes.len() as u32);
+        self.fixed(bytes);
+    }
+
+    pub fn str(&mut self, s: &str) {
+        self.bytes(s.as_bytes());
+    }
+
+    pub fn into_bytes(self) -> Vec<u8> {
+        self.bytes
+    }
+}
+
+pub struct Decoder<'a> {
+    bytes: &'a [u8],
+}
+
+impl<'a> Decoder<'a> {
+    pub fn new(bytes: &'a [u8]) -> Self {
+        Decoder { bytes }
+    }
+
+    pub fn fixed(&mut self, len: usize) -> Result<&'a [u8], DecodeError> {
+        if self.bytes.len() < len {
+            return Err(DecodeError("unexpected end of buffer".to_string()));
+        }
+        let (bytes, rest) = self.bytes.split_at(len);
+        self.bytes = rest;
+        Ok(bytes)
+    }
+
+    pub fn address(&mut self) -> Result<Address, DecodeError> {
+        let mut address = [0; 20];
+        address.copy_from_slice(self.fixed(20)?);
+        Ok(address)
+    }
+
+    /// A hash or a 256 bit number.
+    pub fn word(&mut self) -> Result<[u8; 32], DecodeError> {
+        let mut word = [0; 32];
+        word.copy_from_slice(self.fixed(32)?);
+        Ok(word)
+    }
+
+    pub fn u8(&mut self) -> Result<u8, DecodeError> {
+        Ok(self.fixed(1)?[0])
+    }
+
+    pub fn u32(&mut self) -> Result<u32, DecodeError> {
+        let mut bytes = [0; 4];
+        bytes.copy_from_slice(self.fixed(4)?);
+        Ok(u32::from_le_bytes(bytes))
+    }
+
+    pub fn u64(&mut self) -> Result<u64, DecodeError> {
+        let mut bytes = [0; 8];
+        bytes.copy_from_slice(self.fixed(8)?);
+        Ok(u64::from_le_bytes(bytes))
+    }
+
+    pub fn bool(&mut self) -> Result<bool, DecodeError> {
+        match self.u8()? {
+            0 => Ok(false),
+            1 => Ok(true),
+            b => Err(DecodeError(format!("invalid boolean {}", b))),
+        }
+    }
+
+    pub fn bytes(&mut self) -> Result<&'a [u8], DecodeError> {
+        let len = self.u32()? as usize;
+        self.fixed(len)
+    }
+
+    pub fn string(&mut self) -> Result<String, DecodeError> {
+        String::from_utf8(self.bytes()?.to_vec())
+            .map_err(|e| DecodeError(format!("invalid string: {}", e)))
+    }
+
+    /// A count of items that follow, each of which takes at least one byte.
+    pub fn count(&mut self) -> Result<usize, DecodeError> {
+        let count = self.u32()? as usize;
+        if count > self.bytes.len() {
+            return Err(DecodeError(format!(
+                "count {} exceeds the size of the buffer",
+                count
+            )));
+        }
+        Ok(count)
+    }
+
+    fn finish(&self) -> Result<(), DecodeError> {
+        if !self.bytes.is_empty() {
+            return Err(DecodeError(format!(
+                "{} unexpected bytes at the end of buffer",
+                self.bytes.len()
+            )));
+        }
+        Ok(())
+    }
+}
+
+/// A type that can be passed to the host.
+pub trait Encode {
+    fn encode(&self, encoder: &mut Encoder);
+
+    fn to_bytes(&self) -> Vec<u8> {
+        let mut encoder = Encoder::new();
+        self.encode(&mut encoder);
+        encoder.into_bytes()
+    }
+}
+
+/// A type that can be received from the host.
+pub trait Decode: Sized {
+    fn decode(decoder: &mut Decoder) -> Result<Self, DecodeError>;
+
+    fn from_bytes(bytes: &[u8]) -> Result<Self, DecodeError> {
+        let mut decoder = Decoder::new(bytes);
+        let value = Self::decode(&mut decoder)?;
+        decoder.finish()?;
+        Ok(value)
+    }
+}
+
+impl Encode for u8 {
+    fn encode(&self, encoder: &mut Encoder) {
+        encoder.u8(*self);
+    }
+}
+
+impl Decode for u8 {
+    fn decode(decoder: &mut Decoder) -> Result<Self, DecodeError> {
+        decoder.u8()
+    }
+}
+
+impl Encode for str {
+    fn encode(&self, encoder: &mut Encoder) {
+        encoder.str(self);
+    }
+}
+
+impl Encode for String {
+    fn encode(&self, encoder: &mut Encoder) {
+        encoder.str(self);
+    }
+}
+
+impl Decode for String {
+    fn decode(decoder: &mut Decoder) -> Result<Self, DecodeError> {
+        decoder.string()
+    }
+}
+
+impl Encode for Address {
+    fn encode(&self, encoder: &mut Encoder) {
+        encoder.fixed(self);
+    }
+}
+
+impl Decode for Address {
+    fn decode(decoder: &mut Decoder) -> Result<Self, DecodeError> {
+        decoder.address()
+    }
+}
+
+impl Encode for [u8; 32] {
+    fn encode(&self, encoder: &mut Encoder) {
+        encoder.fixed(self);
+    }
+}
+
+impl Decode for [u8; 32] {
+    fn decode(decoder: &mut Decoder) -> Result<Self, DecodeError> {
+        decoder.word()
+    }
+}
+
+impl<T: Encode> Encode for [T] {
+    fn encode(&self, encoder: &mut Encoder) {
+        encoder.u32(self.len() as u32);
+        for item in self {
+            item.encode(encoder);
+        }
+    }
+}
+
+impl<T: Encode> Encode for Vec<T> {
+    fn encode(&self, encoder: &mut Encoder) {
+        self.as_slice().encode(encoder);
+    }
+}
+
+impl<T: Decode> Decode for Vec<T> {
+    fn decode(decoder: &mut Decoder) -> Result<Self, DecodeError> {
+        (0..decoder.count()?).map(|_| T::decode(decoder)).collect()
+    }
+}
+
+impl<T: Encode> Encode for Option<T> {
+    fn encode(&self, encoder: &mut Encoder) {
+        match self {
+            None => encoder.u8(0),
+            Some(value) => {
+                encoder.u8(1);
+                value.encode(encoder);
+            }
+        }
+    }
+}
+
+impl<T: Decode> Decode for Option<T> {
+    fn decode(decoder: &mut Decoder) -> Result<Self, DecodeError> {
+        match decoder.bool()? {
+            false => Ok(None),
+            true => Ok(Some(T::decode(decoder)?)),
+        }
+    }
+}
+
+const VALUE_NULL: u8 = 0;
+const VALUE_STRING: u8 = 1;
+const VALUE_INT: u8 = 2;
+const VALUE_BIG_DECIMAL: u8 = 3;
+const VALUE_BOOL: u8 = 4;
+const VALUE_LIST: u8 = 5;
+const VALUE_BYTES: u8 = 6;
+const VALUE_BIG_INT: u8 = 7;
+
+impl Encode for Value {
+    fn encode(&self, encoder: &mut Encoder) {
+        match self {
+            Value::Null => encoder.u8(VALUE_NULL),
+            Value::String(s) => {
+                encoder.u8(VALUE_STRING);
+                encoder.str(s);
+            }
+            Value::Int(n) => {
+                encoder.u8(VALUE_INT);
+                encoder.fixed(&n.to_le_bytes());
+            }
+            Value::BigDecimal(n) => {
+                encoder.u8(VALUE_BIG_DECIMAL);
+                encoder.str(n);
+            }
+            Value::Bool(b) => {
+                encoder.u8(VALUE_BOOL);
+                encoder.bool(*b);
+            }
+            Value::List(values) => {
+                encoder.u8(VALUE_LIST);
+                values.encode(encoder);
+            }
+            Value::Bytes(bytes) => {
+                encoder.u8(VALUE_BYTES);
+                encoder.bytes(bytes);
+            }
+            Value::BigInt(n) => {
+                encoder.u8(VALUE_BIG_INT);
+                encoder.bytes(n);
+            }
+        }
+    }
+}
+
+impl Decode for Value {
+    fn decode(decoder: &mut Decoder) -> Result<Self, DecodeError> {
+        Ok(match decoder.u8()? {
+            VALUE_NULL => Value::Null,
+            VALUE_STRING => Value::String(decoder.string()?),
+            VALUE_INT => Value::Int(decoder.u32()? as i32),
+            VALUE_BIG_DECIMAL => Value::BigDecimal(decoder.string()?),
+            VALUE_BOOL => Value::Bool(decoder.bool()?),
+            VALUE_LIST => Value::List(Vec::decode(decoder)?),
+            VALUE_BYTES => Value::Bytes(decoder.bytes()?.to_vec()),
+            VALUE_BIG_INT => Value::BigInt(decoder.bytes()?.to_vec()),
+            tag => return Err(DecodeError(format!("invalid value kind {}", tag))),
+        })
+    }
+}
+
+impl Encode for Entity {
+    fn encode(&self, encoder: &mut Encoder) {
+        encoder.u32(self.len() as u32);
+        for (name, value) in self {
+            encoder.str(name);
+            value.encode(encoder);
+        }
+    }
+}
+
+impl Decode for Entity {
+    fn decode(decoder: &mut Decoder) -> Result<Self, DecodeError> {
+        let mut entity = BTreeMap::new();
+        for _ in 0..decoder.count()? {
+            entity.insert(decoder.string()?, Value::decode(decoder)?);
+        }
+        Ok(entity)
+    }
+}
+
+const TOKEN_ADDRESS: u8 = 0;
+const TOKEN_FIXED_BYTES: u8 = 1;
+const TOKEN_BYTES: u8 = 2;
+const TOKEN_INT: u8 = 3;
+const TOKEN_UINT: u8 = 4;
+const TOKEN_BOOL: u8 = 5;
+const TOKEN_STRING: u8 = 6;
+const TOKEN_FIXED_ARRAY: u8 = 7;
+const TOKEN_ARRAY: u8 = 8;
+const TOKEN_TUPLE: u8 = 9;
+
+impl Encode for Token {
+    fn encode(&self, encoder: &mut Encoder) {
+        match self {
+            Token::Address(address) => {
+                encoder.u8(TOKEN_ADDRESS);
+                encoder.fixed(address);
+            }
+            Token::FixedBytes(bytes) => {
+                encoder.u8(TOKEN_FIXED_BYTES);
+                encoder.bytes(bytes);
+            }
+            Token::Bytes(bytes) => {
+                encoder.u8(TOKEN_BYTES);
+                encoder.bytes(bytes);
+            }
+            Token::Int(n) => {
+                encoder.u8(TOKEN_INT);
+                encoder.fixed(n);
+            }
+            Token::Uint(n) => {
+                encoder.u8(TOKEN_UINT);
+                encoder.fixed(n);
+            }
+            Token::Bool(b) => {
+                encoder.u8(TOKEN_BOOL);
+                encoder.bool(*b);
+            }
+            Token::String(s) => {
+                encoder.u8(TOKEN_STRING);
+                encoder.str(s);
+            }
+            Token::FixedArray(tokens) => {
+                encoder.u8(TOKEN_FIXED_ARRAY);
+                tokens.encode(encoder);
+            }
+            Token::Array(tokens) => {
+                encoder.u8(TOKEN_ARRAY);
+                tokens.encode(encoder);
+            }
+            Token::Tuple(tokens) => {
+                encoder.u8(TOKEN_TUPLE);
+                tokens.encode(encoder);
+            }
+        }
+    }
+}
+
+impl Decode for Token {
+    fn decode(decoder: &mut Decoder) -> Result<Self, DecodeError> {
+        Ok(match decoder.u8()? {
+            TOKEN_ADDRESS => Token::Address(decoder.address()?),
+            TOKEN_FIXED_BYTES => Token::FixedBytes(decoder.bytes()?.to_vec()),
+            TOKEN_BYTES => Token::Bytes(decoder.bytes()?.to_vec()),
+            TOKEN_INT => Token::Int(decoder.word()?),
+            TOKEN_UINT => Token::Uint(decoder.word()?),
+            TOKEN_BOOL => Token::Bool(decoder.bool()?),
+            TOKEN_STRING => Token::String(decoder.string()?),
+            TOKEN_FIXED_ARRAY => Token::FixedArray(Vec::decode(decoder)?),
+            TOKEN_ARRAY => Token::Array(Vec::decode(decoder)?),
+            TOKEN_TUPLE => Token::Tuple(Vec::decode(decoder)?),
+            tag => return Err(DecodeError(format!("invalid token kind {}", tag))),
+        })
+    }
+}
+
+impl Decode for Param {
+    fn decode(decoder: &mut Decoder) -> Result<Self, DecodeError> {
+        Ok(Param {
+            name: decoder.string()?,
+            value: Token::decode(decoder)?,
+        })
+    }
+}
+
+impl Decode for Block {
+    fn decode(decoder: &mut Decoder) -> Result<Self, DecodeError> {
+        Ok(Block {
+            hash: decoder.word()?,
+            parent_hash: decoder.word()?,
+            uncles_hash: decoder.word()?,
+            author: decoder.address()?,
+            state_root: decoder.word()?,
+            transactions_root: decoder.word()?,
+            receipts_root: decoder.word()?,
+            number: decoder.u64()?,
+            gas_used: decoder.word()?,
+            gas_limit: decoder.word()?,
+            timestamp: decoder.word()?,
+            difficulty: decoder.word()?,
+            total_difficulty: decoder.word()?,
+            size: Option::decode(decoder)?,
+        })
+    }
+}
+
+impl Decode for Transaction {
+    fn decode(decoder: &mut Decoder) -> Result<Self, DecodeError> {
+        Ok(Transaction {
+            hash: decoder.word()?,
+            index: decoder.u64()?,
+            from: decoder.address()?,
+            to: Option::decode(decoder)?,
+            value: decoder.word()?,
+            gas_used: decoder.word()?,
+            gas_price: decoder.word()?,
+            input: decoder.bytes()?.to_vec(),
+        })
+    }
+}
+
+impl Decode for Event {
+    fn decode(decoder: &mut Decoder) -> Result<Self, DecodeError> {
+        Ok(Event {
+            address: decoder.address()?,
+            log_index: decoder.word()?,
+            transaction_log_index: decoder.word()?,
+            log_type: Option::decode(decoder)?,
+            block: Block::decode(decoder)?,
+            transaction: Transaction::decode(decoder)?,
+            params: Vec::decode(decoder)?,
+        })
+    }
+}
+
+impl Decode for Call {
+    fn decode(decoder: &mut Decoder) -> Result<Self, DecodeError> {
+        Ok(Call {
+            from: decoder.address()?,
+            to: decoder.address()?,
+            block: Block::decode(decoder)?,
+            transaction: Transaction::decode(decoder)?,
+            inputs: Vec::decode(decoder)?,
+            outputs: Vec::decode(decoder)?,
+        })
+    }
+}
+
+impl Encode for SmartContractCall {
+    fn encode(&self, encoder: &mut Encoder) {
+        encoder.str(&self.contract_name);
+        encoder.fixed(&self.contract_address);
+        encoder.str(&self.function_name);
+        self.function_signature.encode(encoder);
+        self.function_args.encode(encoder);
+    }
+}
@@ -0,0 +1,274 @@
+//! Write subgraph mappings in Rust. Mappings that declare `language: wasm/rust`
+//! in the subgraph manifest exchange data with graph-node through the
+//! layout-stable ABI described in `docs/rust-mappings.md`, which this crate
+//! implements for the guest side.
+//!
+//! Handlers are exported functions that take the address of their argument,
+//! which they turn into an `Event`, `Call` or `Block` with `take`:
+//!
+//! ```ignore
+//! use graph_mapping_sdk::{store, take, Event, Value};
+//!
+//! #[no_mangle]
+//! pub extern "C" fn handleTransfer(event: u32) {
+//!     let event: Event = unsafe { take(event) };
+//!     let mut transfer = store::get("Transfer", "latest").unwrap_or_default();
+//!     transfer.insert("block".to_string(), Value::Int(event.block.number as i32));
+//!     store::set("Transfer", "latest", &transfer);
+//! }
+//! ```
+//!
+//! The crate builds on any target so that the encoding can be tested natively,
+//! but the host functions are only available on `wasm32`.
+pub mod abi;
+mod types;
+
+pub use abi::{Decode, DecodeError, Encode};
+pub use types::*;
+
+/// Encode `value` as a buffer for the host: its length as a little-endian
+/// `u32`, followed by its encoding.
+pub fn buffer<T: Encode + ?Sized>(value: &T) -> Vec<u8> {
+    let payload = value.to_bytes();
+    let mut buffer = Vec::with_capacity(payload.len() + 4);
+    buffer.extend_from_slice(&(payload.len() as u32).to_le_bytes());
+    buffer.extend(payload);
+    buffer
+}
+
+/// Decode a buffer in the format produced by `buffer`.
+pub fn from_buffer<T: Decode>(buffer: &[u8]) -> Result<T, DecodeError> {
+    if buffer.len() < 4 {
+        return Err(DecodeError("buffer is too short".to_string()));
+    }
+    let mut len = [0; 4];
+    len.copy_from_slice(&buffer[..4]);
+    let len = u32::from_le_bytes(len) as usize;
+    match buffer.get(4..4 + len) {
+        Some(payload) => T::from_bytes(payload),
+        None => Err(DecodeError("buffer is too short".to_string())),
+    }
+}
+
+#[cfg(target_arch = "wasm32")]
+mod sys {
+    #[link(wasm_import_module = "graph")]
+    extern "C" {
+        pub fn store_get(entity: u32, id: u32) -> u32;
+        pub fn store_get_at_block(entity: u32, id: u32, block: i32) -> u32;
+        pub fn store_load_related(entity: u32, id: u32, field: u32) -> u32;
+        pub fn store_set(entity: u32, id: u32, data: u32);
+        pub fn store_remove(entity: u32, id: u32);
+        pub fn ethereum_call(call: u32) -> u32;
+        pub fn crypto_keccak256(input: u32) -> u32;
+        pub fn ipfs_cat(hash: u32) -> u32;
+        pub fn data_source_create(name: u32, params: u32);
+        pub fn log(level: u32, msg: u32);
+        pub fn panic(msg: u32) -> !;
+    }
+}
+
+#[cfg(target_arch = "wasm32")]
+mod memory {
+    use std::alloc::{alloc, dealloc, Layout};
+
+    use crate::abi::Decode;
+
+    fn layout(size: u32) -> Layout {
+        Layout::from_size_align(size.max(1) as usize, 1).unwrap()
+    }
+
+    /// Allocates the buffers that the host passes to the mapping. The mapping
+    /// owns them and frees them in `take`.
+    #[no_mangle]
+    pub extern "C" fn graph_alloc(size: u32) -> u32 {
+        unsafe { alloc(layout(size)) as u32 }
+    }
+
+    /// Decode the buffer at `ptr` and free it.
+    ///
+    /// # Safety
+    ///
+    /// `ptr` must be the address of a buffer that the host passed to the
+    /// mapping, and may only be taken once.
+    pub unsafe fn take<T: Decode>(ptr: u32) -> T {
+        let mut len = [0; 4];
+        len.copy_from_slice(std::slice::from_raw_parts(ptr as *const u8, 4));
+        let len = u32::from_le_bytes(len);
+        let payload = std::slice::from_raw_parts((ptr + 4) as *const u8, len as usize);
+        let value = T::from_bytes(payload);
+        dealloc(ptr as *mut u8, layout(len + 4));
+        value.unwrap_or_else(|e| crate::abort(&e.to_string()))
+    }
+}
+
+#[cfg(target_arch = "wasm32")]
+pub use memory::{graph_alloc, take};
+
+/// Fail the handler with `msg`. This is a deterministic error, the subgraph
+/// fails unless it opted into non-fatal errors.
+#[cfg(target_arch = "wasm32")]
+pub fn abort(msg: &str) -> ! {
+    let msg = buffer(msg);
+    unsafe { sys::panic(msg.as_ptr() as u32) }
+}
+
+/// Report panics through `abort` so that the panic message shows up in the
+/// subgraph logs instead of a bare `unreachable` trap.
+#[cfg(target_arch = "wasm32")]
+pub fn set_panic_hook() {
+    std::panic::set_hook(Box::new(|info| abort(&info.to_string())));
+}
+
+#[cfg(target_arch = "wasm32")]
+pub mod store {
+    use crate::{buffer, sys, take, Entity};
+
+    pub fn get(entity: &str, id: &str) -> Option<Entity> {
+        let entity = buffer(entity);
+        let id = buffer(id);
+        match unsafe { sys::store_get(entity.as_ptr() as u32, id.as_ptr() as u32) } {
+            0 => None,
+            ptr => Some(unsafe { take(ptr) }),
+        }
+    }
+
+    /// The entity as it was at the end of `block`, which must be before the
+    /// block that is being processed.
+    pub fn get_at_block(entity: &str, id: &str, block: i32) -> Option<Entity> {
+        let entity = buffer(entity);
+        let id = buffer(id);
+        match unsafe { sys::store_get_at_block(entity.as_ptr() as u32, id.as_ptr() as u32, block) }
+        {
+            0 => None,
+            ptr => Some(unsafe { take(ptr) }),
+        }
+    }
+
+    /// The entities of the field `field` of the entity, which must be
+    /// declared with `@derivedFrom`, ordered by their id.
+    pub fn load_related(entity: &str, id: &str, field: &str) -> Vec<Entity> {
+        let entity = buffer(entity);
+        let id = buffer(id);
+        let field = buffer(field);
+        unsafe {
+            take(sys::store_load_related(
+                entity.as_ptr() as u32,
+                id.as_ptr() as u32,
+                field.as_ptr() as u32,
+            ))
+        }
+    }
+
+    pub fn set(entity: &str, id: &str, data: &Entity) {
+        let entity = buffer(entity);
+        let id = buffer(id);
+        let data = buffer(data);
+        unsafe {
+            sys::store_set(
+                entity.as_ptr() as u32,
+                id.as_ptr() as u32,
+                data.as_ptr() as u32,
+            )
+        }
+    }
+
+    pub fn remove(entity: &str, id: &str) {
+        let entity = buffer(entity);
+        let id = buffer(id);
+        unsafe { sys::store_remove(entity.as_ptr() as u32, id.as_ptr() as u32) }
+    }
+}
+
+#[cfg(target_arch = "wasm32")]
+pub mod ethereum {
+    use crate::{buffer, sys, take, SmartContractCall, Token};
+
+    /// Call a contract function at the block that is being processed. Returns
+    /// `None` if the call reverted.
+    pub fn call(call: &SmartContractCall) -> Option<Vec<Token>> {
+        let call = buffer(call);
+        match unsafe { sys::ethereum_call(call.as_ptr() as u32) } {
+            0 => None,
+            ptr => Some(unsafe { take(ptr) }),
+        }
+    }
+}
+
+#[cfg(target_arch = "wasm32")]
+pub mod crypto {
+    use crate::{buffer, sys, take, H256};
+
+    pub fn keccak256(input: &[u8]) -> H256 {
+        let input = buffer(input);
+        unsafe { take(sys::crypto_keccak256(input.as_ptr() as u32)) }
+    }
+}
+
+#[cfg(target_arch = "wasm32")]
+pub mod ipfs {
+    use crate::{buffer, sys, take};
+
+    /// The file with `hash`, or `None` if it could not be read. Subgraphs
+    /// that use this can only be indexed by nodes that allow non-deterministic
+    /// IPFS access.
+    pub fn cat(hash: &str) -> Option<Vec<u8>> {
+        let hash = buffer(hash);
+        match unsafe { sys::ipfs_cat(hash.as_ptr() as u32) } {
+            0 => None,
+            ptr => Some(unsafe { take(ptr) }),
+        }
+    }
+}
+
+#[cfg(target_arch = "wasm32")]
+pub mod data_source {
+    use crate::{buffer, sys};
+
+    /// Create a data source from the template `name`. For Ethereum contracts,
+    /// `params` is the address of the contract.
+    pub fn create(name: &str, params: &[String]) {
+        let name = buffer(name);
+        let params = buffer(params);
+        unsafe { sys::data_source_create(name.as_ptr() as u32, params.as_ptr() as u32) }
+    }
+}
+
+#[cfg(target_arch = "wasm32")]
+pub mod log {
+    use crate::{buffer, sys};
+
+    #[derive(Copy, Clone, Debug, PartialEq, Eq)]
+    pub enum Level {
+        Critical = 0,
+        Error = 1,
+        Warning = 2,
+        Info = 3,
+        Debug = 4,
+    }
+
+    pub fn log(level: Level, msg: &str) {
+        let msg = buffer(msg);
+        unsafe { sys::log(level as u32, msg.as_ptr() as u32) }
+    }
+
+    pub fn critical(msg: &str) {
+        log(Level::Critical, msg)
+    }
+
+    pub fn error(msg: &str) {
+        log(Level::Error, msg)
+    }
+
+    pub fn warning(msg: &str) {
+        log(Level::Warning, msg)
+    }
+
+    pub fn info(msg: &str) {
+        log(Level::Info, msg)
+    }
+
+    pub fn debug(msg: &str) {
+        log(Level::Debug, msg)
+    }
+}
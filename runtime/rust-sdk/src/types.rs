@@ -0,0 +1,111 @@
+//! The data that mappings receive from and pass to graph-node.
+use std::collections::BTreeMap;
+
+pub type Address = [u8; 20];
+pub type H256 = [u8; 32];
+
+/// A 256 bit number as 32 bytes in little-endian order. Signed numbers are in
+/// two's complement.
+pub type U256 = [u8; 32];
+
+/// The value of an entity attribute.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Value {
+    Null,
+    String(String),
+    Int(i32),
+    /// In decimal notation, for example `-1.5`.
+    BigDecimal(String),
+    Bool(bool),
+    List(Vec<Value>),
+    Bytes(Vec<u8>),
+    /// Two's complement in little-endian order.
+    BigInt(Vec<u8>),
+}
+
+/// The attributes of an entity, by name.
+pub type Entity = BTreeMap<String, Value>;
+
+/// A value that is passed to or returned from a contract.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Token {
+    Address(Address),
+    FixedBytes(Vec<u8>),
+    Bytes(Vec<u8>),
+    Int(U256),
+    Uint(U256),
+    Bool(bool),
+    String(String),
+    FixedArray(Vec<Token>),
+    Array(Vec<Token>),
+    Tuple(Vec<Token>),
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Param {
+    pub name: String,
+    pub value: Token,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Block {
+    pub hash: H256,
+    pub parent_hash: H256,
+    pub uncles_hash: H256,
+    pub author: Address,
+    pub state_root: H256,
+    pub transactions_root: H256,
+    pub receipts_root: H256,
+    pub number: u64,
+    pub gas_used: U256,
+    pub gas_limit: U256,
+    pub timestamp: U256,
+    pub difficulty: U256,
+    pub total_difficulty: U256,
+    pub size: Option<U256>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Transaction {
+    pub hash: H256,
+    pub index: u64,
+    pub from: Address,
+    pub to: Option<Address>,
+    pub value: U256,
+    pub gas_used: U256,
+    pub gas_price: U256,
+    pub input: Vec<u8>,
+}
+
+/// The argument of event handlers.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Event {
+    pub address: Address,
+    pub log_index: U256,
+    pub transaction_log_index: U256,
+    pub log_type: Option<String>,
+    pub block: Block,
+    pub transaction: Transaction,
+    pub params: Vec<Param>,
+}
+
+/// The argument of call handlers.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Call {
+    pub from: Address,
+    pub to: Address,
+    pub block: Block,
+    pub transaction: Transaction,
+    pub inputs: Vec<Param>,
+    pub outputs: Vec<Param>,
+}
+
+/// A call of a contract function, see `ethereum::call`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SmartContractCall {
+    pub contract_name: String,
+    pub contract_address: Address,
+    pub function_name: String,
+    pub function_signature: Option<String>,
+    pub function_args: Vec<Token>,
+}
@@ -1,4 +1,5 @@
 use crate::error::DeterministicHostError;
+use anyhow::anyhow;
 
 use super::{class::EnumPayload, AscHeap, AscType, AscValue};
 use std::fmt;
@@ -49,7 +50,7 @@ impl<C: AscType> AscPtr<C> {
     /// Read from `self` into the Rust struct `C`.
     pub(super) fn read_ptr<H: AscHeap>(self, heap: &H) -> Result<C, DeterministicHostError> {
         let bytes = heap.get(self.0, C::asc_size(self, heap)?)?;
-        C::from_asc_bytes(&bytes)
+        C::from_asc_bytes_versioned(&bytes, &heap.api_version())
     }
 
     /// Allocate `asc_obj` as an Asc object of class `C`.
@@ -57,7 +58,8 @@ impl<C: AscType> AscPtr<C> {
         asc_obj: &C,
         heap: &mut H,
     ) -> Result<AscPtr<C>, DeterministicHostError> {
-        let heap_ptr = heap.raw_new(&asc_obj.to_asc_bytes()?)?;
+        let bytes = asc_obj.to_asc_bytes_versioned(&heap.api_version())?;
+        let heap_ptr = heap.raw_new(&bytes, C::RT_ID)?;
         Ok(AscPtr::new(heap_ptr))
     }
 
@@ -70,6 +72,20 @@ impl<C: AscType> AscPtr<C> {
         Ok(u32::from_le_bytes(u32_bytes))
     }
 
+    /// Read the size of the object in bytes from the header that the
+    /// AssemblyScript runtime puts in front of it. Only valid for
+    /// apiVersion 0.0.5 and later.
+    pub(super) fn read_rt_size<H: AscHeap>(&self, heap: &H) -> Result<u32, DeterministicHostError> {
+        // `rtSize` is the last field of the header, right before the object.
+        let offset = self.0.checked_sub(size_of::<u32>() as u32).ok_or_else(|| {
+            DeterministicHostError(anyhow!(
+                "Attempted to read object header at pointer {}",
+                self.0
+            ))
+        })?;
+        AscPtr::<u32>::new(offset).read_u32(heap)
+    }
+
     /// Conversion to `u64` for use with `AscEnum`.
     pub(crate) fn to_payload(&self) -> u64 {
         self.0 as u64
@@ -79,11 +95,6 @@ impl<C: AscType> AscPtr<C> {
     pub(crate) fn is_null(&self) -> bool {
         self.0 == 0
     }
-
-    // Erase type information.
-    pub(crate) fn erase(self) -> AscPtr<()> {
-        AscPtr::new(self.0)
-    }
 }
 
 impl<C> From<u32> for AscPtr<C> {
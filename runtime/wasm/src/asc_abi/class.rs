@@ -1,11 +1,12 @@
-use super::{AscHeap, AscPtr, AscType, AscValue};
+use super::{uses_runtime_layout, AscHeap, AscPtr, AscType, AscValue};
 use crate::error::DeterministicHostError;
 use anyhow::anyhow;
 use ethabi;
 use graph::data::store;
+use graph::data::subgraph::schema::MappingLogLevel;
 use graph::prelude::serde_json;
-use graph::prelude::slog;
 use graph_runtime_derive::AscType;
+use semver::Version;
 use std::convert::TryInto as _;
 use std::marker::PhantomData;
 use std::mem::{size_of, size_of_val};
@@ -13,6 +14,11 @@ use std::mem::{size_of, size_of_val};
 ///! Rust types that have with a direct correspondence to an Asc class,
 ///! with their `AscType` implementations.
 
+/// Runtime ids that AssemblyScript 0.19 reserves for builtin classes, used
+/// in object headers for apiVersion 0.0.5 and later. Id 0 is `Object`.
+pub(crate) const ARRAY_BUFFER_RT_ID: u32 = 1;
+pub(crate) const STRING_RT_ID: u32 = 2;
+
 /// Asc std ArrayBuffer: "a generic, fixed-length raw binary data buffer".
 /// See https://github.com/AssemblyScript/assemblyscript/wiki/Memory-Layout-&-Management#arrays
 pub(crate) struct ArrayBuffer<T> {
@@ -117,7 +123,40 @@ impl<T> AscType for ArrayBuffer<T> {
         })
     }
 
+    /// For apiVersion 0.0.5 and later, the buffer consists only of its
+    /// content; the length is kept in the object header.
+    fn to_asc_bytes_versioned(
+        &self,
+        api_version: &Version,
+    ) -> Result<Vec<u8>, DeterministicHostError> {
+        if uses_runtime_layout(api_version) {
+            Ok(self.content.to_vec())
+        } else {
+            self.to_asc_bytes()
+        }
+    }
+
+    fn from_asc_bytes_versioned(
+        asc_obj: &[u8],
+        api_version: &Version,
+    ) -> Result<Self, DeterministicHostError> {
+        if uses_runtime_layout(api_version) {
+            Ok(ArrayBuffer {
+                byte_length: asc_obj.len() as u32,
+                padding: [0; 4],
+                content: asc_obj.to_vec().into(),
+                ty: PhantomData,
+            })
+        } else {
+            Self::from_asc_bytes(asc_obj)
+        }
+    }
+
     fn asc_size<H: AscHeap>(ptr: AscPtr<Self>, heap: &H) -> Result<u32, DeterministicHostError> {
+        if uses_runtime_layout(&heap.api_version()) {
+            return ptr.read_rt_size(heap);
+        }
+
         let byte_length = ptr.read_u32(heap)?;
         let byte_length_size = size_of::<u32>() as u32;
         let padding_size = size_of::<u32>() as u32;
@@ -133,7 +172,9 @@ impl<T> AscType for ArrayBuffer<T> {
 #[derive(AscType)]
 pub(crate) struct TypedArray<T> {
     pub buffer: AscPtr<ArrayBuffer<T>>,
-    /// Byte position in `buffer` of the array start.
+    /// Byte position in `buffer` of the array start. For apiVersion 0.0.5
+    /// and later, this is the absolute address of the array start
+    /// (`dataStart`) instead.
     byte_offset: u32,
     byte_length: u32,
 }
@@ -144,17 +185,36 @@ impl<T: AscValue> TypedArray<T> {
         heap: &mut H,
     ) -> Result<Self, DeterministicHostError> {
         let buffer = ArrayBuffer::new(content)?;
+        let buffer_ptr = AscPtr::alloc_obj(&buffer, heap)?;
+        let byte_offset = if uses_runtime_layout(&heap.api_version()) {
+            buffer_ptr.wasm_ptr()
+        } else {
+            0
+        };
         Ok(TypedArray {
-            buffer: AscPtr::alloc_obj(&buffer, heap)?,
-            byte_offset: 0,
+            buffer: buffer_ptr,
+            byte_offset,
             byte_length: buffer.byte_length,
         })
     }
 
     pub(crate) fn to_vec<H: AscHeap>(&self, heap: &H) -> Result<Vec<T>, DeterministicHostError> {
+        let byte_offset = if uses_runtime_layout(&heap.api_version()) {
+            self.byte_offset
+                .checked_sub(self.buffer.wasm_ptr())
+                .ok_or_else(|| {
+                    DeterministicHostError(anyhow!(
+                        "Typed array starts before its buffer: {} < {}",
+                        self.byte_offset,
+                        self.buffer.wasm_ptr()
+                    ))
+                })?
+        } else {
+            self.byte_offset
+        };
         self.buffer
             .read_ptr(heap)?
-            .get(self.byte_offset, self.byte_length / size_of::<T>() as u32)
+            .get(byte_offset, self.byte_length / size_of::<T>() as u32)
     }
 }
 
@@ -251,7 +311,44 @@ impl AscType for AscString {
         AscString::new(&content)
     }
 
+    /// For apiVersion 0.0.5 and later, the string consists only of its code
+    /// units; the length is kept in the object header.
+    fn to_asc_bytes_versioned(
+        &self,
+        api_version: &Version,
+    ) -> Result<Vec<u8>, DeterministicHostError> {
+        let mut asc_layout = self.to_asc_bytes()?;
+        if uses_runtime_layout(api_version) {
+            asc_layout.drain(..size_of::<u32>());
+        }
+        Ok(asc_layout)
+    }
+
+    fn from_asc_bytes_versioned(
+        asc_obj: &[u8],
+        api_version: &Version,
+    ) -> Result<Self, DeterministicHostError> {
+        if !uses_runtime_layout(api_version) {
+            return Self::from_asc_bytes(asc_obj);
+        }
+
+        if asc_obj.len() % 2 != 0 {
+            return Err(DeterministicHostError(anyhow::anyhow!(
+                "Invalid string length"
+            )));
+        }
+        let content: Vec<u16> = asc_obj
+            .chunks_exact(size_of::<u16>())
+            .map(|bytes| u16::from_le_bytes([bytes[0], bytes[1]]))
+            .collect();
+        AscString::new(&content)
+    }
+
     fn asc_size<H: AscHeap>(ptr: AscPtr<Self>, heap: &H) -> Result<u32, DeterministicHostError> {
+        if uses_runtime_layout(&heap.api_version()) {
+            return ptr.read_rt_size(heap);
+        }
+
         let length = ptr.read_u32(heap)?;
         let length_size = size_of::<u32>() as u32;
         let code_point_size = size_of::<u16>() as u32;
@@ -261,21 +358,31 @@ impl AscType for AscString {
             DeterministicHostError(anyhow::anyhow!("Overflowed when getting size of string"))
         })
     }
+
+    const RT_ID: u32 = STRING_RT_ID;
 }
 
 /// Growable array backed by an `ArrayBuffer`.
 /// See https://github.com/AssemblyScript/assemblyscript/wiki/Memory-Layout-&-Management#arrays
-#[repr(C)]
-#[derive(AscType)]
+///
+/// For apiVersion 0.0.5 and later, arrays are laid out as
+/// `{ buffer, dataStart, byteLength, length }`; before that only `buffer`
+/// and `length` are present.
 pub(crate) struct Array<T> {
     buffer: AscPtr<ArrayBuffer<T>>,
+    buffer_data_start: u32,
+    buffer_data_length: u32,
     length: u32,
 }
 
 impl<T: AscValue> Array<T> {
     pub fn new<H: AscHeap>(content: &[T], heap: &mut H) -> Result<Self, DeterministicHostError> {
+        let buffer = ArrayBuffer::new(content)?;
+        let buffer_ptr = AscPtr::alloc_obj(&buffer, heap)?;
         Ok(Array {
-            buffer: AscPtr::alloc_obj(&ArrayBuffer::new(content)?, heap)?,
+            buffer: buffer_ptr,
+            buffer_data_start: buffer_ptr.wasm_ptr(),
+            buffer_data_length: buffer.byte_length,
             // If this cast would overflow, the above line has already panicked.
             length: content.len() as u32,
         })
@@ -286,6 +393,80 @@ impl<T: AscValue> Array<T> {
     }
 }
 
+impl<T> Array<T> {
+    fn asc_size_for(api_version: &Version) -> usize {
+        if uses_runtime_layout(api_version) {
+            4 * size_of::<u32>()
+        } else {
+            2 * size_of::<u32>()
+        }
+    }
+}
+
+impl<T> AscType for Array<T> {
+    fn to_asc_bytes(&self) -> Result<Vec<u8>, DeterministicHostError> {
+        let mut asc_layout = self.buffer.to_asc_bytes()?;
+        asc_layout.extend(self.length.to_asc_bytes()?);
+        Ok(asc_layout)
+    }
+
+    fn from_asc_bytes(asc_obj: &[u8]) -> Result<Self, DeterministicHostError> {
+        if asc_obj.len() != 2 * size_of::<u32>() {
+            return Err(DeterministicHostError(anyhow!(
+                "Size does not match for Array: {}",
+                asc_obj.len()
+            )));
+        }
+        Ok(Array {
+            buffer: AscPtr::from_asc_bytes(&asc_obj[0..4])?,
+            buffer_data_start: 0,
+            buffer_data_length: 0,
+            length: u32::from_asc_bytes(&asc_obj[4..8])?,
+        })
+    }
+
+    fn to_asc_bytes_versioned(
+        &self,
+        api_version: &Version,
+    ) -> Result<Vec<u8>, DeterministicHostError> {
+        if !uses_runtime_layout(api_version) {
+            return self.to_asc_bytes();
+        }
+
+        let mut asc_layout = self.buffer.to_asc_bytes()?;
+        asc_layout.extend(self.buffer_data_start.to_asc_bytes()?);
+        asc_layout.extend(self.buffer_data_length.to_asc_bytes()?);
+        asc_layout.extend(self.length.to_asc_bytes()?);
+        Ok(asc_layout)
+    }
+
+    fn from_asc_bytes_versioned(
+        asc_obj: &[u8],
+        api_version: &Version,
+    ) -> Result<Self, DeterministicHostError> {
+        if !uses_runtime_layout(api_version) {
+            return Self::from_asc_bytes(asc_obj);
+        }
+
+        if asc_obj.len() != Self::asc_size_for(api_version) {
+            return Err(DeterministicHostError(anyhow!(
+                "Size does not match for Array: {}",
+                asc_obj.len()
+            )));
+        }
+        Ok(Array {
+            buffer: AscPtr::from_asc_bytes(&asc_obj[0..4])?,
+            buffer_data_start: u32::from_asc_bytes(&asc_obj[4..8])?,
+            buffer_data_length: u32::from_asc_bytes(&asc_obj[8..12])?,
+            length: u32::from_asc_bytes(&asc_obj[12..16])?,
+        })
+    }
+
+    fn asc_size<H: AscHeap>(_ptr: AscPtr<Self>, heap: &H) -> Result<u32, DeterministicHostError> {
+        Ok(Self::asc_size_for(&heap.api_version()) as u32)
+    }
+}
+
 /// Represents any `AscValue` since they all fit in 64 bits.
 #[repr(C)]
 #[derive(Copy, Clone, Default)]
@@ -626,14 +807,14 @@ pub(crate) enum LogLevel {
     Debug,
 }
 
-impl From<LogLevel> for slog::Level {
-    fn from(level: LogLevel) -> slog::Level {
+impl From<LogLevel> for MappingLogLevel {
+    fn from(level: LogLevel) -> MappingLogLevel {
         match level {
-            LogLevel::Critical => slog::Level::Critical,
-            LogLevel::Error => slog::Level::Error,
-            LogLevel::Warning => slog::Level::Warning,
-            LogLevel::Info => slog::Level::Info,
-            LogLevel::Debug => slog::Level::Debug,
+            LogLevel::Critical => MappingLogLevel::Critical,
+            LogLevel::Error => MappingLogLevel::Error,
+            LogLevel::Warning => MappingLogLevel::Warning,
+            LogLevel::Info => MappingLogLevel::Info,
+            LogLevel::Debug => MappingLogLevel::Debug,
         }
     }
 }
@@ -645,6 +826,23 @@ pub(crate) struct AscResult<V, E> {
     pub error: AscPtr<AscWrapped<E>>,
 }
 
+/// The outcome of a host export call for apiVersion 0.0.5 and later. On
+/// success, `value` holds what the host export returns; if the call failed
+/// deterministically, `error` describes the failure.
+#[repr(C)]
+#[derive(AscType)]
+pub(crate) struct AscHostResult {
+    pub value: EnumPayload,
+    pub error: AscPtr<AscHostError>,
+    pub _padding: u32, // Make padding explicit.
+}
+
+#[repr(C)]
+#[derive(AscType)]
+pub(crate) struct AscHostError {
+    pub message: AscPtr<AscString>,
+}
+
 #[repr(C)]
 #[derive(AscType)]
 pub(crate) struct AscWrapped<V> {
@@ -8,6 +8,7 @@
 pub use self::asc_ptr::AscPtr;
 use crate::error::DeterministicHostError;
 use graph::prelude::anyhow;
+use semver::Version;
 use std::convert::TryInto;
 use std::mem::size_of;
 
@@ -25,10 +26,16 @@ compile_error!("big-endian targets are currently unsupported");
 /// The implementor must provide the direct Asc interface with `raw_new` and `get`.
 pub trait AscHeap: Sized {
     /// Allocate new space and write `bytes`, return the allocated address.
-    fn raw_new(&mut self, bytes: &[u8]) -> Result<u32, DeterministicHostError>;
+    /// For apiVersion 0.0.5 and later, the object is allocated through the
+    /// AssemblyScript runtime and gets a header with runtime id `rt_id`.
+    fn raw_new(&mut self, bytes: &[u8], rt_id: u32) -> Result<u32, DeterministicHostError>;
 
     fn get(&self, offset: u32, size: u32) -> Result<Vec<u8>, DeterministicHostError>;
 
+    /// The mapping API version of the module, which determines the memory
+    /// layout of some classes.
+    fn api_version(&self) -> Version;
+
     /// Instantiate `rust_obj` as an Asc object of class `C`.
     /// Returns a pointer to the Asc heap.
     ///
@@ -98,10 +105,45 @@ pub trait AscType: Sized {
     /// The Rust representation of an Asc object as layed out in Asc memory.
     fn from_asc_bytes(asc_obj: &[u8]) -> Result<Self, DeterministicHostError>;
 
+    /// Like `to_asc_bytes`, for classes whose memory layout depends on the
+    /// mapping API version.
+    fn to_asc_bytes_versioned(
+        &self,
+        _api_version: &Version,
+    ) -> Result<Vec<u8>, DeterministicHostError> {
+        self.to_asc_bytes()
+    }
+
+    /// Like `from_asc_bytes`, for classes whose memory layout depends on the
+    /// mapping API version.
+    fn from_asc_bytes_versioned(
+        asc_obj: &[u8],
+        _api_version: &Version,
+    ) -> Result<Self, DeterministicHostError> {
+        Self::from_asc_bytes(asc_obj)
+    }
+
     /// Size of the corresponding Asc instance in bytes.
     fn asc_size<H: AscHeap>(_ptr: AscPtr<Self>, _heap: &H) -> Result<u32, DeterministicHostError> {
         Ok(size_of::<Self>() as u32)
     }
+
+    /// The runtime id that objects of this class get in their header for
+    /// apiVersion 0.0.5 and later. Apart from `ArrayBuffer` and `String`,
+    /// ids are assigned by the AssemblyScript compiler and differ between
+    /// modules, so other classes are allocated with the id of a plain
+    /// buffer, and the collector does not follow pointers inside them.
+    /// That is fine since the host pins everything it allocates, and the
+    /// memory of an instance is reset to its initial state, or the
+    /// instance discarded, after each trigger.
+    const RT_ID: u32 = class::ARRAY_BUFFER_RT_ID;
+}
+
+/// Whether objects in modules with `api_version` use the memory layout of
+/// the AssemblyScript 0.19 runtime, where every object is preceded by a
+/// 20 byte header `mmInfo, gcInfo, gcInfo2, rtId, rtSize`.
+pub(crate) fn uses_runtime_layout(api_version: &Version) -> bool {
+    *api_version >= Version::new(0, 0, 5)
 }
 
 // `AscValue` also isn't really public.
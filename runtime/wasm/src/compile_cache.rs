@@ -0,0 +1,211 @@
+//! An on-disk cache of compiled modules, so that restarting a node or redeploying a subgraph
+//! with the same mapping does not compile the mapping again. Artifacts are keyed by the hash of
+//! the module they were compiled from, the wasmtime version and the engine settings, and the
+//! oldest artifacts are removed once the cache grows beyond `GRAPH_WASM_CACHE_MAX_SIZE`.
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+use anyhow::Error;
+use graph::prelude::lazy_static;
+use sha2::{Digest, Sha256};
+
+lazy_static! {
+    /// The directory for compiled modules. Caching is disabled if this is not set
+    static ref CACHE_DIR: Option<PathBuf> = env::var("GRAPH_WASM_CACHE_DIR").ok().map(PathBuf::from);
+
+    /// The maximum size of all compiled modules in the cache, in bytes
+    static ref CACHE_MAX_SIZE: u64 = env::var("GRAPH_WASM_CACHE_MAX_SIZE")
+        .ok()
+        .map(|s| {
+            s.parse::<u64>().unwrap_or_else(|_| {
+                panic!("GRAPH_WASM_CACHE_MAX_SIZE must be a number, but is `{}`", s)
+            })
+        })
+        .unwrap_or(1000)
+        * 1_000_000;
+}
+
+/// Bump this when the layout of the cache changes. Changes to the wasmtime version or to the
+/// engine settings do not need a bump since both are part of the key.
+const CACHE_VERSION: u32 = 2;
+
+/// The version of wasmtime that modules are compiled with. This has to match the version in
+/// `Cargo.lock`, which the test below checks.
+const WASMTIME_VERSION: &str = "0.21.0";
+
+/// The settings that mappings are compiled with.
+#[derive(Debug)]
+pub(crate) struct EngineSettings {
+    pub opt_level: wasmtime::OptLevel,
+    pub nan_canonicalization: bool,
+    pub interruptable: bool,
+    pub bulk_memory: bool,
+}
+
+impl EngineSettings {
+    fn engine(&self) -> wasmtime::Engine {
+        let mut config = wasmtime::Config::new();
+        config.strategy(wasmtime::Strategy::Cranelift).unwrap();
+        config.interruptable(self.interruptable);
+        config.cranelift_nan_canonicalization(self.nan_canonicalization);
+        config.cranelift_opt_level(self.opt_level.clone());
+        config.wasm_bulk_memory(self.bulk_memory);
+        wasmtime::Engine::new(&config)
+    }
+}
+
+/// Compile `raw_module` with `settings`, or load it from the cache if it was compiled before.
+pub(crate) fn compile(
+    settings: &EngineSettings,
+    raw_module: &[u8],
+) -> Result<wasmtime::Module, Error> {
+    let engine = settings.engine();
+    let root = match CACHE_DIR.as_ref() {
+        Some(root) => root,
+        None => return wasmtime::Module::from_binary(&engine, raw_module),
+    };
+    let dir = root.join(format!("v{}", CACHE_VERSION));
+    let path = dir.join(format!("{}.cwasm", cache_key(settings, raw_module)));
+
+    // A missing, truncated or incompatible artifact is not an error, we just compile again.
+    if let Ok(bytes) = fs::read(&path) {
+        if let Ok(module) = wasmtime::Module::deserialize(&engine, &bytes) {
+            return Ok(module);
+        }
+    }
+
+    let module = wasmtime::Module::from_binary(&engine, raw_module)?;
+    // Failing to write or clean up the cache should not keep the mapping from running.
+    if store(&dir, &path, &module).is_ok() {
+        remove_old_versions(root).ok();
+        evict(&dir, *CACHE_MAX_SIZE, &path).ok();
+    }
+    Ok(module)
+}
+
+fn cache_key(settings: &EngineSettings, raw_module: &[u8]) -> String {
+    let mut hasher = Sha256::new();
+    hasher.update(WASMTIME_VERSION.as_bytes());
+    hasher.update(format!("{:?}", settings).as_bytes());
+    hasher.update(Sha256::digest(raw_module));
+    hex::encode(hasher.finalize())
+}
+
+fn store(dir: &Path, path: &Path, module: &wasmtime::Module) -> Result<(), Error> {
+    fs::create_dir_all(dir)?;
+
+    // Go through a temporary file so that nobody ever reads a partially written artifact.
+    let tmp_path = dir.join(format!("{}.tmp", uuid::Uuid::new_v4()));
+    fs::write(&tmp_path, module.serialize()?)?;
+    fs::rename(&tmp_path, path)?;
+    Ok(())
+}
+
+/// Remove the directories that earlier versions of the cache wrote to.
+fn remove_old_versions(root: &Path) -> Result<(), Error> {
+    let current = format!("v{}", CACHE_VERSION);
+    for entry in fs::read_dir(root)? {
+        let entry = entry?;
+        let name = entry.file_name();
+        let name = name.to_string_lossy();
+        if name.starts_with('v') && name != current && entry.file_type()?.is_dir() {
+            fs::remove_dir_all(entry.path())?;
+        }
+    }
+    Ok(())
+}
+
+/// Remove the oldest artifacts in `dir` until all of them together take up at most `max_size`
+/// bytes. The artifact at `keep`, which was just written, is never removed.
+fn evict(dir: &Path, max_size: u64, keep: &Path) -> Result<(), Error> {
+    let mut artifacts = Vec::new();
+    let mut size = 0;
+    for entry in fs::read_dir(dir)? {
+        let entry = entry?;
+        let path = entry.path();
+        if path.extension().map_or(true, |ext| ext != "cwasm") {
+            continue;
+        }
+        let metadata = entry.metadata()?;
+        size += metadata.len();
+        if path != keep {
+            let modified = metadata.modified().unwrap_or(SystemTime::UNIX_EPOCH);
+            artifacts.push((modified, metadata.len(), path));
+        }
+    }
+
+    artifacts.sort();
+    for (_, len, path) in artifacts {
+        if size <= max_size {
+            break;
+        }
+        // Another process may have removed the artifact already.
+        if fs::remove_file(&path).is_ok() {
+            size -= len;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::thread::sleep;
+    use std::time::Duration;
+
+    #[test]
+    fn wasmtime_version_matches_lockfile() {
+        let lockfile = include_str!("../../../Cargo.lock");
+        let entry = format!("name = \"wasmtime\"\nversion = \"{}\"\n", WASMTIME_VERSION);
+        assert!(
+            lockfile.contains(&entry),
+            "WASMTIME_VERSION does not match the version of wasmtime in Cargo.lock"
+        );
+    }
+
+    #[test]
+    fn cache_key_depends_on_settings() {
+        let settings = |nan_canonicalization| EngineSettings {
+            opt_level: wasmtime::OptLevel::None,
+            nan_canonicalization,
+            interruptable: true,
+            bulk_memory: true,
+        };
+        let module = b"\0asm\x01\0\0\0";
+
+        assert_eq!(
+            cache_key(&settings(true), module),
+            cache_key(&settings(true), module)
+        );
+        assert_ne!(
+            cache_key(&settings(true), module),
+            cache_key(&settings(false), module)
+        );
+    }
+
+    #[test]
+    fn evict_removes_oldest_artifacts() {
+        let dir = env::temp_dir().join(format!("wasm-cache-{}", uuid::Uuid::new_v4()));
+        fs::create_dir_all(&dir).unwrap();
+        let paths: Vec<_> = (0..4)
+            .map(|i| {
+                let path = dir.join(format!("{}.cwasm", i));
+                fs::write(&path, vec![0u8; 100]).unwrap();
+                // Make sure modification times differ
+                sleep(Duration::from_millis(20));
+                path
+            })
+            .collect();
+        fs::write(dir.join("other.tmp"), vec![0u8; 1000]).unwrap();
+
+        // The oldest artifact is the one that was just written and must be kept
+        evict(&dir, 250, &paths[0]).unwrap();
+        let exists: Vec<_> = paths.iter().map(|path| path.exists()).collect();
+        assert_eq!(vec![true, false, false, true], exists);
+        assert!(dir.join("other.tmp").exists());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
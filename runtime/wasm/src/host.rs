@@ -22,7 +22,7 @@ use graph::prelude::{
     RuntimeHost as RuntimeHostTrait, RuntimeHostBuilder as RuntimeHostBuilderTrait, *,
 };
 use graph::util;
-use web3::types::{Log, Transaction};
+use web3::types::{Address, Log, Transaction};
 
 use crate::mapping::{MappingContext, MappingRequest, MappingTrigger};
 use crate::{host_exports::HostExports, module::ExperimentalFeatures};
@@ -111,6 +111,7 @@ where
 
     fn spawn_mapping(
         raw_module: Vec<u8>,
+        language: MappingLanguage,
         logger: Logger,
         subgraph_id: SubgraphDeploymentId,
         metrics: Arc<HostMetrics>,
@@ -122,6 +123,7 @@ where
         };
         crate::mapping::spawn_module(
             raw_module,
+            language,
             logger,
             subgraph_id,
             metrics,
@@ -206,9 +208,9 @@ impl RuntimeHost {
         three_box_adapter: Arc<dyn ThreeBoxAdapter>,
     ) -> Result<Self, Error> {
         let api_version = Version::parse(&config.mapping.api_version)?;
-        if !VersionReq::parse("<= 0.0.4").unwrap().matches(&api_version) {
+        if !VersionReq::parse("<= 0.0.5").unwrap().matches(&api_version) {
             return Err(anyhow!(
-                "This Graph Node only supports mapping API versions <= 0.0.4, but subgraph `{}` uses `{}`",
+                "This Graph Node only supports mapping API versions <= 0.0.5, but subgraph `{}` uses `{}`",
                 config.subgraph_id,
                 api_version
             ));
@@ -742,6 +744,14 @@ impl RuntimeHostTrait for RuntimeHost {
     fn creation_block_number(&self) -> Option<BlockNumber> {
         self.data_source_creation_block
     }
+
+    fn data_source_name(&self) -> &str {
+        &self.data_source_name
+    }
+
+    fn data_source_address(&self) -> Option<Address> {
+        self.data_source_contract.address
+    }
 }
 
 impl PartialEq for RuntimeHost {
@@ -10,7 +10,10 @@ use graph::components::store::EntityKey;
 use graph::components::subgraph::{ProofOfIndexingEvent, SharedProofOfIndexing};
 use graph::components::three_box::ThreeBoxAdapter;
 use graph::components::{arweave::ArweaveAdapter, store::EntityType};
+use graph::data::graphql::ext::DirectiveFinder;
+use graph::data::graphql::{DirectiveExt, DocumentExt, ObjectTypeExt, TypeExt, ValueExt};
 use graph::data::store;
+use graph::data::subgraph::schema::{MappingLog, MappingLogLevel};
 use graph::prelude::serde_json;
 use graph::prelude::{slog::b, slog::record_static, *};
 use never::Never;
@@ -267,6 +270,125 @@ impl HostExports {
         Ok(state.entity_cache.get(&store_key)?)
     }
 
+    /// Returns the version of the entity at the end of `block`, which must be
+    /// before the block that is being processed; the changes of that block
+    /// can be read with `store_get`.
+    pub(crate) fn store_get_at_block(
+        &self,
+        entity_type: String,
+        entity_id: String,
+        block: BlockNumber,
+        current_block: BlockNumber,
+    ) -> Result<Option<Entity>, HostExportError> {
+        if block < 0 || block >= current_block {
+            return Err(HostExportError::Deterministic(anyhow!(
+                "store.getAtBlock: block {} must be before the current block {}",
+                block,
+                current_block
+            )));
+        }
+
+        // The store only reports an unknown entity type as a generic
+        // error, but it is a mistake in the mapping and therefore
+        // deterministic
+        let schema = self
+            .store
+            .input_schema(&self.subgraph_id)
+            .map_err(|e| HostExportError::Unknown(e.into()))?;
+        if schema
+            .document
+            .get_object_type_definition(&entity_type)
+            .is_none()
+        {
+            return Err(HostExportError::Deterministic(anyhow!(
+                "store.getAtBlock: unknown entity type `{}`",
+                entity_type
+            )));
+        }
+
+        let store_key = EntityKey {
+            subgraph_id: self.subgraph_id.clone(),
+            entity_type: EntityType::new(entity_type),
+            entity_id,
+        };
+        self.store
+            .get_at_block(store_key, block)
+            .map_err(|e| HostExportError::Unknown(e.into()))
+    }
+
+    /// Returns the entities of the derived field `field` of the entity,
+    /// ordered by their id. The field must be declared with `@derivedFrom`.
+    pub(crate) fn store_load_related(
+        &self,
+        state: &mut BlockState,
+        entity_type: String,
+        entity_id: String,
+        field: String,
+    ) -> Result<Vec<Entity>, HostExportError> {
+        let schema = self
+            .store
+            .input_schema(&self.subgraph_id)
+            .map_err(|e| HostExportError::Unknown(e.into()))?;
+        let object_type = schema
+            .document
+            .get_object_type_definition(&entity_type)
+            .ok_or_else(|| {
+                HostExportError::Deterministic(anyhow!(
+                    "store.loadRelated: unknown entity type `{}`",
+                    entity_type
+                ))
+            })?;
+        let derived_from = object_type
+            .field(&field)
+            .and_then(|f| f.find_directive("derivedFrom".to_string()).map(|d| (f, d)))
+            .and_then(|(f, directive)| {
+                directive
+                    .argument("field")
+                    .and_then(|value| value.as_string())
+                    .map(|target_field| (f.field_type.get_base_type(), target_field))
+            });
+        let (target_type, target_field) = derived_from.ok_or_else(|| {
+            HostExportError::Deterministic(anyhow!(
+                "store.loadRelated: `{}.{}` is not a derived field",
+                entity_type,
+                field
+            ))
+        })?;
+        let target = schema
+            .document
+            .get_object_type_definition(target_type)
+            .ok_or_else(|| {
+                HostExportError::Deterministic(anyhow!(
+                    "store.loadRelated: `{}.{}` is derived from the interface `{}`, \
+                     which is not supported",
+                    entity_type,
+                    field,
+                    target_type
+                ))
+            })?;
+        // Like unknown entity types, the store does not report unknown
+        // fields in a way that we could tell apart from other errors
+        if target.field(target_field).is_none() {
+            return Err(HostExportError::Deterministic(anyhow!(
+                "store.loadRelated: `{}.{}` is derived from the unknown field `{}.{}`",
+                entity_type,
+                field,
+                target_type,
+                target_field
+            )));
+        }
+
+        let query = DerivedEntityQuery {
+            entity_type: EntityType::new(target_type.clone()),
+            entity_field: target_field.clone(),
+            value: entity_id,
+        };
+        state
+            .entity_cache
+            .load_related(&self.subgraph_id, &query)
+            .map_err(|e| HostExportError::Unknown(e.into()))
+    }
+
     /// Returns `Ok(None)` if the call was reverted.
     pub(crate) fn ethereum_call(
         &self,
@@ -499,6 +621,33 @@ impl HostExports {
         Ok(big_int.to_signed_bytes_le())
     }
 
+    /// Encode `token` the same way it would be encoded as the only
+    /// argument of a contract call
+    pub(crate) fn ethereum_encode(&self, token: Token) -> Result<Vec<u8>, DeterministicHostError> {
+        Ok(ethabi::encode(&[token]))
+    }
+
+    /// Decode `data` as a value of the Solidity type `types`, for example
+    /// `(uint256,address[])`. An invalid type is a deterministic error;
+    /// if `data` is not a valid encoding of a value of that type, return
+    /// `None`
+    pub(crate) fn ethereum_decode(
+        &self,
+        types: String,
+        data: Vec<u8>,
+    ) -> Result<Option<Token>, DeterministicHostError> {
+        let param_type = ethabi::param_type::Reader::read(&types).map_err(|e| {
+            DeterministicHostError(anyhow!(
+                "invalid type `{}` for ethereum.decode: {}",
+                types,
+                e
+            ))
+        })?;
+        Ok(ethabi::decode(&[param_type], &data)
+            .ok()
+            .and_then(|mut tokens| tokens.pop()))
+    }
+
     pub(crate) fn crypto_keccak_256(
         &self,
         input: Vec<u8>,
@@ -506,6 +655,103 @@ impl HostExports {
         Ok(tiny_keccak::keccak256(&input))
     }
 
+    pub(crate) fn crypto_sha3_256(
+        &self,
+        input: Vec<u8>,
+    ) -> Result<[u8; 32], DeterministicHostError> {
+        Ok(tiny_keccak::sha3_256(&input))
+    }
+
+    pub(crate) fn crypto_sha256(&self, input: Vec<u8>) -> Result<Vec<u8>, DeterministicHostError> {
+        use sha2::Digest;
+
+        Ok(sha2::Sha256::digest(&input).to_vec())
+    }
+
+    pub(crate) fn crypto_ripemd160(
+        &self,
+        input: Vec<u8>,
+    ) -> Result<Vec<u8>, DeterministicHostError> {
+        use ripemd160::Digest;
+
+        Ok(ripemd160::Ripemd160::digest(&input).to_vec())
+    }
+
+    /// BLAKE2b with a 64 byte digest
+    pub(crate) fn crypto_blake2b(&self, input: Vec<u8>) -> Result<Vec<u8>, DeterministicHostError> {
+        use blake2::Digest;
+
+        Ok(blake2::Blake2b::digest(&input).to_vec())
+    }
+
+    /// Recover the address that produced `signature` for `hash`, with the
+    /// same semantics as Ethereum's `ecrecover` precompile. The signature
+    /// is 65 bytes `r || s || v` where `v` is one of 0, 1, 27 or 28.
+    /// Malformed input is a deterministic error; a well-formed signature
+    /// from which no key can be recovered results in `None`
+    pub(crate) fn crypto_ecrecover(
+        &self,
+        hash: Vec<u8>,
+        signature: Vec<u8>,
+    ) -> Result<Option<H160>, DeterministicHostError> {
+        use secp256k1::recovery::{RecoverableSignature, RecoveryId};
+
+        let message = secp256k1_message(&hash)?;
+        if signature.len() != 65 {
+            return Err(DeterministicHostError(anyhow!(
+                "signature for crypto.ecrecover must be 65 bytes long, but is {} bytes",
+                signature.len()
+            )));
+        }
+        let v = match signature[64] {
+            v @ 0..=1 => v,
+            v @ 27..=28 => v - 27,
+            v => {
+                return Err(DeterministicHostError(anyhow!(
+                    "invalid recovery id {} in signature for crypto.ecrecover",
+                    v
+                )))
+            }
+        };
+        let recovery_id = RecoveryId::from_i32(v as i32).unwrap();
+
+        let public_key = RecoverableSignature::from_compact(&signature[..64], recovery_id)
+            .and_then(|signature| SECP256K1.recover(&message, &signature));
+        Ok(public_key.ok().map(|public_key| {
+            // The address is the last 20 bytes of the hash of the
+            // uncompressed public key without its leading 0x04 tag
+            let key = public_key.serialize_uncompressed();
+            H160::from_slice(&tiny_keccak::keccak256(&key[1..])[12..])
+        }))
+    }
+
+    /// Check that `signature`, in 64 byte compact `r || s` form, was made
+    /// for `hash` by the private key for `public_key`, which can be
+    /// compressed or uncompressed. Signatures with a high `s` value are
+    /// accepted, too. Malformed input is a deterministic error
+    pub(crate) fn crypto_secp256k1_verify(
+        &self,
+        hash: Vec<u8>,
+        signature: Vec<u8>,
+        public_key: Vec<u8>,
+    ) -> Result<bool, DeterministicHostError> {
+        let message = secp256k1_message(&hash)?;
+        let mut signature = secp256k1::Signature::from_compact(&signature).map_err(|e| {
+            DeterministicHostError(anyhow!(
+                "invalid signature for crypto.secp256k1Verify: {}",
+                e
+            ))
+        })?;
+        signature.normalize_s();
+        let public_key = secp256k1::PublicKey::from_slice(&public_key).map_err(|e| {
+            DeterministicHostError(anyhow!(
+                "invalid public key for crypto.secp256k1Verify: {}",
+                e
+            ))
+        })?;
+        Ok(SECP256K1.verify(&message, &signature, &public_key).is_ok())
+    }
+
     pub(crate) fn big_int_plus(
         &self,
         x: BigInt,
@@ -730,10 +976,13 @@ impl HostExports {
     pub(crate) fn log_log(
         &self,
         logger: &Logger,
-        level: slog::Level,
+        state: &mut BlockState,
+        block_number: BlockNumber,
+        handler: Option<String>,
+        level: MappingLogLevel,
         msg: String,
     ) -> Result<(), DeterministicHostError> {
-        let rs = record_static!(level, self.data_source_name.as_str());
+        let rs = record_static!(slog::Level::from(level), self.data_source_name.as_str());
 
         logger.log(&slog::Record::new(
             &rs,
@@ -741,7 +990,15 @@ impl HostExports {
             b!("data_source" => &self.data_source_name),
         ));
 
-        if level == slog::Level::Critical {
+        state.mapping_logs.push(MappingLog {
+            block_number,
+            data_source: self.data_source_name.clone(),
+            handler,
+            level,
+            message: msg,
+        });
+
+        if level == MappingLogLevel::Critical {
             return Err(DeterministicHostError(anyhow!(
                 "Critical error logged in mapping"
             )));
@@ -787,6 +1044,21 @@ pub(crate) fn string_to_h160(string: &str) -> Result<H160, DeterministicHostErro
         .map_err(DeterministicHostError)
 }
 
+lazy_static! {
+    static ref SECP256K1: secp256k1::Secp256k1<secp256k1::VerifyOnly> =
+        secp256k1::Secp256k1::verification_only();
+}
+
+fn secp256k1_message(hash: &[u8]) -> Result<secp256k1::Message, DeterministicHostError> {
+    if hash.len() != 32 {
+        return Err(DeterministicHostError(anyhow!(
+            "message hash must be 32 bytes long, but is {} bytes",
+            hash.len()
+        )));
+    }
+    Ok(secp256k1::Message::from_slice(hash).unwrap())
+}
+
 pub(crate) fn bytes_to_string(logger: &Logger, bytes: Vec<u8>) -> String {
     let s = String::from_utf8_lossy(&bytes);
 
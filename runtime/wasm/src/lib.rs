@@ -1,6 +1,9 @@
 mod asc_abi;
 mod to_from;
 
+/// The ABI of mappings written in Rust.
+mod rust_abi;
+
 /// Public interface of the crate, receives triggers to be processed.
 mod host;
 pub use host::RuntimeHostBuilder;
@@ -11,6 +14,16 @@ mod mapping;
 /// WASM module instance.
 mod module;
 
+/// Caches compiled modules on disk.
+mod compile_cache;
+
+/// Runs the handlers of a subgraph without a database or an Ethereum node, for unit tests of
+/// mappings. Available with the `mapping-test` feature.
+#[cfg(any(test, feature = "mapping-test"))]
+mod mapping_test;
+#[cfg(any(test, feature = "mapping-test"))]
+pub use mapping_test::MappingTest;
+
 /// Runtime-agnostic implementation of exports to WASM.
 mod host_exports;
 
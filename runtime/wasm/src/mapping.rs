@@ -1,10 +1,14 @@
-use crate::module::{ExperimentalFeatures, WasmInstance};
+use crate::compile_cache::{self, EngineSettings};
+use crate::module::{
+    export_mutable_globals, inject_metering, ExperimentalFeatures, InstancePool, GAS_LIMIT,
+};
 use ethabi::LogParam;
 use futures::sync::mpsc;
 use futures03::channel::oneshot::Sender;
 use graph::components::ethereum::*;
 use graph::components::subgraph::{MappingError, SharedProofOfIndexing};
 use graph::prelude::*;
+use std::borrow::Cow;
 use std::collections::BTreeMap;
 use std::sync::Arc;
 use std::thread;
@@ -20,6 +24,7 @@ lazy_static! {
 /// Spawn a wasm module in its own thread.
 pub fn spawn_module(
     raw_module: Vec<u8>,
+    language: MappingLanguage,
     logger: Logger,
     subgraph_id: SubgraphDeploymentId,
     host_metrics: Arc<HostMetrics>,
@@ -27,7 +32,7 @@ pub fn spawn_module(
     timeout: Option<Duration>,
     experimental_features: ExperimentalFeatures,
 ) -> Result<mpsc::Sender<MappingRequest>, anyhow::Error> {
-    let valid_module = Arc::new(ValidModule::new(&raw_module)?);
+    let valid_module = Arc::new(ValidModule::new(&raw_module, language)?);
 
     // Create channel for event handling requests
     let (mapping_request_sender, mapping_request_receiver) = mpsc::channel(100);
@@ -41,6 +46,13 @@ pub fn spawn_module(
     let conf =
         thread::Builder::new().name(format!("mapping-{}-{}", &subgraph_id, uuid::Uuid::new_v4()));
     conf.spawn(move || {
+        let mut instances = InstancePool::new(
+            valid_module,
+            host_metrics.cheap_clone(),
+            timeout,
+            experimental_features,
+        );
+
         runtime.enter(|| {
             // Pass incoming triggers to the WASM module and return entity changes;
             // Stop when canceled because all RuntimeHosts and their senders were dropped.
@@ -56,13 +68,7 @@ pub fn spawn_module(
 
                     // Start the WASM module runtime.
                     let section = host_metrics.stopwatch.start_section("module_init");
-                    let module = WasmInstance::from_valid_module_with_ctx(
-                        valid_module.cheap_clone(),
-                        ctx,
-                        host_metrics.cheap_clone(),
-                        timeout,
-                        experimental_features.clone(),
-                    )?;
+                    let mut module = instances.get(ctx)?;
                     section.end();
 
                     let section = host_metrics.stopwatch.start_section("run_handler");
@@ -100,6 +106,10 @@ pub fn spawn_module(
                     };
                     section.end();
 
+                    let section = host_metrics.stopwatch.start_section("module_reset");
+                    instances.release(module);
+                    section.end();
+
                     result_sender
                         .send((result, future::ok(Instant::now())))
                         .map_err(|_| anyhow::anyhow!("WASM module result receiver dropped."))
@@ -175,6 +185,13 @@ impl MappingContext {
 pub(crate) struct ValidModule {
     pub(super) module: wasmtime::Module,
 
+    // Determines the ABI between the module and the host.
+    pub(super) language: MappingLanguage,
+
+    // The names under which the mutable globals of the module are exported, so that instances can
+    // be reset and reused. `None` if the module could not be rewritten to export them.
+    pub(super) mutable_globals: Option<Vec<String>>,
+
     // A wasm import consists of a `module` and a `name`. AS will generate imports such that they
     // have `module` set to the name of the file it is imported from and `name` set to the imported
     // function name or `namespace.function` if inside a namespace. We'd rather not specify names of
@@ -188,17 +205,39 @@ pub(crate) struct ValidModule {
 
 impl ValidModule {
     /// Pre-process and validate the module.
-    pub fn new(raw_module: &[u8]) -> Result<Self, anyhow::Error> {
+    pub fn new(raw_module: &[u8], language: MappingLanguage) -> Result<Self, anyhow::Error> {
         // We currently use Cranelift as a compilation engine. Cranelift is an optimizing compiler,
         // but that should not cause determinism issues since it adheres to the Wasm spec. Still we
         // turn off optional optimizations to be conservative.
-        let mut config = wasmtime::Config::new();
-        config.strategy(wasmtime::Strategy::Cranelift).unwrap();
-        config.interruptable(true); // For timeouts.
-        config.cranelift_nan_canonicalization(true); // For NaN determinism.
-        config.cranelift_opt_level(wasmtime::OptLevel::None);
-        let engine = &wasmtime::Engine::new(&config);
-        let module = wasmtime::Module::from_binary(&engine, raw_module)?;
+        let settings = EngineSettings {
+            opt_level: wasmtime::OptLevel::None,
+            nan_canonicalization: true, // For NaN determinism.
+            interruptable: true,        // For timeouts.
+            // Current Rust toolchains emit `memory.copy` and `memory.fill` for wasm32.
+            bulk_memory: true,
+        };
+
+        let raw_module = inject_metering(raw_module, *GAS_LIMIT)
+            .context("failed to add gas metering to the module")?;
+        let (raw_module, mutable_globals) = match export_mutable_globals(&raw_module) {
+            Ok((raw_module, mutable_globals)) => (Cow::Owned(raw_module), Some(mutable_globals)),
+            // Instances of modules we can't rewrite are simply not reused.
+            Err(_) => (Cow::Borrowed(raw_module.as_slice()), None),
+        };
+        let module = compile_cache::compile(&settings, &raw_module)?;
+
+        // The host exports for Rust mappings are only linked under their own names, so make sure
+        // a Rust mapping does not import anything meant for AssemblyScript.
+        if language == MappingLanguage::Rust {
+            if let Some(import) = module.imports().find(|import| import.module() != "graph") {
+                anyhow::bail!(
+                    "mappings written in Rust can only import from the `graph` module, \
+                     but the mapping imports `{}` from `{}`",
+                    import.name(),
+                    import.module()
+                );
+            }
+        }
 
         let mut import_name_to_modules: BTreeMap<String, Vec<String>> = BTreeMap::new();
         for (name, module) in module
@@ -213,6 +252,8 @@ impl ValidModule {
 
         Ok(ValidModule {
             module,
+            language,
+            mutable_globals,
             import_name_to_modules,
         })
     }
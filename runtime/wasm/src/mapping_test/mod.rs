@@ -0,0 +1,493 @@
+//! Unit tests for mappings. `MappingTest` runs the handlers of a subgraph against synthetic
+//! events, calls and blocks, with the store kept in memory and `ethereum.call` answered by mocks,
+//! so that neither a database nor an Ethereum node is needed.
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use ethabi::{LogParam, Token};
+use graph::bytes::Bytes;
+use graph::components::arweave::ArweaveAdapter;
+use graph::components::store::EntityType;
+use graph::components::subgraph::MappingError;
+use graph::components::three_box::ThreeBoxAdapter;
+use graph::mock::MockEthereumAdapter;
+use graph::prelude::*;
+use graph_mock::{MemoryStore, MockMetricsRegistry};
+use semver::Version;
+use web3::types::{Address, Log, Transaction, H256, U64};
+
+use crate::host_exports::HostExports;
+use crate::mapping::{MappingContext, ValidModule};
+use crate::module::{ExperimentalFeatures, WasmInstance};
+
+#[cfg(test)]
+mod test;
+
+/// Runs the handlers of a subgraph for unit tests of its mappings.
+///
+/// The manifest is loaded from disk, and may refer to the schema, ABIs and mappings by paths
+/// relative to the manifest, as the output of `graph build` does. Each handler runs in a fresh
+/// instance of its mapping, against the entities that the previous handlers left in the store.
+pub struct MappingTest {
+    logger: Logger,
+    manifest: SubgraphManifest,
+    link_resolver: Arc<FileLinkResolver>,
+    store: Arc<MemoryStore>,
+    modules: HashMap<String, Arc<ValidModule>>,
+    call_mocks: Vec<CallMock>,
+    block: Arc<LightEthereumBlock>,
+    created_data_sources: Vec<DataSourceTemplateInfo>,
+}
+
+/// The result of an `ethereum.call`, `None` if the call reverts.
+#[derive(Clone)]
+struct CallMock {
+    address: Address,
+    function_signature: String,
+    args: Vec<Token>,
+    result: Option<Vec<Token>>,
+}
+
+impl MappingTest {
+    /// Load the subgraph with the manifest at `path`.
+    pub async fn load(logger: Logger, path: impl AsRef<Path>) -> Result<Self, Error> {
+        let path = path.as_ref();
+        let link_resolver = Arc::new(FileLinkResolver {
+            base: path.parent().unwrap_or_else(|| Path::new(".")).to_owned(),
+        });
+
+        let mut raw: serde_yaml::Value = serde_yaml::from_slice(
+            &fs::read(path).with_context(|| format!("failed to read `{}`", path.display()))?,
+        )?;
+        file_paths_to_links(&mut raw);
+        let raw = match raw {
+            serde_yaml::Value::Mapping(raw) => raw,
+            _ => return Err(anyhow!("`{}` is not a subgraph manifest", path.display())),
+        };
+        let id = SubgraphDeploymentId::new("mappingTest").unwrap();
+        let manifest =
+            SubgraphManifest::resolve_from_raw(id, raw, link_resolver.as_ref(), &logger).await?;
+
+        let mut modules = HashMap::new();
+        for data_source in &manifest.data_sources {
+            let module = ValidModule::new(
+                data_source.mapping.runtime.as_ref(),
+                data_source.mapping.mapping_language()?,
+            )
+            .with_context(|| format!("invalid mapping for data source `{}`", data_source.name))?;
+            modules.insert(data_source.name.clone(), Arc::new(module));
+        }
+
+        let mut block = LightEthereumBlock::default();
+        block.hash = Some(H256::zero());
+        block.number = Some(U64::zero());
+
+        Ok(MappingTest {
+            logger,
+            store: Arc::new(MemoryStore::new(Arc::new(manifest.schema.clone()))),
+            manifest,
+            link_resolver,
+            modules,
+            call_mocks: Vec::new(),
+            block: Arc::new(block),
+            created_data_sources: Vec::new(),
+        })
+    }
+
+    /// Set the block that handlers run in. It must have a hash and a number.
+    pub fn set_block(&mut self, block: LightEthereumBlock) {
+        self.block = Arc::new(block);
+    }
+
+    /// Make an `ethereum.call` of the function with `function_signature`, e.g.
+    /// `balanceOf(address):(uint256)`, on the contract at `address` with `args` return `result`,
+    /// or revert if `result` is `None`. A call that no mock matches fails the handler.
+    pub fn mock_call(
+        &mut self,
+        address: Address,
+        function_signature: &str,
+        args: Vec<Token>,
+        result: Option<Vec<Token>>,
+    ) {
+        self.call_mocks.push(CallMock {
+            address,
+            function_signature: function_signature.to_owned(),
+            args,
+            result,
+        });
+    }
+
+    /// Store `entity`, e.g. to set up entities that handlers load.
+    pub fn set(&mut self, entity_type: &str, entity: Entity) -> Result<(), Error> {
+        let key = self.key(entity_type, entity.id()?);
+        self.store.set(key, entity);
+        Ok(())
+    }
+
+    /// The entity of `entity_type` with `id` as the handlers left it.
+    pub fn get(&self, entity_type: &str, id: &str) -> Option<Entity> {
+        self.store
+            .get(self.key(entity_type, id.to_owned()))
+            .expect("the memory store does not fail")
+    }
+
+    /// All entities of `entity_type`, ordered by id.
+    pub fn entities(&self, entity_type: &str) -> Vec<Entity> {
+        self.store
+            .entities(&EntityType::new(entity_type.to_owned()))
+    }
+
+    /// The data sources that handlers created from templates.
+    pub fn created_data_sources(&self) -> &[DataSourceTemplateInfo] {
+        &self.created_data_sources
+    }
+
+    /// Run the handler of `data_source` for `event`, which is the event signature as it appears
+    /// in the manifest. The event is emitted by the contract of the data source.
+    pub fn handle_event(
+        &mut self,
+        data_source: &str,
+        event: &str,
+        params: Vec<LogParam>,
+    ) -> Result<(), MappingError> {
+        let data_source = self.data_source(data_source)?;
+        let handler = data_source
+            .mapping
+            .event_handlers
+            .iter()
+            .find(|handler| handler.event == event)
+            .ok_or_else(|| {
+                anyhow!(
+                    "data source `{}` has no handler for event `{}`",
+                    data_source.name,
+                    event
+                )
+            })?;
+
+        let transaction = Arc::new(self.transaction());
+        let log = Arc::new(Log {
+            address: data_source.source.address.unwrap_or_default(),
+            topics: vec![handler.topic0()],
+            data: Default::default(),
+            block_hash: self.block.hash,
+            block_number: self.block.number,
+            transaction_hash: Some(transaction.hash),
+            transaction_index: transaction.transaction_index,
+            log_index: Some(Default::default()),
+            transaction_log_index: Some(Default::default()),
+            log_type: None,
+            removed: Some(false),
+        });
+        let handler = handler.handler.clone();
+        self.run(&data_source, |module| {
+            module.handle_ethereum_log(&handler, transaction, log, params)
+        })
+    }
+
+    /// Run the handler of `data_source` for calls of `function`, which is the function signature
+    /// as it appears in the manifest. The function is called on the contract of the data source.
+    pub fn handle_call(
+        &mut self,
+        data_source: &str,
+        function: &str,
+        inputs: Vec<LogParam>,
+        outputs: Vec<LogParam>,
+    ) -> Result<(), MappingError> {
+        let data_source = self.data_source(data_source)?;
+        let handler = data_source
+            .mapping
+            .call_handlers
+            .iter()
+            .find(|handler| handler.function == function)
+            .ok_or_else(|| {
+                anyhow!(
+                    "data source `{}` has no handler for function `{}`",
+                    data_source.name,
+                    function
+                )
+            })?
+            .handler
+            .clone();
+
+        let transaction = self.transaction();
+        let mut call = EthereumCall::default();
+        call.to = data_source.source.address.unwrap_or_default();
+        call.block_number = self.block.number();
+        call.block_hash = self.block.hash.unwrap_or_default();
+        call.transaction_hash = Some(transaction.hash);
+        self.run(&data_source, |module| {
+            module.handle_ethereum_call(
+                &handler,
+                Arc::new(transaction),
+                Arc::new(call),
+                inputs,
+                outputs,
+            )
+        })
+    }
+
+    /// Run all block handlers of `data_source`, regardless of their filters.
+    pub fn handle_block(&mut self, data_source: &str) -> Result<(), MappingError> {
+        let data_source = self.data_source(data_source)?;
+        for handler in &data_source.mapping.block_handlers {
+            self.run(&data_source, |module| {
+                module.handle_ethereum_block(&handler.handler)
+            })?;
+        }
+        Ok(())
+    }
+
+    fn key(&self, entity_type: &str, entity_id: String) -> EntityKey {
+        EntityKey {
+            subgraph_id: self.manifest.id.clone(),
+            entity_type: EntityType::new(entity_type.to_owned()),
+            entity_id,
+        }
+    }
+
+    fn data_source(&self, name: &str) -> Result<DataSource, Error> {
+        self.manifest
+            .data_sources
+            .iter()
+            .find(|data_source| data_source.name == name)
+            .cloned()
+            .ok_or_else(|| anyhow!("the manifest has no data source `{}`", name))
+    }
+
+    /// The transaction that triggers are part of.
+    fn transaction(&self) -> Transaction {
+        let mut transaction = Transaction::default();
+        transaction.block_hash = self.block.hash;
+        transaction.block_number = self.block.number;
+        transaction
+    }
+
+    /// An adapter that answers calls from `call_mocks`. Calls that no mock matches revert, and
+    /// are added to `unmatched` so that `run` can fail the handler.
+    fn ethereum_adapter(&self, unmatched: Arc<Mutex<Vec<String>>>) -> MockEthereumAdapter {
+        let mocks = self.call_mocks.clone();
+        let mut adapter = MockEthereumAdapter::default();
+        adapter.expect_contract_call().returning(move |_, call, _| {
+            let mock = mocks.iter().find(|mock| {
+                mock.address == call.address
+                    && mock.function_signature == call.function.signature()
+                    && mock.args == call.args
+            });
+            let result = match mock {
+                Some(mock) => mock
+                    .result
+                    .clone()
+                    .ok_or_else(|| EthereumContractCallError::Revert("mocked revert".to_owned())),
+                None => {
+                    unmatched.lock().unwrap().push(format!(
+                        "`{}` on {:?} with {:?}",
+                        call.function.signature(),
+                        call.address,
+                        call.args
+                    ));
+                    Err(EthereumContractCallError::Revert(
+                        "no mock for the call".to_owned(),
+                    ))
+                }
+            };
+            Box::new(future::result(result))
+        });
+        adapter
+    }
+
+    /// Run a handler of `data_source` in a new instance of its mapping and apply its changes to
+    /// the store.
+    fn run(
+        &mut self,
+        data_source: &DataSource,
+        handle: impl FnOnce(&mut WasmInstance) -> Result<BlockState, MappingError>,
+    ) -> Result<(), MappingError> {
+        let unmatched_calls = Arc::new(Mutex::new(Vec::new()));
+        let host_exports = HostExports::new(
+            self.manifest.id.clone(),
+            Version::parse(&data_source.mapping.api_version)
+                .context("invalid mapping API version")?,
+            data_source.name.clone(),
+            data_source.source.address,
+            data_source.network.clone().unwrap_or_default(),
+            data_source.context.clone(),
+            Arc::new(self.manifest.templates.clone()),
+            data_source.mapping.abis.clone(),
+            Arc::new(self.ethereum_adapter(unmatched_calls.clone())),
+            self.link_resolver.clone(),
+            self.store.clone(),
+            Arc::new(NoCallCache),
+            Arc::new(Unavailable),
+            Arc::new(Unavailable),
+        );
+        let ctx = MappingContext {
+            logger: self.logger.clone(),
+            host_exports: Arc::new(host_exports),
+            block: self.block.clone(),
+            state: BlockState::new(self.store.clone(), Default::default()),
+            proof_of_indexing: None,
+        };
+
+        let registry = Arc::new(MockMetricsRegistry::new());
+        let stopwatch = StopwatchMetrics::new(
+            self.logger.clone(),
+            self.manifest.id.clone(),
+            registry.clone(),
+        );
+        let host_metrics = Arc::new(HostMetrics::new(
+            registry,
+            self.manifest.id.as_str(),
+            stopwatch,
+            None,
+        ));
+        // `ipfs.cat` and `ipfs.map` only read files next to the manifest.
+        let experimental_features = ExperimentalFeatures {
+            allow_non_deterministic_ipfs: true,
+            allow_non_deterministic_arweave: false,
+            allow_non_deterministic_3box: false,
+        };
+
+        let mut module = WasmInstance::from_valid_module_with_ctx(
+            self.modules[&data_source.name].clone(),
+            ctx,
+            host_metrics,
+            None,
+            experimental_features,
+        )?;
+        let result = handle(&mut module);
+
+        // A call without a mock is a mistake in the test, whatever the handler made of it.
+        if let Some(call) = unmatched_calls.lock().unwrap().first() {
+            return Err(MappingError::Unknown(anyhow!(
+                "no mock for the call of {}",
+                call
+            )));
+        }
+        let mut state = result?;
+
+        // Handlers that fail deterministically return normally, with their changes reverted.
+        if let Some(error) = state.deterministic_errors.first() {
+            return Err(MappingError::Unknown(anyhow!("{}", error.message)));
+        }
+        self.created_data_sources
+            .extend(state.drain_created_data_sources());
+        let mods = state
+            .entity_cache
+            .as_modifications(self.store.as_ref())
+            .map_err(Error::from)?
+            .modifications;
+        self.store.apply(mods);
+        Ok(())
+    }
+}
+
+/// Rewrite `file: <path>` in the manifest to the `file: { /: <path> }` links that
+/// `FileLinkResolver` resolves.
+fn file_paths_to_links(value: &mut serde_yaml::Value) {
+    match value {
+        serde_yaml::Value::Mapping(mapping) => {
+            for (key, value) in mapping.iter_mut() {
+                if key.as_str() == Some("file") && value.is_string() {
+                    let mut link = serde_yaml::Mapping::new();
+                    link.insert(serde_yaml::Value::from("/"), value.clone());
+                    *value = serde_yaml::Value::Mapping(link);
+                } else {
+                    file_paths_to_links(value);
+                }
+            }
+        }
+        serde_yaml::Value::Sequence(values) => values.iter_mut().for_each(file_paths_to_links),
+        _ => (),
+    }
+}
+
+/// Resolves links as paths relative to the directory of the manifest. This also serves
+/// `ipfs.cat` and `ipfs.map`, so files that mappings read from IPFS can be put there under
+/// their hash.
+struct FileLinkResolver {
+    base: PathBuf,
+}
+
+impl FileLinkResolver {
+    fn path(&self, link: &Link) -> PathBuf {
+        self.base.join(link.link.trim_start_matches("/ipfs/"))
+    }
+}
+
+#[async_trait]
+impl LinkResolver for FileLinkResolver {
+    fn with_timeout(self, _timeout: Duration) -> Self {
+        self
+    }
+
+    fn with_retries(self) -> Self {
+        self
+    }
+
+    async fn cat(&self, _logger: &Logger, link: &Link) -> Result<Vec<u8>, Error> {
+        let path = self.path(link);
+        fs::read(&path).with_context(|| format!("failed to read `{}`", path.display()))
+    }
+
+    async fn json_stream(&self, logger: &Logger, link: &Link) -> Result<JsonValueStream, Error> {
+        let data = String::from_utf8(self.cat(logger, link).await?)?;
+        let values: Vec<Result<JsonStreamValue, Error>> = data
+            .lines()
+            .enumerate()
+            .filter(|(_, line)| !line.is_empty())
+            .map(|(i, line)| {
+                Ok(JsonStreamValue {
+                    value: serde_json::from_str(line)?,
+                    line: i + 1,
+                })
+            })
+            .collect();
+        Ok(Box::pin(futures03::stream::iter(values)))
+    }
+}
+
+/// Contract calls are answered by mocks, so there is nothing to cache.
+struct NoCallCache;
+
+impl EthereumCallCache for NoCallCache {
+    fn get_call(
+        &self,
+        _contract_address: Address,
+        _encoded_call: &[u8],
+        _block: EthereumBlockPointer,
+    ) -> Result<Option<Vec<u8>>, Error> {
+        Ok(None)
+    }
+
+    fn set_call(
+        &self,
+        _contract_address: Address,
+        _encoded_call: &[u8],
+        _block: EthereumBlockPointer,
+        _return_value: &[u8],
+    ) -> Result<(), Error> {
+        Ok(())
+    }
+}
+
+/// Arweave and 3Box are not available in mapping tests.
+struct Unavailable;
+
+#[async_trait]
+impl ArweaveAdapter for Unavailable {
+    async fn tx_data(&self, _tx_id: &str) -> Result<Bytes, Error> {
+        Err(anyhow!("Arweave is not available in mapping tests"))
+    }
+}
+
+#[async_trait]
+impl ThreeBoxAdapter for Unavailable {
+    async fn profile(
+        &self,
+        _address: &str,
+    ) -> Result<serde_json::Map<String, serde_json::Value>, Error> {
+        Err(anyhow!("3Box is not available in mapping tests"))
+    }
+}
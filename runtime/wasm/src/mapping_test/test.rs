@@ -0,0 +1,170 @@
+use ethabi::{LogParam, Token};
+use graph::prelude::*;
+use web3::types::{H160, U256};
+
+use super::MappingTest;
+
+const MANIFEST: &str = "wasm_test/mapping_test/subgraph.yaml";
+const TRANSFER: &str = "Transfer(indexed address,uint256)";
+const BALANCE_OF: &str = "balanceOf(address):(uint256)";
+
+fn logger() -> Logger {
+    Logger::root(slog::Discard, o!())
+}
+
+fn thing(id: &str, value: &str) -> Entity {
+    let mut thing = Entity::new();
+    thing.set("id", id);
+    thing.set("value", value);
+    thing
+}
+
+/// The address of the `Contract` data source.
+fn contract() -> H160 {
+    H160::from_low_u64_be(1)
+}
+
+fn holder() -> H160 {
+    H160::from_low_u64_be(7)
+}
+
+fn param(name: &str, value: Token) -> LogParam {
+    LogParam {
+        name: name.to_owned(),
+        value,
+    }
+}
+
+fn transfer_params() -> Vec<LogParam> {
+    vec![
+        param("to", Token::Address(holder())),
+        param("value", Token::Uint(U256::from(10))),
+    ]
+}
+
+#[tokio::test]
+async fn block_handler() {
+    let mut test = MappingTest::load(logger(), MANIFEST).await.unwrap();
+    test.set("Thing", thing("existing", "before")).unwrap();
+
+    test.handle_block("Contract").unwrap();
+
+    assert_eq!(test.get("Thing", "block"), Some(thing("block", "handled")));
+    assert_eq!(
+        test.entities("Thing"),
+        vec![thing("block", "handled"), thing("existing", "before")]
+    );
+    assert!(test.created_data_sources().is_empty());
+}
+
+#[tokio::test]
+async fn event_handler() {
+    let mut test = MappingTest::load(logger(), MANIFEST).await.unwrap();
+    test.mock_call(
+        contract(),
+        BALANCE_OF,
+        vec![Token::Address(holder())],
+        Some(vec![Token::Uint(U256::from(1000))]),
+    );
+
+    test.handle_event("Contract", TRANSFER, transfer_params())
+        .unwrap();
+
+    let id = format!("{:?}", holder());
+    let mut expected = thing(&id, "balance");
+    expected.set("amount", BigInt::from(1000));
+    expected.set("transferred", BigInt::from(10));
+    assert_eq!(test.get("Thing", &id), Some(expected));
+
+    let created = test.created_data_sources();
+    assert_eq!(created.len(), 1);
+    assert_eq!(created[0].data_source, "Contract");
+    assert_eq!(created[0].template.name, "Token");
+    assert_eq!(created[0].params, vec![id]);
+}
+
+#[tokio::test]
+async fn mocked_revert() {
+    let mut test = MappingTest::load(logger(), MANIFEST).await.unwrap();
+    test.mock_call(contract(), BALANCE_OF, vec![Token::Address(holder())], None);
+
+    test.handle_event("Contract", TRANSFER, transfer_params())
+        .unwrap();
+
+    let id = format!("{:?}", holder());
+    let mut expected = thing(&id, "reverted");
+    expected.set("transferred", BigInt::from(10));
+    assert_eq!(test.get("Thing", &id), Some(expected));
+}
+
+#[tokio::test]
+async fn missing_call_mock() {
+    let mut test = MappingTest::load(logger(), MANIFEST).await.unwrap();
+    // A mock for a different holder does not match.
+    test.mock_call(
+        contract(),
+        BALANCE_OF,
+        vec![Token::Address(contract())],
+        Some(vec![Token::Uint(U256::from(1000))]),
+    );
+
+    let err = test
+        .handle_event("Contract", TRANSFER, transfer_params())
+        .unwrap_err();
+    assert!(format!("{:?}", err).contains("no mock for the call of `balanceOf(address):(uint256)`"));
+    assert!(test.entities("Thing").is_empty());
+    assert!(test.created_data_sources().is_empty());
+}
+
+#[tokio::test]
+async fn call_handler() {
+    let mut test = MappingTest::load(logger(), MANIFEST).await.unwrap();
+
+    test.handle_call(
+        "Contract",
+        "transfer(address,uint256)",
+        transfer_params(),
+        vec![param("success", Token::Bool(true))],
+    )
+    .unwrap();
+
+    let id = format!("{:?}", holder());
+    assert_eq!(test.get("Thing", &id), Some(thing(&id, "called")));
+}
+
+#[tokio::test]
+async fn ipfs_cat() {
+    let mut test = MappingTest::load(logger(), MANIFEST).await.unwrap();
+
+    // Files are read from the directory of the manifest.
+    for hash in &["QmMappingTestFile", "QmMissing"] {
+        test.handle_event(
+            "Contract",
+            "Fetch(string)",
+            vec![param("hash", Token::String(hash.to_string()))],
+        )
+        .unwrap();
+    }
+
+    assert_eq!(
+        test.entities("Thing"),
+        vec![
+            thing("QmMappingTestFile", "hello from ipfs"),
+            thing("QmMissing", "missing")
+        ]
+    );
+}
+
+#[tokio::test]
+async fn unknown_triggers() {
+    let mut test = MappingTest::load(logger(), MANIFEST).await.unwrap();
+
+    let err = test.handle_block("Other").unwrap_err();
+    assert!(format!("{:?}", err).contains("the manifest has no data source `Other`"));
+
+    let err = test
+        .handle_event("Contract", "Transfer(address,address,uint256)", vec![])
+        .unwrap_err();
+    assert!(format!("{:?}", err).contains("has no handler for event"));
+    assert!(test.entities("Thing").is_empty());
+}
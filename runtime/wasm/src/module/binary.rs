@@ -0,0 +1,225 @@
+//! Just enough of a parser and writer for the wasm binary format to rewrite modules before we
+//! compile them.
+use anyhow::{bail, Error};
+
+pub(super) const IMPORT_SECTION: u8 = 2;
+pub(super) const GLOBAL_SECTION: u8 = 6;
+pub(super) const EXPORT_SECTION: u8 = 7;
+pub(super) const CODE_SECTION: u8 = 10;
+
+pub(super) const GLOBAL_KIND: u8 = 3;
+
+const HEADER_SIZE: usize = 8;
+
+/// A module split into its header and the id and payload of each section.
+pub(super) struct Sections<'a> {
+    header: &'a [u8],
+    pub(super) sections: Vec<(u8, Vec<u8>)>,
+}
+
+impl<'a> Sections<'a> {
+    pub(super) fn parse(raw_module: &'a [u8]) -> Result<Self, Error> {
+        if raw_module.len() < HEADER_SIZE {
+            bail!("module is too short");
+        }
+
+        let mut sections = Vec::new();
+        let mut reader = Reader::new(&raw_module[HEADER_SIZE..]);
+        while !reader.is_empty() {
+            let id = reader.byte()?;
+            let size = reader.uleb()? as usize;
+            sections.push((id, reader.bytes(size)?.to_vec()));
+        }
+        Ok(Sections {
+            header: &raw_module[..HEADER_SIZE],
+            sections,
+        })
+    }
+
+    pub(super) fn get(&self, id: u8) -> Option<&[u8]> {
+        self.sections
+            .iter()
+            .find(|(section_id, _)| *section_id == id)
+            .map(|(_, payload)| payload.as_slice())
+    }
+
+    /// Replace the payload of section `id`, or add the section in its place in the section order
+    /// if the module does not have it.
+    pub(super) fn set(&mut self, id: u8, payload: Vec<u8>) {
+        if let Some(section) = self.sections.iter_mut().find(|(sid, _)| *sid == id) {
+            section.1 = payload;
+            return;
+        }
+        let index = self
+            .sections
+            .iter()
+            .position(|(sid, _)| order(*sid) > order(id))
+            .unwrap_or(self.sections.len());
+        self.sections.insert(index, (id, payload));
+    }
+
+    /// The number of imports of the given kind.
+    pub(super) fn imports(&self, kind: u8) -> Result<u64, Error> {
+        let mut count = 0;
+        if let Some(payload) = self.get(IMPORT_SECTION) {
+            let mut reader = Reader::new(payload);
+            for _ in 0..reader.uleb()? {
+                if reader.import()? == kind {
+                    count += 1;
+                }
+            }
+        }
+        Ok(count)
+    }
+
+    pub(super) fn assemble(&self) -> Vec<u8> {
+        let mut module = self.header.to_vec();
+        for (id, payload) in &self.sections {
+            module.push(*id);
+            write_uleb(&mut module, payload.len() as u64);
+            module.extend(payload);
+        }
+        module
+    }
+}
+
+/// The position of a section in the order the spec requires. Custom sections may appear anywhere
+/// and the data count section goes between the element and code sections.
+fn order(id: u8) -> u8 {
+    match id {
+        0 => 0,
+        12 => 10,
+        10 | 11 => id + 1,
+        _ => id,
+    }
+}
+
+pub(super) fn write_uleb(out: &mut Vec<u8>, mut value: u64) {
+    loop {
+        let byte = (value & 0x7f) as u8;
+        value >>= 7;
+        if value == 0 {
+            out.push(byte);
+            return;
+        }
+        out.push(byte | 0x80);
+    }
+}
+
+pub(super) fn write_sleb(out: &mut Vec<u8>, mut value: i64) {
+    loop {
+        let byte = (value & 0x7f) as u8;
+        value >>= 7;
+        if (value == 0 && byte & 0x40 == 0) || (value == -1 && byte & 0x40 != 0) {
+            out.push(byte);
+            return;
+        }
+        out.push(byte | 0x80);
+    }
+}
+
+pub(super) struct Reader<'a> {
+    bytes: &'a [u8],
+}
+
+impl<'a> Reader<'a> {
+    pub(super) fn new(bytes: &'a [u8]) -> Self {
+        Reader { bytes }
+    }
+
+    pub(super) fn is_empty(&self) -> bool {
+        self.bytes.is_empty()
+    }
+
+    pub(super) fn rest(&self) -> &'a [u8] {
+        self.bytes
+    }
+
+    pub(super) fn bytes(&mut self, len: usize) -> Result<&'a [u8], Error> {
+        if self.bytes.len() < len {
+            bail!("unexpected end of module");
+        }
+        let (bytes, rest) = self.bytes.split_at(len);
+        self.bytes = rest;
+        Ok(bytes)
+    }
+
+    pub(super) fn byte(&mut self) -> Result<u8, Error> {
+        Ok(self.bytes(1)?[0])
+    }
+
+    /// Read an unsigned LEB128 number. Signed numbers have the same encoding length, so this is
+    /// also used to skip those.
+    pub(super) fn uleb(&mut self) -> Result<u64, Error> {
+        let mut value = 0;
+        for shift in (0..64).step_by(7) {
+            let byte = self.byte()?;
+            value |= ((byte & 0x7f) as u64) << shift;
+            if byte & 0x80 == 0 {
+                return Ok(value);
+            }
+        }
+        bail!("LEB128 number is too long")
+    }
+
+    pub(super) fn name(&mut self) -> Result<&'a [u8], Error> {
+        let len = self.uleb()? as usize;
+        self.bytes(len)
+    }
+
+    pub(super) fn limits(&mut self) -> Result<(), Error> {
+        let has_max = self.byte()? == 1;
+        self.uleb()?;
+        if has_max {
+            self.uleb()?;
+        }
+        Ok(())
+    }
+
+    /// Read an import entry and return its kind.
+    pub(super) fn import(&mut self) -> Result<u8, Error> {
+        self.name()?;
+        self.name()?;
+        let kind = self.byte()?;
+        match kind {
+            // Function
+            0 => {
+                self.uleb()?;
+            }
+            // Table
+            1 => {
+                self.byte()?;
+                self.limits()?;
+            }
+            // Memory
+            2 => self.limits()?,
+            GLOBAL_KIND => {
+                self.bytes(2)?;
+            }
+            kind => bail!("unknown import kind {}", kind),
+        }
+        Ok(kind)
+    }
+
+    pub(super) fn init_expr(&mut self) -> Result<(), Error> {
+        loop {
+            match self.byte()? {
+                // end
+                0x0b => return Ok(()),
+                // i32.const, i64.const, global.get
+                0x41 | 0x42 | 0x23 => {
+                    self.uleb()?;
+                }
+                // f32.const
+                0x43 => {
+                    self.bytes(4)?;
+                }
+                // f64.const
+                0x44 => {
+                    self.bytes(8)?;
+                }
+                op => bail!("unsupported instruction {:#x} in global initializer", op),
+            }
+        }
+    }
+}
@@ -0,0 +1,210 @@
+//! Deterministic gas metering for mappings. Wasmtime has no metering of its own that we could
+//! use, so `ValidModule` rewrites modules to count instructions in a mutable global. Every
+//! function body and every loop body starts by subtracting the number of instructions it
+//! contains from the global and traps once it becomes negative. Host exports are charged for
+//! through the same global, see `host_fn_cost`.
+//!
+//! Since the costs only depend on the module and on the arguments to host exports, a handler
+//! runs out of gas at the same point on every indexer, which makes it a deterministic error.
+use std::env;
+
+use anyhow::{bail, Error};
+use graph::prelude::{lazy_static, BigInt};
+use wasmparser::{FunctionBody, Operator};
+
+use super::binary::{
+    write_sleb, write_uleb, Reader, Sections, CODE_SECTION, EXPORT_SECTION, GLOBAL_KIND,
+    GLOBAL_SECTION,
+};
+
+lazy_static! {
+    /// The amount of gas that a single handler may use
+    pub(crate) static ref GAS_LIMIT: u64 = env::var("GRAPH_MAX_GAS_PER_HANDLER")
+        .ok()
+        .map(|s| {
+            s.parse::<u64>().unwrap_or_else(|_| {
+                panic!("GRAPH_MAX_GAS_PER_HANDLER must be a number, but is `{}`", s)
+            })
+        })
+        .unwrap_or(100_000_000_000);
+}
+
+/// The name under which the global holding the remaining gas is exported.
+pub(crate) const GAS_GLOBAL: &str = "__graph_gas";
+
+/// What a host export costs if it is not listed in `host_fn_cost`.
+const DEFAULT_HOST_FN_COST: u64 = 100;
+
+/// The gas charged for calling the host export `name`, roughly in proportion to how much time
+/// it takes compared to executing a wasm instruction.
+pub(crate) fn host_fn_cost(name: &str) -> u64 {
+    match name {
+        "ethereum.call" | "ipfs.cat" | "ipfs.map" | "arweave.transactionData" | "box.profile" => {
+            1_000_000
+        }
+        "store.get" | "store.set" | "store.remove" | "ens.nameByHash" => 10_000,
+        "store.getAtBlock" | "store.loadRelated" => 100_000,
+        "dataSource.create" | "dataSource.createWithContext" => 10_000,
+        "ethereum.encode" | "ethereum.decode" | "json.fromBytes" | "json.try_fromBytes" => 1_000,
+
+        // The host exports for mappings written in Rust.
+        "ethereum_call" | "ipfs_cat" => 1_000_000,
+        "store_get" | "store_set" | "store_remove" => 10_000,
+        "store_get_at_block" | "store_load_related" => 100_000,
+        "data_source_create" => 10_000,
+        "crypto_keccak256" => 1_000,
+
+        name if name.starts_with("crypto.") => 1_000,
+        _ => DEFAULT_HOST_FN_COST,
+    }
+}
+
+/// The additional gas charged for `bigInt.pow`, which grows with the square of the size of the
+/// result.
+pub(crate) fn big_int_pow_cost(x: &BigInt, exp: u8) -> u64 {
+    let words = (x.bits() as u64 * exp as u64) / 64 + 1;
+    words * words
+}
+
+/// Rewrite `raw_module` so that it counts the gas it uses in a global exported as `GAS_GLOBAL`,
+/// starting out with `initial_gas`. The start function runs before we could set the global, so
+/// it gets `initial_gas` to spend.
+pub(crate) fn inject_metering(raw_module: &[u8], initial_gas: u64) -> Result<Vec<u8>, Error> {
+    let mut module = Sections::parse(raw_module)?;
+
+    // Add the gas global after all other globals, so that no global indexes change.
+    let imported_globals = module.imports(GLOBAL_KIND)?;
+    let (defined_globals, global_entries) = match module.get(GLOBAL_SECTION) {
+        Some(payload) => {
+            let mut reader = Reader::new(payload);
+            (reader.uleb()?, reader.rest().to_vec())
+        }
+        None => (0, vec![]),
+    };
+    let gas_global = imported_globals + defined_globals;
+    let mut globals = Vec::new();
+    write_uleb(&mut globals, defined_globals + 1);
+    globals.extend(global_entries);
+    // A mutable i64 initialized with `i64.const initial_gas`.
+    globals.extend(&[I64, 0x01, I64_CONST]);
+    write_sleb(&mut globals, initial_gas as i64);
+    globals.push(END);
+    module.set(GLOBAL_SECTION, globals);
+
+    let (export_count, export_entries) = match module.get(EXPORT_SECTION) {
+        Some(payload) => {
+            let mut reader = Reader::new(payload);
+            (reader.uleb()?, reader.rest().to_vec())
+        }
+        None => (0, vec![]),
+    };
+    let mut exports = Vec::new();
+    write_uleb(&mut exports, export_count + 1);
+    exports.extend(export_entries);
+    write_uleb(&mut exports, GAS_GLOBAL.len() as u64);
+    exports.extend(GAS_GLOBAL.as_bytes());
+    exports.push(GLOBAL_KIND);
+    write_uleb(&mut exports, gas_global);
+    module.set(EXPORT_SECTION, exports);
+
+    if let Some(payload) = module.get(CODE_SECTION) {
+        let mut reader = Reader::new(payload);
+        let count = reader.uleb()?;
+        let mut code = Vec::new();
+        write_uleb(&mut code, count);
+        for _ in 0..count {
+            let size = reader.uleb()? as usize;
+            let body = meter_function(reader.bytes(size)?, gas_global)?;
+            write_uleb(&mut code, body.len() as u64);
+            code.extend(body);
+        }
+        module.set(CODE_SECTION, code);
+    }
+
+    Ok(module.assemble())
+}
+
+const END: u8 = 0x0b;
+const IF: u8 = 0x04;
+const I64: u8 = 0x7e;
+const I64_CONST: u8 = 0x42;
+
+/// The instructions of a function body or loop body, without the instructions of nested loops,
+/// which are charged for separately.
+#[derive(Default)]
+struct Region {
+    code: Vec<u8>,
+    cost: u64,
+}
+
+fn meter_function(body: &[u8], gas_global: u64) -> Result<Vec<u8>, Error> {
+    // Positions are relative to the start of `body`.
+    let mut reader = FunctionBody::new(0, body).get_operators_reader()?;
+    let locals = &body[..reader.original_position()];
+
+    let mut regions = vec![Region::default()];
+    // For each block that is open, whether it is a loop.
+    let mut blocks = Vec::new();
+    loop {
+        let start = reader.original_position();
+        let op = reader.read()?;
+        let instruction = &body[start..reader.original_position()];
+
+        // The engine does not enable these proposals, but say why the module is rejected.
+        match instruction[0] {
+            0xfd => bail!("SIMD instructions are not supported"),
+            0xfe => bail!("atomic instructions are not supported"),
+            _ => {}
+        }
+
+        let region = regions.last_mut().unwrap();
+        region.code.extend(instruction);
+        region.cost += 1;
+
+        match op {
+            Operator::Block { .. } | Operator::If { .. } => blocks.push(false),
+            Operator::Loop { .. } => {
+                blocks.push(true);
+                regions.push(Region::default());
+            }
+            Operator::End => match blocks.pop() {
+                Some(false) => {}
+                Some(true) => {
+                    // The region holds the body of the loop, including its `end`.
+                    let region = regions.pop().unwrap();
+                    let parent = regions.last_mut().unwrap();
+                    charge(&mut parent.code, gas_global, region.cost);
+                    parent.code.extend(region.code);
+                }
+                None => {
+                    if !reader.eof() {
+                        bail!("function body continues after its end");
+                    }
+                    let region = regions.pop().unwrap();
+                    let mut body = locals.to_vec();
+                    charge(&mut body, gas_global, region.cost);
+                    body.extend(region.code);
+                    return Ok(body);
+                }
+            },
+            _ => {}
+        }
+    }
+}
+
+/// Append code that subtracts `cost` from the gas global and traps if that leaves it negative.
+fn charge(code: &mut Vec<u8>, gas_global: u64, cost: u64) {
+    // global.get, i64.const, i64.sub, global.set
+    code.push(0x23);
+    write_uleb(code, gas_global);
+    code.push(I64_CONST);
+    write_sleb(code, cost as i64);
+    code.push(0x7d);
+    code.push(0x24);
+    write_uleb(code, gas_global);
+
+    // global.get, i64.const 0, i64.lt_s, if, unreachable, end
+    code.push(0x23);
+    write_uleb(code, gas_global);
+    code.extend(&[I64_CONST, 0x00, 0x53, IF, 0x40, 0x00, END]);
+}
@@ -0,0 +1,301 @@
+//! Reuse of wasm instances across triggers. Instantiating a module for every trigger is a
+//! noticeable part of handler time for event-dense blocks. Instead, we take a snapshot of the
+//! memory and the mutable globals of a fresh instance and, after a handler ran, restore the
+//! instance to that snapshot so it can run the next handler as if it had just been created.
+//!
+//! Wasm only lets us access globals that are exported, and AssemblyScript does not export the
+//! globals its runtime uses, so `ValidModule` rewrites modules to export all mutable globals.
+use std::collections::HashMap;
+use std::env;
+use std::sync::atomic::AtomicBool;
+use std::sync::Arc;
+use std::time::Duration;
+
+use anyhow::{anyhow, bail, Error};
+use graph::prelude::{lazy_static, CheapClone, HostMetrics};
+use wasmparser::{FunctionBody, Operator};
+use wasmtime::{Global, Memory, Val};
+
+use super::binary::{
+    write_uleb, Reader, Sections, CODE_SECTION, EXPORT_SECTION, GLOBAL_KIND, GLOBAL_SECTION,
+};
+use super::stopwatch::TimeoutStopwatch;
+use super::{spawn_timeout_watchdog, ExperimentalFeatures, WasmInstance, WasmInstanceContext};
+use crate::mapping::{MappingContext, ValidModule};
+
+lazy_static! {
+    /// The number of idle instances we keep around for each mapping. Set to 0 to
+    /// create a new instance for every trigger
+    static ref INSTANCE_POOL_SIZE: usize = env::var("GRAPH_WASM_INSTANCE_POOL_SIZE")
+        .ok()
+        .map(|s| {
+            s.parse::<usize>().unwrap_or_else(|_| {
+                panic!("GRAPH_WASM_INSTANCE_POOL_SIZE must be a number, but is `{}`", s)
+            })
+        })
+        .unwrap_or(1);
+}
+
+/// Instances whose handler got this close to the timeout are not reused, since the timeout
+/// watchdog might still interrupt them.
+const TIMEOUT_MARGIN: Duration = Duration::from_secs(2);
+
+/// The prefix for the names under which we export mutable globals that the module itself does
+/// not export.
+const GLOBAL_EXPORT_PREFIX: &str = "__graph_global_";
+
+/// The state of an instance right after instantiation.
+pub(crate) struct InstanceSnapshot {
+    memory: Memory,
+    data: Vec<u8>,
+    globals: Vec<(Global, Val)>,
+}
+
+impl InstanceSnapshot {
+    fn take(instance: &wasmtime::Instance, globals: &[String]) -> Result<Self, Error> {
+        let memory = instance
+            .get_memory("memory")
+            .ok_or_else(|| anyhow!("Failed to find memory export in the WASM module"))?;
+
+        // Safety: see 2155cdca-dfaa-4fba-86e4-289e7683c1bf in `module/mod.rs`.
+        let data = unsafe { memory.data_unchecked().to_vec() };
+
+        let globals = globals
+            .iter()
+            .map(|name| {
+                let global = instance
+                    .get_global(name)
+                    .ok_or_else(|| anyhow!("global `{}` is not exported", name))?;
+                let value = global.get();
+                Ok((global, value))
+            })
+            .collect::<Result<_, Error>>()?;
+
+        Ok(InstanceSnapshot {
+            memory,
+            data,
+            globals,
+        })
+    }
+
+    fn restore(&self) -> Result<(), Error> {
+        // Memory can not shrink. If it grew, we zero the additional pages so they look exactly
+        // like freshly grown memory does.
+        //
+        // Safety: see 2155cdca-dfaa-4fba-86e4-289e7683c1bf in `module/mod.rs`.
+        unsafe {
+            let memory = self.memory.data_unchecked_mut();
+            memory[..self.data.len()].copy_from_slice(&self.data);
+            for byte in memory[self.data.len()..].iter_mut() {
+                *byte = 0;
+            }
+        }
+
+        for (global, value) in &self.globals {
+            global.set(value.clone())?;
+        }
+        Ok(())
+    }
+}
+
+impl WasmInstance {
+    fn take_snapshot(&mut self) -> Result<(), Error> {
+        self.snapshot = match &self.valid_module.mutable_globals {
+            Some(globals) if !self.host_called_in_start => {
+                Some(InstanceSnapshot::take(&self.instance, globals)?)
+            }
+            _ => None,
+        };
+        Ok(())
+    }
+
+    fn is_reusable(&self) -> bool {
+        let elapsed = self.timeout_stopwatch.lock().unwrap().elapsed();
+        self.snapshot.is_some()
+            && self
+                .timeout
+                .map(|timeout| elapsed + TIMEOUT_MARGIN < timeout)
+                .unwrap_or(true)
+    }
+
+    /// Restore the instance to its state right after instantiation.
+    fn reset(&mut self) -> Result<(), Error> {
+        // Drop what is left of the context of the last trigger.
+        self.instance_ctx.borrow_mut().take();
+        match &self.snapshot {
+            Some(snapshot) => snapshot.restore(),
+            None => bail!("instance can not be reset"),
+        }
+    }
+
+    /// Prepare a reset instance to handle a trigger with `ctx`.
+    fn reuse(&mut self, ctx: MappingContext) -> Result<(), Error> {
+        *self.timeout_stopwatch.lock().unwrap() = TimeoutStopwatch::start_new();
+        self.timeout_done = Arc::new(AtomicBool::new(false));
+        if let Some(timeout) = self.timeout {
+            spawn_timeout_watchdog(
+                timeout,
+                self.instance.store().interrupt_handle()?,
+                self.timeout_stopwatch.cheap_clone(),
+                self.timeout_done.clone(),
+            );
+        }
+
+        *self.instance_ctx.borrow_mut() = Some(WasmInstanceContext::from_instance(
+            &self.instance,
+            ctx,
+            self.valid_module.cheap_clone(),
+            self.host_metrics.cheap_clone(),
+            self.timeout,
+            self.timeout_stopwatch.cheap_clone(),
+            self.experimental_features,
+        )?);
+        Ok(())
+    }
+}
+
+/// Idle instances of one `ValidModule` that are ready to handle the next trigger. Like
+/// `WasmInstance`, the pool is not `Send` and lives on the thread that runs the mapping.
+pub(crate) struct InstancePool {
+    valid_module: Arc<ValidModule>,
+    host_metrics: Arc<HostMetrics>,
+    timeout: Option<Duration>,
+    experimental_features: ExperimentalFeatures,
+    instances: Vec<WasmInstance>,
+}
+
+impl InstancePool {
+    pub(crate) fn new(
+        valid_module: Arc<ValidModule>,
+        host_metrics: Arc<HostMetrics>,
+        timeout: Option<Duration>,
+        experimental_features: ExperimentalFeatures,
+    ) -> Self {
+        InstancePool {
+            valid_module,
+            host_metrics,
+            timeout,
+            experimental_features,
+            instances: Vec::new(),
+        }
+    }
+
+    /// Get an instance to handle a trigger with `ctx`, reusing an idle one if possible.
+    pub(crate) fn get(&mut self, ctx: MappingContext) -> Result<WasmInstance, Error> {
+        if let Some(mut instance) = self.instances.pop() {
+            instance.reuse(ctx)?;
+            return Ok(instance);
+        }
+
+        let mut instance = WasmInstance::from_valid_module_with_ctx(
+            self.valid_module.cheap_clone(),
+            ctx,
+            self.host_metrics.cheap_clone(),
+            self.timeout,
+            self.experimental_features,
+        )?;
+        if *INSTANCE_POOL_SIZE > 0 {
+            instance.take_snapshot()?;
+        }
+        Ok(instance)
+    }
+
+    /// Hand an instance back after it handled a trigger. Instances that can't be reset are
+    /// dropped.
+    pub(crate) fn release(&mut self, mut instance: WasmInstance) {
+        if self.instances.len() < *INSTANCE_POOL_SIZE
+            && instance.is_reusable()
+            && instance.reset().is_ok()
+        {
+            self.instances.push(instance);
+        }
+    }
+}
+
+/// Rewrite `raw_module` so that it exports all of its mutable globals. Returns the new module
+/// and the names under which the mutable globals are exported.
+pub(crate) fn export_mutable_globals(raw_module: &[u8]) -> Result<(Vec<u8>, Vec<String>), Error> {
+    let mut module = Sections::parse(raw_module)?;
+    if changes_segments_or_tables(&module)? {
+        bail!("module changes its segments or tables, which resetting an instance does not undo");
+    }
+    let imported_globals = module.imports(GLOBAL_KIND)?;
+
+    let mut mutable_globals = Vec::new();
+    if let Some(payload) = module.get(GLOBAL_SECTION) {
+        let mut reader = Reader::new(payload);
+        for index in 0..reader.uleb()? {
+            let _value_type = reader.byte()?;
+            let mutable = reader.byte()? == 1;
+            reader.init_expr()?;
+            if mutable {
+                mutable_globals.push(imported_globals + index);
+            }
+        }
+    }
+
+    let mut exported_globals = HashMap::new();
+    let mut reader = Reader::new(
+        module
+            .get(EXPORT_SECTION)
+            .ok_or_else(|| anyhow!("module has no export section"))?,
+    );
+    let export_count = reader.uleb()?;
+    let export_entries = reader.rest().to_vec();
+    for _ in 0..export_count {
+        let name = reader.name()?;
+        let kind = reader.byte()?;
+        let index = reader.uleb()?;
+        if kind == GLOBAL_KIND {
+            exported_globals.insert(index, String::from_utf8(name.to_vec())?);
+        }
+    }
+
+    let mut names = Vec::new();
+    let mut new_entries = Vec::new();
+    let mut added = 0;
+    for index in mutable_globals {
+        match exported_globals.get(&index) {
+            Some(name) => names.push(name.clone()),
+            None => {
+                let name = format!("{}{}", GLOBAL_EXPORT_PREFIX, index);
+                write_uleb(&mut new_entries, name.len() as u64);
+                new_entries.extend(name.as_bytes());
+                new_entries.push(GLOBAL_KIND);
+                write_uleb(&mut new_entries, index);
+                names.push(name);
+                added += 1;
+            }
+        }
+    }
+
+    let mut exports = Vec::new();
+    write_uleb(&mut exports, export_count + added);
+    exports.extend(export_entries);
+    exports.extend(new_entries);
+    module.set(EXPORT_SECTION, exports);
+    Ok((module.assemble(), names))
+}
+
+/// Whether the module uses bulk memory instructions that drop segments or write to tables.
+/// Restoring memory and globals does not undo their effects.
+fn changes_segments_or_tables(module: &Sections) -> Result<bool, Error> {
+    let payload = match module.get(CODE_SECTION) {
+        Some(payload) => payload,
+        None => return Ok(false),
+    };
+    let mut reader = Reader::new(payload);
+    for _ in 0..reader.uleb()? {
+        let size = reader.uleb()? as usize;
+        for op in FunctionBody::new(0, reader.bytes(size)?).get_operators_reader()? {
+            match op? {
+                Operator::DataDrop { .. }
+                | Operator::ElemDrop { .. }
+                | Operator::TableInit { .. }
+                | Operator::TableCopy { .. } => return Ok(true),
+                _ => {}
+            }
+        }
+    }
+    Ok(false)
+}
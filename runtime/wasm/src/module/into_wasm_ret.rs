@@ -1,3 +1,4 @@
+use crate::asc_abi::class::EnumPayload;
 use crate::asc_abi::AscPtr;
 use never::Never;
 use wasmtime::Trap;
@@ -30,6 +31,13 @@ impl IntoWasmRet for i32 {
     }
 }
 
+impl IntoWasmRet for u32 {
+    type Ret = Self;
+    fn into_wasm_ret(self) -> Self {
+        self
+    }
+}
+
 impl IntoWasmRet for i64 {
     type Ret = Self;
     fn into_wasm_ret(self) -> Self {
@@ -75,3 +83,63 @@ where
         self.map(|x| x.into_wasm_ret())
     }
 }
+
+/// Helper trait for the `link!` macro for mappings with apiVersion 0.0.5 and
+/// later, which receive the return value of a host export in a `HostResult`.
+pub(crate) trait IntoHostPayload {
+    fn into_host_payload(self) -> EnumPayload;
+}
+
+impl IntoHostPayload for () {
+    fn into_host_payload(self) -> EnumPayload {
+        EnumPayload(0)
+    }
+}
+
+impl IntoHostPayload for Never {
+    fn into_host_payload(self) -> EnumPayload {
+        unreachable!()
+    }
+}
+
+impl IntoHostPayload for i32 {
+    fn into_host_payload(self) -> EnumPayload {
+        self.into()
+    }
+}
+
+impl IntoHostPayload for u32 {
+    fn into_host_payload(self) -> EnumPayload {
+        EnumPayload(self as u64)
+    }
+}
+
+impl IntoHostPayload for i64 {
+    fn into_host_payload(self) -> EnumPayload {
+        self.into()
+    }
+}
+
+impl IntoHostPayload for f64 {
+    fn into_host_payload(self) -> EnumPayload {
+        self.into()
+    }
+}
+
+impl IntoHostPayload for u64 {
+    fn into_host_payload(self) -> EnumPayload {
+        EnumPayload(self)
+    }
+}
+
+impl IntoHostPayload for bool {
+    fn into_host_payload(self) -> EnumPayload {
+        self.into()
+    }
+}
+
+impl<C> IntoHostPayload for AscPtr<C> {
+    fn into_host_payload(self) -> EnumPayload {
+        self.into()
+    }
+}
@@ -3,18 +3,19 @@ use std::collections::HashMap;
 use std::convert::TryFrom;
 use std::ops::Deref;
 use std::rc::Rc;
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::time::Instant;
 
 use never::Never;
 use semver::Version;
-use wasmtime::{Memory, Trap};
+use wasmtime::{Global, Memory, Trap, Val};
 
 use crate::error::{DeterminismLevel, DeterministicHostError};
 use crate::host_exports;
 use crate::mapping::MappingContext;
 use anyhow::Error;
 use ethabi::LogParam;
-use graph::components::subgraph::MappingError;
+use graph::components::subgraph::{HandlerProfile, MappingError};
 use graph::data::store;
 use graph::data::subgraph::schema::SubgraphError;
 use graph::prelude::*;
@@ -26,14 +27,21 @@ use crate::asc_abi::class::*;
 use crate::asc_abi::*;
 use crate::host_exports::{EthereumCallError, HostExports};
 use crate::mapping::ValidModule;
+use crate::rust_abi::{rust_get, rust_new};
 use crate::UnresolvedContractCall;
 
+mod binary;
+mod gas;
+mod instance_pool;
 mod into_wasm_ret;
 mod stopwatch;
 
-use into_wasm_ret::IntoWasmRet;
+use into_wasm_ret::{IntoHostPayload, IntoWasmRet};
 use stopwatch::TimeoutStopwatch;
 
+pub(crate) use gas::{inject_metering, GAS_LIMIT};
+pub(crate) use instance_pool::{export_mutable_globals, InstancePool};
+
 #[cfg(test)]
 mod test;
 
@@ -54,6 +62,24 @@ pub(crate) struct WasmInstance {
     // Also this is the only strong reference, so the instance will be dropped once this is dropped.
     // The weak references are circulary held by instance itself through host exports.
     instance_ctx: Rc<RefCell<Option<WasmInstanceContext>>>,
+
+    // What we need to set up the instance context again when the instance is reused.
+    valid_module: Arc<ValidModule>,
+    host_metrics: Arc<HostMetrics>,
+    timeout: Option<Duration>,
+    timeout_stopwatch: Arc<std::sync::Mutex<TimeoutStopwatch>>,
+    experimental_features: ExperimentalFeatures,
+
+    // Set once the current handler is done, which stops the timeout watchdog.
+    timeout_done: Arc<AtomicBool>,
+
+    // A host export was called from the start function, so the state of the instance depends on
+    // the trigger it was created for.
+    host_called_in_start: bool,
+
+    // The state of the instance right after instantiation, used to reset it for reuse. `None` if
+    // the instance can not be reused.
+    snapshot: Option<instance_pool::InstanceSnapshot>,
 }
 
 impl Drop for WasmInstance {
@@ -65,14 +91,18 @@ impl Drop for WasmInstance {
 
 /// Proxies to the WasmInstanceContext.
 impl AscHeap for WasmInstance {
-    fn raw_new(&mut self, bytes: &[u8]) -> Result<u32, DeterministicHostError> {
+    fn raw_new(&mut self, bytes: &[u8], rt_id: u32) -> Result<u32, DeterministicHostError> {
         let mut ctx = RefMut::map(self.instance_ctx.borrow_mut(), |i| i.as_mut().unwrap());
-        ctx.raw_new(bytes)
+        ctx.raw_new(bytes, rt_id)
     }
 
     fn get(&self, offset: u32, size: u32) -> Result<Vec<u8>, DeterministicHostError> {
         self.instance_ctx().get(offset, size)
     }
+
+    fn api_version(&self) -> Version {
+        self.instance_ctx().api_version()
+    }
 }
 
 impl WasmInstance {
@@ -98,46 +128,41 @@ impl WasmInstance {
     }
 
     pub(crate) fn handle_ethereum_log(
-        mut self,
+        &mut self,
         handler_name: &str,
         transaction: Arc<Transaction>,
         log: Arc<Log>,
         params: Vec<LogParam>,
     ) -> Result<BlockState, MappingError> {
         let block = self.instance_ctx().ctx.block.clone();
+        let event = EthereumEventData {
+            block: EthereumBlockData::from(block.as_ref()),
+            transaction: EthereumTransactionData::from(transaction.deref()),
+            address: log.address,
+            log_index: log.log_index.unwrap_or(U256::zero()),
+            transaction_log_index: log.log_index.unwrap_or(U256::zero()),
+            log_type: log.log_type.clone(),
+            params,
+        };
 
         // Prepare an EthereumEvent for the WASM runtime
         // Decide on the destination type using the mapping
         // api version provided in the subgraph manifest
-        let event = if self.instance_ctx().ctx.host_exports.api_version >= Version::new(0, 0, 2) {
-            self.asc_new::<AscEthereumEvent<AscEthereumTransaction_0_0_2>, _>(&EthereumEventData {
-                block: EthereumBlockData::from(block.as_ref()),
-                transaction: EthereumTransactionData::from(transaction.deref()),
-                address: log.address,
-                log_index: log.log_index.unwrap_or(U256::zero()),
-                transaction_log_index: log.log_index.unwrap_or(U256::zero()),
-                log_type: log.log_type.clone(),
-                params,
-            })?
-            .erase()
+        let arg = if self.valid_module.language == MappingLanguage::Rust {
+            rust_new(self, &event)?
+        } else if self.instance_ctx().ctx.host_exports.api_version >= Version::new(0, 0, 2) {
+            self.asc_new::<AscEthereumEvent<AscEthereumTransaction_0_0_2>, _>(&event)?
+                .wasm_ptr()
         } else {
-            self.asc_new::<AscEthereumEvent<AscEthereumTransaction>, _>(&EthereumEventData {
-                block: EthereumBlockData::from(block.as_ref()),
-                transaction: EthereumTransactionData::from(transaction.deref()),
-                address: log.address,
-                log_index: log.log_index.unwrap_or(U256::zero()),
-                transaction_log_index: log.log_index.unwrap_or(U256::zero()),
-                log_type: log.log_type.clone(),
-                params,
-            })?
-            .erase()
+            self.asc_new::<AscEthereumEvent<AscEthereumTransaction>, _>(&event)?
+                .wasm_ptr()
         };
 
-        self.invoke_handler(handler_name, event)
+        self.invoke_handler(handler_name, arg)
     }
 
     pub(crate) fn handle_ethereum_call(
-        mut self,
+        &mut self,
         handler_name: &str,
         transaction: Arc<Transaction>,
         call: Arc<EthereumCall>,
@@ -152,23 +177,30 @@ impl WasmInstance {
             inputs,
             outputs,
         };
-        let arg = if self.instance_ctx().ctx.host_exports.api_version >= Version::new(0, 0, 3) {
-            self.asc_new::<AscEthereumCall_0_0_3, _>(&call)?.erase()
+        let arg = if self.valid_module.language == MappingLanguage::Rust {
+            rust_new(self, &call)?
+        } else if self.instance_ctx().ctx.host_exports.api_version >= Version::new(0, 0, 3) {
+            self.asc_new::<AscEthereumCall_0_0_3, _>(&call)?.wasm_ptr()
         } else {
-            self.asc_new::<AscEthereumCall, _>(&call)?.erase()
+            self.asc_new::<AscEthereumCall, _>(&call)?.wasm_ptr()
         };
 
         self.invoke_handler(handler_name, arg)
     }
 
     pub(crate) fn handle_ethereum_block(
-        mut self,
+        &mut self,
         handler_name: &str,
     ) -> Result<BlockState, MappingError> {
         let block = EthereumBlockData::from(self.instance_ctx().ctx.block.as_ref());
 
         // Prepare an EthereumBlock for the WASM runtime
-        let arg = self.asc_new(&block)?;
+        let arg = match self.valid_module.language {
+            MappingLanguage::AssemblyScript => {
+                self.asc_new::<AscEthereumBlock, _>(&block)?.wasm_ptr()
+            }
+            MappingLanguage::Rust => rust_new(self, &block)?,
+        };
 
         self.invoke_handler(handler_name, arg)
     }
@@ -190,21 +222,77 @@ impl WasmInstance {
         self.instance.get_func(func_name).unwrap()
     }
 
-    fn invoke_handler<C>(
-        &mut self,
-        handler: &str,
-        arg: AscPtr<C>,
-    ) -> Result<BlockState, MappingError> {
+    /// Start collecting a profile of the handler if the deployment is profiled.
+    fn start_profile(&self) -> Option<(Instant, (usize, usize))> {
+        let mut ctx = self.instance_ctx_mut();
+        ctx.host_metrics.profile.as_ref()?;
+        ctx.handler_profile = Some(HandlerProfile::default());
+        Some((Instant::now(), ctx.ctx.state.entity_cache.cache_stats()))
+    }
+
+    fn finish_profile(&self, handler: &str, start: Option<(Instant, (usize, usize))>) {
+        let (start, (hits, misses)) = match start {
+            Some(start) => start,
+            None => return,
+        };
+        let mut ctx = self.instance_ctx_mut();
+        let mut profile = ctx.handler_profile.take().unwrap_or_default();
+        let (end_hits, end_misses) = ctx.ctx.state.entity_cache.cache_stats();
+        profile.calls = 1;
+        profile.wall_time = start.elapsed();
+        // Profiling must never fail the handler, even if the cache was replaced with one whose
+        // counters are lower.
+        profile.entity_cache_hits = end_hits.saturating_sub(hits) as u64;
+        profile.entity_cache_misses = end_misses.saturating_sub(misses) as u64;
+        if let Some(deployment_profile) = &ctx.host_metrics.profile {
+            deployment_profile.record(handler, profile);
+        }
+    }
+
+    /// Call `handler` with the address of its argument in the memory of the module.
+    fn invoke_handler(&mut self, handler: &str, arg: u32) -> Result<BlockState, MappingError> {
         let func = self
             .instance
             .get_func(handler)
             .with_context(|| format!("function {} not found", handler))?;
 
+        self.instance_ctx().set_gas_left(*GAS_LIMIT)?;
+        let profile_start = self.start_profile();
+
         // Caution: Make sure all exit paths from this function call `exit_handler`.
+        self.instance_ctx_mut().handler = Some(handler.to_string());
         self.instance_ctx_mut().ctx.state.enter_handler();
 
+        let result = func.get1()?(arg);
+        self.timeout_done.store(true, Ordering::SeqCst);
+        if result.is_err() {
+            // The instance may have been interrupted half way through updating its state.
+            self.snapshot = None;
+        }
+
+        self.finish_profile(handler, profile_start);
+
+        let gas_left = self.instance_ctx().gas_left();
+        if let Some(gas_left) = gas_left {
+            let gas_used = (*GAS_LIMIT as i64 - gas_left.max(0)) as u64;
+            self.instance_ctx()
+                .host_metrics
+                .observe_handler_gas_used(gas_used, handler);
+        }
+        let out_of_gas = gas_left.map_or(false, |gas_left| gas_left < 0);
+        let out_of_gas_error = || {
+            anyhow!(
+                "Handler '{}' ran out of gas, the limit is {} gas",
+                handler,
+                *GAS_LIMIT
+            )
+        };
+
         // This `match` will return early if there was a non-deterministic trap.
-        let deterministic_error: Option<Error> = match func.get1()?(arg.wasm_ptr()) {
+        let deterministic_error: Option<Error> = match result {
+            // A mapping that gets host errors passed as a `HostResult` may have returned
+            // normally after a host export ran out of gas.
+            Ok(()) if out_of_gas => Some(out_of_gas_error()),
             Ok(()) => None,
             Err(trap) if self.instance_ctx().possible_reorg => {
                 self.instance_ctx_mut().ctx.state.exit_handler();
@@ -218,6 +306,7 @@ impl WasmInstance {
                     self.instance_ctx().timeout.unwrap().as_secs()
                 ))));
             }
+            Err(_) if out_of_gas => Some(out_of_gas_error()),
             Err(trap) => {
                 use wasmtime::TrapCode::*;
                 let trap_code = trap.trap_code();
@@ -287,7 +376,16 @@ pub(crate) struct WasmInstanceContext {
     // module. And at least AS calls it "memory". There is no uninitialized memory in Wasm, memory
     // is zeroed when initialized or grown.
     memory: Memory,
-    memory_allocate: Box<dyn Fn(i32) -> Result<i32, Trap>>,
+    allocator: Allocator,
+
+    // The global in which the module counts the gas it has left, see `gas::inject_metering`.
+    gas: Option<Global>,
+
+    // Collects the host function calls of the current handler if the deployment is profiled.
+    handler_profile: Option<HandlerProfile>,
+
+    // The handler that is being invoked, for the messages it logs.
+    handler: Option<String>,
 
     pub ctx: MappingContext,
     pub(crate) valid_module: Arc<ValidModule>,
@@ -312,6 +410,59 @@ pub(crate) struct WasmInstanceContext {
     pub(crate) experimental_features: ExperimentalFeatures,
 }
 
+/// How the host allocates objects in the memory of a module.
+enum Allocator {
+    /// For apiVersion 0.0.4 and earlier, we request arenas through
+    /// `memory.allocate` and manage them ourselves.
+    Arena(Box<dyn Fn(i32) -> Result<i32, Trap>>),
+
+    /// For apiVersion 0.0.5 and later, mappings are compiled with
+    /// AssemblyScript 0.19 and `--exportRuntime`. Every object is allocated
+    /// with `__new` so that it gets a runtime header, and pinned with
+    /// `__pin` since the incremental collector may run during any later
+    /// allocation, before the mapping holds a reference to the object.
+    /// Objects stay pinned until the instance is reset after the trigger.
+    Runtime {
+        new: Box<dyn Fn(u32, u32) -> Result<u32, Trap>>,
+        pin: Box<dyn Fn(u32) -> Result<u32, Trap>>,
+    },
+
+    /// Mappings written in Rust export `graph_alloc`, and take ownership
+    /// of the buffers we allocate through it.
+    Rust(Box<dyn Fn(u32) -> Result<u32, Trap>>),
+}
+
+impl Allocator {
+    fn new(
+        language: MappingLanguage,
+        api_version: &Version,
+        get_func: impl Fn(&str) -> Option<wasmtime::Func>,
+    ) -> Result<Self, anyhow::Error> {
+        if language == MappingLanguage::Rust {
+            let alloc = get_func("graph_alloc")
+                .context("`graph_alloc` function not found")?
+                .get1()?;
+            Ok(Allocator::Rust(Box::new(alloc)))
+        } else if uses_runtime_layout(api_version) {
+            let new = get_func("__new")
+                .context("`__new` function not found, the mapping must be compiled with `--exportRuntime`")?
+                .get2()?;
+            let pin = get_func("__pin")
+                .context("`__pin` function not found, the mapping must be compiled with `--exportRuntime`")?
+                .get1()?;
+            Ok(Allocator::Runtime {
+                new: Box::new(new),
+                pin: Box::new(pin),
+            })
+        } else {
+            let memory_allocate = get_func("memory.allocate")
+                .context("`memory.allocate` function not found")?
+                .get1()?;
+            Ok(Allocator::Arena(Box::new(memory_allocate)))
+        }
+    }
+}
+
 impl WasmInstance {
     /// Instantiates the module and sets it to be interrupted after `timeout`.
     pub fn from_valid_module_with_ctx(
@@ -335,25 +486,21 @@ impl WasmInstance {
 
         // Start the timeout watchdog task.
         let timeout_stopwatch = Arc::new(std::sync::Mutex::new(TimeoutStopwatch::start_new()));
+        let timeout_done = Arc::new(AtomicBool::new(false));
         if let Some(timeout) = timeout {
-            // This task is likely to outlive the instance, which is fine.
-            let interrupt_handle = linker.store().interrupt_handle().unwrap();
-            let timeout_stopwatch = timeout_stopwatch.clone();
-            graph::spawn_allow_panic(async move {
-                let minimum_wait = Duration::from_secs(1);
-                loop {
-                    let time_left =
-                        timeout.checked_sub(timeout_stopwatch.lock().unwrap().elapsed());
-                    match time_left {
-                        None => break interrupt_handle.interrupt(), // Timed out.
-
-                        Some(time) if time < minimum_wait => break interrupt_handle.interrupt(),
-                        Some(time) => tokio::time::delay_for(time).await,
-                    }
-                }
-            });
+            spawn_timeout_watchdog(
+                timeout,
+                linker.store().interrupt_handle().unwrap(),
+                timeout_stopwatch.clone(),
+                timeout_done.clone(),
+            );
         }
 
+        // For apiVersion 0.0.5 and later, host exports return a `HostResult`
+        // instead of trapping on deterministic errors.
+        let typed_errors = valid_module.language == MappingLanguage::AssemblyScript
+            && uses_runtime_layout(&ctx.borrow().as_ref().unwrap().host_exports.api_version);
+
         macro_rules! link {
             ($wasm_name:expr, $rust_name:ident, $($param:ident),*) => {
                 link!($wasm_name, $rust_name, "host_export_other", $($param),*)
@@ -373,46 +520,77 @@ impl WasmInstance {
                     let host_metrics = host_metrics.cheap_clone();
                     let timeout_stopwatch = timeout_stopwatch.cheap_clone();
                     let ctx = ctx.cheap_clone();
-                    linker.func(
-                        module,
-                        $wasm_name,
-                        move |caller: wasmtime::Caller, $($param: u32),*| {
-                            let instance = func_shared_ctx.upgrade().unwrap();
-                            let mut instance = instance.borrow_mut();
-
-                            // Happens when calling a host fn in Wasm start.
-                            if instance.is_none() {
-                                *instance = Some(WasmInstanceContext::from_caller(
-                                    caller,
-                                    ctx.borrow_mut().take().unwrap(),
-                                    valid_module.cheap_clone(),
-                                    host_metrics.cheap_clone(),
-                                    timeout,
-                                    timeout_stopwatch.cheap_clone(),
-                                    experimental_features.clone()
-                                ).unwrap())
-                            }
+                    let instance_ctx = move |caller: wasmtime::Caller| {
+                        let instance = func_shared_ctx.upgrade().unwrap();
 
-                            let instance = instance.as_mut().unwrap();
-                            let _section = instance.host_metrics.stopwatch.start_section($section);
-
-                            let result = instance.$rust_name(
-                                $($param.into()),*
-                            );
-                            match result {
-                                Ok(result) => Ok(result.into_wasm_ret()),
-                                Err(e) => {
-                                    match IntoTrap::determinism_level(&e) {
-                                        DeterminismLevel::Deterministic => {
-                                            instance.deterministic_host_trap = true;
-                                        },
-                                        _ => {},
+                        // Happens when calling a host fn in Wasm start.
+                        if instance.borrow().is_none() {
+                            *instance.borrow_mut() = Some(WasmInstanceContext::from_caller(
+                                caller,
+                                ctx.borrow_mut().take().unwrap(),
+                                valid_module.cheap_clone(),
+                                host_metrics.cheap_clone(),
+                                timeout,
+                                timeout_stopwatch.cheap_clone(),
+                                experimental_features.clone()
+                            ).unwrap())
+                        }
+                        instance
+                    };
+
+                    // `abort` is called by code the AssemblyScript compiler
+                    // generates and must always trap. `log.critical` must
+                    // fail the handler, and therefore `log.log` traps, too.
+                    if typed_errors && $wasm_name != "abort" && $wasm_name != "log.log" {
+                        linker.func(
+                            module,
+                            $wasm_name,
+                            move |caller: wasmtime::Caller, $($param: u32),*| {
+                                let instance = instance_ctx(caller);
+                                let mut instance = instance.borrow_mut();
+                                let instance = instance.as_mut().unwrap();
+                                let _section = instance.host_metrics.stopwatch.start_section($section);
+                                instance.consume_host_fn_gas($wasm_name)?;
+
+                                let start = Instant::now();
+                                let result = instance.$rust_name(
+                                    $($param.into()),*
+                                );
+                                instance.profile_host_fn($wasm_name, start);
+                                instance.host_result(result)
+                            }
+                        )?;
+                    } else {
+                        linker.func(
+                            module,
+                            $wasm_name,
+                            move |caller: wasmtime::Caller, $($param: u32),*| {
+                                let instance = instance_ctx(caller);
+                                let mut instance = instance.borrow_mut();
+                                let instance = instance.as_mut().unwrap();
+                                let _section = instance.host_metrics.stopwatch.start_section($section);
+                                instance.consume_host_fn_gas($wasm_name)?;
+
+                                let start = Instant::now();
+                                let result = instance.$rust_name(
+                                    $($param.into()),*
+                                );
+                                instance.profile_host_fn($wasm_name, start);
+                                match result {
+                                    Ok(result) => Ok(result.into_wasm_ret()),
+                                    Err(e) => {
+                                        match IntoTrap::determinism_level(&e) {
+                                            DeterminismLevel::Deterministic => {
+                                                instance.deterministic_host_trap = true;
+                                            },
+                                            _ => {},
+                                        }
+                                        Err(IntoTrap::into_trap(e))
                                     }
-                                    Err(IntoTrap::into_trap(e))
                                 }
                             }
-                        }
-                    )?;
+                        )?;
+                    }
                 }
             };
         }
@@ -461,6 +639,7 @@ impl WasmInstance {
                     let instance = instance.as_mut().unwrap();
                     let stopwatch = &instance.host_metrics.stopwatch;
                     let _section = stopwatch.start_section("host_export_ethereum_call");
+                    instance.consume_host_fn_gas("ethereum.call")?;
 
                     // For apiVersion >= 0.0.4 the call passed from the mapping includes the
                     // function signature; subgraphs using an apiVersion < 0.0.4 don't pass
@@ -485,6 +664,7 @@ impl WasmInstance {
                             HostExportError::Unknown(e) => e,
                         })?
                         .wasm_ptr();
+                    instance.profile_host_fn("ethereum.call", start);
                     instance.host_metrics.observe_host_fn_execution_time(
                         start.elapsed().as_secs_f64(),
                         "ethereum_call",
@@ -518,6 +698,22 @@ impl WasmInstance {
         );
 
         link!("store.remove", store_remove, entity_ptr, id_ptr);
+        link!(
+            "store.getAtBlock",
+            store_get_at_block,
+            "host_export_store_get_at_block",
+            entity,
+            id,
+            block
+        );
+        link!(
+            "store.loadRelated",
+            store_load_related,
+            "host_export_store_load_related",
+            entity,
+            id,
+            field
+        );
 
         link!("typeConversion.bytesToString", bytes_to_string, ptr);
         link!("typeConversion.bytesToHex", bytes_to_hex, ptr);
@@ -533,7 +729,27 @@ impl WasmInstance {
         link!("json.toF64", json_to_f64, ptr);
         link!("json.toBigInt", json_to_big_int, ptr);
 
+        link!("ethereum.encode", ethereum_encode, token_ptr);
+        link!("ethereum.decode", ethereum_decode, types_ptr, data_ptr);
+
         link!("crypto.keccak256", crypto_keccak_256, ptr);
+        link!("crypto.sha3_256", crypto_sha3_256, ptr);
+        link!("crypto.sha256", crypto_sha256, ptr);
+        link!("crypto.ripemd160", crypto_ripemd160, ptr);
+        link!("crypto.blake2b", crypto_blake2b, ptr);
+        link!(
+            "crypto.ecrecover",
+            crypto_ecrecover,
+            hash_ptr,
+            signature_ptr
+        );
+        link!(
+            "crypto.secp256k1Verify",
+            crypto_secp256k1_verify,
+            hash_ptr,
+            signature_ptr,
+            public_key_ptr
+        );
 
         link!("bigInt.plus", big_int_plus, x_ptr, y_ptr);
         link!("bigInt.minus", big_int_minus, x_ptr, y_ptr);
@@ -576,17 +792,68 @@ impl WasmInstance {
 
         link!("box.profile", box_profile, ptr);
 
+        // Host exports for mappings written in Rust, which live in the `graph` module.
+        link!(
+            "store_get",
+            rust_store_get,
+            "host_export_store_get",
+            entity_ptr,
+            id_ptr
+        );
+        link!(
+            "store_set",
+            rust_store_set,
+            "host_export_store_set",
+            entity_ptr,
+            id_ptr,
+            data_ptr
+        );
+        link!("store_remove", rust_store_remove, entity_ptr, id_ptr);
+        link!(
+            "store_get_at_block",
+            rust_store_get_at_block,
+            "host_export_store_get_at_block",
+            entity_ptr,
+            id_ptr,
+            block
+        );
+        link!(
+            "store_load_related",
+            rust_store_load_related,
+            "host_export_store_load_related",
+            entity_ptr,
+            id_ptr,
+            field_ptr
+        );
+        link!(
+            "ethereum_call",
+            rust_ethereum_call,
+            "host_export_ethereum_call",
+            call_ptr
+        );
+        link!("crypto_keccak256", rust_crypto_keccak256, ptr);
+        link!("ipfs_cat", rust_ipfs_cat, "host_export_ipfs_cat", hash_ptr);
+        link!(
+            "data_source_create",
+            rust_data_source_create,
+            name_ptr,
+            params_ptr
+        );
+        link!("log", rust_log, level, msg_ptr);
+        link!("panic", rust_panic, msg_ptr);
+
         let instance = linker.instantiate(&valid_module.module)?;
 
         // Usually `shared_ctx` is still `None` because no host fns were called during start.
-        if shared_ctx.borrow().is_none() {
+        let host_called_in_start = shared_ctx.borrow().is_some();
+        if !host_called_in_start {
             *shared_ctx.borrow_mut() = Some(WasmInstanceContext::from_instance(
                 &instance,
                 ctx.borrow_mut().take().unwrap(),
-                valid_module,
-                host_metrics,
+                valid_module.cheap_clone(),
+                host_metrics.cheap_clone(),
                 timeout,
-                timeout_stopwatch,
+                timeout_stopwatch.cheap_clone(),
                 experimental_features,
             )?);
         }
@@ -594,28 +861,86 @@ impl WasmInstance {
         Ok(WasmInstance {
             instance,
             instance_ctx: shared_ctx,
+            valid_module,
+            host_metrics,
+            timeout,
+            timeout_stopwatch,
+            experimental_features,
+            timeout_done,
+            host_called_in_start,
+            snapshot: None,
         })
     }
 }
 
+/// Interrupt the instance behind `interrupt_handle` once `stopwatch` reaches `timeout`, unless
+/// `done` is set before that.
+fn spawn_timeout_watchdog(
+    timeout: Duration,
+    interrupt_handle: wasmtime::InterruptHandle,
+    stopwatch: Arc<std::sync::Mutex<TimeoutStopwatch>>,
+    done: Arc<AtomicBool>,
+) {
+    // This task is likely to outlive the instance, which is fine.
+    graph::spawn_allow_panic(async move {
+        let minimum_wait = Duration::from_secs(1);
+        loop {
+            if done.load(Ordering::SeqCst) {
+                break;
+            }
+            let time_left = timeout.checked_sub(stopwatch.lock().unwrap().elapsed());
+            match time_left {
+                None => break interrupt_handle.interrupt(), // Timed out.
+
+                Some(time) if time < minimum_wait => break interrupt_handle.interrupt(),
+                Some(time) => tokio::time::delay_for(time).await,
+            }
+        }
+    });
+}
+
 impl AscHeap for WasmInstanceContext {
-    fn raw_new(&mut self, bytes: &[u8]) -> Result<u32, DeterministicHostError> {
-        // We request large chunks from the AssemblyScript allocator to use as arenas that we
-        // manage directly.
-
-        static MIN_ARENA_SIZE: i32 = 10_000;
-
-        let size = i32::try_from(bytes.len()).unwrap();
-        if size > self.arena_free_size {
-            // Allocate a new arena. Any free space left in the previous arena is left unused. This
-            // causes at most half of memory to be wasted, which is acceptable.
-            let arena_size = size.max(MIN_ARENA_SIZE);
-            self.arena_start_ptr = (self.memory_allocate)(arena_size).unwrap();
-            self.arena_free_size = arena_size;
+    fn raw_new(&mut self, bytes: &[u8], rt_id: u32) -> Result<u32, DeterministicHostError> {
+        let ptr = match self.allocator {
+            Allocator::Runtime { ref new, ref pin } => {
+                let size = u32::try_from(bytes.len()).unwrap();
+                let ptr = new(size, rt_id).map_err(|e| {
+                    DeterministicHostError(anyhow!("failed to allocate {} bytes: {}", size, e))
+                })?;
+                pin(ptr).map_err(|e| {
+                    DeterministicHostError(anyhow!("failed to pin object at {}: {}", ptr, e))
+                })?;
+                ptr as usize
+            }
+            Allocator::Rust(ref alloc) => {
+                let size = u32::try_from(bytes.len()).unwrap();
+                alloc(size).map_err(|e| {
+                    DeterministicHostError(anyhow!("failed to allocate {} bytes: {}", size, e))
+                })? as usize
+            }
+            Allocator::Arena(ref memory_allocate) => {
+                // We request large chunks from the AssemblyScript allocator to use as arenas that
+                // we manage directly.
+
+                static MIN_ARENA_SIZE: i32 = 10_000;
+
+                let size = i32::try_from(bytes.len()).unwrap();
+                if size > self.arena_free_size {
+                    // Allocate a new arena. Any free space left in the previous arena is left
+                    // unused. This causes at most half of memory to be wasted, which is
+                    // acceptable.
+                    let arena_size = size.max(MIN_ARENA_SIZE);
+                    self.arena_start_ptr = memory_allocate(arena_size).unwrap();
+                    self.arena_free_size = arena_size;
+                };
+
+                let ptr = self.arena_start_ptr as usize;
+                self.arena_start_ptr += size;
+                self.arena_free_size -= size;
+                ptr
+            }
         };
 
-        let ptr = self.arena_start_ptr as usize;
-
         // Safety:
         // First `wasmtime::Memory` is `!Sync`, so two threads cannot simultaneously hold a
         // reference into it. Given that, accessing the memory is only unsound if a reference into
@@ -629,8 +954,6 @@ impl AscHeap for WasmInstanceContext {
         // This unsafe block has been checked to not cause unsoundness by itself.
         // See also 2155cdca-dfaa-4fba-86e4-289e7683c1bf
         unsafe { self.memory.data_unchecked_mut()[ptr..(ptr + bytes.len())].copy_from_slice(bytes) }
-        self.arena_start_ptr += size;
-        self.arena_free_size -= size;
 
         Ok(ptr as u32)
     }
@@ -665,6 +988,10 @@ impl AscHeap for WasmInstanceContext {
             ))
         })
     }
+
+    fn api_version(&self) -> Version {
+        self.ctx.host_exports.api_version.clone()
+    }
 }
 
 impl WasmInstanceContext {
@@ -682,13 +1009,18 @@ impl WasmInstanceContext {
             .get_memory("memory")
             .context("Failed to find memory export in the WASM module")?;
 
-        let memory_allocate = instance
-            .get_func("memory.allocate")
-            .context("`memory.allocate` function not found")?
-            .get1()?;
+        let allocator = Allocator::new(
+            valid_module.language,
+            &ctx.host_exports.api_version,
+            |name| instance.get_func(name),
+        )?;
+        let gas = instance.get_global(gas::GAS_GLOBAL);
 
         Ok(WasmInstanceContext {
-            memory_allocate: Box::new(memory_allocate),
+            allocator,
+            gas,
+            handler_profile: None,
+            handler: None,
             memory,
             ctx,
             valid_module,
@@ -717,14 +1049,20 @@ impl WasmInstanceContext {
             .and_then(|e| e.into_memory())
             .context("Failed to find memory export in the WASM module")?;
 
-        let memory_allocate = caller
-            .get_export("memory.allocate")
-            .and_then(|e| e.into_func())
-            .context("`memory.allocate` function not found")?
-            .get1()?;
+        let allocator = Allocator::new(
+            valid_module.language,
+            &ctx.host_exports.api_version,
+            |name| caller.get_export(name).and_then(|e| e.into_func()),
+        )?;
+        let gas = caller
+            .get_export(gas::GAS_GLOBAL)
+            .and_then(|e| e.into_global());
 
         Ok(WasmInstanceContext {
-            memory_allocate: Box::new(memory_allocate),
+            allocator,
+            gas,
+            handler_profile: None,
+            handler: None,
             memory,
             ctx,
             valid_module,
@@ -740,6 +1078,76 @@ impl WasmInstanceContext {
     }
 }
 
+impl WasmInstanceContext {
+    fn profile_host_fn(&mut self, name: &str, start: Instant) {
+        if let Some(profile) = self.handler_profile.as_mut() {
+            profile.record_host_fn(name, start.elapsed());
+        }
+    }
+
+    /// The gas the handler has left, negative if it ran out of gas.
+    fn gas_left(&self) -> Option<i64> {
+        self.gas.as_ref().and_then(|gas| gas.get().i64())
+    }
+
+    fn set_gas_left(&self, gas_left: u64) -> Result<(), Error> {
+        match &self.gas {
+            Some(gas) => gas.set(Val::I64(gas_left as i64)),
+            None => Ok(()),
+        }
+    }
+
+    /// Charge `amount` of gas to the handler. Running out of gas is a deterministic error.
+    fn consume_gas(&mut self, amount: u64) -> Result<(), DeterministicHostError> {
+        let gas_left = match self.gas_left() {
+            Some(gas_left) => gas_left.saturating_sub(amount as i64),
+            None => return Ok(()),
+        };
+        self.gas
+            .as_ref()
+            .unwrap()
+            .set(Val::I64(gas_left))
+            .map_err(DeterministicHostError)?;
+        if gas_left < 0 {
+            return Err(DeterministicHostError(anyhow!("out of gas")));
+        }
+        Ok(())
+    }
+
+    /// Charge the cost of calling the host export `name`. Running out of gas traps even for
+    /// mappings that get deterministic errors passed as a `HostResult`.
+    fn consume_host_fn_gas(&mut self, name: &str) -> Result<(), Trap> {
+        self.consume_gas(gas::host_fn_cost(name)).map_err(|e| {
+            self.deterministic_host_trap = true;
+            e.into_trap()
+        })
+    }
+
+    /// Pass the outcome of a host export call to a mapping with apiVersion
+    /// 0.0.5 or later as a `HostResult`. Deterministic errors are reported to
+    /// the mapping, all other errors still trap.
+    fn host_result<R, E>(&mut self, result: Result<R, E>) -> Result<u32, Trap>
+    where
+        R: IntoHostPayload,
+        E: IntoTrap + std::fmt::Display,
+    {
+        let result = match result {
+            Ok(value) => Ok(value.into_host_payload()),
+            Err(e) => match e.determinism_level() {
+                DeterminismLevel::Deterministic => Err(format!("{:#}", e)),
+                _ => return Err(e.into_trap()),
+            },
+        };
+        match self.asc_new::<AscHostResult, _>(&result) {
+            Ok(ptr) => Ok(ptr.wasm_ptr()),
+            Err(e) => {
+                self.deterministic_host_trap = true;
+                Err(e.into_trap())
+            }
+        }
+    }
+}
+
 // Implementation of externals.
 impl WasmInstanceContext {
     /// function abort(message?: string | null, fileName?: string | null, lineNumber?: u32, columnNumber?: u32): void
@@ -842,6 +1250,57 @@ impl WasmInstanceContext {
         Ok(ret)
     }
 
+    /// function store.getAtBlock(entity: string, id: string, block: i32): Entity | null
+    fn store_get_at_block(
+        &mut self,
+        entity_ptr: AscPtr<AscString>,
+        id_ptr: AscPtr<AscString>,
+        block: u32,
+    ) -> Result<AscPtr<AscEntity>, HostExportError> {
+        let _timer = self
+            .host_metrics
+            .cheap_clone()
+            .time_host_fn_execution_region("store_get_at_block");
+        let entity = self.asc_get(entity_ptr)?;
+        let id = self.asc_get(id_ptr)?;
+        let current_block = self.ctx.block.number();
+        let entity_option = self.ctx.host_exports.store_get_at_block(
+            entity,
+            id,
+            block as BlockNumber,
+            current_block,
+        )?;
+
+        let ret = match entity_option {
+            Some(entity) => self.asc_new(&entity)?,
+            None => AscPtr::null(),
+        };
+
+        Ok(ret)
+    }
+
+    /// function store.loadRelated(entity: string, id: string, field: string): Array<Entity>
+    fn store_load_related(
+        &mut self,
+        entity_ptr: AscPtr<AscString>,
+        id_ptr: AscPtr<AscString>,
+        field_ptr: AscPtr<AscString>,
+    ) -> Result<AscPtr<Array<AscPtr<AscEntity>>>, HostExportError> {
+        let _timer = self
+            .host_metrics
+            .cheap_clone()
+            .time_host_fn_execution_region("store_load_related");
+        let entity = self.asc_get(entity_ptr)?;
+        let id = self.asc_get(id_ptr)?;
+        let field = self.asc_get(field_ptr)?;
+        let entities =
+            self.ctx
+                .host_exports
+                .store_load_related(&mut self.ctx.state, entity, id, field)?;
+
+        Ok(self.asc_new(entities.as_slice())?)
+    }
+
     /// function ethereum.call(call: SmartContractCall): Array<Token> | null
     fn ethereum_call(
         &mut self,
@@ -862,6 +1321,34 @@ impl WasmInstanceContext {
         }
     }
 
+    /// function ethereum.encode(token: ethereum.Value): Bytes
+    fn ethereum_encode(
+        &mut self,
+        token_ptr: AscPtr<AscEnum<EthereumValueKind>>,
+    ) -> Result<AscPtr<Uint8Array>, DeterministicHostError> {
+        let data = self
+            .ctx
+            .host_exports
+            .ethereum_encode(self.asc_get(token_ptr)?)?;
+        self.asc_new(&*data)
+    }
+
+    /// function ethereum.decode(types: String, data: Bytes): ethereum.Value | null
+    fn ethereum_decode(
+        &mut self,
+        types_ptr: AscPtr<AscString>,
+        data_ptr: AscPtr<Uint8Array>,
+    ) -> Result<AscPtr<AscEnum<EthereumValueKind>>, DeterministicHostError> {
+        let token = self
+            .ctx
+            .host_exports
+            .ethereum_decode(self.asc_get(types_ptr)?, self.asc_get(data_ptr)?)?;
+        match token {
+            Some(token) => self.asc_new(&token),
+            None => Ok(AscPtr::null()),
+        }
+    }
+
     /// function typeConversion.bytesToString(bytes: Bytes): string
     fn bytes_to_string(
         &mut self,
@@ -1081,6 +1568,84 @@ impl WasmInstanceContext {
         self.asc_new(input.as_ref())
     }
 
+    /// function crypto.sha3_256(input: Bytes): Bytes
+    fn crypto_sha3_256(
+        &mut self,
+        input_ptr: AscPtr<Uint8Array>,
+    ) -> Result<AscPtr<Uint8Array>, DeterministicHostError> {
+        let hash = self
+            .ctx
+            .host_exports
+            .crypto_sha3_256(self.asc_get(input_ptr)?)?;
+        self.asc_new(hash.as_ref())
+    }
+
+    /// function crypto.sha256(input: Bytes): Bytes
+    fn crypto_sha256(
+        &mut self,
+        input_ptr: AscPtr<Uint8Array>,
+    ) -> Result<AscPtr<Uint8Array>, DeterministicHostError> {
+        let hash = self
+            .ctx
+            .host_exports
+            .crypto_sha256(self.asc_get(input_ptr)?)?;
+        self.asc_new(&*hash)
+    }
+
+    /// function crypto.ripemd160(input: Bytes): Bytes
+    fn crypto_ripemd160(
+        &mut self,
+        input_ptr: AscPtr<Uint8Array>,
+    ) -> Result<AscPtr<Uint8Array>, DeterministicHostError> {
+        let hash = self
+            .ctx
+            .host_exports
+            .crypto_ripemd160(self.asc_get(input_ptr)?)?;
+        self.asc_new(&*hash)
+    }
+
+    /// function crypto.blake2b(input: Bytes): Bytes
+    fn crypto_blake2b(
+        &mut self,
+        input_ptr: AscPtr<Uint8Array>,
+    ) -> Result<AscPtr<Uint8Array>, DeterministicHostError> {
+        let hash = self
+            .ctx
+            .host_exports
+            .crypto_blake2b(self.asc_get(input_ptr)?)?;
+        self.asc_new(&*hash)
+    }
+
+    /// function crypto.ecrecover(hash: Bytes, signature: Bytes): Address | null
+    fn crypto_ecrecover(
+        &mut self,
+        hash_ptr: AscPtr<Uint8Array>,
+        signature_ptr: AscPtr<Uint8Array>,
+    ) -> Result<AscPtr<AscH160>, DeterministicHostError> {
+        let address = self
+            .ctx
+            .host_exports
+            .crypto_ecrecover(self.asc_get(hash_ptr)?, self.asc_get(signature_ptr)?)?;
+        match address {
+            Some(address) => self.asc_new(&address),
+            None => Ok(AscPtr::null()),
+        }
+    }
+
+    /// function crypto.secp256k1Verify(hash: Bytes, signature: Bytes, publicKey: Bytes): bool
+    fn crypto_secp256k1_verify(
+        &mut self,
+        hash_ptr: AscPtr<Uint8Array>,
+        signature_ptr: AscPtr<Uint8Array>,
+        public_key_ptr: AscPtr<Uint8Array>,
+    ) -> Result<bool, DeterministicHostError> {
+        self.ctx.host_exports.crypto_secp256k1_verify(
+            self.asc_get(hash_ptr)?,
+            self.asc_get(signature_ptr)?,
+            self.asc_get(public_key_ptr)?,
+        )
+    }
+
     /// function bigInt.plus(x: BigInt, y: BigInt): BigInt
     fn big_int_plus(
         &mut self,
@@ -1167,10 +1732,9 @@ impl WasmInstanceContext {
         exp: u32,
     ) -> Result<AscPtr<AscBigInt>, DeterministicHostError> {
         let exp = u8::try_from(exp).map_err(|e| DeterministicHostError(e.into()))?;
-        let result = self
-            .ctx
-            .host_exports
-            .big_int_pow(self.asc_get(x_ptr)?, exp)?;
+        let x = self.asc_get(x_ptr)?;
+        self.consume_gas(gas::big_int_pow_cost(&x, exp))?;
+        let result = self.ctx.host_exports.big_int_pow(x, exp)?;
         self.asc_new(&result)
     }
 
@@ -1398,7 +1962,14 @@ impl WasmInstanceContext {
     ) -> Result<(), DeterministicHostError> {
         let level = LogLevel::from(level).into();
         let msg: String = self.asc_get(msg)?;
-        self.ctx.host_exports.log_log(&self.ctx.logger, level, msg)
+        self.ctx.host_exports.log_log(
+            &self.ctx.logger,
+            &mut self.ctx.state,
+            self.ctx.block.number(),
+            self.handler.clone(),
+            level,
+            msg,
+        )
     }
 
     /// function arweave.transactionData(txId: string): Bytes | null
@@ -1435,3 +2006,198 @@ impl WasmInstanceContext {
             .unwrap_or(Ok(AscPtr::null()))
     }
 }
+
+// Implementation of externals for mappings written in Rust. Arguments and return values are
+// addresses of buffers encoded as described in `rust_abi`.
+impl WasmInstanceContext {
+    /// fn store_get(entity: &str, id: &str) -> Option<Entity>
+    /// Returns 0 if the entity does not exist.
+    fn rust_store_get(&mut self, entity_ptr: u32, id_ptr: u32) -> Result<u32, HostExportError> {
+        let _timer = self
+            .host_metrics
+            .cheap_clone()
+            .time_host_fn_execution_region("store_get");
+        let entity = rust_get(self, entity_ptr)?;
+        let id = rust_get(self, id_ptr)?;
+        let entity_option = self
+            .ctx
+            .host_exports
+            .store_get(&mut self.ctx.state, entity, id)?;
+        match entity_option {
+            Some(entity) => Ok(rust_new(self, &entity)?),
+            None => Ok(0),
+        }
+    }
+
+    /// fn store_get_at_block(entity: &str, id: &str, block: i32) -> Option<Entity>
+    /// Returns 0 if the entity did not exist at the end of `block`.
+    fn rust_store_get_at_block(
+        &mut self,
+        entity_ptr: u32,
+        id_ptr: u32,
+        block: u32,
+    ) -> Result<u32, HostExportError> {
+        let _timer = self
+            .host_metrics
+            .cheap_clone()
+            .time_host_fn_execution_region("store_get_at_block");
+        let entity = rust_get(self, entity_ptr)?;
+        let id = rust_get(self, id_ptr)?;
+        let current_block = self.ctx.block.number();
+        let entity_option = self.ctx.host_exports.store_get_at_block(
+            entity,
+            id,
+            block as BlockNumber,
+            current_block,
+        )?;
+        match entity_option {
+            Some(entity) => Ok(rust_new(self, &entity)?),
+            None => Ok(0),
+        }
+    }
+
+    /// fn store_load_related(entity: &str, id: &str, field: &str) -> Vec<Entity>
+    fn rust_store_load_related(
+        &mut self,
+        entity_ptr: u32,
+        id_ptr: u32,
+        field_ptr: u32,
+    ) -> Result<u32, HostExportError> {
+        let _timer = self
+            .host_metrics
+            .cheap_clone()
+            .time_host_fn_execution_region("store_load_related");
+        let entity = rust_get(self, entity_ptr)?;
+        let id = rust_get(self, id_ptr)?;
+        let field = rust_get(self, field_ptr)?;
+        let entities =
+            self.ctx
+                .host_exports
+                .store_load_related(&mut self.ctx.state, entity, id, field)?;
+        Ok(rust_new(self, entities.as_slice())?)
+    }
+
+    /// fn store_set(entity: &str, id: &str, data: &Entity)
+    fn rust_store_set(
+        &mut self,
+        entity_ptr: u32,
+        id_ptr: u32,
+        data_ptr: u32,
+    ) -> Result<(), HostExportError> {
+        let entity = rust_get(self, entity_ptr)?;
+        let id = rust_get(self, id_ptr)?;
+        let data = rust_get(self, data_ptr)?;
+        self.ctx.host_exports.store_set(
+            &self.ctx.logger,
+            &mut self.ctx.state,
+            &self.ctx.proof_of_indexing,
+            entity,
+            id,
+            data,
+        )?;
+        Ok(())
+    }
+
+    /// fn store_remove(entity: &str, id: &str)
+    fn rust_store_remove(&mut self, entity_ptr: u32, id_ptr: u32) -> Result<(), HostExportError> {
+        let entity = rust_get(self, entity_ptr)?;
+        let id = rust_get(self, id_ptr)?;
+        self.ctx.host_exports.store_remove(
+            &self.ctx.logger,
+            &mut self.ctx.state,
+            &self.ctx.proof_of_indexing,
+            entity,
+            id,
+        )
+    }
+
+    /// fn ethereum_call(call: &SmartContractCall) -> Option<Vec<Token>>
+    /// Returns 0 if the call reverted.
+    fn rust_ethereum_call(&mut self, call_ptr: u32) -> Result<u32, HostExportError> {
+        let call = rust_get(self, call_ptr)?;
+        let result = self
+            .ctx
+            .host_exports
+            .ethereum_call(&self.ctx.logger, &self.ctx.block, call);
+        match result {
+            Ok(Some(tokens)) => Ok(rust_new(self, tokens.as_slice())?),
+            Ok(None) => Ok(0),
+            Err(EthereumCallError::Unknown(e)) => Err(HostExportError::Unknown(e.into())),
+            Err(EthereumCallError::PossibleReorg(e)) => {
+                self.possible_reorg = true;
+                Err(HostExportError::Unknown(e))
+            }
+        }
+    }
+
+    /// fn crypto_keccak256(input: &[u8]) -> [u8; 32]
+    fn rust_crypto_keccak256(&mut self, input_ptr: u32) -> Result<u32, DeterministicHostError> {
+        let input = rust_get(self, input_ptr)?;
+        let hash = self.ctx.host_exports.crypto_keccak_256(input)?;
+        rust_new(self, hash.as_ref())
+    }
+
+    /// fn ipfs_cat(hash: &str) -> Option<Vec<u8>>
+    /// Returns 0 if the file could not be read.
+    fn rust_ipfs_cat(&mut self, hash_ptr: u32) -> Result<u32, HostExportError> {
+        if !self.experimental_features.allow_non_deterministic_ipfs {
+            return Err(HostExportError::Deterministic(anyhow!(
+                "`ipfs_cat` is deprecated. Improved support for IPFS will be added in the future"
+            )));
+        }
+
+        let hash: String = rust_get(self, hash_ptr)?;
+        match self
+            .ctx
+            .host_exports
+            .ipfs_cat(&self.ctx.logger, hash.clone())
+        {
+            Ok(bytes) => Ok(rust_new(self, bytes.as_slice())?),
+            Err(e) => {
+                info!(&self.ctx.logger, "Failed ipfs_cat, returning 0";
+                                    "link" => hash,
+                                    "error" => e.to_string());
+                Ok(0)
+            }
+        }
+    }
+
+    /// fn data_source_create(name: &str, params: &[String])
+    fn rust_data_source_create(
+        &mut self,
+        name_ptr: u32,
+        params_ptr: u32,
+    ) -> Result<(), HostExportError> {
+        let name = rust_get(self, name_ptr)?;
+        let params = rust_get(self, params_ptr)?;
+        self.ctx.host_exports.data_source_create(
+            &self.ctx.logger,
+            &mut self.ctx.state,
+            name,
+            params,
+            None,
+            self.ctx.block.block_ptr().number,
+        )
+    }
+
+    /// fn log(level: u32, msg: &str)
+    fn rust_log(&mut self, level: u32, msg_ptr: u32) -> Result<(), DeterministicHostError> {
+        let level = LogLevel::from(level).into();
+        let msg = rust_get(self, msg_ptr)?;
+        self.ctx.host_exports.log_log(
+            &self.ctx.logger,
+            &mut self.ctx.state,
+            self.ctx.block.number(),
+            self.handler.clone(),
+            level,
+            msg,
+        )
+    }
+
+    /// fn panic(msg: &str) -> !
+    /// Always returns a trap.
+    fn rust_panic(&mut self, msg_ptr: u32) -> Result<Never, DeterministicHostError> {
+        let msg = rust_get(self, msg_ptr)?;
+        self.ctx.host_exports.abort(Some(msg), None, None, None)
+    }
+}
@@ -1,6 +1,7 @@
 use ethabi::Token;
 use hex;
 use std::collections::{BTreeMap, HashMap};
+use std::convert::TryInto;
 use std::io::Cursor;
 use std::str::FromStr;
 
@@ -20,6 +21,7 @@ use web3::types::{Address, H160};
 use super::*;
 
 mod abi;
+mod rust;
 
 fn test_valid_module_and_store(
     subgraph_id: &str,
@@ -62,6 +64,7 @@ fn test_valid_module_and_store_with_timeout(
         metrics_registry,
         deployment_id.as_str(),
         stopwatch_metrics,
+        None,
     ));
 
     let experimental_features = ExperimentalFeatures {
@@ -71,7 +74,13 @@ fn test_valid_module_and_store_with_timeout(
     };
 
     let module = WasmInstance::from_valid_module_with_ctx(
-        Arc::new(ValidModule::new(data_source.mapping.runtime.as_ref()).unwrap()),
+        Arc::new(
+            ValidModule::new(
+                data_source.mapping.runtime.as_ref(),
+                data_source.mapping.mapping_language().unwrap(),
+            )
+            .unwrap(),
+        ),
         mock_context(
             deployment_id,
             data_source,
@@ -105,7 +114,7 @@ fn mock_data_source(path: &str) -> DataSource {
         },
         mapping: Mapping {
             kind: String::from("ethereum/events"),
-            api_version: String::from("0.1.0"),
+            api_version: String::from("0.0.4"),
             language: String::from("wasm/assemblyscript"),
             entities: vec![],
             abis: vec![],
@@ -141,7 +150,7 @@ fn mock_host_exports(
         },
         mapping: Mapping {
             kind: String::from("ethereum/events"),
-            api_version: String::from("0.1.0"),
+            api_version: String::from("0.0.4"),
             language: String::from("wasm/assemblyscript"),
             entities: vec![],
             abis: vec![],
@@ -221,6 +230,17 @@ impl WasmInstance {
         func(arg0.wasm_ptr(), arg1.wasm_ptr())
     }
 
+    fn invoke_export3<C, D, E, R: wasmtime::WasmTy>(
+        &self,
+        f: &str,
+        arg0: AscPtr<C>,
+        arg1: AscPtr<D>,
+        arg2: AscPtr<E>,
+    ) -> Result<R, wasmtime::Trap> {
+        let func = self.get_func(f).get3().unwrap();
+        func(arg0.wasm_ptr(), arg1.wasm_ptr(), arg2.wasm_ptr())
+    }
+
     fn takes_ptr_returns_val<P, V: wasmtime::WasmTy>(&mut self, fn_name: &str, v: AscPtr<P>) -> V {
         let func = self.get_func(fn_name).get1().unwrap();
         func(v.wasm_ptr()).unwrap()
@@ -476,6 +496,124 @@ async fn crypto_keccak256() {
     );
 }
 
+#[tokio::test]
+async fn crypto_hashes() {
+    let mut module = test_module("cryptoHashes", mock_data_source("wasm_test/crypto.wasm"));
+
+    let mut hash = |func: &str| -> String {
+        let input: &[u8] = "eth".as_ref();
+        let input: AscPtr<Uint8Array> = module.asc_new(input).unwrap();
+        let hash: AscPtr<Uint8Array> = module.invoke_export(func, input);
+        let hash: Vec<u8> = module.asc_get(hash).unwrap();
+        hex::encode(hash)
+    };
+
+    assert_eq!(
+        hash("sha256"),
+        "c69ea13227bace6e1f8a06364d93f4a6f04632432b64ca9b1fc4036baea4d34c"
+    );
+    assert_eq!(
+        hash("sha3_256"),
+        "4b3cdfda85c576e43c848d43fdf8e901d8d02553fec8ee56289d10b8dc47d997"
+    );
+    assert_eq!(
+        hash("ripemd160"),
+        "71ccfeb69d983a6af967c8afe14eeb420fa68f52"
+    );
+    assert_eq!(
+        hash("blake2b"),
+        "baa1250b5eda907f63e1099fffb39d23319587e6c424cf927292fcaf055d089b\
+         7992dc46cb83e774af47128738d7c412282c44ae13d3409339ed7184b2918f35"
+    );
+}
+
+/// keccak256("graph"), signed with the private key
+/// 0x4c0883a69102937d6231471b5dbb6204fe5129617082792ae468d01a3f362318 which
+/// belongs to the address 0x2c7536e3605d9c16a7a3d7b1898e529396a65c23
+const SIGNED_HASH: &str = "a0ed527785244825d33465d49867f1f6bf4936894648395a2a06d501921477b4";
+const SIGNATURE: &str = "bb50e2d89a4ed70663d080659fe0ad4b9bc3e06c17a227433966cb59ceee020d\
+                         3810691e07c1b569cdbafbce72c228726918a419bb5fd5c8124462a8dd052d22";
+const SIGNER: &str = "2c7536e3605d9c16a7a3d7b1898e529396a65c23";
+const SIGNER_PUBLIC_KEY: &str =
+    "024e3b81af9c2234cad09d679ce6035ed1392347ce64ce405f5dcd36228a25de6e";
+
+#[tokio::test]
+async fn crypto_ecrecover() {
+    let mut module = test_module("cryptoEcrecover", mock_data_source("wasm_test/crypto.wasm"));
+
+    let mut ecrecover = |hash: &str, signature: &str| -> Option<H160> {
+        let hash: AscPtr<Uint8Array> = module.asc_new(&*hex::decode(hash).unwrap()).unwrap();
+        let signature: AscPtr<Uint8Array> =
+            module.asc_new(&*hex::decode(signature).unwrap()).unwrap();
+        let address: AscPtr<AscH160> = module.invoke_export2("ecrecover", hash, signature);
+        if address.is_null() {
+            None
+        } else {
+            Some(module.asc_get(address).unwrap())
+        }
+    };
+
+    let signer = H160::from_str(SIGNER).unwrap();
+    let signature = format!("{}1b", SIGNATURE);
+    assert_eq!(Some(signer), ecrecover(SIGNED_HASH, &signature));
+    // Recovery ids can also be given without the offset of 27
+    let signature = format!("{}00", SIGNATURE);
+    assert_eq!(Some(signer), ecrecover(SIGNED_HASH, &signature));
+    // The other recovery id leads to a different key
+    let signature = format!("{}1c", SIGNATURE);
+    assert_ne!(Some(signer), ecrecover(SIGNED_HASH, &signature));
+    // No key can be recovered from an all-zero signature
+    let signature = format!("{}1b", "00".repeat(64));
+    assert_eq!(None, ecrecover(SIGNED_HASH, &signature));
+
+    // Malformed input is a deterministic error
+    let mut module = test_module(
+        "cryptoEcrecoverInvalid",
+        mock_data_source("wasm_test/crypto.wasm"),
+    );
+    let hash: AscPtr<Uint8Array> = module.asc_new(&*hex::decode("00ff").unwrap()).unwrap();
+    let signature = format!("{}1b", SIGNATURE);
+    let signature: AscPtr<Uint8Array> = module.asc_new(&*hex::decode(signature).unwrap()).unwrap();
+    let func = module.get_func("ecrecover").get2().unwrap();
+    let res: Result<u32, _> = func(hash.wasm_ptr(), signature.wasm_ptr());
+    let err = res.unwrap_err();
+    assert!(err
+        .to_string()
+        .contains("message hash must be 32 bytes long, but is 2 bytes"));
+    assert!(module.instance_ctx().deterministic_host_trap);
+}
+
+#[tokio::test]
+async fn crypto_secp256k1_verify() {
+    let mut module = test_module(
+        "cryptoSecp256k1Verify",
+        mock_data_source("wasm_test/crypto.wasm"),
+    );
+
+    let mut verify = |hash: &str, signature: &str, key: &str| -> Result<bool, wasmtime::Trap> {
+        let hash: AscPtr<Uint8Array> = module.asc_new(&*hex::decode(hash).unwrap()).unwrap();
+        let signature: AscPtr<Uint8Array> =
+            module.asc_new(&*hex::decode(signature).unwrap()).unwrap();
+        let key: AscPtr<Uint8Array> = module.asc_new(&*hex::decode(key).unwrap()).unwrap();
+        module
+            .invoke_export3::<_, _, _, i32>("secp256k1Verify", hash, signature, key)
+            .map(|res| res != 0)
+    };
+
+    assert!(verify(SIGNED_HASH, SIGNATURE, SIGNER_PUBLIC_KEY).unwrap());
+    // The signature with the high `s` value is accepted, too
+    let high_s = "bb50e2d89a4ed70663d080659fe0ad4b9bc3e06c17a227433966cb59ceee020d\
+                  c7ef96e1f83e4a96324504318d3dd78c519638ccf3e8ca73ad8dfbe3f331141f";
+    assert!(verify(SIGNED_HASH, high_s, SIGNER_PUBLIC_KEY).unwrap());
+    let other_hash = "00".repeat(32);
+    assert!(!verify(&other_hash, SIGNATURE, SIGNER_PUBLIC_KEY).unwrap());
+
+    let err = verify(SIGNED_HASH, SIGNATURE, "02ff").unwrap_err();
+    assert!(err
+        .to_string()
+        .contains("invalid public key for crypto.secp256k1Verify"));
+}
+
 #[tokio::test]
 async fn big_int_to_hex() {
     let mut module = test_module(
@@ -570,6 +708,165 @@ async fn big_int_arithmetic() {
     assert_eq!(result, BigInt::from(1));
 }
 
+#[tokio::test]
+async fn export_mutable_globals() {
+    let raw_module = std::fs::read("wasm_test/abi_classes.wasm").unwrap();
+    let (rewritten, globals) = super::export_mutable_globals(&raw_module).unwrap();
+    assert!(!globals.is_empty());
+    assert!(globals
+        .iter()
+        .all(|name| name.starts_with("__graph_global_")));
+
+    // Globals that are already exported are exported under their existing name.
+    let (again, again_globals) = super::export_mutable_globals(&rewritten).unwrap();
+    assert_eq!(rewritten, again);
+    assert_eq!(globals, again_globals);
+}
+
+#[tokio::test]
+async fn gas_metering() {
+    let engine = wasmtime::Engine::default();
+    let instantiate = |path: &str| {
+        let raw_module = std::fs::read(path).unwrap();
+        let metered = super::inject_metering(&raw_module, 1_000_000).unwrap();
+        let module = wasmtime::Module::new(&engine, &metered).unwrap();
+        wasmtime::Instance::new(&wasmtime::Store::new(&engine), &module, &[]).unwrap()
+    };
+
+    // Every instruction costs one gas, and `add` has four instructions.
+    let instance = instantiate("wasm_test/add_fn.wasm");
+    let add = instance
+        .get_func("add")
+        .unwrap()
+        .get2::<i32, i32, i32>()
+        .unwrap();
+    assert_eq!(add(1, 2).unwrap(), 3);
+    let gas = instance.get_global("__graph_gas").unwrap();
+    assert_eq!(gas.get().unwrap_i64(), 1_000_000 - 4);
+
+    // Running out of gas traps deterministically.
+    let instance = instantiate("wasm_test/non_terminating.wasm");
+    let func = instance.get_func("loop").unwrap().get0().unwrap();
+    let res: Result<(), _> = func();
+    assert_eq!(
+        res.unwrap_err().trap_code(),
+        Some(wasmtime::TrapCode::UnreachableCodeReached)
+    );
+    let gas = instance.get_global("__graph_gas").unwrap();
+    assert!(gas.get().unwrap_i64() < 0);
+}
+
+#[tokio::test]
+async fn gas_metering_newer_instructions() {
+    // Sign extension, saturating conversions and bulk memory instructions.
+    let raw_module = wat::parse_str(
+        r#"
+        (module
+          (memory 1)
+          (func (export "run") (param i32) (result i32)
+            i32.const 0
+            i32.const 255
+            i32.const 16
+            memory.fill
+            i32.const 16
+            i32.const 0
+            i32.const 16
+            memory.copy
+            local.get 0
+            i32.extend8_s
+            f32.const 1.5
+            i32.trunc_sat_f32_s
+            i32.add))
+        "#,
+    )
+    .unwrap();
+    let metered = super::inject_metering(&raw_module, 1_000_000).unwrap();
+
+    let mut config = wasmtime::Config::new();
+    config.wasm_bulk_memory(true);
+    let engine = wasmtime::Engine::new(&config);
+    let module = wasmtime::Module::new(&engine, &metered).unwrap();
+    let instance = wasmtime::Instance::new(&wasmtime::Store::new(&engine), &module, &[]).unwrap();
+    let run = instance
+        .get_func("run")
+        .unwrap()
+        .get1::<i32, i32>()
+        .unwrap();
+    assert_eq!(run(0x180).unwrap(), -127);
+    let gas = instance.get_global("__graph_gas").unwrap();
+    assert_eq!(gas.get().unwrap_i64(), 1_000_000 - 14);
+
+    // SIMD is not enabled, and the error says so.
+    let raw_module = wat::parse_str(
+        r#"
+        (module
+          (func (result i32)
+            v128.const i32x4 0 0 0 0
+            i32x4.extract_lane 0))
+        "#,
+    )
+    .unwrap();
+    let err = super::inject_metering(&raw_module, 1_000_000).unwrap_err();
+    assert!(err
+        .to_string()
+        .contains("SIMD instructions are not supported"));
+}
+
+#[tokio::test]
+async fn instance_pool_resets_instances() {
+    let data_source = mock_data_source("wasm_test/abi_classes.wasm");
+    let store = STORE.clone();
+    let call_cache = store
+        .block_store()
+        .ethereum_call_cache(NETWORK_NAME)
+        .expect("call cache for test network");
+    let deployment_id = SubgraphDeploymentId::new("instancePool").unwrap();
+    let metrics_registry = Arc::new(MockMetricsRegistry::new());
+    let stopwatch_metrics = StopwatchMetrics::new(
+        Logger::root(slog::Discard, o!()),
+        deployment_id.clone(),
+        metrics_registry.clone(),
+    );
+    let host_metrics = Arc::new(HostMetrics::new(
+        metrics_registry,
+        deployment_id.as_str(),
+        stopwatch_metrics,
+        None,
+    ));
+    let valid_module = Arc::new(
+        ValidModule::new(
+            data_source.mapping.runtime.as_ref(),
+            data_source.mapping.mapping_language().unwrap(),
+        )
+        .unwrap(),
+    );
+    let experimental_features = ExperimentalFeatures {
+        allow_non_deterministic_ipfs: true,
+        allow_non_deterministic_arweave: true,
+        allow_non_deterministic_3box: true,
+    };
+    let mut pool = InstancePool::new(valid_module, host_metrics, None, experimental_features);
+    let ctx = || {
+        mock_context(
+            deployment_id.clone(),
+            data_source.clone(),
+            store.subgraph_store(),
+            call_cache.clone(),
+        )
+    };
+
+    let mut instance = pool.get(ctx()).unwrap();
+    let first: AscPtr<AscString> = instance.asc_new("reused").unwrap();
+    pool.release(instance);
+
+    // The reused instance allocates exactly like a fresh one.
+    let mut instance = pool.get(ctx()).unwrap();
+    let second: AscPtr<AscString> = instance.asc_new("reused").unwrap();
+    assert_eq!(first.wasm_ptr(), second.wasm_ptr());
+    let string: String = instance.asc_get(second).unwrap();
+    assert_eq!(string, "reused");
+}
+
 #[tokio::test]
 async fn abort() {
     let module = test_module("abort", mock_data_source("wasm_test/abort.wasm"));
@@ -595,6 +892,65 @@ async fn bytes_to_base58() {
     assert_eq!(base58, "QmWmyoMoctfbAaiEs2G46gpeUmhqFRDW6KWo64y5r581Vz");
 }
 
+#[tokio::test]
+async fn api_version_0_0_5() {
+    let mut data_source = mock_data_source("wasm_test/api_version_0_0_5.wasm");
+    data_source.mapping.api_version = String::from("0.0.5");
+    let mut module = test_module("apiVersion005", data_source);
+
+    // Returns the `value` and `error` of a `HostResult`.
+    let host_result = |module: &WasmInstance, ptr: AscPtr<AscHostResult>| -> (u64, u32) {
+        let bytes = module.get(ptr.wasm_ptr(), 12).unwrap();
+        let value = u64::from_le_bytes(bytes[0..8].try_into().unwrap());
+        let error = u32::from_le_bytes(bytes[8..12].try_into().unwrap());
+        (value, error)
+    };
+
+    // Objects carry the size the runtime stored in their header.
+    let bytes: AscPtr<Uint8Array> = module.asc_new(&b"hello"[..]).unwrap();
+    let result = module.invoke_export("bytesToString", bytes);
+    let (value, error) = host_result(&module, result);
+    assert_eq!(error, 0);
+    let string: String = module
+        .asc_get(AscPtr::<AscString>::new(value as u32))
+        .unwrap();
+    assert_eq!(string, "hello");
+
+    let address: AscPtr<AscString> = module
+        .asc_new("0x2c7536e3605d9c16a7a3d7b1898e529396a65c23")
+        .unwrap();
+    let result = module.invoke_export("stringToH160", address);
+    let (value, error) = host_result(&module, result);
+    assert_eq!(error, 0);
+    let address: Vec<u8> = module
+        .asc_get(AscPtr::<Uint8Array>::new(value as u32))
+        .unwrap();
+    assert_eq!(
+        hex::encode(address),
+        "2c7536e3605d9c16a7a3d7b1898e529396a65c23"
+    );
+
+    // Deterministic errors are returned to the mapping instead of trapping.
+    let address: AscPtr<AscString> = module.asc_new("not an address").unwrap();
+    let result = module.invoke_export("stringToH160", address);
+    let (value, error) = host_result(&module, result);
+    assert_eq!(value, 0);
+    assert_ne!(error, 0);
+    let message = module.get(error, 4).unwrap();
+    let message = AscPtr::<AscString>::new(u32::from_le_bytes(message[..].try_into().unwrap()));
+    let message: String = module.asc_get(message).unwrap();
+    assert!(message.contains("Failed to convert string to Address/H160"));
+
+    // `log.critical` is not turned into a `HostResult` and fails the handler.
+    let message: AscPtr<AscString> = module.asc_new("giving up").unwrap();
+    let func = module.get_func("logCritical").get1().unwrap();
+    let res: Result<(), _> = func(message.wasm_ptr());
+    assert!(res
+        .unwrap_err()
+        .to_string()
+        .contains("Critical error logged in mapping"));
+}
+
 #[tokio::test]
 async fn data_source_create() {
     let run_data_source_create = move |name: String,
@@ -749,6 +1105,33 @@ async fn entity_store() {
     }
 }
 
+#[tokio::test]
+async fn store_errors_are_deterministic() {
+    let (module, _) =
+        test_valid_module_and_store("storeErrors", mock_data_source("wasm_test/store.wasm"));
+    let mut ctx = module.instance_ctx_mut();
+    let ctx = &mut ctx.ctx;
+
+    let err = ctx
+        .host_exports
+        .store_get_at_block("Unknown".to_owned(), "1".to_owned(), 0, 1)
+        .unwrap_err();
+    assert!(matches!(err, HostExportError::Deterministic(_)), "{}", err);
+
+    for (entity_type, field) in &[("Unknown", "users"), ("User", "name")] {
+        let err = ctx
+            .host_exports
+            .store_load_related(
+                &mut ctx.state,
+                entity_type.to_string(),
+                "1".to_owned(),
+                field.to_string(),
+            )
+            .unwrap_err();
+        assert!(matches!(err, HostExportError::Deterministic(_)), "{}", err);
+    }
+}
+
 #[tokio::test]
 async fn detect_contract_calls() {
     let data_source_without_calls = mock_data_source("wasm_test/abi_store_value.wasm");
@@ -187,6 +187,56 @@ async fn abi_ethabi_token_identity() {
     assert_eq!(new_token, token_array_nested);
 }
 
+#[tokio::test]
+async fn abi_encode_decode() {
+    let mut module = test_module(
+        "abiEncodeDecode",
+        mock_data_source("wasm_test/abi_encode_decode.wasm"),
+    );
+
+    let address = H160([1, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 1]);
+    let token = Token::Tuple(vec![
+        Token::Uint(U256::from(42)),
+        Token::Address(address),
+        Token::Array(vec![
+            Token::Bytes(vec![42, 45, 7, 245, 45]),
+            Token::Bytes(vec![]),
+        ]),
+        Token::String("graph".to_owned()),
+    ]);
+
+    // Encoding gives the same bytes as encoding with ethabi directly
+    let token_ptr = module.asc_new(&token).unwrap();
+    let data_ptr: AscPtr<Uint8Array> = module.invoke_export("encode", token_ptr);
+    let data: Vec<u8> = module.asc_get(data_ptr).unwrap();
+    assert_eq!(ethabi::encode(&[token.clone()]), data);
+
+    // Decoding the encoded bytes gives back the original value
+    let types = module.asc_new("(uint256,address,bytes[],string)").unwrap();
+    let decoded_ptr: AscPtr<AscEnum<EthereumValueKind>> =
+        module.invoke_export2("decode", types, data_ptr);
+    let decoded: Token = module.asc_get(decoded_ptr).unwrap();
+    assert_eq!(token, decoded);
+
+    // Data that is not a valid encoding for the types decodes to null
+    let types = module.asc_new("(uint256,address,bytes[],string)").unwrap();
+    let data_ptr: AscPtr<Uint8Array> = module.asc_new(&[1u8, 2, 3][..]).unwrap();
+    let decoded_ptr: AscPtr<AscEnum<EthereumValueKind>> =
+        module.invoke_export2("decode", types, data_ptr);
+    assert!(decoded_ptr.is_null());
+
+    // An invalid type is a deterministic error
+    let types = module.asc_new("(uint256,notatype)").unwrap();
+    let data_ptr: AscPtr<Uint8Array> = module.asc_new(&[1u8, 2, 3][..]).unwrap();
+    let func = module.get_func("decode").get2().unwrap();
+    let res: Result<u32, _> = func(types.wasm_ptr(), data_ptr.wasm_ptr());
+    assert!(res
+        .unwrap_err()
+        .to_string()
+        .contains("invalid type `(uint256,notatype)` for ethereum.decode"));
+    assert!(module.instance_ctx().deterministic_host_trap);
+}
+
 #[tokio::test]
 async fn abi_store_value() {
     use graph::data::store::Value;
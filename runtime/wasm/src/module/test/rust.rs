@@ -0,0 +1,202 @@
+use graph_mapping_sdk::{self as sdk, Decode, Encode};
+use web3::types::H256;
+
+use crate::rust_abi::{rust_get, rust_new, FromRustAbi, ToRustAbi};
+
+use super::*;
+
+fn rust_data_source(path: &str) -> DataSource {
+    let mut data_source = mock_data_source(path);
+    data_source.mapping.language = String::from("wasm/rust");
+    data_source
+}
+
+#[test]
+fn rust_abi_matches_sdk() {
+    let mut entity = Entity::new();
+    entity.set("id", "one");
+    entity.set("int", -5);
+    entity.set("decimal", BigDecimal::from_str("1.5").unwrap());
+    entity.set("bool", true);
+    entity.set("list", Value::List(vec![Value::from("a"), Value::Null]));
+    entity.set("bytes", scalar::Bytes::from(&[1u8, 2][..]));
+    entity.set("bigInt", BigInt::from(-300));
+
+    let mut sdk_entity = sdk::Entity::new();
+    sdk_entity.insert("id".to_string(), sdk::Value::String("one".to_string()));
+    sdk_entity.insert("int".to_string(), sdk::Value::Int(-5));
+    sdk_entity.insert(
+        "decimal".to_string(),
+        sdk::Value::BigDecimal("1.5".to_string()),
+    );
+    sdk_entity.insert("bool".to_string(), sdk::Value::Bool(true));
+    sdk_entity.insert(
+        "list".to_string(),
+        sdk::Value::List(vec![sdk::Value::String("a".to_string()), sdk::Value::Null]),
+    );
+    sdk_entity.insert("bytes".to_string(), sdk::Value::Bytes(vec![1, 2]));
+    sdk_entity.insert(
+        "bigInt".to_string(),
+        sdk::Value::BigInt(BigInt::from(-300).to_signed_bytes_le()),
+    );
+
+    // Entities go both ways and encode the same on both sides.
+    assert_eq!(entity.to_rust_abi(), sdk_entity.to_bytes());
+    assert_eq!(
+        sdk::Entity::from_bytes(&entity.to_rust_abi()).unwrap(),
+        sdk_entity
+    );
+    assert_eq!(
+        Entity::from(HashMap::<String, Value>::from_rust_abi(&sdk_entity.to_bytes()).unwrap()),
+        entity
+    );
+
+    let mut block = LightEthereumBlock::default();
+    block.hash = Some(H256::repeat_byte(1));
+    block.number = Some(7.into());
+    let event = EthereumEventData {
+        address: H160::repeat_byte(2),
+        log_index: U256::from(3),
+        transaction_log_index: U256::from(4),
+        log_type: None,
+        block: EthereumBlockData::from(&block),
+        transaction: EthereumTransactionData {
+            hash: H256::repeat_byte(5),
+            index: 6.into(),
+            from: H160::repeat_byte(7),
+            to: None,
+            value: U256::from(256),
+            gas_used: U256::zero(),
+            gas_price: U256::zero(),
+            input: vec![8, 9].into(),
+        },
+        params: vec![LogParam {
+            name: "value".to_string(),
+            value: Token::Int(U256::max_value()),
+        }],
+    };
+    let mut value = [0; 32];
+    value[1] = 1;
+    let mut log_index = [0; 32];
+    log_index[0] = 3;
+    let sdk_event = sdk::Event::from_bytes(&event.to_rust_abi()).unwrap();
+    assert_eq!(sdk_event.address, [2; 20]);
+    assert_eq!(sdk_event.log_index, log_index);
+    assert_eq!(sdk_event.block.hash, [1; 32]);
+    assert_eq!(sdk_event.block.number, 7);
+    assert_eq!(sdk_event.block.size, None);
+    assert_eq!(sdk_event.transaction.index, 6);
+    assert_eq!(sdk_event.transaction.value, value);
+    assert_eq!(sdk_event.transaction.input, vec![8, 9]);
+    assert_eq!(
+        sdk_event.params,
+        vec![sdk::Param {
+            name: "value".to_string(),
+            value: sdk::Token::Int([0xff; 32]),
+        }]
+    );
+
+    let call = sdk::SmartContractCall {
+        contract_name: "Token".to_string(),
+        contract_address: [3; 20],
+        function_name: "balanceOf".to_string(),
+        function_signature: Some("balanceOf(address):(uint256)".to_string()),
+        function_args: vec![sdk::Token::Address([4; 20])],
+    };
+    let call = UnresolvedContractCall::from_rust_abi(&call.to_bytes()).unwrap();
+    assert_eq!(call.contract_name, "Token");
+    assert_eq!(call.contract_address, H160::repeat_byte(3));
+    assert_eq!(
+        call.function_signature.as_deref(),
+        Some("balanceOf(address):(uint256)")
+    );
+    assert_eq!(
+        call.function_args,
+        vec![Token::Address(H160::repeat_byte(4))]
+    );
+
+    // Trailing bytes and bogus lengths are deterministic errors.
+    let mut bytes = entity.to_rust_abi();
+    bytes.push(0);
+    assert!(HashMap::<String, Value>::from_rust_abi(&bytes).is_err());
+    assert!(HashMap::<String, Value>::from_rust_abi(&[0xff, 0xff, 0xff, 0xff]).is_err());
+}
+
+#[tokio::test]
+async fn rust_mapping() {
+    let (mut module, store) = test_valid_module_and_store(
+        "rustMapping",
+        rust_data_source("wasm_test/rust_mapping.wasm"),
+    );
+
+    let mut one = Entity::new();
+    one.set("id", "one");
+    one.set("value", "first");
+    test_store::insert_entities(
+        SubgraphDeploymentId::new("rustMapping").unwrap(),
+        vec![(EntityType::from("Thing"), one.clone())],
+    )
+    .unwrap();
+
+    let get_thing = |module: &mut WasmInstance, id: &str| -> Option<Entity> {
+        let id = rust_new(module, &id.to_string()).unwrap();
+        let func = module.get_func("getThing").get1::<u32, u32>().unwrap();
+        match func(id).unwrap() {
+            0 => None,
+            ptr => Some(Entity::from(
+                rust_get::<_, HashMap<String, Value>>(&*module, ptr).unwrap(),
+            )),
+        }
+    };
+    assert_eq!(get_thing(&mut module, "one"), Some(one));
+    assert_eq!(get_thing(&mut module, "missing"), None);
+
+    let mut two = Entity::new();
+    two.set("id", "two");
+    two.set("value", "second");
+    let id = rust_new(&mut module, &"two".to_string()).unwrap();
+    let data = rust_new(&mut module, &two).unwrap();
+    let set_thing = module.get_func("setThing").get2::<u32, u32, ()>().unwrap();
+    set_thing(id, data).unwrap();
+    assert_eq!(get_thing(&mut module, "two"), Some(two));
+
+    // Aborting traps with the message of the mapping.
+    let fail = module.get_func("fail").get0::<()>().unwrap();
+    let err = fail().unwrap_err();
+    assert!(err.to_string().contains("boom"), "{}", err);
+    assert!(module.instance_ctx().deterministic_host_trap);
+    module.instance_ctx_mut().deterministic_host_trap = false;
+
+    // Handlers get their argument in the Rust ABI.
+    let state = module.handle_ethereum_block("handleBlock").unwrap();
+    let mods = state
+        .entity_cache
+        .as_modifications(store.as_ref())
+        .unwrap()
+        .modifications;
+    let block_thing = mods
+        .into_iter()
+        .find_map(|modification| match modification {
+            EntityModification::Insert { key, data } if key.entity_id == "block" => Some(data),
+            _ => None,
+        });
+    assert_eq!(
+        block_thing.and_then(|data| data.get("value").cloned()),
+        Some(Value::from("handled"))
+    );
+}
+
+#[tokio::test]
+async fn rust_mapping_imports() {
+    // A Rust mapping can not use the host exports for AssemblyScript.
+    let data_source = rust_data_source("wasm_test/abi_classes.wasm");
+    let err = ValidModule::new(
+        data_source.mapping.runtime.as_ref(),
+        data_source.mapping.mapping_language().unwrap(),
+    )
+    .err()
+    .unwrap();
+    assert!(err
+        .to_string()
+        .contains("can only import from the `graph` module"));
+}
@@ -0,0 +1,220 @@
+//! The ABI for mappings written in Rust (`language: wasm/rust`). Unlike the AssemblyScript ABI in
+//! `asc_abi`, it does not depend on how the guest lays out objects in memory. Every value crosses
+//! the boundary as a buffer that starts with its length as a little-endian `u32`, and the contents
+//! of the buffer use the encoding described in `docs/rust-mappings.md`.
+//!
+//! The guest side of this ABI is implemented by the `graph-mapping-sdk` crate.
+use anyhow::anyhow;
+
+use crate::asc_abi::AscHeap;
+use crate::error::DeterministicHostError;
+
+/// Write `value` to the memory of the mapping as a length-prefixed buffer and return its address.
+pub(crate) fn rust_new<H: AscHeap, T: ToRustAbi + ?Sized>(
+    heap: &mut H,
+    value: &T,
+) -> Result<u32, DeterministicHostError> {
+    let payload = value.to_rust_abi();
+    let mut bytes = Vec::with_capacity(payload.len() + 4);
+    bytes.extend_from_slice(&(payload.len() as u32).to_le_bytes());
+    bytes.extend(payload);
+    heap.raw_new(&bytes, 0)
+}
+
+/// Read the length-prefixed buffer at `ptr` in the memory of the mapping.
+pub(crate) fn rust_get<H: AscHeap, T: FromRustAbi>(
+    heap: &H,
+    ptr: u32,
+) -> Result<T, DeterministicHostError> {
+    let mut len = [0; 4];
+    len.copy_from_slice(&heap.get(ptr, 4)?);
+    let offset = ptr
+        .checked_add(4)
+        .ok_or_else(|| DeterministicHostError(anyhow!("buffer at {} is out of bounds", ptr)))?;
+    T::from_rust_abi(&heap.get(offset, u32::from_le_bytes(len))?)
+}
+
+/// Encodes values for a Rust mapping.
+#[derive(Default)]
+pub(crate) struct RustAbiWriter {
+    bytes: Vec<u8>,
+}
+
+impl RustAbiWriter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn u8(&mut self, value: u8) {
+        self.bytes.push(value);
+    }
+
+    pub fn u32(&mut self, value: u32) {
+        self.bytes.extend_from_slice(&value.to_le_bytes());
+    }
+
+    pub fn u64(&mut self, value: u64) {
+        self.bytes.extend_from_slice(&value.to_le_bytes());
+    }
+
+    pub fn bool(&mut self, value: bool) {
+        self.u8(value as u8);
+    }
+
+    /// Bytes of a size that is known from the context, like addresses and hashes.
+    pub fn fixed(&mut self, bytes: &[u8]) {
+        self.bytes.extend_from_slice(bytes);
+    }
+
+    /// Bytes prefixed with their length.
+    pub fn bytes(&mut self, bytes: &[u8]) {
+        self.u32(bytes.len() as u32);
+        self.fixed(bytes);
+    }
+
+    pub fn str(&mut self, s: &str) {
+        self.bytes(s.as_bytes());
+    }
+
+    pub fn into_bytes(self) -> Vec<u8> {
+        self.bytes
+    }
+}
+
+/// Decodes values from a Rust mapping.
+pub(crate) struct RustAbiReader<'a> {
+    bytes: &'a [u8],
+}
+
+impl<'a> RustAbiReader<'a> {
+    pub fn new(bytes: &'a [u8]) -> Self {
+        RustAbiReader { bytes }
+    }
+
+    pub fn fixed(&mut self, len: usize) -> Result<&'a [u8], DeterministicHostError> {
+        if self.bytes.len() < len {
+            return Err(DeterministicHostError(anyhow!(
+                "unexpected end of buffer from mapping"
+            )));
+        }
+        let (bytes, rest) = self.bytes.split_at(len);
+        self.bytes = rest;
+        Ok(bytes)
+    }
+
+    pub fn u8(&mut self) -> Result<u8, DeterministicHostError> {
+        Ok(self.fixed(1)?[0])
+    }
+
+    pub fn u32(&mut self) -> Result<u32, DeterministicHostError> {
+        let mut bytes = [0; 4];
+        bytes.copy_from_slice(self.fixed(4)?);
+        Ok(u32::from_le_bytes(bytes))
+    }
+
+    pub fn u64(&mut self) -> Result<u64, DeterministicHostError> {
+        let mut bytes = [0; 8];
+        bytes.copy_from_slice(self.fixed(8)?);
+        Ok(u64::from_le_bytes(bytes))
+    }
+
+    pub fn bool(&mut self) -> Result<bool, DeterministicHostError> {
+        match self.u8()? {
+            0 => Ok(false),
+            1 => Ok(true),
+            b => Err(DeterministicHostError(anyhow!("invalid boolean {}", b))),
+        }
+    }
+
+    pub fn bytes(&mut self) -> Result<&'a [u8], DeterministicHostError> {
+        let len = self.u32()? as usize;
+        self.fixed(len)
+    }
+
+    pub fn string(&mut self) -> Result<String, DeterministicHostError> {
+        String::from_utf8(self.bytes()?.to_vec())
+            .map_err(|e| DeterministicHostError(anyhow!("invalid string: {}", e)))
+    }
+
+    /// A count of items that follow, each of which takes at least one byte. Checking that against
+    /// the size of the buffer keeps a bogus count from making us allocate huge vectors.
+    pub fn count(&mut self) -> Result<usize, DeterministicHostError> {
+        let count = self.u32()? as usize;
+        if count > self.bytes.len() {
+            return Err(DeterministicHostError(anyhow!(
+                "count {} exceeds the size of the buffer",
+                count
+            )));
+        }
+        Ok(count)
+    }
+
+    fn finish(&self) -> Result<(), DeterministicHostError> {
+        if !self.bytes.is_empty() {
+            return Err(DeterministicHostError(anyhow!(
+                "{} unexpected bytes at the end of buffer from mapping",
+                self.bytes.len()
+            )));
+        }
+        Ok(())
+    }
+}
+
+/// A type that can be passed to a Rust mapping.
+pub(crate) trait ToRustAbi {
+    fn write(&self, writer: &mut RustAbiWriter);
+
+    fn to_rust_abi(&self) -> Vec<u8> {
+        let mut writer = RustAbiWriter::new();
+        self.write(&mut writer);
+        writer.into_bytes()
+    }
+}
+
+/// A type that can be received from a Rust mapping.
+pub(crate) trait FromRustAbi: Sized {
+    fn read(reader: &mut RustAbiReader) -> Result<Self, DeterministicHostError>;
+
+    fn from_rust_abi(bytes: &[u8]) -> Result<Self, DeterministicHostError> {
+        let mut reader = RustAbiReader::new(bytes);
+        let value = Self::read(&mut reader)?;
+        reader.finish()?;
+        Ok(value)
+    }
+}
+
+impl<T: ToRustAbi> ToRustAbi for [T] {
+    fn write(&self, writer: &mut RustAbiWriter) {
+        writer.u32(self.len() as u32);
+        for item in self {
+            item.write(writer);
+        }
+    }
+}
+
+impl<T: FromRustAbi> FromRustAbi for Vec<T> {
+    fn read(reader: &mut RustAbiReader) -> Result<Self, DeterministicHostError> {
+        (0..reader.count()?).map(|_| T::read(reader)).collect()
+    }
+}
+
+impl<T: ToRustAbi> ToRustAbi for Option<T> {
+    fn write(&self, writer: &mut RustAbiWriter) {
+        match self {
+            None => writer.u8(0),
+            Some(value) => {
+                writer.u8(1);
+                value.write(writer);
+            }
+        }
+    }
+}
+
+impl<T: FromRustAbi> FromRustAbi for Option<T> {
+    fn read(reader: &mut RustAbiReader) -> Result<Self, DeterministicHostError> {
+        match reader.bool()? {
+            false => Ok(None),
+            true => Ok(Some(T::read(reader)?)),
+        }
+    }
+}
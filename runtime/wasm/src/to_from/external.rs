@@ -512,6 +512,40 @@ impl<T: AscType> ToAscObj<AscWrapped<T>> for AscWrapped<T> {
     }
 }
 
+impl ToAscObj<AscHostResult> for Result<EnumPayload, String> {
+    fn to_asc_obj<H: AscHeap>(
+        &self,
+        heap: &mut H,
+    ) -> Result<AscHostResult, DeterministicHostError> {
+        Ok(match self {
+            Ok(value) => AscHostResult {
+                value: *value,
+                error: AscPtr::null(),
+                _padding: 0,
+            },
+            Err(message) => AscHostResult {
+                value: EnumPayload(0),
+                error: {
+                    let message = heap.asc_new(message.as_str())?;
+                    heap.asc_new(&AscHostError { message })?
+                },
+                _padding: 0,
+            },
+        })
+    }
+}
+
+impl ToAscObj<AscHostError> for AscHostError {
+    fn to_asc_obj<H: AscHeap>(
+        &self,
+        _heap: &mut H,
+    ) -> Result<AscHostError, DeterministicHostError> {
+        Ok(AscHostError {
+            message: self.message,
+        })
+    }
+}
+
 impl<V, E, VAsc, EAsc> ToAscObj<AscResult<VAsc, EAsc>> for Result<V, E>
 where
     V: ToAscObj<VAsc>,
@@ -10,6 +10,9 @@ use crate::error::DeterministicHostError;
 ///! Standard Rust types go in `mod.rs` and external types in `external.rs`.
 mod external;
 
+/// Implementations of `ToRustAbi` and `FromRustAbi` for mappings written in Rust.
+mod rust;
+
 impl<T: AscValue> ToAscObj<TypedArray<T>> for [T] {
     fn to_asc_obj<H: AscHeap>(
         &self,
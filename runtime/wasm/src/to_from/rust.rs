@@ -0,0 +1,333 @@
+//! Implementations of `ToRustAbi` and `FromRustAbi`, the counterpart of the AssemblyScript
+//! conversions for mappings written in Rust.
+use std::collections::HashMap;
+use std::str::FromStr;
+
+use anyhow::anyhow;
+use ethabi::{LogParam, Token};
+use graph::components::ethereum::{
+    EthereumBlockData, EthereumCallData, EthereumEventData, EthereumTransactionData,
+};
+use graph::data::store::{self, scalar};
+use graph::prelude::Entity;
+use web3::types::{H160, H256, U256};
+
+use crate::error::DeterministicHostError;
+use crate::rust_abi::{FromRustAbi, RustAbiReader, RustAbiWriter, ToRustAbi};
+use crate::UnresolvedContractCall;
+
+/// Together with the impls for slices and vectors, byte arrays are passed with their length
+/// followed by their contents.
+impl ToRustAbi for u8 {
+    fn write(&self, writer: &mut RustAbiWriter) {
+        writer.u8(*self);
+    }
+}
+
+impl FromRustAbi for u8 {
+    fn read(reader: &mut RustAbiReader) -> Result<Self, DeterministicHostError> {
+        reader.u8()
+    }
+}
+
+impl ToRustAbi for String {
+    fn write(&self, writer: &mut RustAbiWriter) {
+        writer.str(self);
+    }
+}
+
+impl FromRustAbi for String {
+    fn read(reader: &mut RustAbiReader) -> Result<Self, DeterministicHostError> {
+        reader.string()
+    }
+}
+
+impl ToRustAbi for H160 {
+    fn write(&self, writer: &mut RustAbiWriter) {
+        writer.fixed(self.as_bytes());
+    }
+}
+
+impl FromRustAbi for H160 {
+    fn read(reader: &mut RustAbiReader) -> Result<Self, DeterministicHostError> {
+        Ok(H160::from_slice(reader.fixed(20)?))
+    }
+}
+
+impl ToRustAbi for H256 {
+    fn write(&self, writer: &mut RustAbiWriter) {
+        writer.fixed(self.as_bytes());
+    }
+}
+
+/// 256 bit numbers are passed as 32 bytes in little-endian order.
+impl ToRustAbi for U256 {
+    fn write(&self, writer: &mut RustAbiWriter) {
+        let mut bytes = [0; 32];
+        self.to_little_endian(&mut bytes);
+        writer.fixed(&bytes);
+    }
+}
+
+impl FromRustAbi for U256 {
+    fn read(reader: &mut RustAbiReader) -> Result<Self, DeterministicHostError> {
+        Ok(U256::from_little_endian(reader.fixed(32)?))
+    }
+}
+
+const VALUE_NULL: u8 = 0;
+const VALUE_STRING: u8 = 1;
+const VALUE_INT: u8 = 2;
+const VALUE_BIG_DECIMAL: u8 = 3;
+const VALUE_BOOL: u8 = 4;
+const VALUE_LIST: u8 = 5;
+const VALUE_BYTES: u8 = 6;
+const VALUE_BIG_INT: u8 = 7;
+
+impl ToRustAbi for store::Value {
+    fn write(&self, writer: &mut RustAbiWriter) {
+        use store::Value;
+
+        match self {
+            Value::Null => writer.u8(VALUE_NULL),
+            Value::String(s) => {
+                writer.u8(VALUE_STRING);
+                writer.str(s);
+            }
+            Value::Int(n) => {
+                writer.u8(VALUE_INT);
+                writer.fixed(&n.to_le_bytes());
+            }
+            Value::BigDecimal(n) => {
+                writer.u8(VALUE_BIG_DECIMAL);
+                writer.str(&n.to_string());
+            }
+            Value::Bool(b) => {
+                writer.u8(VALUE_BOOL);
+                writer.bool(*b);
+            }
+            Value::List(values) => {
+                writer.u8(VALUE_LIST);
+                values.as_slice().write(writer);
+            }
+            Value::Bytes(bytes) => {
+                writer.u8(VALUE_BYTES);
+                writer.bytes(bytes.as_slice());
+            }
+            Value::BigInt(n) => {
+                writer.u8(VALUE_BIG_INT);
+                writer.bytes(&n.to_signed_bytes_le());
+            }
+        }
+    }
+}
+
+impl FromRustAbi for store::Value {
+    fn read(reader: &mut RustAbiReader) -> Result<Self, DeterministicHostError> {
+        use store::Value;
+
+        Ok(match reader.u8()? {
+            VALUE_NULL => Value::Null,
+            VALUE_STRING => Value::String(reader.string()?),
+            VALUE_INT => {
+                let mut bytes = [0; 4];
+                bytes.copy_from_slice(reader.fixed(4)?);
+                Value::Int(i32::from_le_bytes(bytes))
+            }
+            VALUE_BIG_DECIMAL => {
+                let s = reader.string()?;
+                Value::BigDecimal(scalar::BigDecimal::from_str(&s).map_err(|e| {
+                    DeterministicHostError(anyhow!("invalid BigDecimal `{}`: {}", s, e))
+                })?)
+            }
+            VALUE_BOOL => Value::Bool(reader.bool()?),
+            VALUE_LIST => Value::List(Vec::read(reader)?),
+            VALUE_BYTES => Value::Bytes(scalar::Bytes::from(reader.bytes()?)),
+            VALUE_BIG_INT => Value::BigInt(scalar::BigInt::from_signed_bytes_le(reader.bytes()?)),
+            tag => {
+                return Err(DeterministicHostError(anyhow!(
+                    "invalid value kind {}",
+                    tag
+                )))
+            }
+        })
+    }
+}
+
+/// Entities are passed as a list of attribute names and values, sorted by name so that the
+/// encoding of an entity is unique.
+impl ToRustAbi for Entity {
+    fn write(&self, writer: &mut RustAbiWriter) {
+        let mut attributes: Vec<_> = self.iter().collect();
+        attributes.sort_by(|(a, _), (b, _)| a.cmp(b));
+        writer.u32(attributes.len() as u32);
+        for (name, value) in attributes {
+            writer.str(name);
+            value.write(writer);
+        }
+    }
+}
+
+impl FromRustAbi for HashMap<String, store::Value> {
+    fn read(reader: &mut RustAbiReader) -> Result<Self, DeterministicHostError> {
+        (0..reader.count()?)
+            .map(|_| Ok((reader.string()?, store::Value::read(reader)?)))
+            .collect()
+    }
+}
+
+const TOKEN_ADDRESS: u8 = 0;
+const TOKEN_FIXED_BYTES: u8 = 1;
+const TOKEN_BYTES: u8 = 2;
+const TOKEN_INT: u8 = 3;
+const TOKEN_UINT: u8 = 4;
+const TOKEN_BOOL: u8 = 5;
+const TOKEN_STRING: u8 = 6;
+const TOKEN_FIXED_ARRAY: u8 = 7;
+const TOKEN_ARRAY: u8 = 8;
+const TOKEN_TUPLE: u8 = 9;
+
+impl ToRustAbi for Token {
+    fn write(&self, writer: &mut RustAbiWriter) {
+        match self {
+            Token::Address(address) => {
+                writer.u8(TOKEN_ADDRESS);
+                address.write(writer);
+            }
+            Token::FixedBytes(bytes) => {
+                writer.u8(TOKEN_FIXED_BYTES);
+                writer.bytes(bytes);
+            }
+            Token::Bytes(bytes) => {
+                writer.u8(TOKEN_BYTES);
+                writer.bytes(bytes);
+            }
+            // Signed integers are in two's complement.
+            Token::Int(n) => {
+                writer.u8(TOKEN_INT);
+                n.write(writer);
+            }
+            Token::Uint(n) => {
+                writer.u8(TOKEN_UINT);
+                n.write(writer);
+            }
+            Token::Bool(b) => {
+                writer.u8(TOKEN_BOOL);
+                writer.bool(*b);
+            }
+            Token::String(s) => {
+                writer.u8(TOKEN_STRING);
+                writer.str(s);
+            }
+            Token::FixedArray(tokens) => {
+                writer.u8(TOKEN_FIXED_ARRAY);
+                tokens.as_slice().write(writer);
+            }
+            Token::Array(tokens) => {
+                writer.u8(TOKEN_ARRAY);
+                tokens.as_slice().write(writer);
+            }
+            Token::Tuple(tokens) => {
+                writer.u8(TOKEN_TUPLE);
+                tokens.as_slice().write(writer);
+            }
+        }
+    }
+}
+
+impl FromRustAbi for Token {
+    fn read(reader: &mut RustAbiReader) -> Result<Self, DeterministicHostError> {
+        Ok(match reader.u8()? {
+            TOKEN_ADDRESS => Token::Address(H160::read(reader)?),
+            TOKEN_FIXED_BYTES => Token::FixedBytes(reader.bytes()?.to_vec()),
+            TOKEN_BYTES => Token::Bytes(reader.bytes()?.to_vec()),
+            TOKEN_INT => Token::Int(U256::read(reader)?),
+            TOKEN_UINT => Token::Uint(U256::read(reader)?),
+            TOKEN_BOOL => Token::Bool(reader.bool()?),
+            TOKEN_STRING => Token::String(reader.string()?),
+            TOKEN_FIXED_ARRAY => Token::FixedArray(Vec::read(reader)?),
+            TOKEN_ARRAY => Token::Array(Vec::read(reader)?),
+            TOKEN_TUPLE => Token::Tuple(Vec::read(reader)?),
+            tag => {
+                return Err(DeterministicHostError(anyhow!(
+                    "invalid token kind {}",
+                    tag
+                )))
+            }
+        })
+    }
+}
+
+impl ToRustAbi for LogParam {
+    fn write(&self, writer: &mut RustAbiWriter) {
+        writer.str(&self.name);
+        self.value.write(writer);
+    }
+}
+
+impl ToRustAbi for EthereumBlockData {
+    fn write(&self, writer: &mut RustAbiWriter) {
+        self.hash.write(writer);
+        self.parent_hash.write(writer);
+        self.uncles_hash.write(writer);
+        self.author.write(writer);
+        self.state_root.write(writer);
+        self.transactions_root.write(writer);
+        self.receipts_root.write(writer);
+        writer.u64(self.number.as_u64());
+        self.gas_used.write(writer);
+        self.gas_limit.write(writer);
+        self.timestamp.write(writer);
+        self.difficulty.write(writer);
+        self.total_difficulty.write(writer);
+        self.size.write(writer);
+    }
+}
+
+impl ToRustAbi for EthereumTransactionData {
+    fn write(&self, writer: &mut RustAbiWriter) {
+        self.hash.write(writer);
+        writer.u64(self.index.as_u64());
+        self.from.write(writer);
+        self.to.write(writer);
+        self.value.write(writer);
+        self.gas_used.write(writer);
+        self.gas_price.write(writer);
+        writer.bytes(&self.input.0);
+    }
+}
+
+impl ToRustAbi for EthereumEventData {
+    fn write(&self, writer: &mut RustAbiWriter) {
+        self.address.write(writer);
+        self.log_index.write(writer);
+        self.transaction_log_index.write(writer);
+        self.log_type.write(writer);
+        self.block.write(writer);
+        self.transaction.write(writer);
+        self.params.as_slice().write(writer);
+    }
+}
+
+impl ToRustAbi for EthereumCallData {
+    fn write(&self, writer: &mut RustAbiWriter) {
+        self.from.write(writer);
+        self.to.write(writer);
+        self.block.write(writer);
+        self.transaction.write(writer);
+        self.inputs.as_slice().write(writer);
+        self.outputs.as_slice().write(writer);
+    }
+}
+
+impl FromRustAbi for UnresolvedContractCall {
+    fn read(reader: &mut RustAbiReader) -> Result<Self, DeterministicHostError> {
+        Ok(UnresolvedContractCall {
+            contract_name: reader.string()?,
+            contract_address: H160::read(reader)?,
+            function_name: reader.string()?,
+            function_signature: Option::read(reader)?,
+            function_args: Vec::read(reader)?,
+        })
+    }
+}
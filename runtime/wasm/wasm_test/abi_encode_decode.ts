@@ -0,0 +1,37 @@
+import "allocator/arena";
+
+export { memory };
+
+enum TokenKind {
+    ADDRESS = 0,
+    FIXED_BYTES = 1,
+    BYTES = 2,
+    INT = 3,
+    UINT = 4,
+    BOOL = 5,
+    STRING = 6,
+    FIXED_ARRAY = 7,
+    ARRAY = 8,
+    TUPLE = 9
+}
+
+// Big enough to fit any pointer or native this.data.
+type Payload = u64
+
+export class Token {
+    kind: TokenKind
+    data: Payload
+}
+
+declare namespace ethereum {
+    function encode(token: Token): Uint8Array
+    function decode(types: String, data: Uint8Array): Token | null
+}
+
+export function encode(token: Token): Uint8Array {
+    return ethereum.encode(token)
+}
+
+export function decode(types: String, data: Uint8Array): Token | null {
+    return ethereum.decode(types, data)
+}
@@ -0,0 +1,55 @@
+;; Stand-in for the output of `asc abi_encode_decode.ts --validate` until it can be
+;; regenerated with `make abi_encode_decode.wasm`. Each export only forwards to the host
+;; function it wraps, and `memory.allocate` is the bump allocator of
+;; `allocator/arena`.
+(module
+  (type (;0;) (func (param i32) (result i32)))
+  (type (;1;) (func (param i32 i32) (result i32)))
+  (import "abi_encode_decode" "ethereum.encode" (func (;0;) (type 0)))
+  (import "abi_encode_decode" "ethereum.decode" (func (;1;) (type 1)))
+  (func (;2;) (type 0) (param i32) (result i32)
+    local.get 0
+    call 0
+  )
+  (func (;3;) (type 1) (param i32 i32) (result i32)
+    local.get 0
+    local.get 1
+    call 1
+  )
+  (func (;4;) (type 0) (param i32) (result i32)
+    (local i32 i32)
+    global.get 0
+    local.set 1
+    local.get 1
+    local.get 0
+    i32.add
+    i32.const 7
+    i32.add
+    i32.const -8
+    i32.and
+    global.set 0
+    block ;; label = @1
+      global.get 0
+      i32.const 65535
+      i32.add
+      i32.const 16
+      i32.shr_u
+      local.tee 2
+      memory.size
+      i32.le_u
+      br_if 0 (;@1;)
+      local.get 2
+      memory.size
+      i32.sub
+      memory.grow
+      drop
+    end
+    local.get 1
+  )
+  (memory (;0;) 1)
+  (global (;0;) (mut i32) i32.const 8)
+  (export "memory" (memory 0))
+  (export "encode" (func 2))
+  (export "decode" (func 3))
+  (export "memory.allocate" (func 4))
+)
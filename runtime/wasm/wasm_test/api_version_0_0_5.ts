@@ -0,0 +1,34 @@
+// Needs AssemblyScript 0.19, compiled with
+// `--runtime incremental --exportRuntime`
+
+export { memory };
+
+class HostError {
+    message: string
+}
+
+class HostResult {
+    value: u64
+    error: HostError | null
+}
+
+declare namespace log {
+    function log(level: i32, msg: string): void
+}
+
+declare namespace typeConversion {
+    function bytesToString(bytes: Uint8Array): HostResult
+    function stringToH160(s: string): HostResult
+}
+
+export function bytesToString(bytes: Uint8Array): HostResult {
+    return typeConversion.bytesToString(bytes)
+}
+
+export function stringToH160(s: string): HostResult {
+    return typeConversion.stringToH160(s)
+}
+
+export function logCritical(message: string): void {
+    log.log(0, message)
+}
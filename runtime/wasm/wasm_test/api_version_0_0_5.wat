@@ -0,0 +1,74 @@
+;; Stand-in for the output of `asc api_version_0_0_5.ts --runtime incremental
+;; --exportRuntime` with AssemblyScript 0.19 until it can be regenerated with
+;; `make api_version_0_0_5.wasm`. It only implements the parts of the runtime
+;; ABI the host uses: `__new` puts the 20 byte header `mmInfo, gcInfo,
+;; gcInfo2, rtId, rtSize` in front of each object and aligns objects at 16
+;; bytes, and `__pin` does nothing since this allocator never collects.
+(module
+  (type (;0;) (func (param i32) (result i32)))
+  (type (;1;) (func (param i32 i32) (result i32)))
+  (type (;2;) (func (param i32 i32)))
+  (type (;3;) (func (param i32)))
+  (import "conversion" "typeConversion.bytesToString" (func $bytesToString (type 0)))
+  (import "conversion" "typeConversion.stringToH160" (func $stringToH160 (type 0)))
+  (import "index" "log.log" (func $log (type 2)))
+  (func (;2;) (type 0) (param i32) (result i32)
+    local.get 0
+    call $bytesToString)
+  (func (;3;) (type 0) (param i32) (result i32)
+    local.get 0
+    call $stringToH160)
+  (func $logCritical (type 3) (param i32)
+    i32.const 0
+    local.get 0
+    call $log)
+  (func $__new (type 1) (param $size i32) (param $id i32) (result i32)
+    (local $ptr i32) (local $pages i32)
+    ;; ptr = align16(top + 20)
+    global.get $top
+    i32.const 35
+    i32.add
+    i32.const -16
+    i32.and
+    local.set $ptr
+    local.get $ptr
+    local.get $size
+    i32.add
+    global.set $top
+    block
+      global.get $top
+      i32.const 65535
+      i32.add
+      i32.const 16
+      i32.shr_u
+      local.tee $pages
+      memory.size
+      i32.le_u
+      br_if 0
+      local.get $pages
+      memory.size
+      i32.sub
+      memory.grow
+      drop
+    end
+    local.get $ptr
+    i32.const 8
+    i32.sub
+    local.get $id
+    i32.store
+    local.get $ptr
+    i32.const 4
+    i32.sub
+    local.get $size
+    i32.store
+    local.get $ptr)
+  (func $__pin (type 0) (param i32) (result i32)
+    local.get 0)
+  (memory (;0;) 1)
+  (global $top (mut i32) i32.const 16)
+  (export "memory" (memory 0))
+  (export "bytesToString" (func 2))
+  (export "stringToH160" (func 3))
+  (export "logCritical" (func $logCritical))
+  (export "__new" (func $__new))
+  (export "__pin" (func $__pin)))
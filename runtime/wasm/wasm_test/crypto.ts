@@ -4,8 +4,38 @@ export { memory };
 
 declare namespace crypto {
     function keccak256(input: Uint8Array): Uint8Array
+    function sha3_256(input: Uint8Array): Uint8Array
+    function sha256(input: Uint8Array): Uint8Array
+    function ripemd160(input: Uint8Array): Uint8Array
+    function blake2b(input: Uint8Array): Uint8Array
+    function ecrecover(hash: Uint8Array, signature: Uint8Array): Uint8Array | null
+    function secp256k1Verify(hash: Uint8Array, signature: Uint8Array, publicKey: Uint8Array): bool
 }
 
 export function hash(input: Uint8Array): Uint8Array {
     return crypto.keccak256(input)
 }
+
+export function sha3_256(input: Uint8Array): Uint8Array {
+    return crypto.sha3_256(input)
+}
+
+export function sha256(input: Uint8Array): Uint8Array {
+    return crypto.sha256(input)
+}
+
+export function ripemd160(input: Uint8Array): Uint8Array {
+    return crypto.ripemd160(input)
+}
+
+export function blake2b(input: Uint8Array): Uint8Array {
+    return crypto.blake2b(input)
+}
+
+export function ecrecover(hash: Uint8Array, signature: Uint8Array): Uint8Array | null {
+    return crypto.ecrecover(hash, signature)
+}
+
+export function secp256k1Verify(hash: Uint8Array, signature: Uint8Array, publicKey: Uint8Array): bool {
+    return crypto.secp256k1Verify(hash, signature, publicKey)
+}
@@ -0,0 +1,88 @@
+;; Stand-in for the output of `asc crypto.ts --validate` until it can be
+;; regenerated with `make crypto.wasm`. Each export only forwards to the host
+;; function it wraps, and `memory.allocate` is the bump allocator of
+;; `allocator/arena`.
+(module
+  (type (;0;) (func (param i32) (result i32)))
+  (type (;1;) (func (param i32 i32) (result i32)))
+  (type (;2;) (func (param i32 i32 i32) (result i32)))
+  (import "crypto" "crypto.keccak256" (func (;0;) (type 0)))
+  (import "crypto" "crypto.sha3_256" (func (;1;) (type 0)))
+  (import "crypto" "crypto.sha256" (func (;2;) (type 0)))
+  (import "crypto" "crypto.ripemd160" (func (;3;) (type 0)))
+  (import "crypto" "crypto.blake2b" (func (;4;) (type 0)))
+  (import "crypto" "crypto.ecrecover" (func (;5;) (type 1)))
+  (import "crypto" "crypto.secp256k1Verify" (func (;6;) (type 2)))
+  (func (;7;) (type 0) (param i32) (result i32)
+    local.get 0
+    call 0
+  )
+  (func (;8;) (type 0) (param i32) (result i32)
+    local.get 0
+    call 1
+  )
+  (func (;9;) (type 0) (param i32) (result i32)
+    local.get 0
+    call 2
+  )
+  (func (;10;) (type 0) (param i32) (result i32)
+    local.get 0
+    call 3
+  )
+  (func (;11;) (type 0) (param i32) (result i32)
+    local.get 0
+    call 4
+  )
+  (func (;12;) (type 1) (param i32 i32) (result i32)
+    local.get 0
+    local.get 1
+    call 5
+  )
+  (func (;13;) (type 2) (param i32 i32 i32) (result i32)
+    local.get 0
+    local.get 1
+    local.get 2
+    call 6
+  )
+  (func (;14;) (type 0) (param i32) (result i32)
+    (local i32 i32)
+    global.get 0
+    local.set 1
+    local.get 1
+    local.get 0
+    i32.add
+    i32.const 7
+    i32.add
+    i32.const -8
+    i32.and
+    global.set 0
+    block ;; label = @1
+      global.get 0
+      i32.const 65535
+      i32.add
+      i32.const 16
+      i32.shr_u
+      local.tee 2
+      memory.size
+      i32.le_u
+      br_if 0 (;@1;)
+      local.get 2
+      memory.size
+      i32.sub
+      memory.grow
+      drop
+    end
+    local.get 1
+  )
+  (memory (;0;) 1)
+  (global (;0;) (mut i32) i32.const 8)
+  (export "memory" (memory 0))
+  (export "hash" (func 7))
+  (export "sha3_256" (func 8))
+  (export "sha256" (func 9))
+  (export "ripemd160" (func 10))
+  (export "blake2b" (func 11))
+  (export "ecrecover" (func 12))
+  (export "secp256k1Verify" (func 13))
+  (export "memory.allocate" (func 14))
+)
@@ -0,0 +1,130 @@
+//! A mapping written in Rust for the tests in `runtime/wasm/src/module/test/rust.rs` and
+//! `runtime/wasm/src/mapping_test/test.rs`.
+#![allow(non_snake_case)]
+
+use graph_mapping_sdk::{
+    abort, buffer, data_source, ethereum, ipfs, log, store, take, Block, Call, Entity, Event,
+    Param, SmartContractCall, Token, Value,
+};
+
+/// Hand a buffer over to the host, which reads it before the mapping runs again.
+fn leak(buffer: Vec<u8>) -> u32 {
+    let ptr = buffer.as_ptr() as u32;
+    std::mem::forget(buffer);
+    ptr
+}
+
+#[no_mangle]
+pub extern "C" fn getThing(id: u32) -> u32 {
+    let id: String = unsafe { take(id) };
+    match store::get("Thing", &id) {
+        Some(thing) => leak(buffer(&thing)),
+        None => 0,
+    }
+}
+
+#[no_mangle]
+pub extern "C" fn setThing(id: u32, data: u32) {
+    let id: String = unsafe { take(id) };
+    let data: Entity = unsafe { take(data) };
+    store::set("Thing", &id, &data);
+}
+
+#[no_mangle]
+pub extern "C" fn handleBlock(block: u32) {
+    let _block: Block = unsafe { take(block) };
+    log::info("handling block");
+    let mut thing = Entity::new();
+    thing.insert("id".to_string(), Value::String("block".to_string()));
+    thing.insert("value".to_string(), Value::String("handled".to_string()));
+    store::set("Thing", "block", &thing);
+}
+
+#[no_mangle]
+pub extern "C" fn fail() {
+    abort("boom")
+}
+
+fn hex(bytes: &[u8]) -> String {
+    let digits: String = bytes.iter().map(|b| format!("{:02x}", b)).collect();
+    format!("0x{}", digits)
+}
+
+fn param<'a>(params: &'a [Param], name: &str) -> &'a Token {
+    match params.iter().find(|param| param.name == name) {
+        Some(param) => &param.value,
+        None => abort(&format!("missing parameter `{}`", name)),
+    }
+}
+
+fn address(token: &Token) -> [u8; 20] {
+    match token {
+        Token::Address(address) => *address,
+        _ => abort("expected an address"),
+    }
+}
+
+fn thing(id: &str, value: &str) -> Entity {
+    let mut thing = Entity::new();
+    thing.insert("id".to_string(), Value::String(id.to_string()));
+    thing.insert("value".to_string(), Value::String(value.to_string()));
+    thing
+}
+
+/// Record the balance of the recipient of a transfer, and track the recipient as a new token
+/// contract.
+#[no_mangle]
+pub extern "C" fn handleTransfer(event: u32) {
+    let event: Event = unsafe { take(event) };
+    let to = address(param(&event.params, "to"));
+
+    let call = SmartContractCall {
+        contract_name: "Contract".to_string(),
+        contract_address: event.address,
+        function_name: "balanceOf".to_string(),
+        function_signature: Some("balanceOf(address):(uint256)".to_string()),
+        function_args: vec![Token::Address(to)],
+    };
+    let mut holder = match ethereum::call(&call).as_deref() {
+        Some([Token::Uint(balance)]) => {
+            let mut holder = thing(&hex(&to), "balance");
+            holder.insert("amount".to_string(), Value::BigInt(balance.to_vec()));
+            holder
+        }
+        Some(_) => abort("unexpected result of balanceOf"),
+        None => thing(&hex(&to), "reverted"),
+    };
+    if let Token::Uint(value) = param(&event.params, "value") {
+        holder.insert("transferred".to_string(), Value::BigInt(value.to_vec()));
+    }
+    store::set("Thing", &hex(&to), &holder);
+
+    data_source::create("Token", &[hex(&to)]);
+}
+
+#[no_mangle]
+pub extern "C" fn handleTransferCall(call: u32) {
+    let call: Call = unsafe { take(call) };
+    let to = address(param(&call.inputs, "to"));
+    let success = match param(&call.outputs, "success") {
+        Token::Bool(success) => *success,
+        _ => abort("expected a bool"),
+    };
+    let value = if success { "called" } else { "failed" };
+    store::set("Thing", &hex(&to), &thing(&hex(&to), value));
+}
+
+/// Store the contents of the IPFS file with the hash from the event.
+#[no_mangle]
+pub extern "C" fn handleFetch(event: u32) {
+    let event: Event = unsafe { take(event) };
+    let hash = match param(&event.params, "hash") {
+        Token::String(hash) => hash.clone(),
+        _ => abort("expected a string"),
+    };
+    let value = match ipfs::cat(&hash) {
+        Some(data) => String::from_utf8(data).unwrap_or_else(|_| abort("invalid UTF-8")),
+        None => "missing".to_string(),
+    };
+    store::set("Thing", &hash, &thing(&hash, &value));
+}
@@ -1,13 +1,15 @@
 use std::collections::HashMap;
 
+use graph::data::subgraph::schema::MappingLogLevel;
 use graph::data::subgraph::status;
 use graph::prelude::*;
 use graph::{
     components::store::StatusStore,
     data::graphql::{IntoValue, ObjectOrInterface, ValueMap},
+    object,
 };
 use graph_graphql::prelude::{ExecutionContext, Resolver};
-use std::convert::TryInto;
+use std::convert::{TryFrom, TryInto};
 use web3::types::{Address, H256};
 
 /// Resolver for the index node GraphQL API.
@@ -15,6 +17,8 @@ pub struct IndexNodeResolver<R, S> {
     logger: Logger,
     graphql_runner: Arc<R>,
     store: Arc<S>,
+    mapping_profiler: Arc<MappingProfiler>,
+    indexing_progress: Arc<IndexingProgress>,
 }
 
 impl<R, S> IndexNodeResolver<R, S>
@@ -22,17 +26,75 @@ where
     R: GraphQlRunner,
     S: StatusStore,
 {
-    pub fn new(logger: &Logger, graphql_runner: Arc<R>, store: Arc<S>) -> Self {
+    pub fn new(
+        logger: &Logger,
+        graphql_runner: Arc<R>,
+        store: Arc<S>,
+        mapping_profiler: Arc<MappingProfiler>,
+        indexing_progress: Arc<IndexingProgress>,
+    ) -> Self {
         let logger = logger.new(o!("component" => "IndexNodeResolver"));
         Self {
             logger,
             graphql_runner,
             store,
+            mapping_profiler,
+            indexing_progress,
         }
     }
 
+    /// Turn `infos` into values and add what the store does not know about:
+    /// the indexing rate and estimated time to sync, which are only known
+    /// if this node indexes the deployment, and the dynamic data sources
+    /// of each deployment if the query selects them. Loading data sources
+    /// is skipped otherwise since deployments can have many of them
+    fn infos_into_values(
+        &self,
+        field: &q::Field,
+        infos: Vec<status::Info>,
+    ) -> Result<Vec<q::Value>, QueryExecutionError> {
+        let with_data_sources = selects(&field.selection_set, "dataSources");
+        infos
+            .into_iter()
+            .map(|info| -> Result<_, QueryExecutionError> {
+                let id = SubgraphDeploymentId::new(info.subgraph.clone())
+                    .map_err(|id| QueryExecutionError::SubgraphDeploymentIdError(id.to_string()))?;
+                let data_sources = if with_data_sources {
+                    Some(self.store.dynamic_data_sources(&id)?)
+                } else {
+                    None
+                };
+                let rate = self.indexing_progress.rate(&id);
+                let time_to_sync = rate.and_then(|rate| {
+                    if info.synced {
+                        return Some(0);
+                    }
+                    let chain = info.chains.first()?;
+                    let head = chain.chain_head_block.as_ref()?.number();
+                    let latest = chain
+                        .latest_block
+                        .as_ref()
+                        .or(chain.earliest_block.as_ref())
+                        .map_or(0, |block| block.number());
+                    rate.time_to_process(head - latest).map(|eta| eta.as_secs())
+                });
+
+                let mut value = info.into_value();
+                if let q::Value::Object(map) = &mut value {
+                    map.insert("indexingRate".to_string(), rate.into_value());
+                    map.insert("estimatedTimeToSync".to_string(), time_to_sync.into_value());
+                    if let Some(data_sources) = data_sources {
+                        map.insert("dataSources".to_string(), data_sources.into_value());
+                    }
+                }
+                Ok(value)
+            })
+            .collect()
+    }
+
     fn resolve_indexing_statuses(
         &self,
+        field: &q::Field,
         arguments: &HashMap<&String, q::Value>,
     ) -> Result<q::Value, QueryExecutionError> {
         let deployments = arguments
@@ -52,11 +114,12 @@ where
         let infos = self
             .store
             .status(status::Filter::Deployments(deployments))?;
-        Ok(infos.into_value())
+        Ok(q::Value::List(self.infos_into_values(field, infos)?))
     }
 
     fn resolve_indexing_statuses_for_subgraph_name(
         &self,
+        field: &q::Field,
         arguments: &HashMap<&String, q::Value>,
     ) -> Result<q::Value, QueryExecutionError> {
         // Get the subgraph name from the arguments; we can safely use `expect` here
@@ -76,7 +139,7 @@ where
             .store
             .status(status::Filter::SubgraphName(subgraph_name))?;
 
-        Ok(infos.into_value())
+        Ok(q::Value::List(self.infos_into_values(field, infos)?))
     }
 
     fn resolve_proof_of_indexing(
@@ -105,30 +168,209 @@ where
             .get_optional::<Address>("indexer")
             .expect("Invalid indexer");
 
-        let poi_fut =
-            self.store
-                .clone()
-                .get_proof_of_indexing(&deployment_id, &indexer, block.clone());
-        let poi = match futures::executor::block_on(poi_fut) {
-            Ok(Some(poi)) => q::Value::String(format!("0x{}", hex::encode(&poi))),
-            Ok(None) => q::Value::Null,
+        let poi = match self.proof_of_indexing(&deployment_id, &indexer, block.clone()) {
+            Ok(poi) => poi,
             Err(e) => {
                 error!(
                     self.logger,
                     "Failed to query proof of indexing";
-                    "subgraph" => deployment_id,
+                    "subgraph" => &deployment_id,
                     "block" => format!("{}", block),
                     "error" => format!("{:?}", e)
                 );
-                q::Value::Null
+                None
+            }
+        };
+        Ok(poi_into_value(poi))
+    }
+
+    /// Resolve each of the requests separately so that an invalid request
+    /// or an error for one deployment does not fail the whole batch
+    fn resolve_proofs_of_indexing(
+        &self,
+        arguments: &HashMap<&String, q::Value>,
+    ) -> Result<q::Value, QueryExecutionError> {
+        let requests = arguments
+            .get_required::<Vec<q::Value>>("requests")
+            .expect("Valid requests required");
+        let indexer = arguments
+            .get_optional::<Address>("indexer")
+            .expect("Invalid indexer");
+
+        let results = requests
+            .iter()
+            .map(|request| {
+                let deployment = request
+                    .get_required::<String>("deployment")
+                    .map(q::Value::String)
+                    .unwrap_or(q::Value::Null);
+                let (deployment_id, block) = match poi_request(request) {
+                    Ok(request) => request,
+                    Err(e) => {
+                        return object! {
+                            __typename: "ProofOfIndexingResult",
+                            deployment: deployment,
+                            block: q::Value::Null,
+                            proofOfIndexing: q::Value::Null,
+                            error: e.to_string(),
+                        }
+                    }
+                };
+
+                let (poi, error) =
+                    match self.proof_of_indexing(&deployment_id, &indexer, block.clone()) {
+                        Ok(poi) => (poi_into_value(poi), q::Value::Null),
+                        Err(e) => (q::Value::Null, q::Value::String(e.to_string())),
+                    };
+                object! {
+                    __typename: "ProofOfIndexingResult",
+                    deployment: deployment,
+                    block: object! {
+                        __typename: "Block",
+                        hash: block.hash_hex(),
+                        number: format!("{}", block.number),
+                    },
+                    proofOfIndexing: poi,
+                    error: error,
+                }
+            })
+            .collect();
+        Ok(q::Value::List(results))
+    }
+
+    fn proof_of_indexing(
+        &self,
+        deployment_id: &SubgraphDeploymentId,
+        indexer: &Option<Address>,
+        block: EthereumBlockPointer,
+    ) -> Result<Option<[u8; 32]>, Error> {
+        let poi_fut = self
+            .store
+            .clone()
+            .get_proof_of_indexing(deployment_id, indexer, block);
+        futures::executor::block_on(poi_fut)
+    }
+
+    fn resolve_mapping_profile(
+        &self,
+        arguments: &HashMap<&String, q::Value>,
+    ) -> Result<q::Value, QueryExecutionError> {
+        let deployment_id = arguments
+            .get_required::<SubgraphDeploymentId>("subgraph")
+            .expect("Valid subgraph required");
+
+        Ok(self
+            .mapping_profiler
+            .profile(&deployment_id)
+            .map(|profile| profile.into_value())
+            .unwrap_or(q::Value::Null))
+    }
+
+    fn resolve_subgraph_logs(
+        &self,
+        arguments: &HashMap<&String, q::Value>,
+    ) -> Result<q::Value, QueryExecutionError> {
+        let deployment_id = arguments
+            .get_required::<SubgraphDeploymentId>("subgraph")
+            .expect("Valid subgraph required");
+        let level = arguments
+            .get_optional::<MappingLogLevel>("level")
+            .expect("Valid level required")
+            .unwrap_or(MappingLogLevel::Debug);
+        let block_number = |name: &str| -> Result<Option<BlockNumber>, QueryExecutionError> {
+            arguments
+                .get_optional::<u64>(name)
+                .expect("Valid block number required")
+                .map(|number| {
+                    BlockNumber::try_from(number).map_err(|_| {
+                        QueryExecutionError::ValueParseError(name.to_owned(), number.to_string())
+                    })
+                })
+                .transpose()
+        };
+        let from_block = block_number("fromBlock")?;
+        let to_block = block_number("toBlock")?;
+
+        let logs = self
+            .store
+            .mapping_logs(&deployment_id, level, from_block, to_block)?;
+        Ok(logs.into_value())
+    }
+
+    fn resolve_health_history(
+        &self,
+        arguments: &HashMap<&String, q::Value>,
+    ) -> Result<q::Value, QueryExecutionError> {
+        let deployment_id = arguments
+            .get_required::<SubgraphDeploymentId>("subgraph")
+            .expect("Valid subgraph required");
+        let first = arguments
+            .get_optional::<u64>("first")
+            .expect("Valid first required")
+            .unwrap_or(100);
+        if first > 1000 {
+            return Err(QueryExecutionError::ValueParseError(
+                "first".to_owned(),
+                first.to_string(),
+            ));
+        }
+
+        let events = self.store.health_history(&deployment_id, first as i64)?;
+        Ok(events.into_value())
+    }
+
+    fn resolve_entity_changes_in_block(
+        &self,
+        arguments: &HashMap<&String, q::Value>,
+    ) -> Result<q::Value, QueryExecutionError> {
+        let deployment_id = arguments
+            .get_required::<SubgraphDeploymentId>("subgraph")
+            .expect("Valid subgraph required");
+        let block_number = arguments
+            .get_required::<u64>("blockNumber")
+            .expect("Valid blockNumber required");
+        let block_number = BlockNumber::try_from(block_number).map_err(|_| {
+            QueryExecutionError::ValueParseError("blockNumber".to_owned(), block_number.to_string())
+        })?;
+
+        let entity_json = |entity: Option<Entity>| match entity {
+            Some(mut entity) => {
+                entity.remove("__typename");
+                let value = q::Value::from(entity);
+                q::Value::String(
+                    serde_json::to_string(&SerializableValue(&value))
+                        .expect("entities can be serialized to JSON"),
+                )
             }
+            None => q::Value::Null,
         };
 
-        Ok(poi)
+        let changes = self
+            .store
+            .entity_changes_in_block(&deployment_id, block_number)?
+            .into_iter()
+            .map(|change| {
+                let operation = match (&change.before, &change.after) {
+                    (None, _) => "insert",
+                    (Some(_), Some(_)) => "update",
+                    (Some(_), None) => "remove",
+                };
+                object! {
+                    __typename: "EntityChangeInBlock",
+                    entityType: change.entity_type.as_str(),
+                    entityId: change.entity_id,
+                    operation: q::Value::Enum(operation.to_owned()),
+                    before: entity_json(change.before),
+                    after: entity_json(change.after),
+                }
+            })
+            .collect();
+        Ok(q::Value::List(changes))
     }
 
     fn resolve_indexing_status_for_version(
         &self,
+        field: &q::Field,
         arguments: &HashMap<&String, q::Value>,
 
         // If `true` return the current version, if `false` return the pending version.
@@ -149,14 +391,45 @@ where
             current_version,
         ))?;
 
-        Ok(infos
+        Ok(self
+            .infos_into_values(field, infos)?
             .into_iter()
             .next()
-            .map(|info| info.into_value())
             .unwrap_or(q::Value::Null))
     }
 }
 
+/// Whether `selection_set` selects the field `name`, either directly or
+/// through a fragment. Since fragment definitions are not available here,
+/// fragment spreads are assumed to select it
+fn selects(selection_set: &q::SelectionSet, name: &str) -> bool {
+    selection_set.items.iter().any(|selection| match selection {
+        q::Selection::Field(field) => field.name == name,
+        q::Selection::InlineFragment(fragment) => selects(&fragment.selection_set, name),
+        q::Selection::FragmentSpread(_) => true,
+    })
+}
+
+/// Parse one of the requests of `proofsOfIndexing`
+fn poi_request(request: &q::Value) -> Result<(SubgraphDeploymentId, EthereumBlockPointer), Error> {
+    let deployment_id = request.get_required::<SubgraphDeploymentId>("deployment")?;
+    let block_number = request.get_required::<u64>("blockNumber")?;
+    let block_number = BlockNumber::try_from(block_number)
+        .map_err(|_| anyhow!("invalid block number {}", block_number as i64))?;
+    let block_hash = request.get_required::<H256>("blockHash")?;
+    Ok((
+        deployment_id,
+        EthereumBlockPointer::from((block_hash, block_number)),
+    ))
+}
+
+fn poi_into_value(poi: Option<[u8; 32]>) -> q::Value {
+    match poi {
+        Some(poi) => q::Value::String(format!("0x{}", hex::encode(&poi))),
+        None => q::Value::Null,
+    }
+}
+
 impl<R, S> Clone for IndexNodeResolver<R, S>
 where
     R: GraphQlRunner,
@@ -167,6 +440,8 @@ where
             logger: self.logger.clone(),
             graphql_runner: self.graphql_runner.clone(),
             store: self.store.clone(),
+            mapping_profiler: self.mapping_profiler.clone(),
+            indexing_progress: self.indexing_progress.clone(),
         }
     }
 }
@@ -221,14 +496,30 @@ where
         match (prefetched_objects, object_type.name(), field.name.as_str()) {
             // The top-level `indexingStatuses` field
             (None, "SubgraphIndexingStatus", "indexingStatuses") => {
-                self.resolve_indexing_statuses(arguments)
+                self.resolve_indexing_statuses(field, arguments)
             }
 
             // The top-level `indexingStatusesForSubgraphName` field
             (None, "SubgraphIndexingStatus", "indexingStatusesForSubgraphName") => {
-                self.resolve_indexing_statuses_for_subgraph_name(arguments)
+                self.resolve_indexing_statuses_for_subgraph_name(field, arguments)
             }
 
+            // The top-level `proofsOfIndexing` field
+            (None, "ProofOfIndexingResult", "proofsOfIndexing") => {
+                self.resolve_proofs_of_indexing(arguments)
+            }
+
+            // The top-level `entityChangesInBlock` field
+            (None, "EntityChangeInBlock", "entityChangesInBlock") => {
+                self.resolve_entity_changes_in_block(arguments)
+            }
+
+            // The top-level `subgraphLogs` field
+            (None, "SubgraphLog", "subgraphLogs") => self.resolve_subgraph_logs(arguments),
+
+            // The top-level `healthHistory` field
+            (None, "HealthEvent", "healthHistory") => self.resolve_health_history(arguments),
+
             // Resolve fields of `Object` values (e.g. the `chains` field of `ChainIndexingStatus`)
             (value, _, _) => Ok(value.unwrap_or(q::Value::Null)),
         }
@@ -245,16 +536,226 @@ where
         match (prefetched_object, field.name.as_str()) {
             // The top-level `indexingStatusForCurrentVersion` field
             (None, "indexingStatusForCurrentVersion") => {
-                self.resolve_indexing_status_for_version(arguments, true)
+                self.resolve_indexing_status_for_version(field, arguments, true)
             }
 
             // The top-level `indexingStatusForPendingVersion` field
             (None, "indexingStatusForPendingVersion") => {
-                self.resolve_indexing_status_for_version(arguments, false)
+                self.resolve_indexing_status_for_version(field, arguments, false)
             }
 
+            // The top-level `mappingProfile` field
+            (None, "mappingProfile") => self.resolve_mapping_profile(arguments),
+
             // Resolve fields of `Object` values (e.g. the `latestBlock` field of `EthereumBlock`)
             (value, _) => Ok(value.unwrap_or(q::Value::Null)),
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use graph::components::server::index_node::VersionInfo;
+    use graph::components::store::EntityVersionChange;
+    use graph::data::graphql::effort::LoadManager;
+    use graph::data::query::{QueryResults, QueryTarget};
+    use graph::data::subgraph::schema::{HealthEvent, MappingLog};
+    use std::collections::HashSet;
+
+    const POI: [u8; 32] = [7; 32];
+    const HASH: &str = "0x0101010101010101010101010101010101010101010101010101010101010101";
+
+    struct TestGraphQlRunner;
+
+    #[async_trait]
+    impl GraphQlRunner for TestGraphQlRunner {
+        async fn run_query(self: Arc<Self>, _: Query, _: QueryTarget, _: bool) -> QueryResults {
+            unimplemented!()
+        }
+
+        async fn run_query_with_complexity(
+            self: Arc<Self>,
+            _: Query,
+            _: QueryTarget,
+            _: Option<u64>,
+            _: Option<u8>,
+            _: Option<u32>,
+            _: Option<u32>,
+            _: bool,
+        ) -> QueryResults {
+            unimplemented!()
+        }
+
+        async fn run_subscription(
+            self: Arc<Self>,
+            _: Subscription,
+            _: QueryTarget,
+        ) -> Result<SubscriptionResult, SubscriptionError> {
+            unimplemented!()
+        }
+
+        fn load_manager(&self) -> Arc<LoadManager> {
+            unimplemented!()
+        }
+    }
+
+    /// Has a proof of indexing for `QmGood` at block 1 and fails for any
+    /// other deployment
+    struct TestStore;
+
+    impl StatusStore for TestStore {
+        fn status(&self, _: status::Filter) -> Result<Vec<status::Info>, StoreError> {
+            unimplemented!()
+        }
+
+        fn version_info(&self, _: &str) -> Result<VersionInfo, StoreError> {
+            unimplemented!()
+        }
+
+        fn versions_for_subgraph_id(
+            &self,
+            _: &str,
+        ) -> Result<(Option<String>, Option<String>), StoreError> {
+            unimplemented!()
+        }
+
+        fn supports_proof_of_indexing<'a>(
+            self: Arc<Self>,
+            _: &'a SubgraphDeploymentId,
+        ) -> DynTryFuture<'a, bool> {
+            unimplemented!()
+        }
+
+        fn get_proof_of_indexing<'a>(
+            self: Arc<Self>,
+            subgraph_id: &'a SubgraphDeploymentId,
+            _: &'a Option<Address>,
+            block: EthereumBlockPointer,
+        ) -> DynTryFuture<'a, Option<[u8; 32]>> {
+            let poi = match subgraph_id.as_str() {
+                "QmGood" if block.number == 1 => Ok(Some(POI)),
+                "QmGood" => Ok(None),
+                _ => Err(anyhow!("deployment {} is broken", subgraph_id)),
+            };
+            Box::pin(futures::future::ready(poi))
+        }
+
+        fn mapping_logs(
+            &self,
+            _: &SubgraphDeploymentId,
+            _: MappingLogLevel,
+            _: Option<BlockNumber>,
+            _: Option<BlockNumber>,
+        ) -> Result<Vec<MappingLog>, StoreError> {
+            unimplemented!()
+        }
+
+        fn dynamic_data_sources(
+            &self,
+            _: &SubgraphDeploymentId,
+        ) -> Result<Vec<status::DataSource>, StoreError> {
+            unimplemented!()
+        }
+
+        fn entity_changes_in_block(
+            &self,
+            _: &SubgraphDeploymentId,
+            _: BlockNumber,
+        ) -> Result<Vec<EntityVersionChange>, StoreError> {
+            unimplemented!()
+        }
+
+        fn health_history(
+            &self,
+            _: &SubgraphDeploymentId,
+            _: i64,
+        ) -> Result<Vec<HealthEvent>, StoreError> {
+            unimplemented!()
+        }
+    }
+
+    fn resolver() -> IndexNodeResolver<TestGraphQlRunner, TestStore> {
+        IndexNodeResolver::new(
+            &Logger::root(slog::Discard, o!()),
+            Arc::new(TestGraphQlRunner),
+            Arc::new(TestStore),
+            Arc::new(MappingProfiler::new(HashSet::new())),
+            Arc::new(IndexingProgress::new()),
+        )
+    }
+
+    fn request(deployment: &str, number: i32, hash: &str) -> q::Value {
+        object! {
+            deployment: deployment,
+            blockNumber: number,
+            blockHash: hash,
+        }
+    }
+
+    fn result(
+        deployment: &str,
+        number: i32,
+        poi: Option<[u8; 32]>,
+        error: Option<&str>,
+    ) -> q::Value {
+        object! {
+            __typename: "ProofOfIndexingResult",
+            deployment: deployment,
+            block: object! {
+                __typename: "Block",
+                hash: HASH.trim_start_matches("0x"),
+                number: format!("{}", number),
+            },
+            proofOfIndexing: poi_into_value(poi),
+            error: error,
+        }
+    }
+
+    #[test]
+    fn proofs_of_indexing() {
+        let requests = String::from("requests");
+        let mut arguments = HashMap::new();
+        arguments.insert(
+            &requests,
+            q::Value::List(vec![
+                request("QmGood", 1, HASH),
+                request("QmGood", 2, HASH),
+                request("QmBroken", 1, HASH),
+                request("not a deployment", 1, HASH),
+                request("QmGood", -1, HASH),
+                request("QmGood", 1, "0x01"),
+            ]),
+        );
+
+        let results = match resolver().resolve_proofs_of_indexing(&arguments) {
+            Ok(q::Value::List(results)) => results,
+            other => panic!("expected a list of results but got {:?}", other),
+        };
+
+        assert_eq!(6, results.len());
+        assert_eq!(result("QmGood", 1, Some(POI), None), results[0]);
+        assert_eq!(result("QmGood", 2, None, None), results[1]);
+        assert_eq!(
+            result("QmBroken", 1, None, Some("deployment QmBroken is broken")),
+            results[2]
+        );
+        // Invalid requests only fail their own entry
+        for (result, deployment, error) in &[
+            (&results[3], "not a deployment", "Invalid subgraph ID"),
+            (&results[4], "QmGood", "invalid block number -1"),
+            (&results[5], "QmGood", "Cannot parse H256 value"),
+        ] {
+            assert_eq!(
+                Some(deployment.to_string()),
+                result.get_optional("deployment").unwrap()
+            );
+            assert_eq!(
+                None,
+                result.get_optional::<String>("proofOfIndexing").unwrap()
+            );
+            let message = result.get_required::<String>("error").unwrap();
+            assert!(message.contains(error), "unexpected error {}", message);
+        }
+    }
+}
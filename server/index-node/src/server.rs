@@ -29,11 +29,19 @@ pub struct IndexNodeServer<Q, S> {
     logger: Logger,
     graphql_runner: Arc<Q>,
     store: Arc<S>,
+    mapping_profiler: Arc<MappingProfiler>,
+    indexing_progress: Arc<IndexingProgress>,
 }
 
 impl<Q, S> IndexNodeServer<Q, S> {
     /// Creates a new GraphQL server.
-    pub fn new(logger_factory: &LoggerFactory, graphql_runner: Arc<Q>, store: Arc<S>) -> Self {
+    pub fn new(
+        logger_factory: &LoggerFactory,
+        graphql_runner: Arc<Q>,
+        store: Arc<S>,
+        mapping_profiler: Arc<MappingProfiler>,
+        indexing_progress: Arc<IndexingProgress>,
+    ) -> Self {
         let logger = logger_factory.component_logger(
             "IndexNodeServer",
             Some(ComponentLoggerConfig {
@@ -47,6 +55,8 @@ impl<Q, S> IndexNodeServer<Q, S> {
             logger,
             graphql_runner,
             store,
+            mapping_profiler,
+            indexing_progress,
         }
     }
 }
@@ -80,6 +90,8 @@ where
             logger_for_service.clone(),
             graphql_runner.clone(),
             store.clone(),
+            self.mapping_profiler.clone(),
+            self.indexing_progress.clone(),
         );
         let new_service =
             make_service_fn(move |_| futures03::future::ok::<_, Error>(service.clone()));
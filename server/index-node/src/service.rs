@@ -26,6 +26,8 @@ pub struct IndexNodeService<Q, S> {
     graphql_runner: Arc<Q>,
     store: Arc<S>,
     explorer: Arc<Explorer<S>>,
+    mapping_profiler: Arc<MappingProfiler>,
+    indexing_progress: Arc<IndexingProgress>,
 }
 
 impl<Q, S> Clone for IndexNodeService<Q, S> {
@@ -35,6 +37,8 @@ impl<Q, S> Clone for IndexNodeService<Q, S> {
             graphql_runner: self.graphql_runner.clone(),
             store: self.store.clone(),
             explorer: self.explorer.clone(),
+            mapping_profiler: self.mapping_profiler.clone(),
+            indexing_progress: self.indexing_progress.clone(),
         }
     }
 }
@@ -47,7 +51,13 @@ where
     S: StatusStore,
 {
     /// Creates a new GraphQL service.
-    pub fn new(logger: Logger, graphql_runner: Arc<Q>, store: Arc<S>) -> Self {
+    pub fn new(
+        logger: Logger,
+        graphql_runner: Arc<Q>,
+        store: Arc<S>,
+        mapping_profiler: Arc<MappingProfiler>,
+        indexing_progress: Arc<IndexingProgress>,
+    ) -> Self {
         let explorer = Arc::new(Explorer::new(store.clone()));
 
         IndexNodeService {
@@ -55,6 +65,8 @@ where
             graphql_runner,
             store,
             explorer,
+            mapping_profiler,
+            indexing_progress,
         }
     }
 
@@ -113,7 +125,13 @@ where
         let logger = self.logger.cheap_clone();
         let result = {
             let options = QueryExecutionOptions {
-                resolver: IndexNodeResolver::new(&logger, graphql_runner, store),
+                resolver: IndexNodeResolver::new(
+                    &logger,
+                    graphql_runner,
+                    store,
+                    self.mapping_profiler.cheap_clone(),
+                    self.indexing_progress.cheap_clone(),
+                ),
                 deadline: None,
                 max_first: std::u32::MAX,
                 max_skip: std::u32::MAX,
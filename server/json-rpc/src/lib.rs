@@ -3,20 +3,31 @@ extern crate jsonrpc_http_server;
 extern crate lazy_static;
 extern crate serde;
 
+use graph::components::store::StatusStore;
+use graph::data::graphql::IntoValue;
+use graph::data::subgraph::status;
 use graph::prelude::futures03::channel::{mpsc, oneshot};
 use graph::prelude::futures03::SinkExt;
 use graph::prelude::serde_json;
+use graph::prelude::tokio::{
+    self,
+    io::AsyncWriteExt,
+    net::{TcpListener, TcpStream},
+};
+use graph::prelude::web3::types::H256;
 use graph::prelude::{JsonRpcServer as JsonRpcServerTrait, *};
 use jsonrpc_http_server::{
-    jsonrpc_core::{self, Compatibility, IoHandler, Params, Value},
+    hyper,
+    jsonrpc_core::{self, Compatibility, MetaIoHandler, Metadata, Params, Value},
     RestApi, Server, ServerBuilder,
 };
 use lazy_static::lazy_static;
+use serde::de::DeserializeOwned;
 
 use std::collections::BTreeMap;
 use std::env;
 use std::io;
-use std::net::{Ipv4Addr, SocketAddrV4};
+use std::net::{IpAddr, Ipv4Addr, SocketAddr, SocketAddrV4};
 
 lazy_static! {
     static ref EXTERNAL_HTTP_BASE_URL: Option<String> = env::var_os("EXTERNAL_HTTP_BASE_URL")
@@ -29,6 +40,51 @@ const JSON_RPC_DEPLOY_ERROR: i64 = 0;
 const JSON_RPC_REMOVE_ERROR: i64 = 1;
 const JSON_RPC_CREATE_ERROR: i64 = 2;
 const JSON_RPC_REASSIGN_ERROR: i64 = 3;
+const JSON_RPC_PAUSE_ERROR: i64 = 4;
+const JSON_RPC_RESUME_ERROR: i64 = 5;
+const JSON_RPC_UNAUTHORIZED_ERROR: i64 = 6;
+const JSON_RPC_UNASSIGN_ERROR: i64 = 7;
+const JSON_RPC_UNFAIL_ERROR: i64 = 8;
+const JSON_RPC_REWIND_ERROR: i64 = 9;
+const JSON_RPC_INFO_ERROR: i64 = 10;
+const JSON_RPC_VERSIONS_ERROR: i64 = 11;
+
+/// The credentials that the caller of an admin method presented
+#[derive(Clone, Debug, Default)]
+struct Credentials {
+    /// The bearer token from the `Authorization` header
+    token: Option<String>,
+    /// The subject of the client certificate, as passed on by the proxy
+    /// that terminated TLS
+    subject: Option<String>,
+}
+
+impl Metadata for Credentials {}
+
+impl Credentials {
+    fn from_request(req: &hyper::Request<hyper::Body>, cert_header: Option<&str>) -> Self {
+        let header = |name: &str| {
+            req.headers()
+                .get(name)
+                .and_then(|value| value.to_str().ok())
+                .map(str::to_owned)
+        };
+        let token = header("authorization").and_then(|value| {
+            value
+                .strip_prefix("Bearer ")
+                .map(|token| token.trim().to_owned())
+        });
+        let subject = cert_header.and_then(header);
+        Credentials { token, subject }
+    }
+}
+
+/// Parameters of admin methods, with what we need to know about them for
+/// the audit log
+trait AuditParams: Debug {
+    /// The subgraph name or deployment the call is about
+    fn subgraph(&self) -> String;
+}
 
 #[derive(Debug, Deserialize)]
 struct SubgraphCreateParams {
@@ -53,8 +109,87 @@ struct SubgraphReassignParams {
     node_id: NodeId,
 }
 
+#[derive(Debug, Deserialize)]
+struct DeploymentParams {
+    ipfs_hash: SubgraphDeploymentId,
+}
+
+#[derive(Debug, Deserialize)]
+struct SubgraphRewindParams {
+    ipfs_hash: SubgraphDeploymentId,
+    block_hash: H256,
+    block_number: BlockNumber,
+}
+
+/// Look up deployments either by subgraph name or by deployment id
+#[derive(Debug, Deserialize)]
+struct SubgraphInfoParams {
+    name: Option<SubgraphName>,
+    ipfs_hash: Option<SubgraphDeploymentId>,
+}
+
+#[derive(Debug, Deserialize)]
+struct SubgraphVersionsParams {
+    name: SubgraphName,
+}
+
+impl AuditParams for SubgraphCreateParams {
+    fn subgraph(&self) -> String {
+        self.name.to_string()
+    }
+}
+
+impl AuditParams for SubgraphDeployParams {
+    fn subgraph(&self) -> String {
+        self.name.to_string()
+    }
+}
+
+impl AuditParams for SubgraphRemoveParams {
+    fn subgraph(&self) -> String {
+        self.name.to_string()
+    }
+}
+
+impl AuditParams for SubgraphReassignParams {
+    fn subgraph(&self) -> String {
+        self.ipfs_hash.to_string()
+    }
+}
+
+impl AuditParams for DeploymentParams {
+    fn subgraph(&self) -> String {
+        self.ipfs_hash.to_string()
+    }
+}
+
+impl AuditParams for SubgraphRewindParams {
+    fn subgraph(&self) -> String {
+        self.ipfs_hash.to_string()
+    }
+}
+
+impl AuditParams for SubgraphInfoParams {
+    fn subgraph(&self) -> String {
+        match (&self.name, &self.ipfs_hash) {
+            (Some(name), _) => name.to_string(),
+            (None, Some(ipfs_hash)) => ipfs_hash.to_string(),
+            (None, None) => String::new(),
+        }
+    }
+}
+
+impl AuditParams for SubgraphVersionsParams {
+    fn subgraph(&self) -> String {
+        self.name.to_string()
+    }
+}
+
 pub struct JsonRpcServer<R> {
     registrar: Arc<R>,
+    store: Arc<dyn SubgraphStore>,
+    status_store: Arc<dyn StatusStore>,
+    auth: AdminAuth,
     http_port: u16,
     ws_port: u16,
     node_id: NodeId,
@@ -62,6 +197,91 @@ pub struct JsonRpcServer<R> {
 }
 
 impl<R: SubgraphRegistrar> JsonRpcServer<R> {
+    /// Check that the caller is allowed to call `method`, run `handler`
+    /// and record the call in the audit log
+    async fn dispatch<P, F, Fut>(
+        self: Arc<Self>,
+        method: &'static str,
+        required: AdminRole,
+        credentials: Credentials,
+        params: Params,
+        handler: F,
+    ) -> Result<Value, jsonrpc_core::Error>
+    where
+        P: DeserializeOwned + AuditParams + Send,
+        F: FnOnce(Arc<Self>, P) -> Fut + Send,
+        Fut: std::future::Future<Output = Result<Value, jsonrpc_core::Error>> + Send,
+    {
+        let user = if self.auth.is_enabled() {
+            self.auth
+                .authenticate(credentials.token.as_deref(), credentials.subject.as_deref())
+                .cloned()
+        } else {
+            None
+        };
+        // Until the parameters are parsed, the audit log gets them as they
+        // were sent so that malformed and unauthorized calls are recorded, too
+        let mut action = AdminAction {
+            caller: user.as_ref().map(|user| user.name.clone()),
+            role: user.as_ref().map(|user| user.role),
+            method: method.to_owned(),
+            subgraph: None,
+            params: serde_json::to_string(&params).unwrap_or_default(),
+            outcome: AdminOutcome::Success,
+        };
+
+        if self.auth.is_enabled() {
+            let denied = match &user {
+                None => Some("authentication required".to_owned()),
+                Some(user) if !user.role.allows(required) => Some(format!(
+                    "user `{}` with role `{}` is not allowed to call {}",
+                    user.name, user.role, method
+                )),
+                Some(_) => None,
+            };
+            if let Some(message) = denied {
+                warn!(self.logger, "Denied admin request";
+                    "method" => method,
+                    "caller" => action.caller.as_deref().unwrap_or("unknown"),
+                    "reason" => &message);
+                action.outcome = AdminOutcome::Denied;
+                self.audit(action);
+                return Err(jsonrpc_core::Error {
+                    code: jsonrpc_core::ErrorCode::ServerError(JSON_RPC_UNAUTHORIZED_ERROR),
+                    message,
+                    data: None,
+                });
+            }
+        }
+
+        let params: P = match params.parse() {
+            Ok(params) => params,
+            Err(e) => {
+                action.outcome = AdminOutcome::Error(e.message.clone());
+                self.audit(action);
+                return Err(e);
+            }
+        };
+        action.subgraph = Some(params.subgraph());
+        action.params = format!("{:?}", params);
+
+        let result = handler(self.clone(), params).await;
+        if let Err(e) = &result {
+            action.outcome = AdminOutcome::Error(e.message.clone());
+        }
+        self.audit(action);
+        result
+    }
+
+    fn audit(&self, action: AdminAction) {
+        let method = action.method.clone();
+        if let Err(e) = self.store.record_admin_action(action) {
+            error!(self.logger, "Failed to record admin request in the audit log";
+                "method" => method,
+                "error" => e.to_string());
+        }
+    }
+
     /// Handler for the `subgraph_create` endpoint.
     async fn create_handler(
         &self,
@@ -151,6 +371,184 @@ impl<R: SubgraphRegistrar> JsonRpcServer<R> {
             )),
         }
     }
+
+    /// Handler for the `subgraph_pause` endpoint.
+    async fn pause_handler(&self, params: DeploymentParams) -> Result<Value, jsonrpc_core::Error> {
+        info!(&self.logger, "Received subgraph_pause request"; "params" => format!("{:?}", params));
+
+        match self
+            .registrar
+            .pause_subgraph(params.ipfs_hash.clone())
+            .await
+        {
+            Ok(_) => Ok(Value::Null),
+            Err(e) => Err(json_rpc_error(
+                &self.logger,
+                "subgraph_pause",
+                e,
+                JSON_RPC_PAUSE_ERROR,
+                params,
+            )),
+        }
+    }
+
+    /// Handler for the `subgraph_resume` endpoint.
+    async fn resume_handler(&self, params: DeploymentParams) -> Result<Value, jsonrpc_core::Error> {
+        info!(&self.logger, "Received subgraph_resume request"; "params" => format!("{:?}", params));
+
+        match self
+            .registrar
+            .resume_subgraph(params.ipfs_hash.clone())
+            .await
+        {
+            Ok(_) => Ok(Value::Null),
+            Err(e) => Err(json_rpc_error(
+                &self.logger,
+                "subgraph_resume",
+                e,
+                JSON_RPC_RESUME_ERROR,
+                params,
+            )),
+        }
+    }
+
+    /// Handler for the `subgraph_unassign` endpoint.
+    async fn unassign_handler(
+        &self,
+        params: DeploymentParams,
+    ) -> Result<Value, jsonrpc_core::Error> {
+        info!(&self.logger, "Received subgraph_unassign request"; "params" => format!("{:?}", params));
+
+        match self
+            .registrar
+            .unassign_subgraph(params.ipfs_hash.clone())
+            .await
+        {
+            Ok(_) => Ok(Value::Null),
+            Err(e) => Err(json_rpc_error(
+                &self.logger,
+                "subgraph_unassign",
+                e,
+                JSON_RPC_UNASSIGN_ERROR,
+                params,
+            )),
+        }
+    }
+
+    /// Handler for the `subgraph_unfail` endpoint.
+    async fn unfail_handler(&self, params: DeploymentParams) -> Result<Value, jsonrpc_core::Error> {
+        info!(&self.logger, "Received subgraph_unfail request"; "params" => format!("{:?}", params));
+
+        match self
+            .registrar
+            .unfail_subgraph(params.ipfs_hash.clone())
+            .await
+        {
+            Ok(_) => Ok(Value::Null),
+            Err(e) => Err(json_rpc_error(
+                &self.logger,
+                "subgraph_unfail",
+                e,
+                JSON_RPC_UNFAIL_ERROR,
+                params,
+            )),
+        }
+    }
+
+    /// Handler for the `subgraph_rewind` endpoint.
+    async fn rewind_handler(
+        &self,
+        params: SubgraphRewindParams,
+    ) -> Result<Value, jsonrpc_core::Error> {
+        info!(&self.logger, "Received subgraph_rewind request"; "params" => format!("{:?}", params));
+
+        let block_ptr = EthereumBlockPointer::from((params.block_hash, params.block_number));
+        match self
+            .registrar
+            .rewind_subgraph(params.ipfs_hash.clone(), block_ptr)
+            .await
+        {
+            Ok(_) => Ok(Value::Null),
+            Err(e) => Err(json_rpc_error(
+                &self.logger,
+                "subgraph_rewind",
+                e,
+                JSON_RPC_REWIND_ERROR,
+                params,
+            )),
+        }
+    }
+
+    /// Handler for the `subgraph_info` endpoint. Returns the indexing status
+    /// of the deployments of a subgraph, or of a single deployment
+    async fn info_handler(&self, params: SubgraphInfoParams) -> Result<Value, jsonrpc_core::Error> {
+        let filter = match (&params.name, &params.ipfs_hash) {
+            (Some(name), None) => status::Filter::SubgraphName(name.to_string()),
+            (None, Some(ipfs_hash)) => status::Filter::Deployments(vec![ipfs_hash.to_string()]),
+            _ => {
+                return Err(jsonrpc_core::Error::invalid_params(
+                    "exactly one of `name` and `ipfs_hash` must be given",
+                ))
+            }
+        };
+
+        match self.status_store.status(filter) {
+            Ok(infos) => Ok(Value::Array(
+                infos
+                    .into_iter()
+                    .map(|info| {
+                        serde_json::to_value(SerializableValue(&info.into_value()))
+                            .expect("invalid subgraph status")
+                    })
+                    .collect(),
+            )),
+            Err(e) => Err(json_rpc_error(
+                &self.logger,
+                "subgraph_info",
+                e.into(),
+                JSON_RPC_INFO_ERROR,
+                params,
+            )),
+        }
+    }
+
+    /// Handler for the `subgraph_versions` endpoint. Returns the
+    /// deployments of the current and pending version of a subgraph
+    async fn versions_handler(
+        &self,
+        params: SubgraphVersionsParams,
+    ) -> Result<Value, jsonrpc_core::Error> {
+        let versions = || -> Result<Value, SubgraphRegistrarError> {
+            if !self.store.subgraph_exists(&params.name)? {
+                return Err(SubgraphRegistrarError::NameNotFound(
+                    params.name.to_string(),
+                ));
+            }
+            let deployment = |current| -> Result<Option<String>, SubgraphRegistrarError> {
+                let filter = status::Filter::SubgraphVersion(params.name.to_string(), current);
+                Ok(self
+                    .status_store
+                    .status(filter)?
+                    .into_iter()
+                    .next()
+                    .map(|info| info.subgraph))
+            };
+            Ok(serde_json::json!({
+                "current": deployment(true)?,
+                "pending": deployment(false)?,
+            }))
+        };
+
+        versions().map_err(|e| {
+            json_rpc_error(
+                &self.logger,
+                "subgraph_versions",
+                e,
+                JSON_RPC_VERSIONS_ERROR,
+                &params,
+            )
+        })
+    }
 }
 
 impl<R> JsonRpcServerTrait<R> for JsonRpcServer<R>
@@ -164,6 +562,9 @@ where
         http_port: u16,
         ws_port: u16,
         registrar: Arc<R>,
+        store: Arc<dyn SubgraphStore>,
+        status_store: Arc<dyn StatusStore>,
+        auth: AdminAuth,
         node_id: NodeId,
         logger: Logger,
     ) -> Result<Self::Server, io::Error> {
@@ -174,12 +575,49 @@ where
             "Starting JSON-RPC admin server at: http://localhost:{}", port
         );
 
-        let addr = SocketAddrV4::new(Ipv4Addr::new(0, 0, 0, 0), port);
+        if !auth.is_enabled() {
+            warn!(
+                logger,
+                "The JSON-RPC admin server does not require authentication"
+            );
+        }
 
-        let mut handler = IoHandler::with_compatibility(Compatibility::Both);
+        // Anybody who can connect could claim any certificate subject with
+        // the certificate header. Only the trusted proxies may connect then,
+        // which the gate checks; without trusted proxies, only connections
+        // from this host are possible
+        let gate = match (&auth.client_cert_header, auth.trusted_proxies.is_empty()) {
+            (None, _) => None,
+            (Some(_), true) => {
+                info!(
+                    logger,
+                    "The JSON-RPC admin server only accepts connections from this host \
+                     since `client_cert_header` is set without `trusted_proxies`"
+                );
+                None
+            }
+            (Some(_), false) => Some(std::net::TcpListener::bind(SocketAddrV4::new(
+                Ipv4Addr::UNSPECIFIED,
+                port,
+            ))?),
+        };
+        let addr = match (&auth.client_cert_header, &gate) {
+            (None, _) => SocketAddrV4::new(Ipv4Addr::UNSPECIFIED, port),
+            (Some(_), None) => SocketAddrV4::new(Ipv4Addr::LOCALHOST, port),
+            // The gate forwards connections to the server on some free port
+            (Some(_), Some(_)) => SocketAddrV4::new(Ipv4Addr::LOCALHOST, 0),
+        };
+        let trusted_proxies = auth.trusted_proxies.clone();
+
+        let mut handler: MetaIoHandler<Credentials> =
+            MetaIoHandler::with_compatibility(Compatibility::Both);
+        let cert_header = auth.client_cert_header.clone();
 
         let arc_self = Arc::new(JsonRpcServer {
             registrar,
+            store,
+            status_store,
+            auth,
             http_port,
             ws_port,
             node_id,
@@ -215,75 +653,346 @@ where
             return_receiver.await.expect("`return_sender` dropped")
         }
 
+        let gate_logger = arc_self.logger.clone();
+
         let me = arc_self.clone();
         let sender = task_sender.clone();
-        handler.add_method("subgraph_create", move |params: Params| {
-            let me = me.clone();
-            Box::pin(tokio02_spawn(
-                sender.clone(),
-                async move {
-                    let params = params.parse()?;
-                    me.create_handler(params).await
-                }
-                .boxed(),
-            ))
-            .compat()
-        });
+        handler.add_method_with_meta(
+            "subgraph_create",
+            move |params: Params, credentials: Credentials| {
+                let me = me.clone();
+                Box::pin(tokio02_spawn(
+                    sender.clone(),
+                    me.dispatch(
+                        "subgraph_create",
+                        AdminRole::Deployer,
+                        credentials,
+                        params,
+                        |me, params: SubgraphCreateParams| async move {
+                            me.create_handler(params).await
+                        },
+                    )
+                    .boxed(),
+                ))
+                .compat()
+            },
+        );
 
         let me = arc_self.clone();
         let sender = task_sender.clone();
 
-        handler.add_method("subgraph_deploy", move |params: Params| {
-            let me = me.clone();
-            Box::pin(tokio02_spawn(
-                sender.clone(),
-                async move {
-                    let params = params.parse()?;
-                    me.deploy_handler(params).await
-                }
-                .boxed(),
-            ))
-            .compat()
-        });
+        handler.add_method_with_meta(
+            "subgraph_deploy",
+            move |params: Params, credentials: Credentials| {
+                let me = me.clone();
+                Box::pin(tokio02_spawn(
+                    sender.clone(),
+                    me.dispatch(
+                        "subgraph_deploy",
+                        AdminRole::Deployer,
+                        credentials,
+                        params,
+                        |me, params: SubgraphDeployParams| async move {
+                            me.deploy_handler(params).await
+                        },
+                    )
+                    .boxed(),
+                ))
+                .compat()
+            },
+        );
 
         let me = arc_self.clone();
         let sender = task_sender.clone();
-        handler.add_method("subgraph_remove", move |params: Params| {
-            let me = me.clone();
-            Box::pin(tokio02_spawn(
-                sender.clone(),
-                async move {
-                    let params = params.parse()?;
-                    me.remove_handler(params).await
-                }
-                .boxed(),
-            ))
-            .compat()
-        });
+        handler.add_method_with_meta(
+            "subgraph_remove",
+            move |params: Params, credentials: Credentials| {
+                let me = me.clone();
+                Box::pin(tokio02_spawn(
+                    sender.clone(),
+                    me.dispatch(
+                        "subgraph_remove",
+                        AdminRole::Deployer,
+                        credentials,
+                        params,
+                        |me, params: SubgraphRemoveParams| async move {
+                            me.remove_handler(params).await
+                        },
+                    )
+                    .boxed(),
+                ))
+                .compat()
+            },
+        );
 
         let me = arc_self.clone();
         let sender = task_sender.clone();
-        handler.add_method("subgraph_reassign", move |params: Params| {
-            let me = me.clone();
-            Box::pin(tokio02_spawn(
-                sender.clone(),
-                async move {
-                    let params = params.parse()?;
-                    me.reassign_handler(params).await
-                }
-                .boxed(),
-            ))
-            .compat()
-        });
+        handler.add_method_with_meta(
+            "subgraph_reassign",
+            move |params: Params, credentials: Credentials| {
+                let me = me.clone();
+                Box::pin(tokio02_spawn(
+                    sender.clone(),
+                    me.dispatch(
+                        "subgraph_reassign",
+                        AdminRole::Operator,
+                        credentials,
+                        params,
+                        |me, params: SubgraphReassignParams| async move {
+                            me.reassign_handler(params).await
+                        },
+                    )
+                    .boxed(),
+                ))
+                .compat()
+            },
+        );
+
+        let me = arc_self.clone();
+        let sender = task_sender.clone();
+        handler.add_method_with_meta(
+            "subgraph_pause",
+            move |params: Params, credentials: Credentials| {
+                let me = me.clone();
+                Box::pin(
+                    tokio02_spawn(
+                        sender.clone(),
+                        me.dispatch(
+                            "subgraph_pause",
+                            AdminRole::Operator,
+                            credentials,
+                            params,
+                            |me, params: DeploymentParams| async move {
+                                me.pause_handler(params).await
+                            },
+                        )
+                        .boxed(),
+                    ),
+                )
+                .compat()
+            },
+        );
+
+        let me = arc_self.clone();
+        let sender = task_sender.clone();
+        handler.add_method_with_meta(
+            "subgraph_resume",
+            move |params: Params, credentials: Credentials| {
+                let me = me.clone();
+                Box::pin(
+                    tokio02_spawn(
+                        sender.clone(),
+                        me.dispatch(
+                            "subgraph_resume",
+                            AdminRole::Operator,
+                            credentials,
+                            params,
+                            |me, params: DeploymentParams| async move {
+                                me.resume_handler(params).await
+                            },
+                        )
+                        .boxed(),
+                    ),
+                )
+                .compat()
+            },
+        );
+
+        let me = arc_self.clone();
+        let sender = task_sender.clone();
+        handler.add_method_with_meta(
+            "subgraph_unassign",
+            move |params: Params, credentials: Credentials| {
+                let me = me.clone();
+                Box::pin(
+                    tokio02_spawn(
+                        sender.clone(),
+                        me.dispatch(
+                            "subgraph_unassign",
+                            AdminRole::Operator,
+                            credentials,
+                            params,
+                            |me, params: DeploymentParams| async move {
+                                me.unassign_handler(params).await
+                            },
+                        )
+                        .boxed(),
+                    ),
+                )
+                .compat()
+            },
+        );
+
+        let me = arc_self.clone();
+        let sender = task_sender.clone();
+        handler.add_method_with_meta(
+            "subgraph_unfail",
+            move |params: Params, credentials: Credentials| {
+                let me = me.clone();
+                Box::pin(
+                    tokio02_spawn(
+                        sender.clone(),
+                        me.dispatch(
+                            "subgraph_unfail",
+                            AdminRole::Operator,
+                            credentials,
+                            params,
+                            |me, params: DeploymentParams| async move {
+                                me.unfail_handler(params).await
+                            },
+                        )
+                        .boxed(),
+                    ),
+                )
+                .compat()
+            },
+        );
+
+        let me = arc_self.clone();
+        let sender = task_sender.clone();
+        handler.add_method_with_meta(
+            "subgraph_rewind",
+            move |params: Params, credentials: Credentials| {
+                let me = me.clone();
+                Box::pin(tokio02_spawn(
+                    sender.clone(),
+                    me.dispatch(
+                        "subgraph_rewind",
+                        AdminRole::Operator,
+                        credentials,
+                        params,
+                        |me, params: SubgraphRewindParams| async move {
+                            me.rewind_handler(params).await
+                        },
+                    )
+                    .boxed(),
+                ))
+                .compat()
+            },
+        );
+
+        let me = arc_self.clone();
+        let sender = task_sender.clone();
+        handler.add_method_with_meta(
+            "subgraph_info",
+            move |params: Params, credentials: Credentials| {
+                let me = me.clone();
+                Box::pin(
+                    tokio02_spawn(
+                        sender.clone(),
+                        me.dispatch(
+                            "subgraph_info",
+                            AdminRole::ReadOnly,
+                            credentials,
+                            params,
+                            |me, params: SubgraphInfoParams| async move {
+                                me.info_handler(params).await
+                            },
+                        )
+                        .boxed(),
+                    ),
+                )
+                .compat()
+            },
+        );
+
+        let me = arc_self.clone();
+        let sender = task_sender.clone();
+        handler.add_method_with_meta(
+            "subgraph_versions",
+            move |params: Params, credentials: Credentials| {
+                let me = me.clone();
+                Box::pin(tokio02_spawn(
+                    sender.clone(),
+                    me.dispatch(
+                        "subgraph_versions",
+                        AdminRole::ReadOnly,
+                        credentials,
+                        params,
+                        |me, params: SubgraphVersionsParams| async move {
+                            me.versions_handler(params).await
+                        },
+                    )
+                    .boxed(),
+                ))
+                .compat()
+            },
+        );
 
-        ServerBuilder::new(handler)
-            // Enable REST API:
-            // POST /<method>/<param1>/<param2>
-            .rest_api(RestApi::Secure)
-            .start_http(&addr.into())
+        let server = ServerBuilder::with_meta_extractor(
+            handler,
+            move |req: &hyper::Request<hyper::Body>| {
+                Credentials::from_request(req, cert_header.as_deref())
+            },
+        )
+        // Enable REST API:
+        // POST /<method>/<param1>/<param2>
+        .rest_api(RestApi::Secure)
+        .start_http(&addr.into())?;
+
+        if let Some(gate) = gate {
+            gate.set_nonblocking(true)?;
+            let listener = TcpListener::from_std(gate)?;
+            let server_addr = *server.address();
+            graph::spawn(run_gate(
+                gate_logger,
+                listener,
+                trusted_proxies,
+                server_addr,
+            ));
+        }
+        Ok(server)
     }
 }
 
+/// Forward connections from `trusted_proxies` and from this host to the
+/// admin server at `server_addr` and close all others
+async fn run_gate(
+    logger: Logger,
+    mut listener: TcpListener,
+    trusted_proxies: Vec<IpAddr>,
+    server_addr: SocketAddr,
+) {
+    loop {
+        let (inbound, peer) = match listener.accept().await {
+            Ok(conn) => conn,
+            Err(e) => {
+                warn!(logger, "Failed to accept admin connection"; "error" => e.to_string());
+                continue;
+            }
+        };
+        if !peer.ip().is_loopback() && !trusted_proxies.contains(&peer.ip()) {
+            warn!(logger, "Refused admin connection from untrusted address";
+                "address" => peer.to_string());
+            continue;
+        }
+        let logger = logger.clone();
+        graph::spawn(async move {
+            if let Err(e) = forward(inbound, server_addr).await {
+                debug!(logger, "Admin connection failed";
+                    "address" => peer.to_string(),
+                    "error" => e.to_string());
+            }
+        });
+    }
+}
+
+async fn forward(mut inbound: TcpStream, server_addr: SocketAddr) -> io::Result<()> {
+    let mut outbound = TcpStream::connect(server_addr).await?;
+    let (mut from_client, mut to_client) = inbound.split();
+    let (mut from_server, mut to_server) = outbound.split();
+    let requests = async {
+        tokio::io::copy(&mut from_client, &mut to_server).await?;
+        to_server.shutdown().await
+    };
+    let responses = async {
+        tokio::io::copy(&mut from_server, &mut to_client).await?;
+        to_client.shutdown().await
+    };
+    futures03::future::try_join(requests, responses).await?;
+    Ok(())
+}
+
 fn json_rpc_error(
     logger: &Logger,
     operation: &str,
@@ -343,3 +1052,335 @@ fn subgraph_routes(name: &SubgraphName, http_port: u16, ws_port: u16) -> Value {
     );
     jsonrpc_core::to_value(map).unwrap()
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use graph::components::server::index_node::VersionInfo;
+    use graph::components::store::EntityVersionChange;
+    use graph::data::subgraph::schema::{HealthEvent, MappingLog, MappingLogLevel};
+    use graph::prelude::web3::types::Address;
+    use graph_mock::MemoryStore;
+    use jsonrpc_core::ErrorCode;
+
+    struct TestRegistrar;
+
+    #[async_trait]
+    impl SubgraphRegistrar for TestRegistrar {
+        async fn create_subgraph(
+            &self,
+            _: SubgraphName,
+        ) -> Result<CreateSubgraphResult, SubgraphRegistrarError> {
+            unimplemented!()
+        }
+
+        async fn create_subgraph_version(
+            &self,
+            _: SubgraphName,
+            _: SubgraphDeploymentId,
+            _: NodeId,
+        ) -> Result<(), SubgraphRegistrarError> {
+            unimplemented!()
+        }
+
+        async fn remove_subgraph(&self, _: SubgraphName) -> Result<(), SubgraphRegistrarError> {
+            unimplemented!()
+        }
+
+        async fn reassign_subgraph(
+            &self,
+            _: SubgraphDeploymentId,
+            _: NodeId,
+        ) -> Result<(), SubgraphRegistrarError> {
+            unimplemented!()
+        }
+
+        async fn pause_subgraph(
+            &self,
+            _: SubgraphDeploymentId,
+        ) -> Result<(), SubgraphRegistrarError> {
+            unimplemented!()
+        }
+
+        async fn resume_subgraph(
+            &self,
+            _: SubgraphDeploymentId,
+        ) -> Result<(), SubgraphRegistrarError> {
+            unimplemented!()
+        }
+
+        async fn unassign_subgraph(
+            &self,
+            _: SubgraphDeploymentId,
+        ) -> Result<(), SubgraphRegistrarError> {
+            unimplemented!()
+        }
+
+        async fn unfail_subgraph(
+            &self,
+            _: SubgraphDeploymentId,
+        ) -> Result<(), SubgraphRegistrarError> {
+            unimplemented!()
+        }
+
+        async fn rewind_subgraph(
+            &self,
+            _: SubgraphDeploymentId,
+            _: EthereumBlockPointer,
+        ) -> Result<(), SubgraphRegistrarError> {
+            unimplemented!()
+        }
+    }
+
+    struct TestStatusStore;
+
+    impl StatusStore for TestStatusStore {
+        fn status(&self, _: status::Filter) -> Result<Vec<status::Info>, StoreError> {
+            unimplemented!()
+        }
+
+        fn version_info(&self, _: &str) -> Result<VersionInfo, StoreError> {
+            unimplemented!()
+        }
+
+        fn versions_for_subgraph_id(
+            &self,
+            _: &str,
+        ) -> Result<(Option<String>, Option<String>), StoreError> {
+            unimplemented!()
+        }
+
+        fn supports_proof_of_indexing<'a>(
+            self: Arc<Self>,
+            _: &'a SubgraphDeploymentId,
+        ) -> DynTryFuture<'a, bool> {
+            unimplemented!()
+        }
+
+        fn get_proof_of_indexing<'a>(
+            self: Arc<Self>,
+            _: &'a SubgraphDeploymentId,
+            _: &'a Option<Address>,
+            _: EthereumBlockPointer,
+        ) -> DynTryFuture<'a, Option<[u8; 32]>> {
+            unimplemented!()
+        }
+
+        fn mapping_logs(
+            &self,
+            _: &SubgraphDeploymentId,
+            _: MappingLogLevel,
+            _: Option<BlockNumber>,
+            _: Option<BlockNumber>,
+        ) -> Result<Vec<MappingLog>, StoreError> {
+            unimplemented!()
+        }
+
+        fn dynamic_data_sources(
+            &self,
+            _: &SubgraphDeploymentId,
+        ) -> Result<Vec<status::DataSource>, StoreError> {
+            unimplemented!()
+        }
+
+        fn entity_changes_in_block(
+            &self,
+            _: &SubgraphDeploymentId,
+            _: BlockNumber,
+        ) -> Result<Vec<EntityVersionChange>, StoreError> {
+            unimplemented!()
+        }
+
+        fn health_history(
+            &self,
+            _: &SubgraphDeploymentId,
+            _: i64,
+        ) -> Result<Vec<HealthEvent>, StoreError> {
+            unimplemented!()
+        }
+    }
+
+    fn server(store: Arc<MemoryStore>) -> Arc<JsonRpcServer<TestRegistrar>> {
+        let auth = AdminAuth {
+            users: vec![AdminUser {
+                name: "ci".to_string(),
+                role: AdminRole::Deployer,
+                token: Some("s3cr3t".to_string()),
+                subject: None,
+            }],
+            client_cert_header: None,
+            trusted_proxies: vec![],
+        };
+        Arc::new(JsonRpcServer {
+            registrar: Arc::new(TestRegistrar),
+            store,
+            status_store: Arc::new(TestStatusStore),
+            auth,
+            http_port: 8000,
+            ws_port: 8001,
+            node_id: NodeId::new("test").unwrap(),
+            logger: Logger::root(slog::Discard, o!()),
+        })
+    }
+
+    fn memory_store() -> Arc<MemoryStore> {
+        let id = SubgraphDeploymentId::new("test").unwrap();
+        let schema = Schema::parse("type Thing @entity { id: ID! }", id).unwrap();
+        Arc::new(MemoryStore::new(Arc::new(schema)))
+    }
+
+    fn credentials(token: Option<&str>) -> Credentials {
+        Credentials {
+            token: token.map(str::to_owned),
+            subject: None,
+        }
+    }
+
+    fn params(name: &str) -> Params {
+        Params::Map(
+            vec![("name".to_owned(), Value::String(name.to_owned()))]
+                .into_iter()
+                .collect(),
+        )
+    }
+
+    /// Call `subgraph_remove` with a handler that fails for the subgraph
+    /// `broken` and return the result and whether the handler ran
+    async fn remove(
+        server: Arc<JsonRpcServer<TestRegistrar>>,
+        required: AdminRole,
+        credentials: Credentials,
+        params: Params,
+    ) -> (Result<Value, jsonrpc_core::Error>, bool) {
+        let called = Arc::new(std::sync::atomic::AtomicBool::new(false));
+        let handler_called = called.clone();
+        let result = server
+            .dispatch(
+                "subgraph_remove",
+                required,
+                credentials,
+                params,
+                |_, params: SubgraphRemoveParams| async move {
+                    handler_called.store(true, std::sync::atomic::Ordering::SeqCst);
+                    if params.name.as_str() == "broken" {
+                        Err(jsonrpc_core::Error::internal_error())
+                    } else {
+                        Ok(Value::Null)
+                    }
+                },
+            )
+            .await;
+        (result, called.load(std::sync::atomic::Ordering::SeqCst))
+    }
+
+    #[tokio::test]
+    async fn dispatch_audits_successful_and_failed_calls() {
+        let store = memory_store();
+
+        let (result, called) = remove(
+            server(store.clone()),
+            AdminRole::Deployer,
+            credentials(Some("s3cr3t")),
+            params("subgraph"),
+        )
+        .await;
+        assert_eq!(Ok(Value::Null), result);
+        assert!(called);
+
+        let (result, called) = remove(
+            server(store.clone()),
+            AdminRole::Deployer,
+            credentials(Some("s3cr3t")),
+            params("broken"),
+        )
+        .await;
+        assert!(result.is_err());
+        assert!(called);
+
+        let actions = store.admin_actions();
+        assert_eq!(2, actions.len());
+        assert_eq!(Some("ci".to_string()), actions[0].caller);
+        assert_eq!(Some(AdminRole::Deployer), actions[0].role);
+        assert_eq!("subgraph_remove", actions[0].method);
+        assert_eq!(Some("subgraph".to_string()), actions[0].subgraph);
+        assert_eq!(AdminOutcome::Success, actions[0].outcome);
+        assert_eq!(Some("broken".to_string()), actions[1].subgraph);
+        assert_eq!(
+            AdminOutcome::Error("Internal error".to_string()),
+            actions[1].outcome
+        );
+    }
+
+    #[tokio::test]
+    async fn dispatch_denies_before_parsing_params() {
+        let store = memory_store();
+
+        // Unauthenticated callers are denied, even with malformed params
+        for token in &[None, Some("wrong")] {
+            let (result, called) = remove(
+                server(store.clone()),
+                AdminRole::Deployer,
+                credentials(*token),
+                Params::Array(vec![Value::Bool(true)]),
+            )
+            .await;
+            assert_eq!(
+                ErrorCode::ServerError(JSON_RPC_UNAUTHORIZED_ERROR),
+                result.unwrap_err().code
+            );
+            assert!(!called);
+        }
+
+        // Authenticated callers without the required role are denied
+        let (result, called) = remove(
+            server(store.clone()),
+            AdminRole::Operator,
+            credentials(Some("s3cr3t")),
+            params("subgraph"),
+        )
+        .await;
+        assert_eq!(
+            ErrorCode::ServerError(JSON_RPC_UNAUTHORIZED_ERROR),
+            result.unwrap_err().code
+        );
+        assert!(!called);
+
+        let actions = store.admin_actions();
+        assert_eq!(3, actions.len());
+        assert!(actions
+            .iter()
+            .all(|action| action.outcome == AdminOutcome::Denied));
+        assert_eq!(None, actions[0].caller);
+        assert_eq!(None, actions[0].subgraph);
+        assert_eq!("[true]", actions[0].params);
+        assert_eq!(None, actions[1].caller);
+        assert_eq!(Some("ci".to_string()), actions[2].caller);
+        assert_eq!(None, actions[2].subgraph);
+        assert_eq!(r#"{"name":"subgraph"}"#, actions[2].params);
+    }
+
+    #[tokio::test]
+    async fn dispatch_audits_malformed_params() {
+        let store = memory_store();
+
+        let (result, called) = remove(
+            server(store.clone()),
+            AdminRole::Deployer,
+            credentials(Some("s3cr3t")),
+            Params::Array(vec![Value::Bool(true)]),
+        )
+        .await;
+        assert_eq!(ErrorCode::InvalidParams, result.unwrap_err().code);
+        assert!(!called);
+
+        let actions = store.admin_actions();
+        assert_eq!(1, actions.len());
+        assert_eq!(Some("ci".to_string()), actions[0].caller);
+        assert_eq!(None, actions[0].subgraph);
+        assert_eq!("[true]", actions[0].params);
+        match &actions[0].outcome {
+            AdminOutcome::Error(_) => (),
+            outcome => panic!("unexpected outcome {:?}", outcome),
+        }
+    }
+}
@@ -0,0 +1 @@
+drop table subgraphs.mapping_log;
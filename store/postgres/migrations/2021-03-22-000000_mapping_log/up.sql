@@ -0,0 +1,14 @@
+create table subgraphs.mapping_log (
+    vid          bigserial primary key,
+    subgraph_id  text not null
+                 references subgraphs.subgraph_deployment(id) on delete cascade,
+    block_number int not null,
+    data_source  text not null,
+    handler      text,
+    level        text not null,
+    message      text not null,
+    created_at   timestamptz not null default now()
+);
+
+create index mapping_log_subgraph_id_block_number
+    on subgraphs.mapping_log(subgraph_id, block_number);
@@ -0,0 +1,2 @@
+alter table subgraphs.dynamic_ethereum_contract_data_source
+  drop column trigger_count;
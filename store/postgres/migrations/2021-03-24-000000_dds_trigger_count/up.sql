@@ -0,0 +1,2 @@
+alter table subgraphs.dynamic_ethereum_contract_data_source
+  add column trigger_count int8 not null default 0;
@@ -0,0 +1 @@
+drop table subgraphs.subgraph_restart;
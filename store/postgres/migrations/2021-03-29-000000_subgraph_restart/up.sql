@@ -0,0 +1,15 @@
+create table subgraphs.subgraph_restart (
+    vid          bigserial primary key,
+    subgraph_id  text not null
+                 references subgraphs.subgraph_deployment(id) on delete cascade,
+    attempt      int not null,
+    message      text not null,
+    block_hash   bytea,
+    block_number int,
+    failed_at    timestamptz not null default now(),
+    restart_at   timestamptz,
+    recovered    boolean not null default false
+);
+
+create index subgraph_restart_subgraph_id
+    on subgraphs.subgraph_restart(subgraph_id, vid);
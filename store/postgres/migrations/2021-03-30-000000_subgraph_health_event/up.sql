@@ -0,0 +1,14 @@
+create table subgraphs.subgraph_health_event (
+    vid          bigserial primary key,
+    subgraph_id  text not null
+                 references subgraphs.subgraph_deployment(id) on delete cascade,
+    kind         text not null,
+    health       text,
+    message      text,
+    block_hash   bytea,
+    block_number int,
+    created_at   timestamptz not null default now()
+);
+
+create index subgraph_health_event_subgraph_id
+    on subgraphs.subgraph_health_event(subgraph_id, vid);
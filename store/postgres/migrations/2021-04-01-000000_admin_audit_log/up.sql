@@ -0,0 +1,17 @@
+create table admin_audit_log (
+    id         bigserial primary key,
+    -- The user that made the call; null if the admin API does not require
+    -- authentication or the caller could not be authenticated
+    caller     text,
+    role       text,
+    method     text not null,
+    subgraph   text,
+    params     text not null,
+    -- One of 'success', 'denied', or 'error'
+    outcome    text not null,
+    error      text,
+    created_at timestamptz not null default now()
+);
+
+create index admin_audit_log_subgraph
+    on admin_audit_log(subgraph, id);
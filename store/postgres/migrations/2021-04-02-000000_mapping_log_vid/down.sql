@@ -0,0 +1 @@
+drop index subgraphs.mapping_log_subgraph_id_vid;
@@ -0,0 +1,3 @@
+-- Trimming the messages of a deployment looks up its newest messages by vid
+create index mapping_log_subgraph_id_vid
+    on subgraphs.mapping_log(subgraph_id, vid);
@@ -0,0 +1,2 @@
+drop table subgraphs.poi_event;
+drop table subgraphs.poi_event_request;
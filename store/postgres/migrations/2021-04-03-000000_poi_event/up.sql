@@ -0,0 +1,20 @@
+-- Requests to record the proof of indexing events of a deployment when it
+-- processes a block
+create table subgraphs.poi_event_request (
+    subgraph_id  text primary key
+                 references subgraphs.subgraph_deployment(id) on delete cascade,
+    block_number int not null
+);
+
+-- The proof of indexing events that were recorded for a block
+create table subgraphs.poi_event (
+    vid              bigserial primary key,
+    subgraph_id      text not null
+                     references subgraphs.subgraph_deployment(id) on delete cascade,
+    block_number     int not null,
+    causality_region text not null,
+    event            text not null
+);
+
+create index poi_event_subgraph_id_block_number
+    on subgraphs.poi_event(subgraph_id, block_number);
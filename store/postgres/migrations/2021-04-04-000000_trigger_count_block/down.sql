@@ -0,0 +1 @@
+drop table subgraphs.dynamic_ethereum_contract_data_source_trigger_count;
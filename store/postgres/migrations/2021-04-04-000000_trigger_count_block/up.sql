@@ -0,0 +1,15 @@
+-- The number of triggers dynamic data sources handled in recent blocks.
+-- They are kept per block so that they can be reverted, and added to
+-- `trigger_count` once the block can not be reverted anymore
+create table subgraphs.dynamic_ethereum_contract_data_source_trigger_count(
+  vid            bigserial primary key,
+  deployment     text not null,
+  name           text not null,
+  address        bytea not null,
+  creation_block int not null,
+  block_number   int not null,
+  count          int8 not null
+);
+
+create index dynamic_ethereum_contract_data_source_trigger_count_block
+    on subgraphs.dynamic_ethereum_contract_data_source_trigger_count(deployment, block_number);
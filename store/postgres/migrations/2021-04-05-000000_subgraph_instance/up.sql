@@ -0,0 +1,11 @@
+-- The nodes that run an instance of a deployment, i.e., that might still
+-- write to it. A node adds its entry when it starts indexing a deployment
+-- and removes it once it has stopped
+create table subgraphs.subgraph_instance (
+    id         text primary key,
+    node_id    text not null,
+    started_at timestamptz not null default now()
+);
+
+create index subgraph_instance_node_id
+    on subgraphs.subgraph_instance(node_id);
@@ -5,7 +5,7 @@ use diesel::result::QueryResult;
 use diesel::serialize::{Output, ToSql};
 use diesel::sql_types::{Integer, Range};
 use std::io::Write;
-use std::ops::{Bound, RangeBounds, RangeFrom};
+use std::ops::{Bound, Range as StdRange, RangeBounds, RangeFrom};
 
 use graph::prelude::{BlockNumber, EthereumBlockPointer, BLOCK_NUMBER_MAX};
 
@@ -80,6 +80,15 @@ impl From<RangeFrom<BlockNumber>> for BlockRange {
     }
 }
 
+impl From<StdRange<BlockNumber>> for BlockRange {
+    fn from(range: StdRange<BlockNumber>) -> BlockRange {
+        BlockRange(
+            clone_bound(range.start_bound()),
+            clone_bound(range.end_bound()),
+        )
+    }
+}
+
 impl ToSql<Range<Integer>, Pg> for BlockRange {
     fn to_sql<W: Write>(&self, out: &mut Output<W, Pg>) -> diesel::serialize::Result {
         let pair = (self.0, self.1);
@@ -1,5 +1,5 @@
 use diesel::prelude::RunQueryDsl;
-use diesel::sql_types::Text;
+use diesel::sql_types::{BigInt, Bool, Text};
 use diesel::{pg::PgConnection, sql_query};
 use std::collections::{HashMap, HashSet};
 
@@ -103,3 +103,160 @@ pub fn current_servers(conn: &PgConnection) -> Result<Vec<String>, StoreError> {
         .map(|srv| srv.srvname)
         .collect())
 }
+
+/// Return the names of all indexes in the database schema `namespace`
+pub fn index_names(conn: &PgConnection, namespace: &Namespace) -> Result<Vec<String>, StoreError> {
+    #[derive(QueryableByName)]
+    struct IndexName {
+        #[sql_type = "Text"]
+        indexname: String,
+    }
+
+    Ok(
+        sql_query("select indexname from pg_indexes where schemaname = $1")
+            .bind::<Text, _>(namespace.as_str())
+            .get_results::<IndexName>(conn)?
+            .into_iter()
+            .map(|index| index.indexname)
+            .collect(),
+    )
+}
+
+/// Return the definitions of all indexes on `table` in the database schema
+/// `namespace` as `(name, definition)` pairs
+pub fn indexes_for_table(
+    conn: &PgConnection,
+    namespace: &Namespace,
+    table: &SqlName,
+) -> Result<Vec<(String, String)>, StoreError> {
+    #[derive(QueryableByName)]
+    struct IndexDef {
+        #[sql_type = "Text"]
+        indexname: String,
+        #[sql_type = "Text"]
+        indexdef: String,
+    }
+
+    let query = "
+        select indexname, indexdef
+          from pg_indexes
+         where schemaname = $1 and tablename = $2
+         order by indexname";
+    Ok(sql_query(query)
+        .bind::<Text, _>(namespace.as_str())
+        .bind::<Text, _>(table.as_str())
+        .get_results::<IndexDef>(conn)?
+        .into_iter()
+        .map(|index| (index.indexname, index.indexdef))
+        .collect())
+}
+
+/// Size and usage information for one index
+#[derive(Clone, Debug)]
+pub struct IndexStats {
+    pub name: String,
+    /// The size of the index on disk, in bytes
+    pub bytes: i64,
+    /// How often the index was used since statistics were last reset
+    pub scans: i64,
+    /// Whether the index enforces a constraint like a primary key or an
+    /// exclusion constraint. Such indexes can not be dropped even if they
+    /// are never used for queries
+    pub is_constraint: bool,
+}
+
+/// Size and usage information for one table
+#[derive(Clone, Debug)]
+pub struct TableStats {
+    pub name: String,
+    /// The size of the table itself on disk, in bytes
+    pub table_bytes: i64,
+    /// The size of all the table's indexes on disk, in bytes
+    pub index_bytes: i64,
+    /// The number of rows that were deleted or updated but whose space
+    /// has not been reclaimed by vacuum yet
+    pub dead_tuples: i64,
+    pub indexes: Vec<IndexStats>,
+}
+
+/// Return size and index usage information for all tables in the database
+/// schema `namespace`, ordered by table name
+pub fn table_stats(
+    conn: &PgConnection,
+    namespace: &Namespace,
+) -> Result<Vec<TableStats>, StoreError> {
+    #[derive(QueryableByName)]
+    struct TableRow {
+        #[sql_type = "Text"]
+        name: String,
+        #[sql_type = "BigInt"]
+        table_bytes: i64,
+        #[sql_type = "BigInt"]
+        index_bytes: i64,
+        #[sql_type = "BigInt"]
+        dead_tuples: i64,
+    }
+
+    #[derive(QueryableByName)]
+    struct IndexRow {
+        #[sql_type = "Text"]
+        table_name: String,
+        #[sql_type = "Text"]
+        name: String,
+        #[sql_type = "BigInt"]
+        bytes: i64,
+        #[sql_type = "BigInt"]
+        scans: i64,
+        #[sql_type = "Bool"]
+        is_constraint: bool,
+    }
+
+    let query = "
+        select s.relname as name,
+               pg_table_size(s.relid)::int8 as table_bytes,
+               pg_indexes_size(s.relid)::int8 as index_bytes,
+               coalesce(s.n_dead_tup, 0)::int8 as dead_tuples
+          from pg_stat_user_tables s
+         where s.schemaname = $1
+         order by s.relname";
+    let tables = sql_query(query)
+        .bind::<Text, _>(namespace.as_str())
+        .get_results::<TableRow>(conn)?;
+
+    let query = "
+        select s.relname as table_name,
+               s.indexrelname as name,
+               pg_relation_size(s.indexrelid)::int8 as bytes,
+               coalesce(s.idx_scan, 0)::int8 as scans,
+               exists (select 1 from pg_constraint c
+                        where c.conindid = s.indexrelid) as is_constraint
+          from pg_stat_user_indexes s
+         where s.schemaname = $1
+         order by s.indexrelname";
+    let mut indexes: HashMap<String, Vec<IndexStats>> = HashMap::new();
+    for index in sql_query(query)
+        .bind::<Text, _>(namespace.as_str())
+        .get_results::<IndexRow>(conn)?
+    {
+        indexes
+            .entry(index.table_name)
+            .or_default()
+            .push(IndexStats {
+                name: index.name,
+                bytes: index.bytes,
+                scans: index.scans,
+                is_constraint: index.is_constraint,
+            });
+    }
+
+    Ok(tables
+        .into_iter()
+        .map(|table| TableStats {
+            indexes: indexes.remove(&table.name).unwrap_or_default(),
+            name: table.name,
+            table_bytes: table.table_bytes,
+            index_bytes: table.index_bytes,
+            dead_tuples: table.dead_tuples,
+        })
+        .collect())
+}
@@ -156,6 +156,23 @@ impl ForeignServer {
     }
 }
 
+/// Whether a pool connects to the main database of a shard or to one of
+/// its read replicas
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum PoolName {
+    Main,
+    Replica(String),
+}
+
+impl PoolName {
+    pub fn as_str(&self) -> &str {
+        match self {
+            PoolName::Main => "main",
+            PoolName::Replica(name) => name,
+        }
+    }
+}
+
 #[derive(Clone)]
 pub struct ConnectionPool {
     logger: Logger,
@@ -164,6 +181,8 @@ pub struct ConnectionPool {
     limiter: Arc<Semaphore>,
     postgres_url: String,
     pub(crate) wait_stats: PoolWaitStats,
+    /// Only read replicas report their replication lag
+    lag_gauge: Option<Gauge>,
 }
 
 struct ErrorHandler(Logger, Counter);
@@ -264,7 +283,7 @@ impl std::ops::Deref for ConnectionPool {
 impl ConnectionPool {
     pub fn create(
         shard_name: &str,
-        pool_name: &str,
+        pool_name: PoolName,
         postgres_url: String,
         pool_size: u32,
         logger: &Logger,
@@ -274,7 +293,7 @@ impl ConnectionPool {
         let logger_pool = logger.new(o!("component" => "ConnectionPool"));
         let const_labels = {
             let mut map = HashMap::new();
-            map.insert("pool".to_owned(), pool_name.to_owned());
+            map.insert("pool".to_owned(), pool_name.as_str().to_owned());
             map.insert("shard".to_string(), shard_name.to_owned());
             map
         };
@@ -289,10 +308,22 @@ impl ConnectionPool {
         let wait_stats = Arc::new(RwLock::new(MovingStats::default()));
         let event_handler = Box::new(EventHandler::new(
             logger_pool.clone(),
-            registry,
+            registry.clone(),
             wait_stats.clone(),
-            const_labels,
+            const_labels.clone(),
         ));
+        let lag_gauge = match pool_name {
+            PoolName::Main => None,
+            PoolName::Replica(_) => Some(
+                registry
+                    .global_gauge(
+                        "store_replication_lag_seconds",
+                        "How far the database is behind the server it replicates from",
+                        const_labels,
+                    )
+                    .expect("failed to create `store_replication_lag_seconds` gauge"),
+            ),
+        };
 
         // Connect to Postgres
         let conn_manager = ConnectionManager::new(postgres_url.clone());
@@ -326,6 +357,7 @@ impl ConnectionPool {
             pool,
             limiter,
             wait_stats,
+            lag_gauge,
         }
     }
 
@@ -417,6 +449,34 @@ impl ConnectionPool {
         }
     }
 
+    /// Measure how far the database behind this pool is behind the server
+    /// it replicates from, and, for read replicas, record that in the
+    /// `store_replication_lag_seconds` gauge. A replica that has replayed everything it received is not
+    /// lagging, even if the last transaction it replayed is old. For a
+    /// database that is not a replica, the lag is always zero
+    pub(crate) async fn replication_lag(&self) -> Result<Duration, StoreError> {
+        #[derive(QueryableByName)]
+        struct Lag {
+            #[sql_type = "diesel::sql_types::Double"]
+            lag: f64,
+        }
+
+        let query = "select (case \
+                        when not pg_is_in_recovery() then 0 \
+                        when pg_last_wal_receive_lsn() = pg_last_wal_replay_lsn() then 0 \
+                        else coalesce(extract(epoch from \
+                               now() - pg_last_xact_replay_timestamp()), 0) \
+                      end)::float8 as lag";
+        let lag = self
+            .with_conn(move |conn, _| Ok(sql_query(query).get_result::<Lag>(conn)?.lag))
+            .await?
+            .max(0.0);
+        if let Some(gauge) = &self.lag_gauge {
+            gauge.set(lag);
+        }
+        Ok(Duration::from_secs_f64(lag))
+    }
+
     pub fn get_with_timeout_warning(
         &self,
         logger: &Logger,
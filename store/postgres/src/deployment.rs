@@ -16,12 +16,16 @@ use graph::prelude::{
     anyhow, bigdecimal::ToPrimitive, hex, web3::types::H256, BigDecimal, BlockNumber,
     DeploymentState, EthereumBlockPointer, Schema, StoreError, SubgraphDeploymentId,
 };
-use graph::{data::subgraph::schema::SubgraphError, prelude::SubgraphDeploymentEntity};
+use graph::{
+    data::subgraph::schema::{HealthEventKind, SubgraphError},
+    prelude::SubgraphDeploymentEntity,
+};
 use stable_hash::crypto::SetHasher;
 use std::str::FromStr;
 use std::{collections::BTreeSet, convert::TryFrom, ops::Bound};
 
 use crate::block_range::{BLOCK_RANGE_COLUMN, UNVERSIONED_RANGE};
+use crate::health_history;
 use graph::constraint_violation;
 
 #[derive(DbEnum, Debug, Clone, Copy)]
@@ -272,6 +276,27 @@ pub fn block_ptr(
     Ok(ptr)
 }
 
+/// The block before the first block that the deployment indexes, or
+/// `None` if it starts with the genesis block
+pub fn earliest_block_ptr(
+    conn: &PgConnection,
+    id: &SubgraphDeploymentId,
+) -> Result<Option<EthereumBlockPointer>, StoreError> {
+    use subgraph_deployment as d;
+
+    let (number, hash) = d::table
+        .filter(d::id.eq(id.as_str()))
+        .select((
+            d::earliest_ethereum_block_number,
+            d::earliest_ethereum_block_hash,
+        ))
+        .first::<(Option<BigDecimal>, Option<Vec<u8>>)>(conn)?;
+
+    let ptr = crate::detail::block(id.as_str(), "earliest_ethereum_block", hash, number)?
+        .map(|block| block.to_ptr());
+    Ok(ptr)
+}
+
 fn convert_to_u32(number: Option<i32>, field: &str, subgraph: &str) -> Result<u32, StoreError> {
     number
         .ok_or_else(|| constraint_violation!("missing {} for subgraph `{}`", field, subgraph))
@@ -429,6 +454,8 @@ pub fn fail(
 ) -> Result<(), StoreError> {
     use subgraph_deployment as d;
 
+    let message = error.message.clone();
+    let block_ptr = error.block_ptr.clone();
     let error_id = insert_subgraph_error(conn, error)?;
     update(d::table.filter(d::id.eq(id.as_str())))
         .set((
@@ -437,7 +464,15 @@ pub fn fail(
             d::fatal_error.eq(Some(error_id)),
         ))
         .execute(conn)?;
-    Ok(())
+    health_history::record(
+        conn,
+        id,
+        HealthEventKind::HealthChange,
+        Some(SubgraphHealth::Failed.into()),
+        Some(&message),
+        block_ptr.as_ref().map(|ptr| ptr.number),
+        block_ptr.as_ref().map(|ptr| ptr.hash_slice()),
+    )
 }
 
 /// If `block` is `None`, assumes the latest block.
@@ -507,7 +542,15 @@ pub fn unfail(conn: &PgConnection, id: &SubgraphDeploymentId) -> Result<(), Stor
     // Delete the fatal error.
     delete(e::table.filter(e::id.eq(fatal_error_id))).execute(conn)?;
 
-    Ok(())
+    health_history::record(
+        conn,
+        id,
+        HealthEventKind::Unfail,
+        Some(prev_health.into()),
+        None,
+        None,
+        None,
+    )
 }
 
 /// Insert the errors and check if the subgraph needs to be set as unhealthy.
@@ -553,15 +596,26 @@ fn check_health(
         false => (SubgraphHealth::Healthy, SubgraphHealth::Unhealthy),
     };
 
-    update(
+    let changed = update(
         d::table
             .filter(d::id.eq(id.as_str()))
             .filter(d::health.eq(old)),
     )
     .set(d::health.eq(new))
-    .execute(conn)
-    .map(|_| ())
-    .map_err(|e| e.into())
+    .execute(conn)?;
+
+    if changed > 0 {
+        health_history::record(
+            conn,
+            id,
+            HealthEventKind::HealthChange,
+            Some(new.into()),
+            None,
+            Some(block),
+            None,
+        )?;
+    }
+    Ok(())
 }
 
 /// Reverts the errors and updates the subgraph health if necessary.
@@ -601,8 +655,8 @@ pub fn drop_metadata(conn: &PgConnection, id: &SubgraphDeploymentId) -> Result<(
     use subgraph_deployment as d;
     use subgraph_manifest as m;
 
-    // We don't need to delete from subgraph_error since that cascades from
-    // deleting the subgraph_deployment
+    // We don't need to delete from subgraph_error and mapping_log since that
+    // cascades from deleting the subgraph_deployment
     let manifest: String = delete(d::table.filter(d::id.eq(id.as_str())))
         .returning(d::manifest)
         .get_result(conn)?;
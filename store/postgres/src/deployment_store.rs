@@ -4,7 +4,7 @@ use diesel::pg::PgConnection;
 use diesel::prelude::*;
 use diesel::r2d2::{ConnectionManager, PooledConnection};
 use futures03::FutureExt as _;
-use graph::components::store::{EntityType, StoredDynamicDataSource};
+use graph::components::store::{EntityType, EntityVersionChange, StoredDynamicDataSource};
 use graph::data::subgraph::status;
 use graph::prelude::{
     CancelHandle, CancelToken, CancelableError, PoolWaitStats, SubgraphDeploymentEntity,
@@ -13,30 +13,56 @@ use lru_time_cache::LruCache;
 use rand::{seq::SliceRandom, thread_rng};
 use std::collections::{BTreeMap, HashMap};
 use std::convert::TryInto;
+use std::env;
 use std::iter::FromIterator;
 use std::ops::Deref;
-use std::sync::{atomic::AtomicUsize, Arc, Mutex};
-use std::time::Instant;
+use std::sync::{
+    atomic::{AtomicU64, AtomicUsize},
+    Arc, Mutex,
+};
+use std::time::{Duration, Instant};
 
 use graph::components::store::EntityCollection;
 use graph::components::subgraph::ProofOfIndexingFinisher;
-use graph::data::subgraph::schema::{SubgraphError, POI_OBJECT};
+use graph::data::subgraph::schema::{
+    HealthEvent, HealthEventKind, MappingLog, MappingLogLevel, SubgraphError, SubgraphRestart,
+    POI_OBJECT,
+};
 use graph::prelude::{
-    anyhow, debug, futures03, info, o, web3, ApiSchema, BlockNumber, CheapClone, DeploymentState,
-    DynTryFuture, Entity, EntityKey, EntityModification, EntityQuery, EntityRange, Error,
-    EthereumBlockPointer, Logger, QueryExecutionError, Schema, StopwatchMetrics, StoreError,
+    anyhow, debug, futures03, info, lazy_static, o, warn, web3, ApiSchema, BlockNumber, CheapClone,
+    DeploymentState, DerivedEntityQuery, DynTryFuture, Entity, EntityKey, EntityQuery, EntityRange,
+    Error, EthereumBlockPointer, Logger, QueryExecutionError, Schema, StopwatchMetrics, StoreError,
     StoreEvent, SubgraphDeploymentId, Value, BLOCK_NUMBER_MAX,
 };
 
 use graph_graphql::prelude::api_schema;
 use web3::types::Address;
 
-use crate::block_range::block_number;
+use crate::block_range::BlockRange;
+use crate::catalog::TableStats;
 use crate::deployment;
-use crate::relational::{Catalog, Layout};
+use crate::mapping_log::{self, MAPPING_LOG_LIMIT};
+use crate::poi_event;
+use crate::relational::{Catalog, Layout, VersionCount};
 use crate::relational_queries::FromEntityData;
+use crate::write_buffer::{merge_modifications, BlockChanges, TableChanges};
 use crate::{connection_pool::ConnectionPool, detail};
-use crate::{dynds, primary::Site};
+use crate::{dynds, health_history, primary::Site, restart};
+
+lazy_static! {
+    /// Read replicas that are further behind the main database than this
+    /// are not used for queries until they have caught up again
+    static ref REPLICA_MAX_LAG: Duration = Duration::from_secs(
+        env::var("GRAPH_STORE_REPLICA_MAX_LAG")
+            .ok()
+            .map(|s| {
+                s.parse::<u64>().unwrap_or_else(|_| {
+                    panic!("GRAPH_STORE_REPLICA_MAX_LAG must be a number, but is `{}`", s)
+                })
+            })
+            .unwrap_or(30)
+    );
+}
 
 /// When connected to read replicas, this allows choosing which DB server to use for an operation.
 #[derive(Copy, Clone, Debug, PartialEq, Eq)]
@@ -77,6 +103,9 @@ pub struct StoreInner {
     /// The current position in `replica_order` so we know which one to
     /// pick next
     conn_round_robin_counter: AtomicUsize,
+    /// The replication lag of each of the `read_only_pools` in
+    /// milliseconds, as of the last time we checked
+    replica_lag: Vec<AtomicU64>,
 
     /// A cache of commonly needed data about a subgraph.
     subgraph_cache: Mutex<LruCache<SubgraphDeploymentId, SubgraphInfo>>,
@@ -132,6 +161,8 @@ impl DeploymentStore {
         replica_order.shuffle(&mut rng);
         debug!(logger, "Using postgres host order {:?}", replica_order);
 
+        let replica_lag = read_only_pools.iter().map(|_| AtomicU64::new(0)).collect();
+
         // Create the store
         let store = StoreInner {
             logger: logger.clone(),
@@ -139,6 +170,7 @@ impl DeploymentStore {
             read_only_pools,
             replica_order,
             conn_round_robin_counter: AtomicUsize::new(0),
+            replica_lag,
             subgraph_cache: Mutex::new(LruCache::with_capacity(100)),
             layout_cache: Mutex::new(HashMap::new()),
         };
@@ -273,114 +305,64 @@ impl DeploymentStore {
         Ok(())
     }
 
-    fn apply_entity_modifications(
+    /// Write the merged changes `tables` and return by how much the
+    /// number of entities changed
+    fn apply_table_changes(
         &self,
         conn: &PgConnection,
         layout: &Layout,
-        mods: Vec<EntityModification>,
-        ptr: &EthereumBlockPointer,
-        stopwatch: StopwatchMetrics,
+        tables: BTreeMap<EntityType, TableChanges>,
+        stopwatch: &StopwatchMetrics,
     ) -> Result<i32, StoreError> {
-        use EntityModification::*;
         let mut count = 0;
+        let mut changed_keys = Vec::new();
+        for (entity_type, changes) in tables {
+            let TableChanges {
+                clamps,
+                versions,
+                count: table_count,
+            } = changes;
+
+            // End the versions from before the first block first so that
+            // this does not touch any of the versions we insert
+            for (block, ids) in clamps {
+                let _section = stopwatch.start_section("apply_entity_modifications_delete");
+                layout
+                    .delete(conn, &entity_type, &ids, block, stopwatch)
+                    .map_err(|_error| anyhow!("Failed to remove entities: {:?}", ids))?;
+            }
 
-        // Group `Insert`s and `Overwrite`s by key, and accumulate `Remove`s.
-        let mut inserts = HashMap::new();
-        let mut overwrites = HashMap::new();
-        let mut removals = HashMap::new();
-        for modification in mods.into_iter() {
-            match modification {
-                Insert { key, data } => {
-                    inserts
-                        .entry(key.entity_type.clone())
-                        .or_insert_with(Vec::new)
-                        .push((key, data));
-                }
-                Overwrite { key, data } => {
-                    overwrites
-                        .entry(key.entity_type.clone())
-                        .or_insert_with(Vec::new)
-                        .push((key, data));
-                }
-                Remove { key } => {
-                    removals
-                        .entry(key.entity_type.clone())
-                        .or_insert_with(Vec::new)
-                        .push(key.entity_id);
+            if !versions.is_empty() {
+                let mut entities = Vec::with_capacity(versions.len());
+                let mut ranges = Vec::with_capacity(versions.len());
+                for version in versions {
+                    let range: BlockRange = match version.end {
+                        Some(end) => (version.start..end).into(),
+                        None => (version.start..).into(),
+                    };
+                    changed_keys.push(version.key.clone());
+                    entities.push((version.key, version.data));
+                    ranges.push(range);
                 }
+                let _section = stopwatch.start_section("apply_entity_modifications_insert");
+                layout.insert_versions(conn, &entity_type, entities, ranges, stopwatch)?;
             }
+            count += table_count;
         }
 
-        // apply modification groups
-        for (entity_type, entities) in inserts.iter_mut() {
-            count +=
-                self.insert_entities(entity_type, entities, conn, layout, ptr, &stopwatch)? as i32
-        }
-        for (entity_type, entities) in overwrites.drain() {
-            // not updating count since the number of entities remains the same
-            self.overwrite_entities(entity_type, entities, conn, layout, ptr, &stopwatch)?;
-        }
-        for (entity_type, entity_keys) in removals.iter() {
-            count -=
-                self.remove_entities(entity_type, entity_keys, conn, layout, ptr, &stopwatch)?
-                    as i32;
-        }
-        Ok(count)
-    }
-
-    fn insert_entities(
-        &self,
-        entity_type: &EntityType,
-        data: &mut Vec<(EntityKey, Entity)>,
-        conn: &PgConnection,
-        layout: &Layout,
-        ptr: &EthereumBlockPointer,
-        stopwatch: &StopwatchMetrics,
-    ) -> Result<usize, StoreError> {
+        // We check for conflicting ids once everything is written, and
+        // therefore only catch conflicts that still exist after the last
+        // block
         let section = stopwatch.start_section("check_interface_entity_uniqueness");
-        for (key, _) in data.iter() {
+        changed_keys
+            .sort_by(|a, b| (&a.entity_type, &a.entity_id).cmp(&(&b.entity_type, &b.entity_id)));
+        changed_keys.dedup();
+        for key in changed_keys.iter() {
             // WARNING: This will potentially execute 2 queries for each entity key.
             self.check_interface_entity_uniqueness(conn, layout, key)?;
         }
         section.end();
-
-        let _section = stopwatch.start_section("apply_entity_modifications_insert");
-        layout.insert(conn, entity_type, data, block_number(ptr), stopwatch)
-    }
-
-    fn overwrite_entities(
-        &self,
-        entity_type: EntityType,
-        data: Vec<(EntityKey, Entity)>,
-        conn: &PgConnection,
-        layout: &Layout,
-        ptr: &EthereumBlockPointer,
-        stopwatch: &StopwatchMetrics,
-    ) -> Result<usize, StoreError> {
-        let section = stopwatch.start_section("check_interface_entity_uniqueness");
-        for (key, _) in data.iter() {
-            // WARNING: This will potentially execute 2 queries for each entity key.
-            self.check_interface_entity_uniqueness(conn, layout, key)?;
-        }
-        section.end();
-
-        let _section = stopwatch.start_section("apply_entity_modifications_update");
-        layout.update(conn, entity_type, data, block_number(ptr), stopwatch)
-    }
-
-    fn remove_entities(
-        &self,
-        entity_type: &EntityType,
-        entity_keys: &Vec<String>,
-        conn: &PgConnection,
-        layout: &Layout,
-        ptr: &EthereumBlockPointer,
-        stopwatch: &StopwatchMetrics,
-    ) -> Result<usize, StoreError> {
-        let _section = stopwatch.start_section("apply_entity_modifications_delete");
-        layout
-            .delete(conn, entity_type, entity_keys, block_number(ptr), stopwatch)
-            .map_err(|_error| anyhow!("Failed to remove entities: {:?}", entity_keys).into())
+        Ok(count)
     }
 
     /// Execute a closure with a connection to the database.
@@ -568,7 +550,13 @@ impl DeploymentStore {
     ) -> Result<Vec<status::Info>, StoreError> {
         let conn = self.get_conn()?;
         conn.transaction(|| -> Result<Vec<status::Info>, StoreError> {
-            detail::deployment_statuses(&conn, sites)
+            let mut infos = detail::deployment_statuses(&conn, sites)?;
+            let ids = infos.iter().map(|info| info.subgraph.clone()).collect();
+            let mut retry_states = restart::retry_states(&conn, ids)?;
+            for info in &mut infos {
+                info.retry = retry_states.remove(&info.subgraph);
+            }
+            Ok(infos)
         })
     }
 
@@ -585,6 +573,45 @@ impl DeploymentStore {
         conn.transaction(|| deployment::set_synced(&conn, id))
     }
 
+    pub(crate) fn create_manual_index(
+        &self,
+        site: &Site,
+        entity: &EntityType,
+        fields: Vec<String>,
+    ) -> Result<String, StoreError> {
+        let conn = self.get_conn()?;
+        let layout = self.layout(&conn, site)?;
+        layout.create_manual_index(&conn, entity, &fields)
+    }
+
+    pub(crate) fn indexes_for_entity(
+        &self,
+        site: &Site,
+        entity: &EntityType,
+    ) -> Result<Vec<(String, String)>, StoreError> {
+        let conn = self.get_conn()?;
+        let layout = self.layout(&conn, site)?;
+        let table = layout.table_for_entity(entity)?;
+        crate::catalog::indexes_for_table(&conn, &site.namespace, &table.name)
+    }
+
+    pub(crate) fn drop_index(&self, site: &Site, name: &str) -> Result<(), StoreError> {
+        let conn = self.get_conn()?;
+        let layout = self.layout(&conn, site)?;
+        layout.drop_index(&conn, name)
+    }
+
+    pub(crate) fn stats(
+        &self,
+        site: &Site,
+    ) -> Result<(Vec<VersionCount>, Vec<TableStats>), StoreError> {
+        let conn = self.get_conn()?;
+        let layout = self.layout(&conn, site)?;
+        let counts = layout.version_counts(&conn)?;
+        let stats = crate::catalog::table_stats(&conn, &site.namespace)?;
+        Ok((counts, stats))
+    }
+
     // Only used for tests
     #[cfg(debug_assertions)]
     pub(crate) fn drop_deployment_schema(
@@ -603,9 +630,11 @@ impl DeploymentStore {
         // This needs to touch all the tables in the subgraphs schema
         const QUERY: &str = "
         delete from subgraphs.dynamic_ethereum_contract_data_source;
+        delete from subgraphs.dynamic_ethereum_contract_data_source_trigger_count;
         delete from subgraphs.subgraph;
         delete from subgraphs.subgraph_deployment;
         delete from subgraphs.subgraph_deployment_assignment;
+        delete from subgraphs.subgraph_instance;
         delete from subgraphs.subgraph_version;
         delete from subgraphs.subgraph_manifest;
     ";
@@ -623,6 +652,41 @@ impl DeploymentStore {
         })
         .await
     }
+
+    /// Delete old mapping logs so that each deployment has at most
+    /// `GRAPH_MAPPING_LOG_LIMIT` messages
+    pub(crate) async fn trim_mapping_logs(&self) -> Result<usize, StoreError> {
+        self.with_conn(|conn, _| Ok(mapping_log::trim(conn, *MAPPING_LOG_LIMIT)?))
+            .await
+    }
+
+    /// Check how far each read replica is behind the main database. A
+    /// replica that we can not reach is treated as if it was lagging so
+    /// that we stop sending queries to it
+    pub(crate) async fn refresh_replica_lag(&self) {
+        use std::sync::atomic::Ordering;
+
+        for (idx, (pool, lag)) in self
+            .read_only_pools
+            .iter()
+            .zip(self.replica_lag.iter())
+            .enumerate()
+        {
+            let millis = match pool.replication_lag().await {
+                Ok(lag) => lag.as_millis().try_into().unwrap_or(u64::MAX),
+                Err(e) => {
+                    warn!(self.logger, "Failed to determine replication lag";
+                        "replica" => idx + 1, "error" => e.to_string());
+                    u64::MAX
+                }
+            };
+            if millis > REPLICA_MAX_LAG.as_millis() as u64 {
+                debug!(self.logger, "Not using lagging read replica for queries";
+                    "replica" => idx + 1, "lag_ms" => millis);
+            }
+            lag.store(millis, Ordering::SeqCst);
+        }
+    }
 }
 
 /// Methods that back the trait `graph::components::Store`, but have small
@@ -633,6 +697,19 @@ impl DeploymentStore {
         Self::block_ptr_with_conn(&site.deployment, &conn)
     }
 
+    /// The number of the latest block of the deployment that `replica`
+    /// has applied, or `None` if the deployment has not processed any
+    /// blocks there yet
+    pub(crate) fn replica_block_number(
+        &self,
+        site: &Site,
+        replica: ReplicaId,
+    ) -> Result<Option<BlockNumber>, Error> {
+        let conn = self.get_replica_conn(replica)?;
+        let ptr = Self::block_ptr_with_conn(&site.deployment, &conn)?;
+        Ok(ptr.map(|ptr| ptr.block_number()))
+    }
+
     pub(crate) fn supports_proof_of_indexing<'a>(
         self: Arc<Self>,
         site: Arc<Site>,
@@ -714,24 +791,10 @@ impl DeploymentStore {
                 return Ok(None);
             };
 
-            let mut by_causality_region = entities
-                .into_iter()
-                .map(|e| {
-                    let causality_region = e.id()?;
-                    let digest = match e.get("digest") {
-                        Some(Value::Bytes(b)) => Ok(b.to_owned()),
-                        other => Err(anyhow::anyhow!(
-                            "Entity has non-bytes digest attribute: {:?}",
-                            other
-                        )),
-                    }?;
-
-                    Ok((causality_region, digest))
-                })
-                .collect::<Result<HashMap<_, _>, anyhow::Error>>()?;
+            let by_causality_region: HashMap<_, _> = poi_digests(entities)?;
 
             let mut finisher = ProofOfIndexingFinisher::new(&block2, &site3.deployment, &indexer);
-            for (name, region) in by_causality_region.drain() {
+            for (name, region) in by_causality_region {
                 finisher.add_causality_region(&name, &region);
             }
 
@@ -740,21 +803,52 @@ impl DeploymentStore {
         .boxed()
     }
 
+    /// The digest of the proof of indexing for each causality region as of
+    /// `block`. Returns `None` if the deployment does not support proofs of
+    /// indexing
+    pub(crate) fn poi_digests(
+        &self,
+        site: &Site,
+        block: BlockNumber,
+    ) -> Result<Option<BTreeMap<String, Vec<u8>>>, StoreError> {
+        let conn = self.get_conn()?;
+        let layout = self.layout(&conn, site)?;
+        if !layout.supports_proof_of_indexing() {
+            return Ok(None);
+        }
+
+        let query = EntityQuery::new(
+            site.deployment.clone(),
+            block,
+            EntityCollection::All(vec![POI_OBJECT.cheap_clone()]),
+        );
+        let entities = self.execute_query::<Entity>(&conn, site, query)?;
+        Ok(Some(poi_digests(entities)?))
+    }
+
     pub(crate) fn get(
         &self,
         site: &Site,
         key: EntityKey,
     ) -> Result<Option<Entity>, QueryExecutionError> {
-        let conn = self.get_conn().map_err(|e| StoreError::Unknown(e))?;
-        let layout = self.layout(&conn, site)?;
-
         // We should really have callers pass in a block number; but until
         // that is fully plumbed in, we just use the biggest possible block
         // number so that we will always return the latest version,
         // i.e., the one with an infinite upper bound
+        self.get_at_block(site, key, BLOCK_NUMBER_MAX)
+    }
+
+    pub(crate) fn get_at_block(
+        &self,
+        site: &Site,
+        key: EntityKey,
+        block: BlockNumber,
+    ) -> Result<Option<Entity>, QueryExecutionError> {
+        let conn = self.get_conn().map_err(|e| StoreError::Unknown(e))?;
+        let layout = self.layout(&conn, site)?;
 
         layout
-            .find(&conn, &key.entity_type, &key.entity_id, BLOCK_NUMBER_MAX)
+            .find(&conn, &key.entity_type, &key.entity_id, block)
             .map_err(|e| {
                 QueryExecutionError::ResolveEntityError(
                     key.subgraph_id.clone(),
@@ -779,6 +873,17 @@ impl DeploymentStore {
         layout.find_many(&conn, ids_for_type, BLOCK_NUMBER_MAX)
     }
 
+    pub(crate) fn get_derived(
+        &self,
+        site: &Site,
+        query: &DerivedEntityQuery,
+    ) -> Result<Vec<Entity>, StoreError> {
+        let conn = self.get_conn()?;
+        let layout = self.layout(&conn, site)?;
+
+        layout.find_derived(&conn, query, BLOCK_NUMBER_MAX)
+    }
+
     pub(crate) fn find(
         &self,
         site: &Site,
@@ -808,18 +913,21 @@ impl DeploymentStore {
         }
     }
 
-    pub(crate) fn transact_block_operations(
+    /// Write the changes for `blocks` in one transaction. The blocks must
+    /// be consecutive, and the first one must be a child of the current
+    /// subgraph block pointer. The changes for each block are written
+    /// with that block's number so that block ranges are exactly the same
+    /// as if each block had been written on its own
+    pub(crate) fn transact_blocks(
         &self,
         site: &Site,
-        block_ptr_to: EthereumBlockPointer,
-        mods: Vec<EntityModification>,
+        mut blocks: Vec<BlockChanges>,
         stopwatch: StopwatchMetrics,
-        data_sources: Vec<StoredDynamicDataSource>,
-        deterministic_errors: Vec<SubgraphError>,
     ) -> Result<StoreEvent, StoreError> {
         // All operations should apply only to data or metadata for this subgraph
-        if mods
+        if blocks
             .iter()
+            .flat_map(|block| block.mods.iter())
             .map(|modification| modification.entity_key())
             .any(|key| key.subgraph_id != site.deployment)
         {
@@ -829,15 +937,22 @@ impl DeploymentStore {
             );
         }
 
+        let block_ptr_to = match blocks.last() {
+            Some(block) => block.ptr.clone(),
+            None => return Ok(StoreEvent::new(vec![])),
+        };
+
         let conn = self.get_conn()?;
 
         let event = conn.transaction(|| -> Result<_, StoreError> {
             let block_ptr_from = Self::block_ptr_with_conn(&site.deployment, &conn)?;
             if let Some(ref block_ptr_from) = block_ptr_from {
-                if block_ptr_from.number >= block_ptr_to.number {
+                // Unwrap: `blocks` is not empty
+                let first = &blocks.first().unwrap().ptr;
+                if block_ptr_from.number >= first.number {
                     return Err(StoreError::DuplicateBlockProcessing(
                         site.deployment.clone(),
-                        block_ptr_to.number,
+                        first.number,
                     ));
                 }
             }
@@ -846,36 +961,53 @@ impl DeploymentStore {
             // wait with sending it until we have done all our other work
             // so that we do not hold a lock on the notification queue
             // for longer than we have to
-            let event: StoreEvent = mods.iter().collect();
+            let event: StoreEvent = blocks.iter().flat_map(|block| block.mods.iter()).collect();
 
-            // Make the changes
+            // Make the changes. Entity changes for all blocks are merged
+            // per entity type so that each type is written with a few
+            // statements regardless of how many blocks there are
             let layout = self.layout(&conn, site)?;
+            let mods = blocks
+                .iter_mut()
+                .map(|block| (block.ptr.number, std::mem::take(&mut block.mods)))
+                .collect();
             let section = stopwatch.start_section("apply_entity_modifications");
-            let count = self.apply_entity_modifications(
+            let count = self.apply_table_changes(
                 &conn,
                 layout.as_ref(),
-                mods,
-                &block_ptr_to,
-                stopwatch,
+                merge_modifications(mods),
+                &stopwatch,
             )?;
+            section.end();
+
+            for block in blocks {
+                let BlockChanges {
+                    ptr,
+                    mods: _,
+                    data_sources,
+                    deterministic_errors,
+                    trigger_counts,
+                } = block;
+
+                dynds::insert(&conn, &site.deployment, data_sources, &ptr)?;
+                dynds::insert_trigger_counts(&conn, &site.deployment, ptr.number, trigger_counts)?;
+
+                if !deterministic_errors.is_empty() {
+                    deployment::insert_subgraph_errors(
+                        &conn,
+                        &site.deployment,
+                        deterministic_errors,
+                        ptr.block_number(),
+                    )?;
+                }
+            }
+            dynds::fold_trigger_counts(&conn, &site.deployment, block_ptr_to.number)?;
             deployment::update_entity_count(
                 &conn,
                 &site.deployment,
                 layout.count_query.as_str(),
                 count,
             )?;
-            section.end();
-
-            dynds::insert(&conn, &site.deployment, data_sources, &block_ptr_to)?;
-
-            if !deterministic_errors.is_empty() {
-                deployment::insert_subgraph_errors(
-                    &conn,
-                    &site.deployment,
-                    deterministic_errors,
-                    block_ptr_to.block_number(),
-                )?;
-            }
 
             deployment::forward_block_ptr(&conn, &site.deployment, block_ptr_to)?;
             Ok(event)
@@ -884,6 +1016,80 @@ impl DeploymentStore {
         Ok(event)
     }
 
+    pub(crate) fn write_mapping_logs(
+        &self,
+        site: &Site,
+        logs: Vec<MappingLog>,
+    ) -> Result<(), StoreError> {
+        if *MAPPING_LOG_LIMIT == 0 {
+            return Ok(());
+        }
+        let conn = self.get_conn()?;
+        conn.transaction(|| mapping_log::insert(&conn, &site.deployment, logs))
+    }
+
+    pub(crate) fn poi_events_block(&self, site: &Site) -> Result<Option<BlockNumber>, StoreError> {
+        let conn = self.get_conn()?;
+        poi_event::requested_block(&conn, &site.deployment)
+    }
+
+    pub(crate) fn request_poi_events(
+        &self,
+        site: &Site,
+        block: BlockNumber,
+    ) -> Result<(), StoreError> {
+        let conn = self.get_conn()?;
+        poi_event::request(&conn, &site.deployment, block)
+    }
+
+    pub(crate) fn write_poi_events(
+        &self,
+        site: &Site,
+        block: BlockNumber,
+        events: BTreeMap<String, Vec<String>>,
+    ) -> Result<(), StoreError> {
+        let conn = self.get_conn()?;
+        poi_event::insert(&conn, &site.deployment, block, events)
+    }
+
+    pub(crate) fn poi_events(
+        &self,
+        site: &Site,
+        block: BlockNumber,
+    ) -> Result<Option<BTreeMap<String, Vec<String>>>, StoreError> {
+        let conn = self.get_conn()?;
+        poi_event::load(&conn, &site.deployment, block)
+    }
+
+    pub(crate) fn dynamic_data_sources(
+        &self,
+        site: &Site,
+    ) -> Result<Vec<status::DataSource>, StoreError> {
+        let conn = self.get_conn()?;
+        dynds::list(&conn, site.deployment.as_str())
+    }
+
+    pub(crate) fn mapping_logs(
+        &self,
+        site: &Site,
+        level: MappingLogLevel,
+        from_block: Option<BlockNumber>,
+        to_block: Option<BlockNumber>,
+    ) -> Result<Vec<MappingLog>, StoreError> {
+        let conn = self.get_conn()?;
+        mapping_log::load(&conn, &site.deployment, level, from_block, to_block)
+    }
+
+    pub(crate) fn entity_changes_in_block(
+        &self,
+        site: &Site,
+        block: BlockNumber,
+    ) -> Result<Vec<EntityVersionChange>, StoreError> {
+        let conn = self.get_conn()?;
+        let layout = self.layout(&conn, site)?;
+        layout.entity_changes_in_block(&conn, block)
+    }
+
     pub(crate) fn revert_block_operations(
         &self,
         site: &Site,
@@ -900,52 +1106,98 @@ impl DeploymentStore {
                 panic!("revert_block_operations must revert a single block only");
             }
 
-            // Don't revert past a graft point
-            let info = self.subgraph_info_with_conn(&conn, &site.deployment)?;
-            if let Some(graft_block) = info.graft_block {
-                if graft_block > block_ptr_to.number {
+            self.revert_to_with_conn(&conn, site, block_ptr_to)
+        })?;
+
+        Ok(event)
+    }
+
+    /// Revert all changes the deployment made after `block_ptr_to`, which
+    /// can be any number of blocks before its current block pointer
+    pub(crate) fn rewind(
+        &self,
+        site: &Site,
+        block_ptr_to: EthereumBlockPointer,
+    ) -> Result<StoreEvent, StoreError> {
+        let conn = self.get_conn()?;
+
+        conn.transaction(|| -> Result<_, StoreError> {
+            if let Some(earliest) = deployment::earliest_block_ptr(&conn, &site.deployment)? {
+                if block_ptr_to.number < earliest.number {
                     return Err(anyhow!(
-                        "Can not revert subgraph `{}` to block {} as it was \
-                        grafted at block {} and reverting past a graft point \
-                        is not possible",
-                        site.deployment.clone(),
+                        "Can not rewind subgraph `{}` to block {} since it starts at block {}",
+                        site.deployment,
                         block_ptr_to.number,
-                        graft_block
+                        earliest.number + 1
                     )
                     .into());
                 }
             }
+            match Self::block_ptr_with_conn(&site.deployment, &conn)? {
+                Some(block_ptr_from) if block_ptr_from.number > block_ptr_to.number => {
+                    self.revert_to_with_conn(&conn, site, block_ptr_to)
+                }
+                Some(block_ptr_from) => Err(anyhow!(
+                    "Can not rewind subgraph `{}` to block {} since it is only at block {}",
+                    site.deployment,
+                    block_ptr_to.number,
+                    block_ptr_from.number
+                )
+                .into()),
+                None => Err(anyhow!(
+                    "Can not rewind subgraph `{}` since it has not processed any blocks yet",
+                    site.deployment
+                )
+                .into()),
+            }
+        })
+    }
 
-            deployment::revert_block_ptr(&conn, &site.deployment, block_ptr_to)?;
+    fn revert_to_with_conn(
+        &self,
+        conn: &PgConnection,
+        site: &Site,
+        block_ptr_to: EthereumBlockPointer,
+    ) -> Result<StoreEvent, StoreError> {
+        // Don't revert past a graft point
+        let info = self.subgraph_info_with_conn(conn, &site.deployment)?;
+        if let Some(graft_block) = info.graft_block {
+            if graft_block > block_ptr_to.number {
+                return Err(anyhow!(
+                    "Can not revert subgraph `{}` to block {} as it was \
+                    grafted at block {} and reverting past a graft point \
+                    is not possible",
+                    site.deployment.clone(),
+                    block_ptr_to.number,
+                    graft_block
+                )
+                .into());
+            }
+        }
 
-            // Revert the data
-            let layout = self.layout(&conn, site)?;
+        // The first block whose changes we revert
+        let block = block_ptr_to.number + 1;
 
-            // At 1 block per 15 seconds, the maximum i32
-            // value affords just over 1020 years of blocks.
-            let block = block_ptr_from
-                .number
-                .try_into()
-                .expect("block numbers fit into an i32");
+        deployment::revert_block_ptr(conn, &site.deployment, block_ptr_to)?;
 
-            let (event, count) = layout.revert_block(&conn, &site.deployment, block)?;
+        // Revert the data
+        let layout = self.layout(conn, site)?;
 
-            // Revert the meta data changes that correspond to this subgraph.
-            // Only certain meta data changes need to be reverted, most
-            // importantly creation of dynamic data sources. We ensure in the
-            // rest of the code that we only record history for those meta data
-            // changes that might need to be reverted
-            Layout::revert_metadata(&conn, &site.deployment, block)?;
+        let (event, count) = layout.revert_block(conn, &site.deployment, block)?;
 
-            deployment::update_entity_count(
-                &conn,
-                &site.deployment,
-                layout.count_query.as_str(),
-                count,
-            )?;
-            Ok(event)
-        })?;
+        // Revert the meta data changes that correspond to this subgraph.
+        // Only certain meta data changes need to be reverted, most
+        // importantly creation of dynamic data sources. We ensure in the
+        // rest of the code that we only record history for those meta data
+        // changes that might need to be reverted
+        Layout::revert_metadata(conn, &site.deployment, block)?;
 
+        deployment::update_entity_count(
+            conn,
+            &site.deployment,
+            layout.count_query.as_str(),
+            count,
+        )?;
         Ok(event)
     }
 
@@ -970,6 +1222,55 @@ impl DeploymentStore {
         Ok(())
     }
 
+    pub(crate) fn record_subgraph_restart(
+        &self,
+        site: &Site,
+        restart: SubgraphRestart,
+    ) -> Result<(), StoreError> {
+        let conn = self.get_conn()?;
+        conn.transaction(|| {
+            health_history::record(
+                &conn,
+                &site.deployment,
+                HealthEventKind::Restart,
+                None,
+                Some(&restart.message),
+                restart.block_ptr.as_ref().map(|ptr| ptr.number),
+                restart.block_ptr.as_ref().map(|ptr| ptr.hash_slice()),
+            )?;
+            restart::insert(&conn, &site.deployment, restart)
+        })
+    }
+
+    /// Add an event that happened outside of the deployment's shard, like a
+    /// reassignment, to the deployment's health history
+    pub(crate) fn record_health_event(
+        &self,
+        site: &Site,
+        kind: HealthEventKind,
+        message: &str,
+    ) -> Result<(), StoreError> {
+        let conn = self.get_conn()?;
+        health_history::record(
+            &conn,
+            &site.deployment,
+            kind,
+            None,
+            Some(message),
+            None,
+            None,
+        )
+    }
+
+    pub(crate) fn health_history(
+        &self,
+        site: &Site,
+        first: i64,
+    ) -> Result<Vec<HealthEvent>, StoreError> {
+        let conn = self.get_conn()?;
+        health_history::load(&conn, &site.deployment, first)
+    }
+
     pub(crate) fn replica_for_query(
         &self,
         for_subscription: bool,
@@ -978,12 +1279,29 @@ impl DeploymentStore {
 
         let replica_id = match for_subscription {
             // Pick a weighted ReplicaId. `replica_order` contains a list of
-            // replicas with repetitions according to their weight
+            // replicas with repetitions according to their weight. If the
+            // replica we picked is lagging too far behind, we use the next
+            // read replica in `replica_order` that has caught up, and only
+            // use the main database if all of them are lagging
             false => {
                 let weights_count = self.replica_order.len();
-                let index =
+                let max_lag = REPLICA_MAX_LAG.as_millis() as u64;
+                let start =
                     self.conn_round_robin_counter.fetch_add(1, Ordering::SeqCst) % weights_count;
-                *self.replica_order.get(index).unwrap()
+                let caught_up = |replica: &ReplicaId| match replica {
+                    ReplicaId::Main => true,
+                    ReplicaId::ReadOnly(idx) => {
+                        self.replica_lag[*idx].load(Ordering::SeqCst) <= max_lag
+                    }
+                };
+                match self.replica_order[start] {
+                    ReplicaId::Main => ReplicaId::Main,
+                    replica if caught_up(&replica) => replica,
+                    _ => (1..weights_count)
+                        .map(|i| self.replica_order[(start + i) % weights_count])
+                        .find(|replica| *replica != ReplicaId::Main && caught_up(replica))
+                        .unwrap_or(ReplicaId::Main),
+                }
             }
             // Subscriptions always go to the main replica.
             true => ReplicaId::Main,
@@ -1050,7 +1368,10 @@ impl DeploymentStore {
 
     pub(crate) fn unfail(&self, site: Arc<Site>) -> Result<(), StoreError> {
         let conn = self.get_conn()?;
-        conn.transaction(|| deployment::unfail(&conn, &site.deployment))
+        conn.transaction(|| {
+            restart::recover(&conn, &site.deployment)?;
+            deployment::unfail(&conn, &site.deployment)
+        })
     }
 
     #[cfg(debug_assertions)]
@@ -1059,3 +1380,22 @@ impl DeploymentStore {
         deployment::error_count(&conn, id)
     }
 }
+
+/// Extract the digest for each causality region from the `Poi$` entities
+fn poi_digests<C: FromIterator<(String, Vec<u8>)>>(entities: Vec<Entity>) -> Result<C, Error> {
+    entities
+        .into_iter()
+        .map(|e| {
+            let causality_region = e.id()?;
+            let digest = match e.get("digest") {
+                Some(Value::Bytes(b)) => Ok(b.to_owned()),
+                other => Err(anyhow::anyhow!(
+                    "Entity has non-bytes digest attribute: {:?}",
+                    other
+                )),
+            }?;
+
+            Ok((causality_region, digest))
+        })
+        .collect()
+}
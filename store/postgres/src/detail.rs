@@ -193,7 +193,8 @@ impl<'a> TryFrom<DetailAndError<'a>> for status::Info {
             constraint_violation!("the entityCount for {} is not representable as a u64", id)
         })?;
         let fatal_error = error.map(|e| SubgraphError::try_from(e)).transpose()?;
-        // 'node' needs to be filled in later from a different shard
+        // 'node' and 'paused' need to be filled in later from a different
+        // shard, and 'retry' from a different table
         Ok(status::Info {
             subgraph: id,
             synced,
@@ -203,6 +204,8 @@ impl<'a> TryFrom<DetailAndError<'a>> for status::Info {
             chains: vec![chain],
             entity_count,
             node: None,
+            paused: None,
+            retry: None,
         })
     }
 }
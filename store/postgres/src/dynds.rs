@@ -5,20 +5,34 @@ use diesel::{
     dsl::sql,
     prelude::{ExpressionMethods, QueryDsl, RunQueryDsl},
     sql_query,
-    sql_types::Text,
+    sql_types::{Array, BigInt, Binary, Integer, Text},
 };
 use diesel::{insert_into, pg::PgConnection};
+use std::collections::HashMap;
+use std::env;
+use std::str::FromStr;
 
 use graph::{
-    components::store::StoredDynamicDataSource,
+    components::store::{DynamicDataSourceKey, StoredDynamicDataSource},
     constraint_violation,
-    data::subgraph::Source,
+    data::subgraph::{status, Source},
     prelude::{
-        bigdecimal::ToPrimitive, web3::types::H160, BigDecimal, BlockNumber, EthereumBlockPointer,
-        StoreError, SubgraphDeploymentId,
+        bigdecimal::ToPrimitive, lazy_static, web3::types::H160, BigDecimal, BlockNumber,
+        EthereumBlockPointer, StoreError, SubgraphDeploymentId,
     },
 };
 
+lazy_static! {
+    /// Trigger counts are kept per block for this many blocks so that they
+    /// can be reverted. Older counts are added to the total of their data
+    /// source. This is the same setting that the block ingestor uses
+    static ref REORG_THRESHOLD: BlockNumber = env::var("ETHEREUM_REORG_THRESHOLD")
+        .ok()
+        .map(|s| BlockNumber::from_str(&s)
+            .unwrap_or_else(|_| panic!("failed to parse env var ETHEREUM_REORG_THRESHOLD")))
+        .unwrap_or(50);
+}
+
 table! {
     subgraphs.dynamic_ethereum_contract_data_source (vid) {
         vid -> BigInt,
@@ -31,6 +45,19 @@ table! {
         ethereum_block_number -> Numeric,
         deployment -> Text,
         context -> Nullable<Text>,
+        trigger_count -> BigInt,
+    }
+}
+
+table! {
+    subgraphs.dynamic_ethereum_contract_data_source_trigger_count (vid) {
+        vid -> BigInt,
+        deployment -> Text,
+        name -> Text,
+        address -> Binary,
+        creation_block -> Integer,
+        block_number -> Integer,
+        count -> BigInt,
     }
 }
 
@@ -107,6 +134,154 @@ pub fn load(conn: &PgConnection, id: &str) -> Result<Vec<StoredDynamicDataSource
     Ok(data_sources)
 }
 
+/// The dynamic data sources of the deployment `id` with the number of
+/// triggers they handled, in the order in which they were created
+pub(crate) fn list(conn: &PgConnection, id: &str) -> Result<Vec<status::DataSource>, StoreError> {
+    use dynamic_ethereum_contract_data_source as decds;
+
+    let recent = recent_trigger_counts(conn, id)?;
+    decds::table
+        .filter(decds::deployment.eq(id))
+        .select((
+            decds::vid,
+            decds::name,
+            decds::address,
+            decds::context,
+            decds::ethereum_block_number,
+            decds::trigger_count,
+        ))
+        .order_by((decds::ethereum_block_number, decds::vid))
+        .load::<(i64, String, Vec<u8>, Option<String>, BigDecimal, i64)>(conn)?
+        .into_iter()
+        .map(
+            |(vid, template, address, context, creation_block, trigger_count)| {
+                if address.len() != 20 {
+                    return Err(constraint_violation!(
+                        "Data source address 0x`{:?}` for dynamic data source {} in deployment {} should have be 20 bytes long but is {} bytes long",
+                        address, vid, id,
+                        address.len()
+                    ));
+                }
+                let creation_block = creation_block.to_i32();
+                let recent = creation_block
+                    .and_then(|block| recent.get(&(template.clone(), address.clone(), block)))
+                    .cloned()
+                    .unwrap_or(0);
+                Ok(status::DataSource {
+                    template,
+                    address: Some(H160::from_slice(address.as_slice())),
+                    creation_block,
+                    context,
+                    trigger_count: trigger_count + recent,
+                })
+            },
+        )
+        .collect()
+}
+
+/// Record the number of triggers that the dynamic data sources of
+/// `deployment` handled in `block`
+pub(crate) fn insert_trigger_counts(
+    conn: &PgConnection,
+    deployment: &SubgraphDeploymentId,
+    block: BlockNumber,
+    counts: HashMap<DynamicDataSourceKey, i64>,
+) -> Result<(), StoreError> {
+    const QUERY: &str = "\
+      insert into subgraphs.dynamic_ethereum_contract_data_source_trigger_count(deployment,
+             name, address, creation_block, block_number, count)
+      select $1, c.name, c.address, c.block, $2, c.count
+        from unnest($3::text[], $4::bytea[], $5::int4[], $6::int8[])
+               as c(name, address, block, count)";
+
+    if counts.is_empty() {
+        return Ok(());
+    }
+
+    let mut names = Vec::with_capacity(counts.len());
+    let mut addresses = Vec::with_capacity(counts.len());
+    let mut blocks = Vec::with_capacity(counts.len());
+    let mut trigger_counts = Vec::with_capacity(counts.len());
+    for (key, count) in counts {
+        names.push(key.name);
+        addresses.push(key.address.as_bytes().to_vec());
+        blocks.push(key.creation_block);
+        trigger_counts.push(count);
+    }
+
+    sql_query(QUERY)
+        .bind::<Text, _>(deployment.as_str())
+        .bind::<Integer, _>(block)
+        .bind::<Array<Text>, _>(names)
+        .bind::<Array<Binary>, _>(addresses)
+        .bind::<Array<Integer>, _>(blocks)
+        .bind::<Array<BigInt>, _>(trigger_counts)
+        .execute(conn)?;
+    Ok(())
+}
+
+/// Add the trigger counts of `deployment` for blocks that are too far
+/// behind `latest` to still be reverted to the totals of the data sources
+pub(crate) fn fold_trigger_counts(
+    conn: &PgConnection,
+    deployment: &SubgraphDeploymentId,
+    latest: BlockNumber,
+) -> Result<(), StoreError> {
+    const QUERY: &str = "\
+      with folded as (
+        delete from subgraphs.dynamic_ethereum_contract_data_source_trigger_count tc
+         where tc.deployment = $1
+           and tc.block_number < $2
+        returning tc.name, tc.address, tc.creation_block, tc.count)
+      update subgraphs.dynamic_ethereum_contract_data_source ds
+         set trigger_count = ds.trigger_count + c.count
+        from (select name, address, creation_block, sum(count)::int8 as count
+                from folded
+               group by name, address, creation_block) c
+       where ds.deployment = $1
+         and ds.name = c.name
+         and ds.address = c.address
+         and ds.ethereum_block_number = c.creation_block";
+
+    sql_query(QUERY)
+        .bind::<Text, _>(deployment.as_str())
+        .bind::<Integer, _>(latest - *REORG_THRESHOLD)
+        .execute(conn)?;
+    Ok(())
+}
+
+/// The trigger counts of the dynamic data sources of `deployment` that
+/// have not been added to their totals yet
+fn recent_trigger_counts(
+    conn: &PgConnection,
+    deployment: &str,
+) -> Result<HashMap<(String, Vec<u8>, BlockNumber), i64>, StoreError> {
+    #[derive(QueryableByName)]
+    struct TriggerCount {
+        #[sql_type = "Text"]
+        name: String,
+        #[sql_type = "Binary"]
+        address: Vec<u8>,
+        #[sql_type = "Integer"]
+        creation_block: BlockNumber,
+        #[sql_type = "BigInt"]
+        count: i64,
+    }
+
+    const QUERY: &str = "\
+      select name, address, creation_block, sum(count)::int8 as count
+        from subgraphs.dynamic_ethereum_contract_data_source_trigger_count
+       where deployment = $1
+       group by name, address, creation_block";
+
+    Ok(sql_query(QUERY)
+        .bind::<Text, _>(deployment)
+        .get_results::<TriggerCount>(conn)?
+        .into_iter()
+        .map(|tc| ((tc.name, tc.address, tc.creation_block), tc.count))
+        .collect())
+}
+
 pub(crate) fn insert(
     conn: &PgConnection,
     deployment: &SubgraphDeploymentId,
@@ -165,17 +340,28 @@ pub(crate) fn copy(
     const QUERY: &str = "\
       insert into subgraphs.dynamic_ethereum_contract_data_source(name,
              address, abi, start_block, ethereum_block_hash,
-             ethereum_block_number, deployment, context)
+             ethereum_block_number, deployment, context, trigger_count)
       select e.name, e.address, e.abi, e.start_block,
              e.ethereum_block_hash, e.ethereum_block_number, $2 as deployment,
-             e.context
+             e.context, e.trigger_count
         from subgraphs.dynamic_ethereum_contract_data_source e
        where e.deployment = $1";
+    const TRIGGER_COUNT_QUERY: &str = "\
+      insert into subgraphs.dynamic_ethereum_contract_data_source_trigger_count(deployment,
+             name, address, creation_block, block_number, count)
+      select $2, e.name, e.address, e.creation_block, e.block_number, e.count
+        from subgraphs.dynamic_ethereum_contract_data_source_trigger_count e
+       where e.deployment = $1";
 
-    Ok(sql_query(QUERY)
+    let count = sql_query(QUERY)
         .bind::<Text, _>(src.as_str())
         .bind::<Text, _>(dst.as_str())
-        .execute(conn)?)
+        .execute(conn)?;
+    sql_query(TRIGGER_COUNT_QUERY)
+        .bind::<Text, _>(src.as_str())
+        .bind::<Text, _>(dst.as_str())
+        .execute(conn)?;
+    Ok(count)
 }
 
 pub(crate) fn revert(
@@ -185,14 +371,20 @@ pub(crate) fn revert(
 ) -> Result<(), StoreError> {
     use dynamic_ethereum_contract_data_source as decds;
 
+    use dynamic_ethereum_contract_data_source_trigger_count as tc;
+
     let dds = decds::table.filter(decds::deployment.eq(id.as_str()));
     delete(dds.filter(decds::ethereum_block_number.ge(sql(&block.to_string())))).execute(conn)?;
+    let counts = tc::table.filter(tc::deployment.eq(id.as_str()));
+    delete(counts.filter(tc::block_number.ge(block))).execute(conn)?;
     Ok(())
 }
 
 pub(crate) fn drop(conn: &PgConnection, id: &SubgraphDeploymentId) -> Result<usize, StoreError> {
     use dynamic_ethereum_contract_data_source as decds;
+    use dynamic_ethereum_contract_data_source_trigger_count as tc;
 
+    delete(tc::table.filter(tc::deployment.eq(id.as_str()))).execute(conn)?;
     delete(decds::table.filter(decds::deployment.eq(id.as_str())))
         .execute(conn)
         .map_err(|e| e.into())
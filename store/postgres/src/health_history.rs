@@ -0,0 +1,113 @@
+//! SQL queries to record and load the health history of deployments: changes
+//! in their health, automatic restarts, unfails, reassignments, version
+//! switches and rewinds
+
+use diesel::{
+    insert_into,
+    pg::PgConnection,
+    prelude::{ExpressionMethods, RunQueryDsl},
+    sql_query,
+    sql_types::{BigInt, Binary, Integer, Nullable, Text},
+};
+use std::str::FromStr;
+
+use graph::{
+    data::subgraph::schema::{HealthEvent, HealthEventKind, SubgraphHealth},
+    prelude::{web3::types::H256, BlockNumber, StoreError, SubgraphDeploymentId},
+};
+
+table! {
+    subgraphs.subgraph_health_event (vid) {
+        vid -> BigInt,
+        subgraph_id -> Text,
+        kind -> Text,
+        health -> Nullable<Text>,
+        message -> Nullable<Text>,
+        block_hash -> Nullable<Binary>,
+        block_number -> Nullable<Integer>,
+    }
+}
+
+/// Add an event to the health history of deployment `id`
+pub(crate) fn record(
+    conn: &PgConnection,
+    id: &SubgraphDeploymentId,
+    kind: HealthEventKind,
+    health: Option<SubgraphHealth>,
+    message: Option<&str>,
+    block_number: Option<BlockNumber>,
+    block_hash: Option<&[u8]>,
+) -> Result<(), StoreError> {
+    use subgraph_health_event as h;
+
+    insert_into(h::table)
+        .values((
+            h::subgraph_id.eq(id.as_str()),
+            h::kind.eq(kind.as_str()),
+            h::health.eq(health.as_ref().map(SubgraphHealth::as_str)),
+            h::message.eq(message),
+            h::block_hash.eq(block_hash),
+            h::block_number.eq(block_number),
+        ))
+        .execute(conn)?;
+    Ok(())
+}
+
+#[derive(QueryableByName)]
+struct Event {
+    #[sql_type = "Text"]
+    kind: String,
+    #[sql_type = "Nullable<Text>"]
+    health: Option<String>,
+    #[sql_type = "Nullable<Text>"]
+    message: Option<String>,
+    #[sql_type = "Nullable<Binary>"]
+    block_hash: Option<Vec<u8>>,
+    #[sql_type = "Nullable<Integer>"]
+    block_number: Option<i32>,
+    #[sql_type = "BigInt"]
+    created_at: i64,
+}
+
+/// The latest `first` events in the health history of deployment `id`, from
+/// oldest to newest
+pub(crate) fn load(
+    conn: &PgConnection,
+    id: &SubgraphDeploymentId,
+    first: i64,
+) -> Result<Vec<HealthEvent>, StoreError> {
+    const QUERY: &str = "\
+      select kind, health, message, block_hash, block_number, created_at
+        from (select vid, kind, health, message, block_hash, block_number,
+                     extract(epoch from created_at)::int8 as created_at
+                from subgraphs.subgraph_health_event
+               where subgraph_id = $1
+               order by vid desc
+               limit $2) e
+       order by vid";
+
+    sql_query(QUERY)
+        .bind::<Text, _>(id.as_str())
+        .bind::<BigInt, _>(first)
+        .load::<Event>(conn)?
+        .into_iter()
+        .map(|event| {
+            let kind = HealthEventKind::from_str(&event.kind)
+                .map_err(|e| StoreError::ConstraintViolation(e.to_string()))?;
+            let health = event
+                .health
+                .as_deref()
+                .map(SubgraphHealth::from_str)
+                .transpose()
+                .map_err(|e| StoreError::ConstraintViolation(e.to_string()))?;
+            Ok(HealthEvent {
+                kind,
+                health,
+                message: event.message,
+                block_number: event.block_number,
+                block_hash: event.block_hash.map(|hash| H256::from_slice(&hash)),
+                at: event.created_at,
+            })
+        })
+        .collect()
+}
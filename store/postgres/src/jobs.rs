@@ -4,7 +4,7 @@ use std::time::Duration;
 
 use async_trait::async_trait;
 
-use graph::prelude::{error, Logger};
+use graph::prelude::{debug, error, Logger};
 use graph::util::jobs::{Job, Runner};
 
 use crate::{Store, SubgraphStore};
@@ -14,6 +14,14 @@ pub fn register(runner: &mut Runner, store: Arc<Store>) {
         Arc::new(VacuumDeploymentsJob::new(store.subgraph_store())),
         Duration::from_secs(60),
     );
+    runner.register(
+        Arc::new(ReplicaLagJob::new(store.subgraph_store())),
+        Duration::from_secs(10),
+    );
+    runner.register(
+        Arc::new(TrimMappingLogsJob::new(store.subgraph_store())),
+        Duration::from_secs(300),
+    );
 }
 
 /// A job that vacuums `subgraphs.subgraph_deployment`. With a large number
@@ -47,3 +55,56 @@ impl Job for VacuumDeploymentsJob {
         }
     }
 }
+
+/// A job that checks how far each read replica is behind its main database
+/// so that queries are not sent to replicas that are lagging too much
+struct ReplicaLagJob {
+    store: Arc<SubgraphStore>,
+}
+
+impl ReplicaLagJob {
+    fn new(store: Arc<SubgraphStore>) -> ReplicaLagJob {
+        ReplicaLagJob { store }
+    }
+}
+
+#[async_trait]
+impl Job for ReplicaLagJob {
+    fn name(&self) -> &str {
+        "Check replication lag of read replicas"
+    }
+
+    async fn run(&self, _: &Logger) {
+        self.store.refresh_replica_lag().await
+    }
+}
+
+/// A job that deletes the oldest mapping logs of each deployment once it
+/// has more than `GRAPH_MAPPING_LOG_LIMIT` of them. Doing that when logs
+/// are written would slow down indexing
+struct TrimMappingLogsJob {
+    store: Arc<SubgraphStore>,
+}
+
+impl TrimMappingLogsJob {
+    fn new(store: Arc<SubgraphStore>) -> TrimMappingLogsJob {
+        TrimMappingLogsJob { store }
+    }
+}
+
+#[async_trait]
+impl Job for TrimMappingLogsJob {
+    fn name(&self) -> &str {
+        "Trim subgraphs.mapping_log"
+    }
+
+    async fn run(&self, logger: &Logger) {
+        for res in self.store.trim_mapping_logs().await {
+            match res {
+                Ok(0) => {}
+                Ok(count) => debug!(logger, "Trimmed mapping logs"; "deleted" => count),
+                Err(e) => error!(logger, "Trimming subgraphs.mapping_log failed: {}", e),
+            }
+        }
+    }
+}
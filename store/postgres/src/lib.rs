@@ -34,23 +34,29 @@ mod deployment_store;
 mod detail;
 mod dynds;
 mod functions;
+mod health_history;
 mod jobs;
 mod jsonb;
+mod mapping_log;
 mod notification_listener;
+mod poi_event;
 mod primary;
 pub mod query_store;
 mod relational;
 mod relational_queries;
+mod restart;
 mod sql_value;
 mod store;
 mod store_events;
 mod subgraph_store;
+mod write_buffer;
 
 #[cfg(debug_assertions)]
 pub mod layout_for_tests {
     pub use crate::block_range::*;
     pub use crate::block_store::FAKE_NETWORK_SHARED;
     pub use crate::chain_store::test_support as chain_support;
+    pub use crate::mapping_log::trim as trim_mapping_logs;
     pub use crate::primary::{Connection, Namespace, EVENT_TAP, EVENT_TAP_ENABLED};
     pub use crate::relational::*;
 }
@@ -75,6 +81,7 @@ pub mod command_support {
             subgraph_version,
         };
     }
+    pub use crate::catalog::{IndexStats, TableStats};
     pub use crate::primary::Namespace;
-    pub use crate::relational::{Catalog, Column, ColumnType, Layout};
+    pub use crate::relational::{Catalog, Column, ColumnType, Layout, VersionCount};
 }
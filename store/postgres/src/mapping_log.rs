@@ -0,0 +1,164 @@
+//! SQL queries to store and load the messages that mappings logged
+
+use diesel::{
+    delete,
+    dsl::any,
+    insert_into,
+    pg::PgConnection,
+    prelude::{ExpressionMethods, QueryDsl, RunQueryDsl},
+    sql_query,
+    sql_types::BigInt,
+};
+use std::env;
+use std::str::FromStr;
+
+use graph::{
+    constraint_violation,
+    data::subgraph::schema::{MappingLog, MappingLogLevel},
+    prelude::{lazy_static, BlockNumber, StoreError, SubgraphDeploymentId},
+};
+
+lazy_static! {
+    /// How many messages to keep for each deployment. Older messages are
+    /// deleted periodically. Setting this to 0 turns storing messages off.
+    pub(crate) static ref MAPPING_LOG_LIMIT: i64 = env::var("GRAPH_MAPPING_LOG_LIMIT")
+        .ok()
+        .map(|s| {
+            i64::from_str(&s).unwrap_or_else(|_| {
+                panic!("GRAPH_MAPPING_LOG_LIMIT must be a number, but is `{}`", s)
+            })
+        })
+        .unwrap_or(10_000);
+}
+
+table! {
+    subgraphs.mapping_log (vid) {
+        vid -> BigInt,
+        subgraph_id -> Text,
+        block_number -> Integer,
+        data_source -> Text,
+        handler -> Nullable<Text>,
+        level -> Text,
+        message -> Text,
+    }
+}
+
+/// Store `logs`, replacing the messages for the blocks they belong to and
+/// any later blocks. Old messages are deleted separately by `trim`.
+pub(crate) fn insert(
+    conn: &PgConnection,
+    id: &SubgraphDeploymentId,
+    logs: Vec<MappingLog>,
+) -> Result<(), StoreError> {
+    use mapping_log as l;
+
+    let first_block = match logs.iter().map(|log| log.block_number).min() {
+        Some(block) => block,
+        None => return Ok(()),
+    };
+
+    // Processing a block again, e.g., after a restart, must not lead to
+    // duplicate messages
+    revert(conn, id, first_block)?;
+
+    let rows: Vec<_> = logs
+        .into_iter()
+        .map(|log| {
+            (
+                l::subgraph_id.eq(id.as_str()),
+                l::block_number.eq(log.block_number),
+                l::data_source.eq(log.data_source),
+                l::handler.eq(log.handler),
+                l::level.eq(log.level.as_str()),
+                l::message.eq(log.message),
+            )
+        })
+        .collect();
+    insert_into(l::table).values(rows).execute(conn)?;
+    Ok(())
+}
+
+/// Delete the oldest messages of each deployment so that at most `limit`
+/// remain. Returns the number of deleted messages.
+pub fn trim(conn: &PgConnection, limit: i64) -> Result<usize, StoreError> {
+    // For each deployment, find the newest message that falls outside of
+    // the limit and delete it together with all older ones. Both lookups
+    // use the index on `(subgraph_id, vid)`
+    const QUERY: &str = "\
+        delete from subgraphs.mapping_log l
+         using (select d.id, c.vid
+                  from subgraphs.subgraph_deployment d,
+                       lateral (select m.vid
+                                  from subgraphs.mapping_log m
+                                 where m.subgraph_id = d.id
+                                 order by m.vid desc
+                                offset $1
+                                 limit 1) c) cutoff
+         where l.subgraph_id = cutoff.id
+           and l.vid <= cutoff.vid";
+
+    Ok(sql_query(QUERY).bind::<BigInt, _>(limit).execute(conn)?)
+}
+
+pub(crate) fn load(
+    conn: &PgConnection,
+    id: &SubgraphDeploymentId,
+    level: MappingLogLevel,
+    from_block: Option<BlockNumber>,
+    to_block: Option<BlockNumber>,
+) -> Result<Vec<MappingLog>, StoreError> {
+    use mapping_log as l;
+
+    let levels: Vec<_> = level.and_above().map(|level| level.as_str()).collect();
+    let mut query = l::table
+        .filter(l::subgraph_id.eq(id.as_str()))
+        .filter(l::level.eq(any(levels)))
+        .select((
+            l::block_number,
+            l::data_source,
+            l::handler,
+            l::level,
+            l::message,
+        ))
+        .order_by((l::block_number, l::vid))
+        .into_boxed();
+    if let Some(from_block) = from_block {
+        query = query.filter(l::block_number.ge(from_block));
+    }
+    if let Some(to_block) = to_block {
+        query = query.filter(l::block_number.le(to_block));
+    }
+
+    query
+        .load::<(BlockNumber, String, Option<String>, String, String)>(conn)?
+        .into_iter()
+        .map(|(block_number, data_source, handler, level, message)| {
+            let level = MappingLogLevel::from_str(&level)
+                .map_err(|_| constraint_violation!("invalid level `{}` of a mapping log", level))?;
+            Ok(MappingLog {
+                block_number,
+                data_source,
+                handler,
+                level,
+                message,
+            })
+        })
+        .collect()
+}
+
+/// Delete the messages for `block` and later blocks.
+pub(crate) fn revert(
+    conn: &PgConnection,
+    id: &SubgraphDeploymentId,
+    block: BlockNumber,
+) -> Result<(), StoreError> {
+    use mapping_log as l;
+
+    delete(
+        l::table
+            .filter(l::subgraph_id.eq(id.as_str()))
+            .filter(l::block_number.ge(block)),
+    )
+    .execute(conn)?;
+    Ok(())
+}
@@ -0,0 +1,135 @@
+//! SQL queries to request, store and load the proof of indexing events that
+//! a deployment recorded for a block
+
+use diesel::{
+    delete, insert_into,
+    pg::PgConnection,
+    prelude::{ExpressionMethods, OptionalExtension, QueryDsl, RunQueryDsl},
+    Connection,
+};
+use std::collections::BTreeMap;
+
+use graph::prelude::{BlockNumber, StoreError, SubgraphDeploymentId};
+
+table! {
+    subgraphs.poi_event_request (subgraph_id) {
+        subgraph_id -> Text,
+        block_number -> Integer,
+    }
+}
+
+table! {
+    subgraphs.poi_event (vid) {
+        vid -> BigInt,
+        subgraph_id -> Text,
+        block_number -> Integer,
+        causality_region -> Text,
+        event -> Text,
+    }
+}
+
+/// Ask for the events of `block` to be recorded the next time the
+/// deployment processes it. This replaces any earlier request
+pub(crate) fn request(
+    conn: &PgConnection,
+    id: &SubgraphDeploymentId,
+    block: BlockNumber,
+) -> Result<(), StoreError> {
+    use poi_event_request as r;
+
+    insert_into(r::table)
+        .values((r::subgraph_id.eq(id.as_str()), r::block_number.eq(block)))
+        .on_conflict(r::subgraph_id)
+        .do_update()
+        .set(r::block_number.eq(block))
+        .execute(conn)?;
+    Ok(())
+}
+
+pub(crate) fn requested_block(
+    conn: &PgConnection,
+    id: &SubgraphDeploymentId,
+) -> Result<Option<BlockNumber>, StoreError> {
+    use poi_event_request as r;
+
+    Ok(r::table
+        .filter(r::subgraph_id.eq(id.as_str()))
+        .select(r::block_number)
+        .first::<BlockNumber>(conn)
+        .optional()?)
+}
+
+/// Store the `events` for `block`, replacing the ones that were stored for
+/// it before, and remove the request to record them
+pub(crate) fn insert(
+    conn: &PgConnection,
+    id: &SubgraphDeploymentId,
+    block: BlockNumber,
+    events: BTreeMap<String, Vec<String>>,
+) -> Result<(), StoreError> {
+    use poi_event as e;
+    use poi_event_request as r;
+
+    let events: Vec<_> = events
+        .into_iter()
+        .flat_map(|(region, events)| events.into_iter().map(move |event| (region.clone(), event)))
+        .collect();
+
+    conn.transaction(|| {
+        delete(
+            e::table
+                .filter(e::subgraph_id.eq(id.as_str()))
+                .filter(e::block_number.eq(block)),
+        )
+        .execute(conn)?;
+        // Postgres limits the number of bind parameters per statement
+        for chunk in events.chunks(10_000) {
+            let rows: Vec<_> = chunk
+                .iter()
+                .map(|(region, event)| {
+                    (
+                        e::subgraph_id.eq(id.as_str()),
+                        e::block_number.eq(block),
+                        e::causality_region.eq(region.as_str()),
+                        e::event.eq(event.as_str()),
+                    )
+                })
+                .collect();
+            insert_into(e::table).values(rows).execute(conn)?;
+        }
+        delete(
+            r::table
+                .filter(r::subgraph_id.eq(id.as_str()))
+                .filter(r::block_number.eq(block)),
+        )
+        .execute(conn)?;
+        Ok(())
+    })
+}
+
+/// The events that were recorded for `block`, by causality region and in
+/// the order in which they were written. Returns `None` if no events were
+/// recorded for the block
+pub(crate) fn load(
+    conn: &PgConnection,
+    id: &SubgraphDeploymentId,
+    block: BlockNumber,
+) -> Result<Option<BTreeMap<String, Vec<String>>>, StoreError> {
+    use poi_event as e;
+
+    let rows = e::table
+        .filter(e::subgraph_id.eq(id.as_str()))
+        .filter(e::block_number.eq(block))
+        .select((e::causality_region, e::event))
+        .order_by(e::vid)
+        .load::<(String, String)>(conn)?;
+    if rows.is_empty() {
+        return Ok(None);
+    }
+
+    let mut events: BTreeMap<String, Vec<String>> = BTreeMap::new();
+    for (region, event) in rows {
+        events.entry(region).or_default().push(event);
+    }
+    Ok(Some(events))
+}
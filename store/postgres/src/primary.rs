@@ -25,8 +25,9 @@ use graph::{
     constraint_violation,
     data::subgraph::status,
     prelude::{
-        anyhow, bigdecimal::ToPrimitive, serde_json, EntityChange, EntityChangeOperation, NodeId,
-        StoreError, SubgraphDeploymentId, SubgraphName, SubgraphVersionSwitchingMode,
+        anyhow, bigdecimal::ToPrimitive, serde_json, AdminAction, AdminOutcome, AdminRole,
+        EntityChange, EntityChangeOperation, NodeId, StoreError, SubgraphDeploymentId,
+        SubgraphName, SubgraphVersionSwitchingMode,
     },
 };
 use graph::{data::subgraph::schema::generate_entity_id, prelude::StoreEvent};
@@ -87,6 +88,14 @@ table! {
         node_id -> Text,
         cost -> Numeric,
         block_range -> Range<Integer>,
+        paused -> Bool,
+    }
+}
+
+table! {
+    subgraphs.subgraph_instance (id) {
+        id -> Text,
+        node_id -> Text,
     }
 }
 
@@ -145,6 +154,20 @@ table! {
     }
 }
 
+table! {
+    /// The audit log of calls to the admin API
+    admin_audit_log(id) {
+        id -> BigInt,
+        caller -> Nullable<Text>,
+        role -> Nullable<Text>,
+        method -> Text,
+        subgraph -> Nullable<Text>,
+        params -> Text,
+        outcome -> Text,
+        error -> Nullable<Text>,
+    }
+}
+
 allow_tables_to_appear_in_same_query!(
     subgraph,
     subgraph_version,
@@ -345,27 +368,28 @@ impl<'a> Connection<'a> {
 
     /// Promote the deployment `id` to the current version everywhere where it was
     /// the pending version so far, and remove any assignments that are not needed
-    /// any longer as a result. Return the changes that were made to assignments
-    /// in the process
+    /// any longer as a result. Return the names of the subgraphs whose current
+    /// version changed and the changes that were made to assignments in the
+    /// process
     pub fn promote_deployment(
         &self,
         id: &SubgraphDeploymentId,
-    ) -> Result<Vec<EntityChange>, StoreError> {
+    ) -> Result<(Vec<String>, Vec<EntityChange>), StoreError> {
         use subgraph as s;
         use subgraph_version as v;
 
         let conn = self.0.as_ref();
 
         // Subgraphs where we need to promote the version
-        let pending_subgraph_versions: Vec<(String, String)> = s::table
+        let pending_subgraph_versions: Vec<(String, String, String)> = s::table
             .inner_join(v::table.on(s::pending_version.eq(v::id.nullable())))
             .filter(v::deployment.eq(id.as_str()))
-            .select((s::id, v::id))
+            .select((s::id, v::id, s::name))
             .for_update()
             .load(conn)?;
 
         // Switch the pending version to the current version
-        for (subgraph, version) in &pending_subgraph_versions {
+        for (subgraph, version, _) in &pending_subgraph_versions {
             update(s::table.filter(s::id.eq(subgraph)))
                 .set((
                     s::current_version.eq(version),
@@ -381,7 +405,11 @@ impl<'a> Connection<'a> {
         } else {
             self.remove_unused_assignments()?
         };
-        Ok(changes)
+        let names = pending_subgraph_versions
+            .into_iter()
+            .map(|(_, _, name)| name)
+            .collect();
+        Ok((names, changes))
     }
 
     /// Create a new subgraph with the given name. If one already exists, use
@@ -418,6 +446,10 @@ impl<'a> Connection<'a> {
         }
     }
 
+    /// Create a version of subgraph `name` for deployment `id` and make it
+    /// the current or pending version, depending on `mode`. Return the
+    /// changes that were made to assignments, and whether the deployment
+    /// became the current version
     pub fn create_subgraph_version<F>(
         &self,
         name: SubgraphName,
@@ -425,7 +457,7 @@ impl<'a> Connection<'a> {
         node_id: NodeId,
         mode: SubgraphVersionSwitchingMode,
         exists_and_synced: F,
-    ) -> Result<Vec<EntityChange>, StoreError>
+    ) -> Result<(Vec<EntityChange>, bool), StoreError>
     where
         F: FnOnce(&SubgraphDeploymentId) -> Result<bool, StoreError>,
     {
@@ -480,7 +512,7 @@ impl<'a> Connection<'a> {
             (Synced, true) => pending_deployment.as_deref() != Some(id.as_str()),
         };
         if !change_needed {
-            return Ok(vec![]);
+            return Ok((vec![], false));
         }
 
         // Create the actual subgraph version
@@ -516,7 +548,7 @@ impl<'a> Connection<'a> {
 
         // See if we should make this the current or pending version
         let subgraph_row = update(s::table.filter(s::id.eq(&subgraph_id)));
-        match (mode, current_exists_and_synced) {
+        let current = match (mode, current_exists_and_synced) {
             (Instant, _) | (Synced, false) => {
                 subgraph_row
                     .set((
@@ -524,13 +556,15 @@ impl<'a> Connection<'a> {
                         s::pending_version.eq::<Option<&str>>(None),
                     ))
                     .execute(conn)?;
+                true
             }
             (Synced, true) => {
                 subgraph_row
                     .set(s::pending_version.eq(&version_id))
                     .execute(conn)?;
+                false
             }
-        }
+        };
 
         // Clean up any assignments we might have displaced
         let mut changes = self.remove_unused_assignments()?;
@@ -538,7 +572,7 @@ impl<'a> Connection<'a> {
             let change = EntityChange::for_assignment(id.clone(), EntityChangeOperation::Set);
             changes.push(change);
         }
-        Ok(changes)
+        Ok((changes, current))
     }
 
     pub fn remove_subgraph(&self, name: SubgraphName) -> Result<Vec<EntityChange>, StoreError> {
@@ -621,6 +655,79 @@ impl<'a> Connection<'a> {
         }
     }
 
+    /// Pause (`paused = true`) or resume indexing of the deployment `id`
+    /// without changing the node it is assigned to
+    fn set_paused(
+        &self,
+        id: &SubgraphDeploymentId,
+        paused: bool,
+    ) -> Result<Vec<EntityChange>, StoreError> {
+        use subgraph_deployment_assignment as a;
+
+        let conn = self.0.as_ref();
+        let updates = update(
+            a::table
+                .filter(a::id.eq(id.as_str()))
+                .filter(a::paused.ne(paused)),
+        )
+        .set(a::paused.eq(paused))
+        .execute(conn)?;
+        match updates {
+            0 => {
+                // Either the deployment is not assigned, or it already is
+                // in the desired state
+                if self.assigned_node(id)?.is_none() {
+                    return Err(StoreError::DeploymentNotFound(id.to_string()));
+                }
+                Ok(vec![])
+            }
+            1 => {
+                let change = EntityChange::for_assignment(id.clone(), EntityChangeOperation::Set);
+                Ok(vec![change])
+            }
+            _ => {
+                // `id` is the primary key of the subgraph_deployment_assignment table,
+                // and we can therefore only update no or one entry
+                unreachable!()
+            }
+        }
+    }
+
+    pub fn pause_subgraph(
+        &self,
+        id: &SubgraphDeploymentId,
+    ) -> Result<Vec<EntityChange>, StoreError> {
+        self.set_paused(id, true)
+    }
+
+    pub fn resume_subgraph(
+        &self,
+        id: &SubgraphDeploymentId,
+    ) -> Result<Vec<EntityChange>, StoreError> {
+        self.set_paused(id, false)
+    }
+
+    pub fn record_admin_action(&self, action: AdminAction) -> Result<(), StoreError> {
+        use admin_audit_log as a;
+
+        let error = match &action.outcome {
+            AdminOutcome::Error(e) => Some(e.as_str()),
+            AdminOutcome::Success | AdminOutcome::Denied => None,
+        };
+        insert_into(a::table)
+            .values((
+                a::caller.eq(&action.caller),
+                a::role.eq(action.role.as_ref().map(AdminRole::as_str)),
+                a::method.eq(&action.method),
+                a::subgraph.eq(&action.subgraph),
+                a::params.eq(&action.params),
+                a::outcome.eq(action.outcome.as_str()),
+                a::error.eq(error),
+            ))
+            .execute(self.0.as_ref())?;
+        Ok(())
+    }
+
     pub fn allocate_site(
         &self,
         shard: Shard,
@@ -798,11 +905,35 @@ impl<'a> Connection<'a> {
             .transpose()
     }
 
+    /// The node that deployment `id` is assigned to, and whether indexing
+    /// it is paused
+    pub fn assignment_status(
+        &self,
+        id: &SubgraphDeploymentId,
+    ) -> Result<Option<(NodeId, bool)>, StoreError> {
+        use subgraph_deployment_assignment as a;
+
+        a::table
+            .filter(a::id.eq(id.as_str()))
+            .select((a::node_id, a::paused))
+            .first::<(String, bool)>(self.0.as_ref())
+            .optional()?
+            .map(|(node, paused)| {
+                NodeId::new(&node).map(|node| (node, paused)).map_err(|()| {
+                    constraint_violation!("invalid node id `{}` in assignment for `{}`", node, id)
+                })
+            })
+            .transpose()
+    }
+
+    /// The deployments that `node` should index. Paused deployments are
+    /// not included
     pub fn assignments(&self, node: &NodeId) -> Result<Vec<SubgraphDeploymentId>, StoreError> {
         use subgraph_deployment_assignment as a;
 
         a::table
             .filter(a::node_id.eq(node.as_str()))
+            .filter(a::paused.eq(false))
             .select(a::id)
             .load::<String>(self.0.as_ref())?
             .into_iter()
@@ -818,6 +949,62 @@ impl<'a> Connection<'a> {
             .collect()
     }
 
+    /// Record whether `node` runs an instance of deployment `id`. Another
+    /// node's entry is only replaced when `node` starts an instance
+    pub fn set_instance_running(
+        &self,
+        id: &SubgraphDeploymentId,
+        node: &NodeId,
+        running: bool,
+    ) -> Result<(), StoreError> {
+        use subgraph_instance as i;
+
+        if running {
+            insert_into(i::table)
+                .values((i::id.eq(id.as_str()), i::node_id.eq(node.as_str())))
+                .on_conflict(i::id)
+                .do_update()
+                .set(i::node_id.eq(node.as_str()))
+                .execute(self.0.as_ref())?;
+        } else {
+            delete(
+                i::table
+                    .filter(i::id.eq(id.as_str()))
+                    .filter(i::node_id.eq(node.as_str())),
+            )
+            .execute(self.0.as_ref())?;
+        }
+        Ok(())
+    }
+
+    /// The node that runs an instance of deployment `id`, if any
+    pub fn running_instance(
+        &self,
+        id: &SubgraphDeploymentId,
+    ) -> Result<Option<NodeId>, StoreError> {
+        use subgraph_instance as i;
+
+        i::table
+            .filter(i::id.eq(id.as_str()))
+            .select(i::node_id)
+            .first::<String>(self.0.as_ref())
+            .optional()?
+            .map(|node| {
+                NodeId::new(&node).map_err(|()| {
+                    constraint_violation!("invalid node id `{}` in instance of `{}`", node, id)
+                })
+            })
+            .transpose()
+    }
+
+    /// Remove the entries of all instances that `node` ran
+    pub fn clear_running_instances(&self, node: &NodeId) -> Result<(), StoreError> {
+        use subgraph_instance as i;
+
+        delete(i::table.filter(i::node_id.eq(node.as_str()))).execute(self.0.as_ref())?;
+        Ok(())
+    }
+
     pub fn fill_assignments(
         &self,
         mut infos: Vec<status::Info>,
@@ -827,12 +1014,14 @@ impl<'a> Connection<'a> {
         let ids: Vec<_> = infos.iter().map(|info| &info.subgraph).collect();
         let nodes: HashMap<_, _> = a::table
             .filter(a::id.eq(any(ids)))
-            .select((a::id, a::node_id))
-            .load::<(String, String)>(self.0.as_ref())?
+            .select((a::id, (a::node_id, a::paused)))
+            .load::<(String, (String, bool))>(self.0.as_ref())?
             .into_iter()
             .collect();
         for mut info in &mut infos {
-            info.node = nodes.get(&info.subgraph).map(|s| s.clone());
+            let assignment = nodes.get(&info.subgraph);
+            info.node = assignment.map(|(node, _)| node.clone());
+            info.paused = assignment.map(|(_, paused)| *paused);
         }
         Ok(infos)
     }
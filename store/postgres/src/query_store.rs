@@ -1,4 +1,5 @@
 use std::collections::BTreeMap;
+use std::sync::Mutex;
 
 use web3::types::H256;
 
@@ -13,6 +14,9 @@ pub(crate) struct QueryStore {
     replica_id: ReplicaId,
     store: Arc<DeploymentStore>,
     chain_store: Arc<crate::ChainStore>,
+    /// The latest block of the deployment that the replica `replica_id`
+    /// has applied. Only looked up once a query needs it
+    replica_block: Mutex<Option<Option<BlockNumber>>>,
 }
 
 impl QueryStore {
@@ -27,6 +31,28 @@ impl QueryStore {
             replica_id,
             store,
             chain_store,
+            replica_block: Mutex::new(None),
+        }
+    }
+
+    /// A read replica might not have applied the block at which a query
+    /// wants to see the data yet. In that case, use the main database which
+    /// always has all the blocks the deployment has processed
+    fn replica_for_block(&self, block: BlockNumber) -> Result<ReplicaId, Error> {
+        if self.replica_id == ReplicaId::Main || block == BLOCK_NUMBER_MAX {
+            return Ok(self.replica_id);
+        }
+
+        let mut replica_block = self.replica_block.lock().unwrap();
+        if replica_block.is_none() {
+            *replica_block = Some(
+                self.store
+                    .replica_block_number(&self.site, self.replica_id)?,
+            );
+        }
+        match *replica_block {
+            Some(Some(replica_block)) if replica_block >= block => Ok(self.replica_id),
+            _ => Ok(ReplicaId::Main),
         }
     }
 }
@@ -38,9 +64,12 @@ impl QueryStoreTrait for QueryStore {
         query: EntityQuery,
     ) -> Result<Vec<BTreeMap<String, q::Value>>, QueryExecutionError> {
         assert_eq!(&self.site.deployment, &query.subgraph_id);
+        let replica_id = self
+            .replica_for_block(query.block)
+            .map_err(|e| QueryExecutionError::StoreError(e.into()))?;
         let conn = self
             .store
-            .get_replica_conn(self.replica_id)
+            .get_replica_conn(replica_id)
             .map_err(|e| QueryExecutionError::StoreError(e.into()))?;
         self.store.execute_query(&conn, &self.site, query)
     }
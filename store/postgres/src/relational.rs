@@ -23,21 +23,24 @@ use crate::{
     primary::Namespace,
     relational_queries::{
         self as rq, ClampRangeQuery, ConflictingEntityQuery, EntityData, FilterCollection,
-        FilterQuery, FindManyQuery, FindQuery, InsertQuery, RevertClampQuery, RevertRemoveQuery,
+        FilterQuery, FindDerivedQuery, FindManyQuery, FindQuery, FindVersionsAtBlockQuery,
+        InsertQuery, RevertClampQuery, RevertRemoveQuery,
     },
 };
-use graph::components::store::EntityType;
-use graph::data::graphql::ext::{DocumentExt, ObjectTypeExt};
-use graph::data::schema::{FulltextConfig, FulltextDefinition, Schema, SCHEMA_TYPE_NAME};
+use graph::components::store::{EntityType, EntityVersionChange};
+use graph::data::graphql::ext::{DirectiveFinder, DocumentExt, ObjectTypeExt};
+use graph::data::schema::{
+    FulltextConfig, FulltextDefinition, IndexDefinition, Schema, SCHEMA_TYPE_NAME,
+};
 use graph::data::store::BYTES_SCALAR;
 use graph::data::subgraph::schema::{POI_OBJECT, POI_TABLE};
 use graph::prelude::{
-    anyhow, info, BlockNumber, Entity, EntityChange, EntityCollection, EntityFilter, EntityKey,
-    EntityOrder, EntityRange, EthereumBlockPointer, Logger, QueryExecutionError, StoreError,
-    StoreEvent, SubgraphDeploymentId, ValueType, BLOCK_NUMBER_MAX,
+    anyhow, info, BlockNumber, DerivedEntityQuery, Entity, EntityChange, EntityCollection,
+    EntityFilter, EntityKey, EntityOrder, EntityRange, EthereumBlockPointer, Logger,
+    QueryExecutionError, StoreError, StoreEvent, SubgraphDeploymentId, ValueType, BLOCK_NUMBER_MAX,
 };
 
-use crate::block_range::BLOCK_RANGE_COLUMN;
+use crate::block_range::{BlockRange, BLOCK_RANGE_COLUMN};
 pub use crate::catalog::Catalog;
 use crate::dynds;
 
@@ -49,6 +52,13 @@ use crate::dynds;
 /// bigger than Postgres' limit on such strings which is about 2k
 pub const STRING_PREFIX_SIZE: usize = 256;
 
+/// The maximum number of bind parameters Postgres allows in one statement
+const POSTGRES_MAX_PARAMETERS: usize = u16::MAX as usize;
+
+/// Postgres silently truncates identifiers that are longer than this many
+/// bytes, which can make two different names collide
+const MAX_IDENTIFIER_LEN: usize = 63;
+
 lazy_static! {
     /// Experimental: a list of fully qualified table names that contain
     /// entities that are like accounts in that they have a relatively small
@@ -205,6 +215,18 @@ type IdTypeMap = HashMap<EntityType, IdType>;
 
 type EnumMap = BTreeMap<String, Arc<BTreeSet<String>>>;
 
+/// The number of entity versions in a table, and how many of them are the
+/// current version of an entity
+#[derive(Clone, Debug)]
+pub struct VersionCount {
+    pub entity: EntityType,
+    pub table: SqlName,
+    /// The number of rows in the table
+    pub versions: i64,
+    /// The number of rows whose block range is still open
+    pub current: i64,
+}
+
 #[derive(Debug, Clone)]
 pub struct Layout {
     /// Maps the GraphQL name of a type to the relational table
@@ -299,6 +321,8 @@ impl Layout {
                     &catalog,
                     Schema::entity_fulltext_definitions(&obj_type.name, &schema.document)
                         .map_err(|_| StoreError::FulltextSearchNonDeterministic)?,
+                    Schema::entity_index_definitions(obj_type)
+                        .map_err(|e| StoreError::MalformedDirective(e.to_string()))?,
                     &enums,
                     &id_types,
                     i as u32,
@@ -354,6 +378,7 @@ impl Layout {
                     column_type: ColumnType::Bytes,
                     fulltext_fields: None,
                     is_reference: false,
+                    is_indexed: true,
                 },
                 Column {
                     name: SqlName::from(PRIMARY_KEY_COLUMN),
@@ -364,8 +389,10 @@ impl Layout {
                     column_type: ColumnType::String,
                     fulltext_fields: None,
                     is_reference: false,
+                    is_indexed: true,
                 },
             ],
+            composite_indexes: vec![],
             /// The position of this table in all the tables for this layout; this
             /// is really only needed for the tests to make the names of indexes
             /// predictable
@@ -512,6 +539,107 @@ impl Layout {
             .ok_or_else(|| StoreError::UnknownTable(entity.to_string()))
     }
 
+    /// Create a btree index on the attributes `fields` of `entity` for an
+    /// existing deployment. The index is built concurrently so that
+    /// indexing and queries can continue while it is being built. Since
+    /// Postgres does not allow that inside a transaction, `conn` must not
+    /// be in a transaction. Returns the name of the new index
+    pub fn create_manual_index(
+        &self,
+        conn: &PgConnection,
+        entity: &EntityType,
+        fields: &[String],
+    ) -> Result<String, StoreError> {
+        let table = self.table_for_entity(entity)?;
+        let columns = fields
+            .iter()
+            .map(|field| table.column_for_field(field))
+            .collect::<Result<Vec<_>, _>>()?;
+        if columns.is_empty() {
+            return Err(StoreError::Unknown(anyhow!(
+                "an index needs at least one attribute"
+            )));
+        }
+        if let Some(column) = columns.iter().find(|column| column.is_list()) {
+            return Err(StoreError::Unknown(anyhow!(
+                "the attribute {}.{} is a list and can not be part of a btree index",
+                table.object,
+                column.field
+            )));
+        }
+        let name = manual_index_name(table, &columns);
+        let sql = table
+            .btree_index_ddl(&self.catalog.namespace, &name, &columns, true)
+            .map_err(|_| StoreError::Unknown(anyhow!("failed to generate DDL for index")))?;
+        if let Err(e) = conn.batch_execute(&sql) {
+            // A failed `create index concurrently` leaves an invalid index
+            // behind that Postgres still maintains on every write
+            let drop = format!(
+                "drop index concurrently if exists {}.\"{}\"",
+                self.catalog.namespace, name
+            );
+            conn.batch_execute(&drop)?;
+            return Err(e.into());
+        }
+        Ok(name)
+    }
+
+    /// Drop the index `name` from this layout's database schema without
+    /// blocking writes to the underlying table. Just like for
+    /// `create_manual_index`, `conn` must not be in a transaction
+    pub fn drop_index(&self, conn: &PgConnection, name: &str) -> Result<(), StoreError> {
+        SqlName::check_valid_identifier(name, "index")?;
+        let indexes = crate::catalog::index_names(conn, &self.catalog.namespace)?;
+        if !indexes.iter().any(|index| index == name) {
+            return Err(StoreError::Unknown(anyhow!(
+                "there is no index `{}` in {}",
+                name,
+                self.catalog.namespace
+            )));
+        }
+        let sql = format!(
+            "drop index concurrently if exists {}.\"{}\"",
+            self.catalog.namespace, name
+        );
+        conn.batch_execute(&sql)?;
+        Ok(())
+    }
+
+    /// Count how many versions of entities, and how many current versions,
+    /// each table in this layout contains. This scans every table in full
+    /// and should only be used for diagnostics
+    pub fn version_counts(&self, conn: &PgConnection) -> Result<Vec<VersionCount>, StoreError> {
+        #[derive(QueryableByName)]
+        struct Counts {
+            #[sql_type = "diesel::sql_types::BigInt"]
+            versions: i64,
+            #[sql_type = "diesel::sql_types::BigInt"]
+            current: i64,
+        }
+
+        let mut tables: Vec<_> = self.tables.values().collect();
+        tables.sort_by(|a, b| a.name.as_str().cmp(b.name.as_str()));
+
+        let mut counts = Vec::new();
+        for table in tables {
+            let query = format!(
+                "select count(*) as versions, \
+                        count(*) filter (where upper_inf({})) as current \
+                   from {}",
+                BLOCK_RANGE_COLUMN, table.qualified_name
+            );
+            let Counts { versions, current } =
+                diesel::sql_query(query).get_result::<Counts>(conn)?;
+            counts.push(VersionCount {
+                entity: table.object.clone(),
+                table: table.name.clone(),
+                versions,
+                current,
+            });
+        }
+        Ok(counts)
+    }
+
     pub fn find(
         &self,
         conn: &PgConnection,
@@ -527,6 +655,74 @@ impl Layout {
             .transpose()
     }
 
+    /// Find the entities of `query.entity_type` whose `query.entity_field`
+    /// references the entity with id `query.value`
+    pub fn find_derived(
+        &self,
+        conn: &PgConnection,
+        query: &DerivedEntityQuery,
+        block: BlockNumber,
+    ) -> Result<Vec<Entity>, StoreError> {
+        let table = self.table_for_entity(&query.entity_type)?;
+        let column = table.column_for_field(&query.entity_field)?;
+        if !column.is_reference() {
+            return Err(StoreError::Unknown(anyhow!(
+                "the field `{}` of `{}` does not reference another entity",
+                query.entity_field,
+                query.entity_type
+            )));
+        }
+        FindDerivedQuery::new(table.as_ref(), column, &query.value, block)
+            .load::<EntityData>(conn)?
+            .into_iter()
+            .map(|entity_data| entity_data.deserialize_with_layout(self))
+            .collect()
+    }
+
+    /// The entities that were inserted, updated or removed in `block`,
+    /// ordered by entity type and id
+    pub fn entity_changes_in_block(
+        &self,
+        conn: &PgConnection,
+        block: BlockNumber,
+    ) -> Result<Vec<EntityVersionChange>, StoreError> {
+        // The changes to the proof of indexing are not interesting
+        let tables: Vec<_> = self
+            .tables
+            .values()
+            .filter(|table| table.object != *POI_OBJECT)
+            .map(|table| table.as_ref())
+            .collect();
+        if tables.is_empty() {
+            return Ok(vec![]);
+        }
+
+        let mut changes: BTreeMap<(EntityType, String), EntityVersionChange> = BTreeMap::new();
+        for ended in &[true, false] {
+            for entity_data in
+                FindVersionsAtBlockQuery::new(&tables, block, *ended).load::<EntityData>(conn)?
+            {
+                let entity_type = entity_data.entity_type();
+                let entity: Entity = entity_data.deserialize_with_layout(self)?;
+                let entity_id = entity.id()?;
+                let change = changes
+                    .entry((entity_type.clone(), entity_id.clone()))
+                    .or_insert_with(|| EntityVersionChange {
+                        entity_type,
+                        entity_id,
+                        before: None,
+                        after: None,
+                    });
+                if *ended {
+                    change.before = Some(entity);
+                } else {
+                    change.after = Some(entity);
+                }
+            }
+        }
+        Ok(changes.into_iter().map(|(_, change)| change).collect())
+    }
+
     pub fn find_many<'a>(
         &self,
         conn: &PgConnection,
@@ -572,6 +768,31 @@ impl Layout {
             .map(|ids| ids.len())?)
     }
 
+    /// Insert `entities` where each entity is valid for the corresponding
+    /// block range in `ranges`. Entities are inserted with as few
+    /// statements as the limit on bind parameters allows
+    pub fn insert_versions(
+        &self,
+        conn: &PgConnection,
+        entity_type: &EntityType,
+        mut entities: Vec<(EntityKey, Entity)>,
+        ranges: Vec<BlockRange>,
+        stopwatch: &StopwatchMetrics,
+    ) -> Result<usize, StoreError> {
+        let table = self.table_for_entity(entity_type)?;
+        let _section = stopwatch.start_section("insert_modification_insert_query");
+        let chunk_size = POSTGRES_MAX_PARAMETERS / (table.columns.len() + 1);
+        let mut ranges = ranges.into_iter();
+        let mut count = 0;
+        while !entities.is_empty() {
+            let rest = entities.split_off(entities.len().min(chunk_size));
+            let mut chunk = std::mem::replace(&mut entities, rest);
+            let chunk_ranges = ranges.by_ref().take(chunk.len()).collect();
+            count += InsertQuery::with_ranges(table, &mut chunk, chunk_ranges)?.execute(conn)?;
+        }
+        Ok(count)
+    }
+
     pub fn conflicting_entity(
         &self,
         conn: &PgConnection,
@@ -760,6 +981,7 @@ impl Layout {
     ) -> Result<(), StoreError> {
         crate::dynds::revert(conn, &subgraph, block)?;
         crate::deployment::revert_subgraph_errors(conn, &subgraph, block)?;
+        crate::mapping_log::revert(conn, &subgraph, block)?;
 
         Ok(())
     }
@@ -904,6 +1126,9 @@ pub struct Column {
     pub column_type: ColumnType,
     pub fulltext_fields: Option<HashSet<String>>,
     is_reference: bool,
+    /// Whether we create an attribute index for this column. Users can
+    /// turn that off with a `@noIndex` directive on the field
+    is_indexed: bool,
 }
 
 impl Column {
@@ -939,6 +1164,7 @@ impl Column {
             field_type: field.field_type.clone(),
             fulltext_fields: None,
             is_reference,
+            is_indexed: field.find_directive(String::from("noIndex")).is_none(),
         })
     }
 
@@ -953,6 +1179,7 @@ impl Column {
             column_type: ColumnType::TSVector(def.config.clone()),
             fulltext_fields: Some(def.included_fields.clone()),
             is_reference: false,
+            is_indexed: true,
         })
     }
 
@@ -999,6 +1226,10 @@ impl Column {
         self.is_reference
     }
 
+    pub fn is_indexed(&self) -> bool {
+        self.is_indexed
+    }
+
     pub fn is_primary_key(&self) -> bool {
         self.name.as_str() == PRIMARY_KEY_COLUMN
     }
@@ -1077,6 +1308,10 @@ pub struct Table {
 
     pub columns: Vec<Column>,
 
+    /// Composite indexes declared with `@index` on the GraphQL type. Each
+    /// entry lists the SQL names of the columns in the index
+    pub composite_indexes: Vec<Vec<SqlName>>,
+
     /// This kind of entity behaves like an account in that it has a low
     /// ratio of distinct entities to overall number of rows because
     /// entities are updated frequently on average
@@ -1093,6 +1328,7 @@ impl Table {
         defn: &s::ObjectType,
         catalog: &Catalog,
         fulltexts: Vec<FulltextDefinition>,
+        indexes: Vec<IndexDefinition>,
         enums: &EnumMap,
         id_types: &IdTypeMap,
         position: u32,
@@ -1107,6 +1343,22 @@ impl Table {
             .map(|field| Column::new(&table_name, field, catalog, enums, id_types))
             .chain(fulltexts.iter().map(|def| Column::new_fulltext(def)))
             .collect::<Result<Vec<Column>, StoreError>>()?;
+        let composite_indexes = indexes
+            .iter()
+            .map(|index| {
+                index
+                    .fields
+                    .iter()
+                    .map(|field| {
+                        columns
+                            .iter()
+                            .find(|column| &column.field == field)
+                            .map(|column| column.name.clone())
+                            .ok_or_else(|| StoreError::UnknownField(field.to_string()))
+                    })
+                    .collect::<Result<Vec<_>, _>>()
+            })
+            .collect::<Result<Vec<_>, _>>()?;
         let is_account_like =
             ACCOUNT_TABLES.contains(&format!("{}.{}", catalog.namespace, table_name));
         let table = Table {
@@ -1115,6 +1367,7 @@ impl Table {
             qualified_name: SqlName::qualified_name(&catalog.namespace, &table_name),
             is_account_like,
             columns,
+            composite_indexes,
             position,
         };
         Ok(table)
@@ -1238,12 +1491,16 @@ impl Table {
         // Create indexes. Skip columns whose type is an array of enum,
         // since there is no good way to index them with Postgres 9.6.
         // Once we move to Postgres 11, we can enable that
-        // (tracked in graph-node issue #1330)
+        // (tracked in graph-node issue #1330). We also skip columns that
+        // the user marked with `@noIndex`; we filter those out after
+        // numbering columns so that the names of the remaining indexes
+        // do not depend on whether some columns are not indexed
         for (i, column) in self
             .columns
             .iter()
             .filter(|col| !(col.is_list() && col.is_enum()))
             .enumerate()
+            .filter(|(_, col)| col.is_indexed())
         {
             let (method, index_expr) = if column.is_reference() && !column.is_list() {
                 // For foreign keys, index the key together with the block range
@@ -1282,8 +1539,63 @@ impl Table {
                 index_expr = index_expr,
             )?;
         }
+
+        // Create the composite indexes the user asked for with `@index`
+        for (i, names) in self.composite_indexes.iter().enumerate() {
+            let columns = names
+                .iter()
+                .map(|name| self.column(name).ok_or(fmt::Error))
+                .collect::<Result<Vec<_>, _>>()?;
+            let index_name = format!(
+                "composite_{table_index}_{index}_{table_name}",
+                table_index = self.position,
+                index = i,
+                table_name = self.name
+            );
+            out.push_str(&self.btree_index_ddl(
+                &layout.catalog.namespace,
+                &index_name,
+                &columns,
+                false,
+            )?);
+        }
         writeln!(out)
     }
+
+    /// Generate the `create index` statement for a btree index called
+    /// `name` on `columns`. Just like for attribute indexes, we only index
+    /// a prefix of text columns. With `concurrently`, the index is built
+    /// without locking out writes to the table
+    fn btree_index_ddl(
+        &self,
+        namespace: &Namespace,
+        name: &str,
+        columns: &[&Column],
+        concurrently: bool,
+    ) -> Result<String, fmt::Error> {
+        let index_expr = columns
+            .iter()
+            .map(|column| {
+                if column.is_text() {
+                    format!("left({}, {})", column.name.quoted(), STRING_PREFIX_SIZE)
+                } else {
+                    column.name.quoted()
+                }
+            })
+            .collect::<Vec<_>>()
+            .join(", ");
+        let mut out = String::new();
+        write!(
+            out,
+            "create index {concurrently}\"{name}\"\n    on {schema_name}.\"{table_name}\" using btree({index_expr});\n",
+            concurrently = if concurrently { "concurrently " } else { "" },
+            name = name,
+            schema_name = namespace,
+            table_name = self.name,
+            index_expr = index_expr,
+        )?;
+        Ok(out)
+    }
 }
 
 /// Return the enclosed named type for a field type, i.e., the type after
@@ -1296,6 +1608,31 @@ fn named_type(field_type: &q::Type) -> &str {
     }
 }
 
+/// The name for a manual index on `columns` of `table`. Names that would
+/// be too long for Postgres are truncated and made unique again by
+/// appending a hash of the full name
+fn manual_index_name(table: &Table, columns: &[&Column]) -> String {
+    let name = format!(
+        "manual_{}_{}",
+        table.name,
+        columns
+            .iter()
+            .map(|column| column.name.as_str())
+            .collect::<Vec<_>>()
+            .join("_")
+    );
+    if name.len() <= MAX_IDENTIFIER_LEN {
+        return name;
+    }
+    let hash = blake3::hash(name.as_bytes()).to_hex();
+    let hash = &hash[..8];
+    let mut end = MAX_IDENTIFIER_LEN - hash.len() - 1;
+    while !name.is_char_boundary(end) {
+        end -= 1;
+    }
+    format!("{}_{}", &name[..end], hash)
+}
+
 fn derived_column(field: &s::Field) -> bool {
     field
         .directives
@@ -1370,6 +1707,44 @@ mod tests {
         let layout = test_layout(FORWARD_ENUM_GQL);
         let sql = layout.as_ddl().expect("Failed to generate DDL");
         assert_eq!(FORWARD_ENUM_SQL, sql);
+
+        let layout = test_layout(INDEX_GQL);
+        let sql = layout.as_ddl().expect("Failed to generate DDL");
+        assert_eq!(INDEX_DDL, sql);
+    }
+
+    #[test]
+    fn manual_index_name() {
+        let layout = test_layout(INDEX_GQL);
+        let table = layout.table(&SqlName::from("transfer")).unwrap();
+        let column = |name: &str| table.column(&SqlName::from(name)).unwrap();
+
+        let name = super::manual_index_name(table, &[column("owner"), column("timestamp")]);
+        assert_eq!("manual_transfer_owner_timestamp", name);
+
+        let mut columns = vec![
+            "owner",
+            "memo",
+            "note",
+            "timestamp",
+            "owner",
+            "memo",
+            "note",
+        ]
+        .into_iter()
+        .map(column)
+        .collect::<Vec<_>>();
+        columns.push(column("timestamp"));
+        let long = super::manual_index_name(table, &columns);
+        assert_eq!(MAX_IDENTIFIER_LEN, long.len());
+        assert!(long.starts_with("manual_transfer_owner_memo_note_timestamp_"));
+
+        // Names that only differ after the cutoff still differ
+        columns.pop();
+        columns.push(column("id"));
+        let other = super::manual_index_name(table, &columns);
+        assert_eq!(MAX_IDENTIFIER_LEN, other.len());
+        assert_ne!(long, other);
     }
 
     #[test]
@@ -1857,5 +2232,47 @@ create index attr_0_0_thing_id
 create index attr_0_1_thing_orientation
     on sgd0815.\"thing\" using btree(\"orientation\");
 
+";
+
+    const INDEX_GQL: &str = "
+type Transfer @entity @index(fields: [\"owner\", \"timestamp\"]) @index(fields: [\"note\", \"owner\"]) {
+    id: ID!,
+    owner: Bytes!,
+    memo: String @noIndex,
+    note: String,
+    timestamp: BigInt!
+}
+";
+
+    const INDEX_DDL: &str = "create table sgd0815.\"transfer\" (
+        \"id\"                 text not null,
+        \"owner\"              bytea not null,
+        \"memo\"               text,
+        \"note\"               text,
+        \"timestamp\"          numeric not null,
+
+        vid                  bigserial primary key,
+        block_range          int4range not null,
+        exclude using gist   (id with =, block_range with &&)
+);
+create index brin_transfer
+    on sgd0815.transfer
+ using brin(lower(block_range), coalesce(upper(block_range), 2147483647), vid);
+create index transfer_block_range_closed
+    on sgd0815.transfer(coalesce(upper(block_range), 2147483647))
+ where coalesce(upper(block_range), 2147483647) < 2147483647;
+create index attr_0_0_transfer_id
+    on sgd0815.\"transfer\" using btree(\"id\");
+create index attr_0_1_transfer_owner
+    on sgd0815.\"transfer\" using btree(\"owner\");
+create index attr_0_3_transfer_note
+    on sgd0815.\"transfer\" using btree(left(\"note\", 256));
+create index attr_0_4_transfer_timestamp
+    on sgd0815.\"transfer\" using btree(\"timestamp\");
+create index \"composite_0_0_transfer\"
+    on sgd0815.\"transfer\" using btree(\"owner\", \"timestamp\");
+create index \"composite_0_1_transfer\"
+    on sgd0815.\"transfer\" using btree(left(\"note\", 256), \"owner\");
+
 ";
 }
@@ -22,7 +22,7 @@ use std::str::FromStr;
 use graph::prelude::{
     anyhow, q, serde_json, Attribute, BlockNumber, ChildMultiplicity, Entity, EntityCollection,
     EntityFilter, EntityKey, EntityLink, EntityOrder, EntityRange, EntityWindow, ParentLink,
-    QueryExecutionError, StoreError, Value,
+    QueryExecutionError, StoreError, Value, BLOCK_NUMBER_MAX,
 };
 use graph::{
     components::store::EntityType,
@@ -1176,6 +1176,137 @@ impl<'a> LoadQuery<PgConnection, EntityData> for FindQuery<'a> {
 
 impl<'a, Conn> RunQueryDsl<Conn> for FindQuery<'a> {}
 
+/// Find the entities in `table` whose `column` references the entity with
+/// `id`, i.e., the entities that make up a derived field of that entity
+#[derive(Debug, Clone, Constructor)]
+pub struct FindDerivedQuery<'a> {
+    table: &'a Table,
+    column: &'a Column,
+    id: &'a str,
+    block: BlockNumber,
+}
+
+impl<'a> QueryFragment<Pg> for FindDerivedQuery<'a> {
+    fn walk_ast(&self, mut out: AstPass<Pg>) -> QueryResult<()> {
+        out.unsafe_to_cache_prepared();
+
+        // Generate
+        //    select '..' as entity, to_jsonb(e.*) as data
+        //      from schema.table e where e.column = $1 (or $1 = any(e.column))
+        //       and block_range @> $block
+        //     order by e.id
+        out.push_sql("select ");
+        out.push_bind_param::<Text, _>(&self.table.object.as_str())?;
+        out.push_sql(" as entity, to_jsonb(e.*) as data\n");
+        out.push_sql("  from ");
+        out.push_sql(self.table.qualified_name.as_str());
+        out.push_sql(" e\n where ");
+        if self.column.is_list() {
+            self.column.bind_id(&self.id, &mut out)?;
+            out.push_sql(" = any(e.");
+            out.push_identifier(self.column.name.as_str())?;
+            out.push_sql(")");
+        } else {
+            out.push_sql("e.");
+            out.push_identifier(self.column.name.as_str())?;
+            out.push_sql(" = ");
+            self.column.bind_id(&self.id, &mut out)?;
+        }
+        out.push_sql(" and ");
+        BlockRangeContainsClause::new(&self.table, "e.", self.block).walk_ast(out.reborrow())?;
+        out.push_sql("\n order by e.");
+        out.push_identifier(PRIMARY_KEY_COLUMN)
+    }
+}
+
+impl<'a> QueryId for FindDerivedQuery<'a> {
+    type QueryId = ();
+
+    const HAS_STATIC_QUERY_ID: bool = false;
+}
+
+impl<'a> LoadQuery<PgConnection, EntityData> for FindDerivedQuery<'a> {
+    fn internal_load(self, conn: &PgConnection) -> QueryResult<Vec<EntityData>> {
+        conn.query_by_name(&self)
+    }
+}
+
+impl<'a, Conn> RunQueryDsl<Conn> for FindDerivedQuery<'a> {}
+
+/// Find the versions of entities in `tables` that were written in `block`,
+/// i.e., whose block range starts at `block`, or, if `ended` is `true`, the
+/// versions that stopped being current in `block` because the entity was
+/// updated or removed, i.e., whose block range ends at `block`. The
+/// conditions on the block range use the same expressions as the BRIN index
+/// on `lower(block_range)` and the `block_range_closed` index on the upper
+/// bound so that the query does not have to scan the whole table
+#[derive(Debug, Clone, Constructor)]
+pub struct FindVersionsAtBlockQuery<'a> {
+    tables: &'a [&'a Table],
+    block: BlockNumber,
+    ended: bool,
+}
+
+impl<'a> QueryFragment<Pg> for FindVersionsAtBlockQuery<'a> {
+    fn walk_ast(&self, mut out: AstPass<Pg>) -> QueryResult<()> {
+        out.unsafe_to_cache_prepared();
+
+        // Generate
+        //    select $object0 as entity, to_jsonb(e.*) as data
+        //      from schema.<table0> e where lower(e.block_range) = $block
+        //    union all
+        //    ...
+        // or, if `ended` is true, with the condition
+        //    coalesce(upper(e.block_range), $max) = $block
+        //    and coalesce(upper(e.block_range), $max) < $max
+        // where the second clause, with `$max` inlined, lets Postgres use
+        // the partial index on the upper bound
+        for (i, table) in self.tables.iter().enumerate() {
+            if i > 0 {
+                out.push_sql("\nunion all\n");
+            }
+            out.push_sql("select ");
+            out.push_bind_param::<Text, _>(&table.object.as_str())?;
+            out.push_sql(" as entity, to_jsonb(e.*) as data\n");
+            out.push_sql("  from ");
+            out.push_sql(table.qualified_name.as_str());
+            out.push_sql(" e\n where ");
+            if self.ended {
+                let upper = format!(
+                    "coalesce(upper(e.{}), {})",
+                    BLOCK_RANGE_COLUMN, BLOCK_NUMBER_MAX
+                );
+                out.push_sql(&upper);
+                out.push_sql(" = ");
+                out.push_bind_param::<Integer, _>(&self.block)?;
+                out.push_sql(" and ");
+                out.push_sql(&upper);
+                out.push_sql(&format!(" < {}", BLOCK_NUMBER_MAX));
+            } else {
+                out.push_sql("lower(e.");
+                out.push_identifier(BLOCK_RANGE_COLUMN)?;
+                out.push_sql(") = ");
+                out.push_bind_param::<Integer, _>(&self.block)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl<'a> QueryId for FindVersionsAtBlockQuery<'a> {
+    type QueryId = ();
+
+    const HAS_STATIC_QUERY_ID: bool = false;
+}
+
+impl<'a> LoadQuery<PgConnection, EntityData> for FindVersionsAtBlockQuery<'a> {
+    fn internal_load(self, conn: &PgConnection) -> QueryResult<Vec<EntityData>> {
+        conn.query_by_name(&self)
+    }
+}
+
+impl<'a, Conn> RunQueryDsl<Conn> for FindVersionsAtBlockQuery<'a> {}
+
 #[derive(Debug, Clone, Constructor)]
 pub struct FindManyQuery<'a> {
     pub(crate) namespace: &'a Namespace,
@@ -1237,7 +1368,8 @@ pub struct InsertQuery<'a> {
     table: &'a Table,
     entities: &'a Vec<(EntityKey, Entity)>,
     unique_columns: Vec<&'a Column>,
-    block: BlockNumber,
+    /// The block range for each of `entities`
+    ranges: Vec<BlockRange>,
 }
 
 impl<'a> InsertQuery<'a> {
@@ -1246,6 +1378,18 @@ impl<'a> InsertQuery<'a> {
         entities: &'a mut Vec<(EntityKey, Entity)>,
         block: BlockNumber,
     ) -> Result<InsertQuery<'a>, StoreError> {
+        let ranges = vec![(block..).into(); entities.len()];
+        Self::with_ranges(table, entities, ranges)
+    }
+
+    /// Insert each of `entities` with the corresponding block range from
+    /// `ranges` rather than making all of them current as of one block
+    pub fn with_ranges(
+        table: &'a Table,
+        entities: &'a mut Vec<(EntityKey, Entity)>,
+        ranges: Vec<BlockRange>,
+    ) -> Result<InsertQuery<'a>, StoreError> {
+        assert_eq!(entities.len(), ranges.len());
         for (entity_key, entity) in entities.iter_mut() {
             for column in table.columns.iter() {
                 match column.fulltext_fields.as_ref() {
@@ -1280,7 +1424,7 @@ impl<'a> InsertQuery<'a> {
             table,
             entities,
             unique_columns,
-            block,
+            ranges,
         })
     }
 
@@ -1325,8 +1469,13 @@ impl<'a> QueryFragment<Pg> for InsertQuery<'a> {
         out.push_sql(") values\n");
 
         // Use a `Peekable` iterator to help us decide how to finalize each line.
-        let mut iter = self.entities.iter().map(|(_key, entity)| entity).peekable();
-        while let Some(entity) = iter.next() {
+        let mut iter = self
+            .entities
+            .iter()
+            .map(|(_key, entity)| entity)
+            .zip(self.ranges.iter())
+            .peekable();
+        while let Some((entity, block_range)) = iter.next() {
             out.push_sql("(");
             for column in &self.unique_columns {
                 // If the column name is not within this entity's fields, we will issue the
@@ -1338,8 +1487,7 @@ impl<'a> QueryFragment<Pg> for InsertQuery<'a> {
                 }
                 out.push_sql(", ");
             }
-            let block_range: BlockRange = (self.block..).into();
-            out.push_bind_param::<Range<Integer>, _>(&block_range)?;
+            out.push_bind_param::<Range<Integer>, _>(block_range)?;
             out.push_sql(")");
 
             // finalize line according to remaining entities to insert
@@ -0,0 +1,131 @@
+//! SQL queries to record the automatic restarts of deployments that failed
+//! with a non-deterministic error, and to load the current retry state
+
+use diesel::{
+    pg::PgConnection,
+    prelude::{ExpressionMethods, QueryDsl, RunQueryDsl},
+    sql_query,
+    sql_types::{Array, BigInt, Binary, Double, Integer, Nullable, Text},
+    update,
+};
+use std::collections::HashMap;
+
+use graph::{
+    data::subgraph::{schema::SubgraphRestart, status},
+    prelude::{web3::types::H256, StoreError, SubgraphDeploymentId},
+};
+
+table! {
+    subgraphs.subgraph_restart (vid) {
+        vid -> BigInt,
+        subgraph_id -> Text,
+        attempt -> Integer,
+        message -> Text,
+        block_hash -> Nullable<Binary>,
+        block_number -> Nullable<Integer>,
+        recovered -> Bool,
+    }
+}
+
+/// Record that deployment `id` failed and will be restarted after
+/// `restart.delay`
+pub(crate) fn insert(
+    conn: &PgConnection,
+    id: &SubgraphDeploymentId,
+    restart: SubgraphRestart,
+) -> Result<(), StoreError> {
+    const QUERY: &str = "\
+      insert into subgraphs.subgraph_restart(subgraph_id, attempt, message,
+                  block_hash, block_number, restart_at)
+      values ($1, $2, $3, $4, $5, now() + $6 * interval '1 second')";
+
+    let SubgraphRestart {
+        attempt,
+        message,
+        block_ptr,
+        delay,
+    } = restart;
+
+    sql_query(QUERY)
+        .bind::<Text, _>(id.as_str())
+        .bind::<Integer, _>(attempt)
+        .bind::<Text, _>(message)
+        .bind::<Nullable<Binary>, _>(block_ptr.as_ref().map(|ptr| ptr.hash_slice().to_vec()))
+        .bind::<Nullable<Integer>, _>(block_ptr.map(|ptr| ptr.number))
+        .bind::<Nullable<Double>, _>(delay.map(|delay| delay.as_secs_f64()))
+        .execute(conn)?;
+    Ok(())
+}
+
+/// Mark all restarts of deployment `id` as recovered since it processed a
+/// block successfully
+pub(crate) fn recover(conn: &PgConnection, id: &SubgraphDeploymentId) -> Result<(), StoreError> {
+    use subgraph_restart as r;
+
+    update(
+        r::table
+            .filter(r::subgraph_id.eq(id.as_str()))
+            .filter(r::recovered.eq(false)),
+    )
+    .set(r::recovered.eq(true))
+    .execute(conn)?;
+    Ok(())
+}
+
+#[derive(QueryableByName)]
+struct RetryState {
+    #[sql_type = "Text"]
+    subgraph_id: String,
+    #[sql_type = "Integer"]
+    attempt: i32,
+    #[sql_type = "Text"]
+    message: String,
+    #[sql_type = "Nullable<Binary>"]
+    block_hash: Option<Vec<u8>>,
+    #[sql_type = "Nullable<Integer>"]
+    block_number: Option<i32>,
+    #[sql_type = "BigInt"]
+    failed_at: i64,
+    #[sql_type = "Nullable<BigInt>"]
+    restart_at: Option<i64>,
+}
+
+/// The latest restart of each of the deployments `ids` that has not
+/// recovered since
+pub(crate) fn retry_states(
+    conn: &PgConnection,
+    ids: Vec<String>,
+) -> Result<HashMap<String, status::RetryState>, StoreError> {
+    const QUERY: &str = "\
+      select distinct on (subgraph_id)
+             subgraph_id, attempt, message, block_hash, block_number,
+             extract(epoch from failed_at)::int8 as failed_at,
+             extract(epoch from restart_at)::int8 as restart_at
+        from subgraphs.subgraph_restart
+       where subgraph_id = any($1)
+         and not recovered
+       order by subgraph_id, vid desc";
+
+    Ok(sql_query(QUERY)
+        .bind::<Array<Text>, _>(ids)
+        .load::<RetryState>(conn)?
+        .into_iter()
+        .map(|state| {
+            let block = match (state.block_hash, state.block_number) {
+                (Some(hash), Some(number)) => Some(status::EthereumBlock::new(
+                    H256::from_slice(&hash),
+                    number as u64,
+                )),
+                _ => None,
+            };
+            let retry_state = status::RetryState {
+                attempt: state.attempt,
+                message: state.message,
+                block,
+                failed_at: state.failed_at,
+                next_attempt_at: state.restart_at,
+            };
+            (state.subgraph_id, retry_state)
+        })
+        .collect())
+}
@@ -5,15 +5,18 @@ use graph::{
     components::{
         server::index_node::VersionInfo,
         store::{
-            BlockStore as BlockStoreTrait, QueryStoreManager, StatusStore,
+            BlockStore as BlockStoreTrait, EntityVersionChange, QueryStoreManager, StatusStore,
             SubgraphStore as SubgraphStoreTrait,
         },
     },
     constraint_violation,
-    data::subgraph::status,
+    data::subgraph::{
+        schema::{HealthEvent, MappingLog, MappingLogLevel},
+        status,
+    },
     prelude::{
-        web3::types::Address, CheapClone, EthereumBlockPointer, QueryExecutionError, StoreError,
-        SubgraphDeploymentId,
+        web3::types::Address, BlockNumber, CheapClone, EthereumBlockPointer, QueryExecutionError,
+        StoreError, SubgraphDeploymentId,
     },
 };
 
@@ -129,4 +132,39 @@ impl StatusStore for Store {
             .clone()
             .get_proof_of_indexing(subgraph_id, indexer, block)
     }
+
+    fn mapping_logs(
+        &self,
+        subgraph_id: &SubgraphDeploymentId,
+        level: MappingLogLevel,
+        from_block: Option<BlockNumber>,
+        to_block: Option<BlockNumber>,
+    ) -> Result<Vec<MappingLog>, StoreError> {
+        self.subgraph_store
+            .mapping_logs(subgraph_id, level, from_block, to_block)
+    }
+
+    fn dynamic_data_sources(
+        &self,
+        subgraph_id: &SubgraphDeploymentId,
+    ) -> Result<Vec<status::DataSource>, StoreError> {
+        self.subgraph_store.dynamic_data_sources(subgraph_id)
+    }
+
+    fn entity_changes_in_block(
+        &self,
+        subgraph_id: &SubgraphDeploymentId,
+        block: BlockNumber,
+    ) -> Result<Vec<EntityVersionChange>, StoreError> {
+        self.subgraph_store
+            .entity_changes_in_block(subgraph_id, block)
+    }
+
+    fn health_history(
+        &self,
+        subgraph_id: &SubgraphDeploymentId,
+        first: i64,
+    ) -> Result<Vec<HealthEvent>, StoreError> {
+        self.subgraph_store.health_history(subgraph_id, first)
+    }
 }
@@ -5,36 +5,40 @@ use diesel::{
     types::{FromSql, ToSql},
 };
 use std::iter::FromIterator;
-use std::sync::RwLock;
+use std::sync::{Mutex, RwLock};
 use std::{collections::BTreeMap, collections::HashMap, sync::Arc};
 use std::{fmt, io::Write};
 
 use graph::{
     components::{
         server::index_node::VersionInfo,
-        store::{self, EntityType},
+        store::{self, DynamicDataSourceKey, EntityType, EntityVersionChange},
     },
     constraint_violation,
     data::query::QueryTarget,
-    data::subgraph::schema::SubgraphError,
+    data::subgraph::schema::{
+        HealthEvent, HealthEventKind, MappingLog, MappingLogLevel, SubgraphError, SubgraphRestart,
+    },
     data::subgraph::status,
     prelude::StoreEvent,
     prelude::SubgraphDeploymentEntity,
     prelude::{
-        futures03::future::join_all, lazy_static, o, web3::types::Address, ApiSchema,
-        DeploymentState, DynTryFuture, Entity, EntityKey, EntityModification, EntityQuery, Error,
-        EthereumBlockPointer, Logger, NodeId, QueryExecutionError, Schema, StopwatchMetrics,
-        StoreError, SubgraphDeploymentId, SubgraphName, SubgraphStore as SubgraphStoreTrait,
-        SubgraphVersionSwitchingMode,
+        debug, futures03::future::join_all, lazy_static, o, web3::types::Address, AdminAction,
+        ApiSchema, BlockNumber, DeploymentState, DerivedEntityQuery, DynTryFuture, Entity,
+        EntityKey, EntityModification, EntityQuery, Error, EthereumBlockPointer, Logger, NodeId,
+        QueryExecutionError, Schema, StopwatchMetrics, StoreError, SubgraphDeploymentId,
+        SubgraphName, SubgraphStore as SubgraphStoreTrait, SubgraphVersionSwitchingMode,
     },
 };
 use store::StoredDynamicDataSource;
 
+use crate::{catalog::TableStats, relational::VersionCount};
 use crate::{connection_pool::ConnectionPool, primary, primary::Site};
 use crate::{
     deployment_store::{DeploymentStore, ReplicaId},
     detail::DeploymentDetail,
     primary::UnusedDeployment,
+    write_buffer::{BlockChanges, WriteBuffer},
 };
 
 /// The name of a database shard; valid names must match `[a-z0-9_]+`
@@ -172,6 +176,9 @@ pub struct SubgraphStore {
     /// Cache for the mapping from deployment id to shard/namespace/id
     sites: RwLock<HashMap<SubgraphDeploymentId, Arc<Site>>>,
     placer: Arc<dyn DeploymentPlacer + Send + Sync + 'static>,
+    /// Changes from `buffer_block_operations` that have not been written
+    /// to the database yet
+    write_buffers: Mutex<HashMap<SubgraphDeploymentId, Arc<Mutex<WriteBuffer>>>>,
 }
 
 impl SubgraphStore {
@@ -222,6 +229,7 @@ impl SubgraphStore {
             stores,
             sites,
             placer,
+            write_buffers: Mutex::new(HashMap::new()),
         }
     }
 
@@ -232,6 +240,7 @@ impl SubgraphStore {
             store.layout_cache.lock().unwrap().clear();
         }
         self.sites.write().unwrap().clear();
+        self.write_buffers.lock().unwrap().clear();
     }
 
     fn site(&self, id: &SubgraphDeploymentId) -> Result<Arc<Site>, StoreError> {
@@ -272,6 +281,43 @@ impl SubgraphStore {
         Ok((store, site))
     }
 
+    /// The write buffer for `id`, creating an empty one if there is none
+    fn write_buffer(&self, id: &SubgraphDeploymentId) -> Arc<Mutex<WriteBuffer>> {
+        let mut buffers = self.write_buffers.lock().unwrap();
+        if let Some(buffer) = buffers.get(id) {
+            return buffer.clone();
+        }
+        buffers.entry(id.clone()).or_default().clone()
+    }
+
+    /// The write buffer for `id` if there is one. Reads should use this
+    /// rather than `write_buffer` so that they do not create buffers for
+    /// deployments that never buffer anything
+    fn buffered(&self, id: &SubgraphDeploymentId) -> Option<Arc<Mutex<WriteBuffer>>> {
+        self.write_buffers.lock().unwrap().get(id).cloned()
+    }
+
+    /// Write everything in `buffer` to the database. The buffer is emptied
+    /// even if writing fails; since buffered blocks are written in one
+    /// transaction, the database is then still at the block where the
+    /// last write left it, and the subgraph will process the lost blocks
+    /// again when it is restarted
+    fn flush_buffer(
+        &self,
+        id: &SubgraphDeploymentId,
+        buffer: &mut WriteBuffer,
+    ) -> Result<(), StoreError> {
+        if buffer.is_empty() {
+            return Ok(());
+        }
+        let (blocks, stopwatch) = buffer.take();
+        // Unwrap: the buffer is not empty, and therefore has a stopwatch
+        let stopwatch = stopwatch.unwrap();
+        let (store, site) = self.store(id)?;
+        let event = store.transact_blocks(site.as_ref(), blocks, stopwatch)?;
+        self.send_store_event(&event)
+    }
+
     fn place(
         &self,
         name: &SubgraphName,
@@ -354,20 +400,28 @@ impl SubgraphStore {
 
         // FIXME: This simultaneously holds a `primary_conn` and a shard connection, which can
         // potentially deadlock.
-        let pconn = self.primary_conn()?;
-        pconn.transaction(|| -> Result<_, StoreError> {
-            // Create subgraph, subgraph version, and assignment
-            let changes = pconn.create_subgraph_version(
-                name,
-                &schema.id,
-                node_id,
-                mode,
-                exists_and_synced,
-            )?;
-            let event = StoreEvent::new(changes);
-            pconn.send_store_event(&event)?;
-            Ok(())
-        })
+        let message = format!("current version of {}", name);
+        let current = {
+            let pconn = self.primary_conn()?;
+            pconn.transaction(|| -> Result<_, StoreError> {
+                // Create subgraph, subgraph version, and assignment
+                let (changes, current) = pconn.create_subgraph_version(
+                    name,
+                    &schema.id,
+                    node_id,
+                    mode,
+                    exists_and_synced,
+                )?;
+                let event = StoreEvent::new(changes);
+                pconn.send_store_event(&event)?;
+                Ok(current)
+            })?
+        };
+
+        if current {
+            self.record_health_event(&site.deployment, HealthEventKind::VersionSwitch, &message);
+        }
+        Ok(())
     }
 
     // Only for tests to simplify their handling of test fixtures, so that
@@ -556,12 +610,89 @@ impl SubgraphStore {
         }
 
         store.drop_deployment(&site)?;
+        self.write_buffers.lock().unwrap().remove(id);
 
         self.primary_conn()?.drop_site(&site.deployment)?;
 
         Ok(())
     }
 
+    /// Create a btree index on the attributes `fields` of `entity` in the
+    /// deployment `id`. The index is built concurrently and this method
+    /// only returns once the index is ready. Returns the name of the index
+    pub fn create_manual_index(
+        &self,
+        id: &SubgraphDeploymentId,
+        entity: &EntityType,
+        fields: Vec<String>,
+    ) -> Result<String, StoreError> {
+        let (store, site) = self.store(id)?;
+        store.create_manual_index(&site, entity, fields)
+    }
+
+    /// List the indexes on the table for `entity` in the deployment `id` as
+    /// pairs of index name and index definition
+    pub fn indexes_for_entity(
+        &self,
+        id: &SubgraphDeploymentId,
+        entity: &EntityType,
+    ) -> Result<Vec<(String, String)>, StoreError> {
+        let (store, site) = self.store(id)?;
+        store.indexes_for_entity(&site, entity)
+    }
+
+    /// Drop the index `name` from the deployment `id`
+    pub fn drop_index(&self, id: &SubgraphDeploymentId, name: &str) -> Result<(), StoreError> {
+        let (store, site) = self.store(id)?;
+        store.drop_index(&site, name)
+    }
+
+    /// Return how many entity versions each table of the deployment `id`
+    /// has, together with size and index usage information for all its
+    /// tables. Counting versions requires a full scan of each table
+    pub fn stats(
+        &self,
+        id: &SubgraphDeploymentId,
+    ) -> Result<(Vec<VersionCount>, Vec<TableStats>), StoreError> {
+        let (store, site) = self.store(id)?;
+        store.stats(&site)
+    }
+
+    /// The digest of the proof of indexing for each causality region of the
+    /// deployment as of `block`
+    pub fn poi_digests(
+        &self,
+        id: &SubgraphDeploymentId,
+        block: BlockNumber,
+    ) -> Result<Option<BTreeMap<String, Vec<u8>>>, StoreError> {
+        let (store, site) = self.store(id)?;
+        store.poi_digests(&site, block)
+    }
+
+    /// Record the proof of indexing events of the deployment the next time
+    /// it processes `block`. Since deployments only process a block again
+    /// after a reorg, this is usually followed by a rewind to the block
+    /// before `block`
+    pub fn request_poi_events(
+        &self,
+        id: &SubgraphDeploymentId,
+        block: BlockNumber,
+    ) -> Result<(), StoreError> {
+        let (store, site) = self.store(id)?;
+        store.request_poi_events(&site, block)
+    }
+
+    /// The proof of indexing events that the deployment recorded for
+    /// `block`, by causality region
+    pub fn poi_events(
+        &self,
+        id: &SubgraphDeploymentId,
+        block: BlockNumber,
+    ) -> Result<Option<BTreeMap<String, Vec<String>>>, StoreError> {
+        let (store, site) = self.store(id)?;
+        store.poi_events(&site, block)
+    }
+
     pub(crate) fn status(&self, filter: status::Filter) -> Result<Vec<status::Info>, StoreError> {
         let deployments = match filter {
             status::Filter::SubgraphName(name) => {
@@ -598,6 +729,67 @@ impl SubgraphStore {
         Ok(infos)
     }
 
+    pub(crate) fn mapping_logs(
+        &self,
+        id: &SubgraphDeploymentId,
+        level: MappingLogLevel,
+        from_block: Option<BlockNumber>,
+        to_block: Option<BlockNumber>,
+    ) -> Result<Vec<MappingLog>, StoreError> {
+        let (store, site) = self.store(id)?;
+        store.mapping_logs(site.as_ref(), level, from_block, to_block)
+    }
+
+    /// The dynamic data sources of deployment `id`, in the order in which
+    /// they were created
+    pub fn dynamic_data_sources(
+        &self,
+        id: &SubgraphDeploymentId,
+    ) -> Result<Vec<status::DataSource>, StoreError> {
+        let (store, site) = self.store(id)?;
+        store.dynamic_data_sources(site.as_ref())
+    }
+
+    /// The latest `first` entries in the health history of deployment `id`,
+    /// from oldest to newest
+    pub fn health_history(
+        &self,
+        id: &SubgraphDeploymentId,
+        first: i64,
+    ) -> Result<Vec<HealthEvent>, StoreError> {
+        let (store, site) = self.store(id)?;
+        store.health_history(site.as_ref(), first)
+    }
+
+    /// Add an event to the health history of deployment `id`. The event
+    /// describes a change that has already been made, and failing to
+    /// record it should not make that change look like it failed. Failures
+    /// are therefore only logged
+    fn record_health_event(&self, id: &SubgraphDeploymentId, kind: HealthEventKind, message: &str) {
+        let res = self
+            .store(id)
+            .and_then(|(store, site)| store.record_health_event(site.as_ref(), kind, message));
+        if let Err(e) = res {
+            warn!(self.logger, "Failed to record health event";
+                  "id" => id.as_str(),
+                  "kind" => format!("{:?}", kind),
+                  "message" => message,
+                  "error" => e.to_string());
+        }
+    }
+
+    pub(crate) fn entity_changes_in_block(
+        &self,
+        id: &SubgraphDeploymentId,
+        block: BlockNumber,
+    ) -> Result<Vec<EntityVersionChange>, StoreError> {
+        // The changes are read from the block ranges in the database, and
+        // changes that are still buffered would be missing
+        self.flush(id)?;
+        let (store, site) = self.store(id)?;
+        store.entity_changes_in_block(site.as_ref(), block)
+    }
+
     pub(crate) fn version_info(&self, version: &str) -> Result<VersionInfo, StoreError> {
         if let Some((deployment_id, created_at)) = self.primary_conn()?.version_info(version)? {
             let id = SubgraphDeploymentId::new(deployment_id.clone())
@@ -653,11 +845,29 @@ impl SubgraphStore {
     pub(crate) async fn vacuum(&self) -> Vec<Result<(), StoreError>> {
         join_all(self.stores.values().map(|store| store.vacuum())).await
     }
+
+    pub(crate) async fn trim_mapping_logs(&self) -> Vec<Result<usize, StoreError>> {
+        join_all(self.stores.values().map(|store| store.trim_mapping_logs())).await
+    }
+
+    pub(crate) async fn refresh_replica_lag(&self) {
+        join_all(
+            self.stores
+                .values()
+                .map(|store| store.refresh_replica_lag()),
+        )
+        .await;
+    }
 }
 
 #[async_trait::async_trait]
 impl SubgraphStoreTrait for SubgraphStore {
     fn block_ptr(&self, id: &SubgraphDeploymentId) -> Result<Option<EthereumBlockPointer>, Error> {
+        if let Some(buffer) = self.buffered(id) {
+            if let Some(ptr) = buffer.lock().unwrap().block_ptr() {
+                return Ok(Some(ptr.clone()));
+            }
+        }
         let (store, site) = self.store(id)?;
         store.block_ptr(site.as_ref())
     }
@@ -681,6 +891,11 @@ impl SubgraphStoreTrait for SubgraphStore {
     }
 
     fn get(&self, key: EntityKey) -> Result<Option<Entity>, QueryExecutionError> {
+        if let Some(buffer) = self.buffered(&key.subgraph_id) {
+            if let Some(entity) = buffer.lock().unwrap().get(&key) {
+                return Ok(entity.clone());
+            }
+        }
         let (store, site) = self.store(&key.subgraph_id)?;
         store.get(site.as_ref(), key)
     }
@@ -688,18 +903,96 @@ impl SubgraphStoreTrait for SubgraphStore {
     fn get_many(
         &self,
         id: &SubgraphDeploymentId,
-        ids_for_type: BTreeMap<&EntityType, Vec<&str>>,
+        mut ids_for_type: BTreeMap<&EntityType, Vec<&str>>,
     ) -> Result<BTreeMap<EntityType, Vec<Entity>>, StoreError> {
+        // Answer what we can from the buffer, and only ask the database
+        // about the rest
+        let mut buffered: BTreeMap<EntityType, Vec<Entity>> = BTreeMap::new();
+        if let Some(buffer) = self.buffered(id) {
+            let buffer = buffer.lock().unwrap();
+            if !buffer.is_empty() {
+                for (entity_type, ids) in ids_for_type.iter_mut() {
+                    ids.retain(|entity_id| {
+                        let key = EntityKey {
+                            subgraph_id: id.clone(),
+                            entity_type: (*entity_type).clone(),
+                            entity_id: entity_id.to_string(),
+                        };
+                        match buffer.get(&key) {
+                            Some(entity) => {
+                                if let Some(entity) = entity {
+                                    buffered
+                                        .entry((*entity_type).clone())
+                                        .or_default()
+                                        .push(entity.clone());
+                                }
+                                false
+                            }
+                            None => true,
+                        }
+                    });
+                }
+                ids_for_type.retain(|_, ids| !ids.is_empty());
+            }
+        }
+
         let (store, site) = self.store(&id)?;
-        store.get_many(site.as_ref(), ids_for_type)
+        let mut entities = if ids_for_type.is_empty() {
+            BTreeMap::new()
+        } else {
+            store.get_many(site.as_ref(), ids_for_type)?
+        };
+        for (entity_type, mut found) in buffered {
+            entities.entry(entity_type).or_default().append(&mut found);
+        }
+        Ok(entities)
     }
 
+    fn get_at_block(
+        &self,
+        key: EntityKey,
+        block: BlockNumber,
+    ) -> Result<Option<Entity>, QueryExecutionError> {
+        if let Some(buffer) = self.buffered(&key.subgraph_id) {
+            if let Some(entity) = buffer.lock().unwrap().get_at_block(&key, block) {
+                return Ok(entity);
+            }
+        }
+        let (store, site) = self.store(&key.subgraph_id)?;
+        store.get_at_block(site.as_ref(), key, block)
+    }
+
+    fn get_derived(
+        &self,
+        id: &SubgraphDeploymentId,
+        query: &DerivedEntityQuery,
+    ) -> Result<Vec<Entity>, StoreError> {
+        let (store, site) = self.store(id)?;
+        match self.buffered(id) {
+            // Hold on to the lock while we query so that the buffer can not
+            // be written in the meantime
+            Some(buffer) => {
+                let buffer = buffer.lock().unwrap();
+                let entities = store.get_derived(site.as_ref(), query)?;
+                buffer.merge_derived(query, entities)
+            }
+            None => store.get_derived(site.as_ref(), query),
+        }
+    }
+
+    // Arbitrary queries can not be answered from the write buffer, and
+    // `find` and `find_one` therefore write buffered changes first. GraphQL
+    // queries go through the `QueryStore` and do not flush; they see the
+    // deployment as of the block pointer in the database, which lags the
+    // buffer by at most `GRAPH_STORE_WRITE_BATCH_DURATION`
     fn find(&self, query: EntityQuery) -> Result<Vec<Entity>, QueryExecutionError> {
+        self.flush(&query.subgraph_id)?;
         let (store, site) = self.store(&query.subgraph_id)?;
         store.find(site.as_ref(), query)
     }
 
     fn find_one(&self, query: EntityQuery) -> Result<Option<Entity>, QueryExecutionError> {
+        self.flush(&query.subgraph_id)?;
         let (store, site) = self.store(&query.subgraph_id)?;
         store.find_one(site.as_ref(), query)
     }
@@ -716,21 +1009,60 @@ impl SubgraphStoreTrait for SubgraphStore {
         stopwatch: StopwatchMetrics,
         data_sources: Vec<StoredDynamicDataSource>,
         deterministic_errors: Vec<SubgraphError>,
+        trigger_counts: HashMap<DynamicDataSourceKey, i64>,
     ) -> Result<(), StoreError> {
         assert!(
             same_subgraph(&mods, &id),
             "can only transact operations within one shard"
         );
-        let (store, site) = self.store(&id)?;
-        let event = store.transact_block_operations(
-            site.as_ref(),
-            block_ptr_to,
+        let changes = BlockChanges {
+            ptr: block_ptr_to,
             mods,
-            stopwatch,
             data_sources,
             deterministic_errors,
-        )?;
-        self.send_store_event(&event)
+            trigger_counts,
+        };
+        let buffer = self.write_buffer(&id);
+        let mut buffer = buffer.lock().unwrap();
+        buffer.push(changes, stopwatch);
+        self.flush_buffer(&id, &mut buffer)
+    }
+
+    fn buffer_block_operations(
+        &self,
+        id: SubgraphDeploymentId,
+        block_ptr_to: EthereumBlockPointer,
+        mods: Vec<EntityModification>,
+        stopwatch: StopwatchMetrics,
+        data_sources: Vec<StoredDynamicDataSource>,
+        deterministic_errors: Vec<SubgraphError>,
+        trigger_counts: HashMap<DynamicDataSourceKey, i64>,
+    ) -> Result<(), StoreError> {
+        assert!(
+            same_subgraph(&mods, &id),
+            "can only transact operations within one shard"
+        );
+        let changes = BlockChanges {
+            ptr: block_ptr_to,
+            mods,
+            data_sources,
+            deterministic_errors,
+            trigger_counts,
+        };
+        let buffer = self.write_buffer(&id);
+        let mut buffer = buffer.lock().unwrap();
+        buffer.push(changes, stopwatch);
+        if buffer.is_full() {
+            self.flush_buffer(&id, &mut buffer)?;
+        }
+        Ok(())
+    }
+
+    fn flush(&self, id: &SubgraphDeploymentId) -> Result<(), StoreError> {
+        match self.buffered(id) {
+            Some(buffer) => self.flush_buffer(id, &mut buffer.lock().unwrap()),
+            None => Ok(()),
+        }
     }
 
     fn revert_block_operations(
@@ -738,11 +1070,39 @@ impl SubgraphStoreTrait for SubgraphStore {
         id: SubgraphDeploymentId,
         block_ptr_to: EthereumBlockPointer,
     ) -> Result<(), StoreError> {
+        self.flush(&id)?;
         let (store, site) = self.store(&id)?;
         let event = store.revert_block_operations(site.as_ref(), block_ptr_to)?;
         self.send_store_event(&event)
     }
 
+    fn write_mapping_logs(
+        &self,
+        id: &SubgraphDeploymentId,
+        logs: Vec<MappingLog>,
+    ) -> Result<(), StoreError> {
+        let (store, site) = self.store(id)?;
+        store.write_mapping_logs(site.as_ref(), logs)
+    }
+
+    fn poi_events_block(
+        &self,
+        id: &SubgraphDeploymentId,
+    ) -> Result<Option<BlockNumber>, StoreError> {
+        let (store, site) = self.store(id)?;
+        store.poi_events_block(site.as_ref())
+    }
+
+    fn write_poi_events(
+        &self,
+        id: &SubgraphDeploymentId,
+        block: BlockNumber,
+        events: BTreeMap<String, Vec<String>>,
+    ) -> Result<(), StoreError> {
+        let (store, site) = self.store(id)?;
+        store.write_poi_events(site.as_ref(), block, events)
+    }
+
     async fn deployment_state_from_name(
         &self,
         name: SubgraphName,
@@ -766,6 +1126,13 @@ impl SubgraphStoreTrait for SubgraphStore {
         logger: &Logger,
         id: &SubgraphDeploymentId,
     ) -> Result<(), StoreError> {
+        // Anything still buffered was left behind by an earlier run of the
+        // subgraph; the database is authoritative, and the blocks will be
+        // processed again
+        if let Some(buffer) = self.buffered(id) {
+            buffer.lock().unwrap().take();
+        }
+
         let (store, site) = self.store(id)?;
 
         let graft_base = match store.graft_pending(id)? {
@@ -783,27 +1150,67 @@ impl SubgraphStoreTrait for SubgraphStore {
         store.unfail(site)
     }
 
+    fn rewind(
+        &self,
+        id: &SubgraphDeploymentId,
+        block_ptr_to: EthereumBlockPointer,
+    ) -> Result<(), StoreError> {
+        // Rewinding while the deployment is being indexed would race with
+        // the indexing
+        if let Some((node, false)) = self.primary_conn()?.assignment_status(id)? {
+            return Err(StoreError::Unknown(anyhow!(
+                "deployment {} is being indexed by node {}; pause it before rewinding it",
+                id,
+                node
+            )));
+        }
+
+        let message = format!("rewound to block {}", block_ptr_to);
+        self.flush(id)?;
+        let (store, site) = self.store(id)?;
+        let event = store.rewind(site.as_ref(), block_ptr_to)?;
+        self.send_store_event(&event)?;
+        self.record_health_event(id, HealthEventKind::Rewind, &message);
+        Ok(())
+    }
+
+    fn record_subgraph_restart(
+        &self,
+        id: &SubgraphDeploymentId,
+        restart: SubgraphRestart,
+    ) -> Result<(), StoreError> {
+        let (store, site) = self.store(id)?;
+        store.record_subgraph_restart(site.as_ref(), restart)
+    }
+
     fn is_deployment_synced(&self, id: &SubgraphDeploymentId) -> Result<bool, Error> {
         let (store, _) = self.store(&id)?;
         Ok(store.exists_and_synced(&id)?)
     }
 
     fn deployment_synced(&self, id: &SubgraphDeploymentId) -> Result<(), Error> {
-        let event = {
+        self.flush(id)?;
+
+        let (names, event) = {
             // Make sure we drop `pconn` before we call into the deployment
             // store so that we do not hold two database connections which
             // might come from the same pool and could therefore deadlock
             let pconn = self.primary_conn()?;
             pconn.transaction(|| -> Result<_, Error> {
-                let changes = pconn.promote_deployment(id)?;
-                Ok(StoreEvent::new(changes))
+                let (names, changes) = pconn.promote_deployment(id)?;
+                Ok((names, StoreEvent::new(changes)))
             })?
         };
 
         let (dstore, _) = self.store(id)?;
         dstore.deployment_synced(id)?;
+        self.primary_conn()?.send_store_event(&event)?;
 
-        Ok(self.primary_conn()?.send_store_event(&event)?)
+        for name in names {
+            let message = format!("current version of {}", name);
+            self.record_health_event(id, HealthEventKind::VersionSwitch, &message);
+        }
+        Ok(())
     }
 
     // FIXME: This method should not get a node_id
@@ -845,17 +1252,54 @@ impl SubgraphStoreTrait for SubgraphStore {
         id: &SubgraphDeploymentId,
         node_id: &NodeId,
     ) -> Result<(), StoreError> {
+        let changed = {
+            let pconn = self.primary_conn()?;
+            pconn.transaction(|| -> Result<_, StoreError> {
+                let changes = pconn.reassign_subgraph(id, node_id)?;
+                let changed = !changes.is_empty();
+                pconn.send_store_event(&StoreEvent::new(changes))?;
+                Ok(changed)
+            })?
+        };
+        if changed {
+            let message = format!("assigned to {}", node_id);
+            self.record_health_event(id, HealthEventKind::Reassignment, &message);
+        }
+        Ok(())
+    }
+
+    fn unassign_subgraph(&self, id: &SubgraphDeploymentId) -> Result<(), StoreError> {
+        let changed = {
+            let pconn = self.primary_conn()?;
+            pconn.transaction(|| -> Result<_, StoreError> {
+                let changes = pconn.unassign_subgraph(id)?;
+                let changed = !changes.is_empty();
+                pconn.send_store_event(&StoreEvent::new(changes))?;
+                Ok(changed)
+            })?
+        };
+        if changed {
+            self.record_health_event(id, HealthEventKind::Reassignment, "unassigned");
+        }
+        Ok(())
+    }
+
+    fn record_admin_action(&self, action: AdminAction) -> Result<(), StoreError> {
+        self.primary_conn()?.record_admin_action(action)
+    }
+
+    fn pause_subgraph(&self, id: &SubgraphDeploymentId) -> Result<(), StoreError> {
         let pconn = self.primary_conn()?;
         pconn.transaction(|| -> Result<_, StoreError> {
-            let changes = pconn.reassign_subgraph(id, node_id)?;
+            let changes = pconn.pause_subgraph(id)?;
             pconn.send_store_event(&StoreEvent::new(changes))
         })
     }
 
-    fn unassign_subgraph(&self, id: &SubgraphDeploymentId) -> Result<(), StoreError> {
+    fn resume_subgraph(&self, id: &SubgraphDeploymentId) -> Result<(), StoreError> {
         let pconn = self.primary_conn()?;
         pconn.transaction(|| -> Result<_, StoreError> {
-            let changes = pconn.unassign_subgraph(id)?;
+            let changes = pconn.resume_subgraph(id)?;
             pconn.send_store_event(&StoreEvent::new(changes))
         })
     }
@@ -873,6 +1317,12 @@ impl SubgraphStoreTrait for SubgraphStore {
         id: SubgraphDeploymentId,
         error: SubgraphError,
     ) -> Result<(), StoreError> {
+        // Keep the work for the blocks before the failure. If that is not
+        // possible, the failure should still be recorded
+        if let Err(e) = self.flush(&id) {
+            debug!(self.logger, "Failed to flush buffered changes: {}", e;
+                   "id" => id.to_string());
+        }
         let (store, _) = self.store(&id)?;
         store.fail_subgraph(id, error).await
     }
@@ -882,11 +1332,36 @@ impl SubgraphStoreTrait for SubgraphStore {
         primary.assigned_node(id)
     }
 
+    fn assignment_status(
+        &self,
+        id: &SubgraphDeploymentId,
+    ) -> Result<Option<(NodeId, bool)>, StoreError> {
+        let primary = self.primary_conn()?;
+        primary.assignment_status(id)
+    }
+
     fn assignments(&self, node: &NodeId) -> Result<Vec<SubgraphDeploymentId>, StoreError> {
         let primary = self.primary_conn()?;
         primary.assignments(node)
     }
 
+    fn set_instance_running(
+        &self,
+        id: &SubgraphDeploymentId,
+        node: &NodeId,
+        running: bool,
+    ) -> Result<(), StoreError> {
+        self.primary_conn()?.set_instance_running(id, node, running)
+    }
+
+    fn running_instance(&self, id: &SubgraphDeploymentId) -> Result<Option<NodeId>, StoreError> {
+        self.primary_conn()?.running_instance(id)
+    }
+
+    fn clear_running_instances(&self, node: &NodeId) -> Result<(), StoreError> {
+        self.primary_conn()?.clear_running_instances(node)
+    }
+
     fn subgraph_exists(&self, name: &SubgraphName) -> Result<bool, StoreError> {
         let primary = self.primary_conn()?;
         primary.subgraph_exists(name)
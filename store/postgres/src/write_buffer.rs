@@ -0,0 +1,496 @@
+//! Hold on to the changes for several blocks of a deployment in memory so
+//! that they can be written to the database in one transaction. This is
+//! only worthwhile while a deployment is far behind the chain head; the
+//! instance manager decides when changes may be buffered, and the buffer
+//! decides when it has accumulated enough to be written.
+use std::collections::{BTreeMap, HashMap};
+use std::env;
+use std::time::{Duration, Instant};
+
+use graph::components::store::{DynamicDataSourceKey, EntityType, StoredDynamicDataSource};
+use graph::data::subgraph::schema::SubgraphError;
+use graph::prelude::{
+    lazy_static, BlockNumber, CacheWeight, DerivedEntityQuery, Entity, EntityKey,
+    EntityModification, EthereumBlockPointer, StopwatchMetrics, StoreError,
+};
+
+lazy_static! {
+    /// The maximum number of blocks whose changes we buffer before
+    /// writing them. Set to 1 to disable buffering
+    static ref WRITE_BATCH_BLOCKS: usize = env::var("GRAPH_STORE_WRITE_BATCH_BLOCKS")
+        .ok()
+        .map(|s| {
+            s.parse::<usize>().unwrap_or_else(|_| {
+                panic!("GRAPH_STORE_WRITE_BATCH_BLOCKS must be a number, but is `{}`", s)
+            })
+        })
+        .unwrap_or(100);
+
+    /// The maximum size of buffered entity data, in bytes. The env var is
+    /// in KB
+    static ref WRITE_BATCH_SIZE: usize = 1000 * env::var("GRAPH_STORE_WRITE_BATCH_SIZE")
+        .ok()
+        .map(|s| {
+            s.parse::<usize>().unwrap_or_else(|_| {
+                panic!("GRAPH_STORE_WRITE_BATCH_SIZE must be a number, but is `{}`", s)
+            })
+        })
+        .unwrap_or(10_000);
+
+    /// How long we hold on to buffered changes at most, in seconds. This
+    /// bounds how much work is lost on a restart, and how stale the data
+    /// that queries see can get
+    static ref WRITE_BATCH_DURATION: Duration = Duration::from_secs(
+        env::var("GRAPH_STORE_WRITE_BATCH_DURATION")
+            .ok()
+            .map(|s| {
+                s.parse::<u64>().unwrap_or_else(|_| {
+                    panic!("GRAPH_STORE_WRITE_BATCH_DURATION must be a number, but is `{}`", s)
+                })
+            })
+            .unwrap_or(30)
+    );
+}
+
+/// The changes a single block made to a deployment
+pub(crate) struct BlockChanges {
+    pub ptr: EthereumBlockPointer,
+    pub mods: Vec<EntityModification>,
+    pub data_sources: Vec<StoredDynamicDataSource>,
+    pub deterministic_errors: Vec<SubgraphError>,
+    /// The number of triggers each dynamic data source handled in the block
+    pub trigger_counts: HashMap<DynamicDataSourceKey, i64>,
+}
+
+/// The changes for consecutive blocks of one deployment that have not
+/// been written to the database yet. Reads for the deployment must consult
+/// the buffer before going to the database so that mappings see their own
+/// writes
+#[derive(Default)]
+pub(crate) struct WriteBuffer {
+    blocks: Vec<BlockChanges>,
+    /// The latest version of each entity that was changed in `blocks`; a
+    /// value of `None` means the entity was removed
+    entities: HashMap<EntityKey, Option<Entity>>,
+    /// The approximate size of `entities` in bytes
+    weight: usize,
+    /// When the first of `blocks` was added
+    since: Option<Instant>,
+    stopwatch: Option<StopwatchMetrics>,
+}
+
+impl WriteBuffer {
+    pub fn is_empty(&self) -> bool {
+        self.blocks.is_empty()
+    }
+
+    /// The pointer to the last block in the buffer, if there is one
+    pub fn block_ptr(&self) -> Option<&EthereumBlockPointer> {
+        self.blocks.last().map(|block| &block.ptr)
+    }
+
+    /// Look up the entity for `key` in the buffer. Returns `None` if the
+    /// buffer does not know anything about the entity, and `Some(None)` if
+    /// the entity was removed in one of the buffered blocks
+    pub fn get(&self, key: &EntityKey) -> Option<&Option<Entity>> {
+        self.entities.get(key)
+    }
+
+    /// Look up the entity for `key` as of `block`. Returns `None` if none
+    /// of the buffered blocks up to and including `block` changed the
+    /// entity, in which case the database has the right version, and
+    /// `Some(None)` if the entity did not exist at `block`
+    pub fn get_at_block(&self, key: &EntityKey, block: BlockNumber) -> Option<Option<Entity>> {
+        use EntityModification::*;
+
+        if !self.entities.contains_key(key) {
+            return None;
+        }
+        self.blocks
+            .iter()
+            .rev()
+            .filter(|changes| changes.ptr.number <= block)
+            .find_map(|changes| changes.mods.iter().find(|md| md.entity_key() == key))
+            .map(|md| match md {
+                Insert { data, .. } | Overwrite { data, .. } => Some(data.clone()),
+                Remove { .. } => None,
+            })
+    }
+
+    /// Apply the buffered changes to `entities`, the result of running
+    /// `query` against the database, the same way that
+    /// `EntityCache::load_related` applies its updates. The result is
+    /// ordered by id, like the database's
+    pub fn merge_derived(
+        &self,
+        query: &DerivedEntityQuery,
+        entities: Vec<Entity>,
+    ) -> Result<Vec<Entity>, StoreError> {
+        let mut merged = BTreeMap::new();
+        for entity in entities {
+            merged.insert(entity.id()?, entity);
+        }
+        for (key, entity) in &self.entities {
+            if key.entity_type != query.entity_type {
+                continue;
+            }
+            match entity {
+                Some(entity) if query.matches(entity) => {
+                    merged.insert(key.entity_id.clone(), entity.clone());
+                }
+                _ => {
+                    merged.remove(&key.entity_id);
+                }
+            }
+        }
+        Ok(merged.into_iter().map(|(_, entity)| entity).collect())
+    }
+
+    pub fn push(&mut self, changes: BlockChanges, stopwatch: StopwatchMetrics) {
+        use EntityModification::*;
+
+        for md in &changes.mods {
+            let (key, entity) = match md {
+                Insert { key, data } | Overwrite { key, data } => (key, Some(data.clone())),
+                Remove { key } => (key, None),
+            };
+            self.weight += entity.weight();
+            if let Some(old) = self.entities.insert(key.clone(), entity) {
+                self.weight -= old.weight();
+            } else {
+                self.weight += key.weight();
+            }
+        }
+        self.blocks.push(changes);
+        self.since.get_or_insert_with(Instant::now);
+        self.stopwatch = Some(stopwatch);
+    }
+
+    /// Return `true` if the buffer has accumulated enough changes that it
+    /// should be written
+    pub fn is_full(&self) -> bool {
+        self.blocks.len() >= *WRITE_BATCH_BLOCKS
+            || self.weight >= *WRITE_BATCH_SIZE
+            || self
+                .since
+                .map(|since| since.elapsed() >= *WRITE_BATCH_DURATION)
+                .unwrap_or(false)
+    }
+
+    /// Empty the buffer and return the buffered blocks in the order in
+    /// which they were added, together with the stopwatch from the most
+    /// recent block
+    pub fn take(&mut self) -> (Vec<BlockChanges>, Option<StopwatchMetrics>) {
+        let blocks = std::mem::take(&mut self.blocks);
+        let stopwatch = self.stopwatch.take();
+        self.entities.clear();
+        self.weight = 0;
+        self.since = None;
+        (blocks, stopwatch)
+    }
+}
+
+/// A version of an entity that one of the buffered blocks created. The
+/// version is valid from block `start` up to, but not including, `end`
+#[derive(Debug, PartialEq)]
+pub(crate) struct EntityVersion {
+    pub key: EntityKey,
+    pub data: Entity,
+    pub start: BlockNumber,
+    pub end: Option<BlockNumber>,
+}
+
+/// The changes that several consecutive blocks make to the entities of
+/// one type, merged so that they can be written with a few statements for
+/// all blocks rather than a few statements per block
+#[derive(Debug, Default, PartialEq)]
+pub(crate) struct TableChanges {
+    /// The ids of entities whose version from before the first block needs
+    /// to be ended, grouped by the block at which it ends
+    pub clamps: BTreeMap<BlockNumber, Vec<String>>,
+    /// The new versions of entities, in the order in which the blocks
+    /// created them
+    pub versions: Vec<EntityVersion>,
+    /// By how much the number of entities changed
+    pub count: i32,
+}
+
+/// Merge the modifications `blocks` make into the changes for each entity
+/// type. `blocks` must be in block order, and each block must have at
+/// most one modification per entity
+pub(crate) fn merge_modifications(
+    blocks: Vec<(BlockNumber, Vec<EntityModification>)>,
+) -> BTreeMap<EntityType, TableChanges> {
+    use EntityModification::*;
+
+    let mut tables: BTreeMap<EntityType, TableChanges> = BTreeMap::new();
+    // For each entity that we have seen so far, the position of its current
+    // version in the `versions` of its table, or `None` if it was removed
+    let mut current: HashMap<EntityKey, Option<usize>> = HashMap::new();
+    for (block, mods) in blocks {
+        for md in mods {
+            let (key, data, existed, delta) = match md {
+                Insert { key, data } => (key, Some(data), false, 1),
+                Overwrite { key, data } => (key, Some(data), true, 0),
+                Remove { key } => (key, None, true, -1),
+            };
+            let table = tables.entry(key.entity_type.clone()).or_default();
+            match current.get(&key) {
+                Some(Some(pos)) => table.versions[*pos].end = Some(block),
+                Some(None) => { /* removed in an earlier block */ }
+                None if existed => table
+                    .clamps
+                    .entry(block)
+                    .or_default()
+                    .push(key.entity_id.clone()),
+                None => { /* a new entity */ }
+            }
+            let pos = data.map(|data| {
+                table.versions.push(EntityVersion {
+                    key: key.clone(),
+                    data,
+                    start: block,
+                    end: None,
+                });
+                table.versions.len() - 1
+            });
+            table.count += delta;
+            current.insert(key, pos);
+        }
+    }
+    tables
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use graph::prelude::{web3::types::H256, SubgraphDeploymentId, Value};
+
+    fn key(entity_type: &str, id: &str) -> EntityKey {
+        EntityKey::data(
+            SubgraphDeploymentId::new("merge").unwrap(),
+            entity_type.to_owned(),
+            id.to_owned(),
+        )
+    }
+
+    fn entity(name: &str) -> Entity {
+        let mut entity = Entity::new();
+        entity.set("name", Value::from(name));
+        entity
+    }
+
+    fn version(
+        id: &str,
+        name: &str,
+        start: BlockNumber,
+        end: Option<BlockNumber>,
+    ) -> EntityVersion {
+        EntityVersion {
+            key: key("User", id),
+            data: entity(name),
+            start,
+            end,
+        }
+    }
+
+    /// A buffer with the changes that `blocks` made. We do not go through
+    /// `push` since that needs a stopwatch
+    fn buffer(blocks: Vec<(BlockNumber, Vec<EntityModification>)>) -> WriteBuffer {
+        use EntityModification::*;
+
+        let mut buffer = WriteBuffer::default();
+        for (number, mods) in blocks {
+            for md in &mods {
+                let entity = match md {
+                    Insert { data, .. } | Overwrite { data, .. } => Some(data.clone()),
+                    Remove { .. } => None,
+                };
+                buffer.entities.insert(md.entity_key().clone(), entity);
+            }
+            buffer.blocks.push(BlockChanges {
+                ptr: EthereumBlockPointer::from((H256::from_low_u64_be(number as u64), number)),
+                mods,
+                data_sources: vec![],
+                deterministic_errors: vec![],
+                trigger_counts: HashMap::new(),
+            });
+        }
+        buffer
+    }
+
+    fn pet(id: &str, owner: &str) -> Entity {
+        let mut pet = Entity::new();
+        pet.set("id", id);
+        pet.set("owner", owner);
+        pet
+    }
+
+    #[test]
+    fn get_at_block() {
+        use EntityModification::*;
+
+        let buffer = buffer(vec![
+            (
+                3,
+                vec![Insert {
+                    key: key("User", "1"),
+                    data: entity("one"),
+                }],
+            ),
+            (
+                5,
+                vec![Overwrite {
+                    key: key("User", "1"),
+                    data: entity("uno"),
+                }],
+            ),
+            (
+                7,
+                vec![Remove {
+                    key: key("User", "1"),
+                }],
+            ),
+        ]);
+
+        let user = key("User", "1");
+        assert_eq!(None, buffer.get_at_block(&user, 2));
+        assert_eq!(Some(Some(entity("one"))), buffer.get_at_block(&user, 3));
+        assert_eq!(Some(Some(entity("one"))), buffer.get_at_block(&user, 4));
+        assert_eq!(Some(Some(entity("uno"))), buffer.get_at_block(&user, 6));
+        assert_eq!(Some(None), buffer.get_at_block(&user, 7));
+        assert_eq!(None, buffer.get_at_block(&key("User", "2"), 7));
+    }
+
+    #[test]
+    fn merge_derived() {
+        use EntityModification::*;
+
+        let buffer = buffer(vec![
+            (
+                1,
+                vec![
+                    // A new pet of `alice`
+                    Insert {
+                        key: key("Pet", "3"),
+                        data: pet("3", "alice"),
+                    },
+                    // Belongs to `bob` now
+                    Overwrite {
+                        key: key("Pet", "2"),
+                        data: pet("2", "bob"),
+                    },
+                    // A user, not a pet
+                    Insert {
+                        key: key("User", "0"),
+                        data: pet("0", "alice"),
+                    },
+                ],
+            ),
+            (
+                2,
+                vec![
+                    Remove {
+                        key: key("Pet", "1"),
+                    },
+                    Overwrite {
+                        key: key("Pet", "4"),
+                        data: pet("4", "alice"),
+                    },
+                ],
+            ),
+        ]);
+        let query = DerivedEntityQuery {
+            entity_type: EntityType::from("Pet"),
+            entity_field: "owner".to_owned(),
+            value: "alice".to_owned(),
+        };
+
+        // What the database has for the query
+        let stored = vec![pet("1", "alice"), pet("2", "alice"), pet("4", "alice")];
+        assert_eq!(
+            vec![pet("3", "alice"), pet("4", "alice")],
+            buffer.merge_derived(&query, stored).unwrap()
+        );
+    }
+
+    #[test]
+    fn merge_modifications() {
+        use EntityModification::*;
+
+        let blocks = vec![
+            (
+                1,
+                vec![
+                    Insert {
+                        key: key("User", "1"),
+                        data: entity("one"),
+                    },
+                    Overwrite {
+                        key: key("User", "2"),
+                        data: entity("two"),
+                    },
+                    Insert {
+                        key: key("Pet", "1"),
+                        data: entity("pet"),
+                    },
+                ],
+            ),
+            (
+                2,
+                vec![
+                    Overwrite {
+                        key: key("User", "1"),
+                        data: entity("uno"),
+                    },
+                    Remove {
+                        key: key("User", "3"),
+                    },
+                ],
+            ),
+            (
+                4,
+                vec![
+                    Remove {
+                        key: key("User", "1"),
+                    },
+                    Remove {
+                        key: key("User", "2"),
+                    },
+                    Insert {
+                        key: key("User", "3"),
+                        data: entity("three"),
+                    },
+                ],
+            ),
+        ];
+
+        let mut tables = super::merge_modifications(blocks);
+        assert_eq!(2, tables.len());
+
+        let pets = tables.remove(&EntityType::from("Pet")).unwrap();
+        assert!(pets.clamps.is_empty());
+        assert_eq!(1, pets.count);
+        assert_eq!(1, pets.versions.len());
+
+        // Only the versions of `2` and `3` from before the first block get
+        // clamped; everything else is written with its final block range
+        let users = tables.remove(&EntityType::from("User")).unwrap();
+        let mut clamps = BTreeMap::new();
+        clamps.insert(1, vec!["2".to_owned()]);
+        clamps.insert(2, vec!["3".to_owned()]);
+        assert_eq!(
+            TableChanges {
+                clamps,
+                versions: vec![
+                    version("1", "one", 1, Some(2)),
+                    version("2", "two", 1, Some(4)),
+                    version("1", "uno", 2, Some(4)),
+                    version("3", "three", 4, None),
+                ],
+                count: -1,
+            },
+            users
+        );
+    }
+}
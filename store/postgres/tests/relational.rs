@@ -2,9 +2,9 @@
 use diesel::connection::SimpleConnection as _;
 use diesel::pg::PgConnection;
 use graph::prelude::{
-    o, slog, web3::types::H256, Entity, EntityCollection, EntityFilter, EntityKey, EntityOrder,
-    EntityQuery, EntityRange, Logger, Schema, StopwatchMetrics, SubgraphDeploymentId, Value,
-    ValueType, BLOCK_NUMBER_MAX,
+    o, slog, web3::types::H256, BlockNumber, DerivedEntityQuery, Entity, EntityCollection,
+    EntityFilter, EntityKey, EntityOrder, EntityQuery, EntityRange, Logger, Schema,
+    StopwatchMetrics, SubgraphDeploymentId, Value, ValueType, BLOCK_NUMBER_MAX,
 };
 use graph_mock::MockMetricsRegistry;
 use hex_literal::hex;
@@ -490,6 +490,157 @@ fn find() {
     });
 }
 
+#[test]
+fn find_derived() {
+    fn thing(id: &str, big_thing: &str) -> Entity {
+        let mut thing = Entity::new();
+        thing.set("id", id);
+        thing.set("bigThing", big_thing);
+        thing
+    }
+
+    run_test(|conn, layout| {
+        insert_entity(
+            &conn,
+            &layout,
+            "Thing",
+            vec![
+                thing("b", "root"),
+                thing("a", "root"),
+                thing("c", "other"),
+                thing("root", "root"),
+            ],
+        );
+
+        let query = DerivedEntityQuery {
+            entity_type: EntityType::from("Thing"),
+            entity_field: "bigThing".to_owned(),
+            value: "root".to_owned(),
+        };
+        let ids: Vec<_> = layout
+            .find_derived(conn, &query, BLOCK_NUMBER_MAX)
+            .expect("Failed to find derived Things")
+            .into_iter()
+            .map(|entity| entity.id().unwrap())
+            .collect();
+        assert_eq!(vec!["a", "b", "root"], ids);
+
+        // Nothing references `a`
+        let query = DerivedEntityQuery {
+            value: "a".to_owned(),
+            ..query
+        };
+        let entities = layout
+            .find_derived(conn, &query, BLOCK_NUMBER_MAX)
+            .expect("Failed to find derived Things");
+        assert!(entities.is_empty());
+
+        // Only references can be used
+        let query = DerivedEntityQuery {
+            entity_type: SCALAR.clone(),
+            entity_field: "string".to_owned(),
+            value: "one".to_owned(),
+        };
+        assert!(layout.find_derived(conn, &query, BLOCK_NUMBER_MAX).is_err());
+    });
+}
+
+#[test]
+fn entity_changes_in_block() {
+    fn thing(id: &str, big_thing: &str) -> Entity {
+        let mut thing = Entity::new();
+        thing.set("id", id);
+        thing.set("bigThing", big_thing);
+        thing
+    }
+
+    fn key(id: &str) -> EntityKey {
+        EntityKey::data(
+            THINGS_SUBGRAPH_ID.clone(),
+            "Thing".to_owned(),
+            id.to_owned(),
+        )
+    }
+
+    // The id and the `bigThing` before and after the block of each change
+    fn changes(
+        conn: &PgConnection,
+        layout: &Layout,
+        block: BlockNumber,
+    ) -> Vec<(String, Option<Value>, Option<Value>)> {
+        let big_thing = |entity: Option<Entity>| entity.map(|entity| entity["bigThing"].clone());
+        layout
+            .entity_changes_in_block(conn, block)
+            .expect("Failed to find entity changes")
+            .into_iter()
+            .map(|change| {
+                assert_eq!("Thing", change.entity_type.as_str());
+                (
+                    change.entity_id,
+                    big_thing(change.before),
+                    big_thing(change.after),
+                )
+            })
+            .collect()
+    }
+
+    run_test(|conn, layout| {
+        let thing_type = EntityType::from("Thing");
+        insert_entity(
+            &conn,
+            &layout,
+            "Thing",
+            vec![thing("a", "root"), thing("b", "root")],
+        );
+
+        layout
+            .update(
+                &conn,
+                thing_type.clone(),
+                vec![(key("a"), thing("a", "b"))],
+                1,
+                &MOCK_STOPWATCH,
+            )
+            .expect("Failed to update");
+        layout
+            .delete(
+                &conn,
+                &thing_type,
+                &vec!["b".to_owned()],
+                1,
+                &MOCK_STOPWATCH,
+            )
+            .expect("Failed to delete");
+        layout
+            .insert(
+                &conn,
+                &thing_type,
+                &mut vec![(key("c"), thing("c", "a"))],
+                1,
+                &MOCK_STOPWATCH,
+            )
+            .expect("Failed to insert");
+
+        let s = |s: &str| Some(Value::from(s));
+        assert_eq!(
+            vec![
+                ("a".to_owned(), None, s("root")),
+                ("b".to_owned(), None, s("root")),
+            ],
+            changes(conn, layout, 0)
+        );
+        assert_eq!(
+            vec![
+                ("a".to_owned(), s("root"), s("b")),
+                ("b".to_owned(), s("root"), None),
+                ("c".to_owned(), None, s("a")),
+            ],
+            changes(conn, layout, 1)
+        );
+        assert!(changes(conn, layout, 2).is_empty());
+    });
+}
+
 #[test]
 fn insert_null_fulltext_fields() {
     run_test(|conn, layout| {
@@ -544,6 +695,44 @@ fn update() {
     });
 }
 
+#[test]
+fn version_counts() {
+    run_test(|conn, layout| {
+        insert_entity(&conn, &layout, "Scalar", vec![SCALAR_ENTITY.clone()]);
+
+        let mut entity = SCALAR_ENTITY.clone();
+        entity.set("string", "updated");
+        let key = EntityKey::data(
+            THINGS_SUBGRAPH_ID.clone(),
+            "Scalar".to_owned(),
+            entity.id().unwrap().clone(),
+        );
+        layout
+            .update(
+                &conn,
+                EntityType::from("Scalar"),
+                vec![(key, entity)],
+                1,
+                &MOCK_STOPWATCH,
+            )
+            .expect("Failed to update");
+
+        let counts = layout
+            .version_counts(conn)
+            .expect("Failed to count versions");
+        let scalar = counts
+            .iter()
+            .find(|count| count.entity == *SCALAR)
+            .expect("Scalar has a table");
+        assert_eq!((2, 1), (scalar.versions, scalar.current));
+        let user = counts
+            .iter()
+            .find(|count| count.entity.as_str() == "User")
+            .expect("User has a table");
+        assert_eq!((0, 0), (user.versions, user.current));
+    });
+}
+
 #[test]
 fn update_many() {
     run_test(|conn, layout| {
@@ -3,12 +3,15 @@ use hex_literal::hex;
 use lazy_static::lazy_static;
 use std::str::FromStr;
 use std::time::Duration;
-use std::{collections::HashSet, sync::Mutex};
+use std::{
+    collections::{HashMap, HashSet},
+    sync::Mutex,
+};
 use test_store::*;
 
 use graph::components::store::{
-    BlockStore as _, EntityFilter, EntityKey, EntityOrder, EntityQuery, EntityType, StatusStore,
-    SubscriptionManager as _,
+    BlockStore as _, DynamicDataSourceKey, EntityFilter, EntityKey, EntityOrder, EntityQuery,
+    EntityType, StatusStore, SubscriptionManager as _,
 };
 use graph::data::store::scalar;
 use graph::data::subgraph::schema::*;
@@ -418,6 +421,139 @@ fn insert_entity() {
     })
 }
 
+#[test]
+fn buffered_block_operations() {
+    fn name_at_block(store: &Arc<DieselStore>, id: &str, block: BlockNumber) -> Option<Value> {
+        let mut query = user_query().filter(EntityFilter::Equal("id".to_owned(), id.into()));
+        query.block = block;
+        let entities = store.subgraph_store().find(query).unwrap();
+        entities
+            .first()
+            .and_then(|entity| entity.get("name").cloned())
+    }
+
+    run_test(|store| async move {
+        let subgraph_store = store.subgraph_store();
+        let key7 = EntityKey::data(TEST_SUBGRAPH_ID.clone(), USER.to_owned(), "7".to_owned());
+        let key3 = EntityKey::data(TEST_SUBGRAPH_ID.clone(), USER.to_owned(), "3".to_owned());
+        let stopwatch_metrics = StopwatchMetrics::new(
+            Logger::root(slog::Discard, o!()),
+            TEST_SUBGRAPH_ID.clone(),
+            Arc::new(MockMetricsRegistry::new()),
+        );
+        let modification = |op: EntityOperation, insert: bool| match op {
+            EntityOperation::Set { key, data } if insert => {
+                EntityModification::Insert { key, data }
+            }
+            EntityOperation::Set { key, data } => EntityModification::Overwrite { key, data },
+            EntityOperation::Remove { .. } => unreachable!(),
+        };
+        let count = get_entity_count(store.clone(), &TEST_SUBGRAPH_ID);
+
+        let wanjon = create_test_entity(
+            "7",
+            USER,
+            "Wanjon",
+            "wanjon@email.com",
+            76,
+            111.7,
+            true,
+            None,
+        );
+        subgraph_store
+            .buffer_block_operations(
+                TEST_SUBGRAPH_ID.clone(),
+                TEST_BLOCK_3_PTR.clone(),
+                vec![modification(wanjon, true)],
+                stopwatch_metrics.clone(),
+                Vec::new(),
+                Vec::new(),
+                HashMap::new(),
+            )
+            .unwrap();
+
+        let tonka =
+            create_test_entity("7", USER, "Tonka", "tonka@email.com", 77, 111.7, true, None);
+        subgraph_store
+            .buffer_block_operations(
+                TEST_SUBGRAPH_ID.clone(),
+                TEST_BLOCK_4_PTR.clone(),
+                vec![
+                    modification(tonka, false),
+                    EntityModification::Remove { key: key3.clone() },
+                ],
+                stopwatch_metrics,
+                Vec::new(),
+                Vec::new(),
+                HashMap::new(),
+            )
+            .unwrap();
+
+        // Buffered changes are visible to `get` and move the block pointer,
+        // but have not been written yet
+        assert_eq!(
+            Some(TEST_BLOCK_4_PTR.clone()),
+            subgraph_store.block_ptr(&TEST_SUBGRAPH_ID).unwrap()
+        );
+        let entity = subgraph_store.get(key7.clone()).unwrap().unwrap();
+        assert_eq!(Some(&Value::from("Tonka")), entity.get("name"));
+        assert_eq!(None, subgraph_store.get(key3.clone()).unwrap());
+
+        // The changes in a block are read from the database, and the
+        // buffer is written first
+        let changes = store
+            .entity_changes_in_block(&TEST_SUBGRAPH_ID, TEST_BLOCK_4_PTR.number)
+            .unwrap();
+        let changes: Vec<_> = changes
+            .iter()
+            .map(|change| {
+                let name = |entity: &Option<Entity>| {
+                    entity.as_ref().map(|entity| entity.get("name").cloned())
+                };
+                (
+                    change.entity_id.as_str(),
+                    name(&change.before),
+                    name(&change.after),
+                )
+            })
+            .collect();
+        assert_eq!(
+            vec![
+                ("3", Some(Some(Value::from("Shaqueeena"))), None),
+                (
+                    "7",
+                    Some(Some(Value::from("Wanjon"))),
+                    Some(Some(Value::from("Tonka")))
+                ),
+            ],
+            changes
+        );
+
+        assert_eq!(
+            Some(Value::from("Tonka")),
+            name_at_block(&store, "7", BLOCK_NUMBER_MAX)
+        );
+
+        // Each block was written with its own block number
+        assert_eq!(
+            Some(Value::from("Wanjon")),
+            name_at_block(&store, "7", TEST_BLOCK_3_PTR.number)
+        );
+        assert_eq!(
+            Some(Value::from("Tonka")),
+            name_at_block(&store, "7", TEST_BLOCK_4_PTR.number)
+        );
+        assert!(name_at_block(&store, "3", TEST_BLOCK_3_PTR.number).is_some());
+        assert_eq!(None, name_at_block(&store, "3", TEST_BLOCK_4_PTR.number));
+        assert_eq!(None, subgraph_store.get(key3).unwrap());
+        assert_eq!(
+            Some(TEST_BLOCK_4_PTR.clone()),
+            subgraph_store.block_ptr(&TEST_SUBGRAPH_ID).unwrap()
+        );
+        assert_eq!(count, get_entity_count(store.clone(), &TEST_SUBGRAPH_ID));
+    })
+}
+
 #[test]
 fn update_existing() {
     run_test(|store| async move {
@@ -1111,6 +1247,101 @@ fn revert_block_with_delete() {
     })
 }
 
+#[test]
+fn rewind() {
+    run_test(|store| async move {
+        let subgraph_store = store.subgraph_store();
+        let email = |id: &str| {
+            let key = EntityKey::data(TEST_SUBGRAPH_ID.clone(), USER.to_owned(), id.to_owned());
+            subgraph_store
+                .get(key)
+                .unwrap()
+                .map(|user| user.get("email").unwrap().clone())
+        };
+
+        // Delete the user with id 2 in block 3
+        let del_key = EntityKey::data(TEST_SUBGRAPH_ID.clone(), USER.to_owned(), "2".to_owned());
+        transact_entity_operations(
+            &subgraph_store,
+            TEST_SUBGRAPH_ID.clone(),
+            TEST_BLOCK_3_PTR.clone(),
+            vec![EntityOperation::Remove { key: del_key }],
+        )
+        .unwrap();
+        assert_eq!(2, get_entity_count(store.clone(), &TEST_SUBGRAPH_ID));
+
+        // The deployment is being indexed and can't be rewound
+        assert!(subgraph_store
+            .rewind(&TEST_SUBGRAPH_ID, TEST_BLOCK_1_PTR.clone())
+            .is_err());
+
+        // Rewinding a paused deployment reverts blocks 2 and 3
+        subgraph_store.pause_subgraph(&TEST_SUBGRAPH_ID).unwrap();
+        subgraph_store
+            .rewind(&TEST_SUBGRAPH_ID, TEST_BLOCK_1_PTR.clone())
+            .unwrap();
+        assert_eq!(
+            Some(TEST_BLOCK_1_PTR.clone()),
+            subgraph_store.block_ptr(&TEST_SUBGRAPH_ID).unwrap()
+        );
+        assert_eq!(3, get_entity_count(store.clone(), &TEST_SUBGRAPH_ID));
+        assert_eq!(
+            Some(Value::String("dinici@email.com".to_owned())),
+            email("2")
+        );
+        assert_eq!(
+            Some(Value::String("queensha@email.com".to_owned())),
+            email("3")
+        );
+
+        let history = subgraph_store.health_history(&TEST_SUBGRAPH_ID, 1).unwrap();
+        assert_eq!(
+            vec![HealthEventKind::Rewind],
+            history.iter().map(|event| event.kind).collect::<Vec<_>>()
+        );
+
+        // Rewinding can only go backwards
+        assert!(subgraph_store
+            .rewind(&TEST_SUBGRAPH_ID, TEST_BLOCK_2_PTR.clone())
+            .is_err());
+    })
+}
+
+#[test]
+fn running_instances() {
+    run_test(|store| async move {
+        let subgraph_store = store.subgraph_store();
+        let node1 = NodeId::new("node_1").unwrap();
+        let node2 = NodeId::new("node_2").unwrap();
+        let running = || subgraph_store.running_instance(&TEST_SUBGRAPH_ID).unwrap();
+
+        assert_eq!(None, running());
+        subgraph_store
+            .set_instance_running(&TEST_SUBGRAPH_ID, &node1, true)
+            .unwrap();
+        assert_eq!(Some(node1.clone()), running());
+
+        // Only the node that runs the instance can remove it
+        subgraph_store
+            .set_instance_running(&TEST_SUBGRAPH_ID, &node2, false)
+            .unwrap();
+        assert_eq!(Some(node1.clone()), running());
+        subgraph_store
+            .set_instance_running(&TEST_SUBGRAPH_ID, &node1, false)
+            .unwrap();
+        assert_eq!(None, running());
+
+        // A node that starts up removes all its instances
+        subgraph_store
+            .set_instance_running(&TEST_SUBGRAPH_ID, &node2, true)
+            .unwrap();
+        subgraph_store.clear_running_instances(&node1).unwrap();
+        assert_eq!(Some(node2.clone()), running());
+        subgraph_store.clear_running_instances(&node2).unwrap();
+        assert_eq!(None, running());
+    })
+}
+
 #[test]
 fn revert_block_with_partial_update() {
     run_test(|store| async move {
@@ -1288,6 +1519,66 @@ fn revert_block_with_dynamic_data_source_operations() {
     })
 }
 
+#[test]
+fn dynamic_data_source_trigger_counts() {
+    run_test(|store| async move {
+        let subgraph_store = store.subgraph_store();
+        let data_source = mock_data_source();
+
+        transact_entities_and_dynamic_data_sources(
+            &subgraph_store,
+            TEST_SUBGRAPH_ID.clone(),
+            TEST_BLOCK_3_PTR.clone(),
+            vec![&data_source],
+            vec![],
+        )
+        .unwrap();
+
+        let key = DynamicDataSourceKey {
+            name: data_source.name.clone(),
+            address: data_source.source.address.unwrap(),
+            creation_block: TEST_BLOCK_3_PTR.number as BlockNumber,
+        };
+        let stopwatch_metrics = StopwatchMetrics::new(
+            Logger::root(slog::Discard, o!()),
+            TEST_SUBGRAPH_ID.clone(),
+            Arc::new(MockMetricsRegistry::new()),
+        );
+        for (block_ptr, count) in vec![(&*TEST_BLOCK_4_PTR, 3), (&*TEST_BLOCK_5_PTR, 4)] {
+            let counts = HashMap::from_iter(vec![(key.clone(), count)]);
+            subgraph_store
+                .transact_block_operations(
+                    TEST_SUBGRAPH_ID.clone(),
+                    block_ptr.clone(),
+                    vec![],
+                    stopwatch_metrics.clone(),
+                    vec![],
+                    vec![],
+                    counts,
+                )
+                .unwrap();
+        }
+
+        let data_sources = store.dynamic_data_sources(&TEST_SUBGRAPH_ID).unwrap();
+        assert_eq!(1, data_sources.len());
+        let ds = &data_sources[0];
+        assert_eq!(data_source.name, ds.template);
+        assert_eq!(data_source.source.address, ds.address);
+        assert_eq!(
+            Some(TEST_BLOCK_3_PTR.number as BlockNumber),
+            ds.creation_block
+        );
+        assert_eq!(7, ds.trigger_count);
+
+        // Reverting a block also reverts the triggers handled in it
+        subgraph_store
+            .revert_block_operations(TEST_SUBGRAPH_ID.clone(), TEST_BLOCK_4_PTR.clone())
+            .unwrap();
+        let data_sources = store.dynamic_data_sources(&TEST_SUBGRAPH_ID).unwrap();
+        assert_eq!(3, data_sources[0].trigger_count);
+    })
+}
+
 #[test]
 fn entity_changes_are_fired_and_forwarded_to_subscriptions() {
     run_test(|store| async move {
@@ -1578,6 +1869,7 @@ fn handle_large_string_with_index() {
                 stopwatch_metrics,
                 Vec::new(),
                 Vec::new(),
+                HashMap::new(),
             )
             .expect("Failed to insert large text");
 
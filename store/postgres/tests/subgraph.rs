@@ -1,7 +1,11 @@
 use graph::{
     components::store::StatusStore,
+    data::subgraph::schema::HealthEventKind,
     data::subgraph::schema::SubgraphError,
     data::subgraph::schema::SubgraphHealth,
+    data::subgraph::schema::SubgraphRestart,
+    data::subgraph::schema::{MappingLog, MappingLogLevel},
+    prelude::BlockNumber,
     prelude::EntityChange,
     prelude::EntityChangeOperation,
     prelude::QueryStoreManager,
@@ -17,6 +21,7 @@ use graph_store_postgres::layout_for_tests::Connection as Primary;
 use graph_store_postgres::SubgraphStore;
 
 use std::collections::HashSet;
+use std::time::Duration;
 use test_store::*;
 
 const SUBGRAPH_GQL: &str = "
@@ -85,6 +90,50 @@ fn reassign_subgraph() {
     })
 }
 
+#[test]
+fn pause_and_resume_subgraph() {
+    fn setup() -> SubgraphDeploymentId {
+        let id = SubgraphDeploymentId::new("pauseSubgraph").unwrap();
+        remove_subgraphs();
+        create_test_subgraph(&id, SUBGRAPH_GQL);
+        id
+    }
+
+    run_test_sequentially(setup, |store, id| async move {
+        let store = store.subgraph_store();
+        let node = NodeId::new("left").unwrap();
+        store.reassign_subgraph(&id, &node).unwrap();
+        assert!(store.assignments(&node).unwrap().contains(&id));
+
+        // Pausing keeps the assignment, but the deployment is no longer
+        // among the ones the node should index. Pausing twice only
+        // sends one event
+        let expected = vec![StoreEvent::new(vec![assigned(&id)])];
+        let events = tap_store_events(|| {
+            store.pause_subgraph(&id).unwrap();
+            store.pause_subgraph(&id).unwrap();
+        });
+        assert_eq!(expected, events);
+        assert_eq!(
+            Some((node.clone(), true)),
+            store.assignment_status(&id).unwrap()
+        );
+        assert!(!store.assignments(&node).unwrap().contains(&id));
+
+        let events = tap_store_events(|| store.resume_subgraph(&id).unwrap());
+        assert_eq!(expected, events);
+        assert_eq!(
+            Some((node.clone(), false)),
+            store.assignment_status(&id).unwrap()
+        );
+        assert!(store.assignments(&node).unwrap().contains(&id));
+
+        // Deployments that are not assigned can not be paused
+        store.unassign_subgraph(&id).unwrap();
+        assert!(store.pause_subgraph(&id).is_err());
+    })
+}
+
 #[test]
 fn create_subgraph() {
     const SUBGRAPH_NAME: &str = "create/subgraph";
@@ -596,3 +645,202 @@ fn fail_unfail() {
         test_store::remove_subgraphs();
     })
 }
+
+#[test]
+fn subgraph_restarts() {
+    fn setup() -> SubgraphDeploymentId {
+        let id = SubgraphDeploymentId::new("subgraphRestarts").unwrap();
+        remove_subgraphs();
+        create_test_subgraph(&id, SUBGRAPH_GQL);
+        id
+    }
+
+    fn restart(attempt: i32, delay: Option<u64>) -> SubgraphRestart {
+        SubgraphRestart {
+            attempt,
+            message: format!("failure {}", attempt),
+            block_ptr: Some(BLOCKS[1].clone()),
+            delay: delay.map(Duration::from_secs),
+        }
+    }
+
+    run_test_sequentially(setup, |store, id| async move {
+        use graph::data::subgraph::status;
+
+        let retry_state = || {
+            let infos = store
+                .status(status::Filter::Deployments(vec![id.to_string()]))
+                .unwrap();
+            infos.into_iter().next().unwrap().retry
+        };
+
+        assert!(retry_state().is_none());
+
+        let subgraph_store = store.subgraph_store();
+        subgraph_store
+            .record_subgraph_restart(&id, restart(1, Some(30)))
+            .unwrap();
+        subgraph_store
+            .record_subgraph_restart(&id, restart(2, None))
+            .unwrap();
+
+        // Only the latest restart is reported
+        let state = retry_state().unwrap();
+        assert_eq!(2, state.attempt);
+        assert_eq!("failure 2", state.message);
+        assert!(state.block.is_some());
+        assert!(state.next_attempt_at.is_none());
+
+        // Once the subgraph makes progress, the restarts are forgotten
+        subgraph_store.unfail(&id).unwrap();
+        assert!(retry_state().is_none());
+
+        test_store::remove_subgraphs();
+    })
+}
+
+#[test]
+fn health_history() {
+    fn setup() -> SubgraphDeploymentId {
+        let id = SubgraphDeploymentId::new("healthHistory").unwrap();
+        remove_subgraphs();
+        create_test_subgraph(&id, SUBGRAPH_GQL);
+        id
+    }
+
+    run_test_sequentially(setup, |store, id| async move {
+        let store = store.subgraph_store();
+        let history = |first| -> Vec<(HealthEventKind, Option<SubgraphHealth>)> {
+            store
+                .health_history(&id, first)
+                .unwrap()
+                .into_iter()
+                .map(|event| (event.kind, event.health))
+                .collect()
+        };
+
+        let error = SubgraphError {
+            subgraph_id: id.clone(),
+            message: "test".to_string(),
+            block_ptr: Some(BLOCKS[1].clone()),
+            handler: None,
+            deterministic: false,
+        };
+        store.fail_subgraph(id.clone(), error).await.unwrap();
+        store.unfail(&id).unwrap();
+        store
+            .reassign_subgraph(&id, &NodeId::new("left").unwrap())
+            .unwrap();
+
+        use HealthEventKind::*;
+        assert_eq!(
+            vec![
+                (HealthChange, Some(SubgraphHealth::Failed)),
+                (Unfail, Some(SubgraphHealth::Healthy)),
+                (Reassignment, None)
+            ],
+            history(10)
+        );
+
+        // Only the latest events are returned, from oldest to newest
+        assert_eq!(
+            vec![
+                (Unfail, Some(SubgraphHealth::Healthy)),
+                (Reassignment, None)
+            ],
+            history(2)
+        );
+
+        let failure = store.health_history(&id, 10).unwrap().remove(0);
+        assert_eq!(Some("test"), failure.message.as_deref());
+        assert_eq!(Some(BLOCKS[1].number), failure.block_number);
+
+        test_store::remove_subgraphs();
+    })
+}
+
+#[test]
+fn mapping_logs() {
+    fn setup() -> SubgraphDeploymentId {
+        let id = SubgraphDeploymentId::new("mappingLogs").unwrap();
+        remove_subgraphs();
+        create_test_subgraph(&id, SUBGRAPH_GQL);
+        id
+    }
+
+    fn log(block_number: BlockNumber, level: MappingLogLevel, message: &str) -> MappingLog {
+        MappingLog {
+            block_number,
+            data_source: "Contract".to_string(),
+            handler: Some("handleTransfer".to_string()),
+            level,
+            message: message.to_string(),
+        }
+    }
+
+    fn messages(logs: Vec<MappingLog>) -> Vec<String> {
+        logs.into_iter().map(|log| log.message).collect()
+    }
+
+    run_test_sequentially(setup, |store, id| async move {
+        let subgraph_store = store.subgraph_store();
+
+        transact_entity_operations(&subgraph_store, id.clone(), BLOCKS[1].clone(), vec![]).unwrap();
+        subgraph_store
+            .write_mapping_logs(
+                &id,
+                vec![
+                    log(1, MappingLogLevel::Info, "one"),
+                    log(1, MappingLogLevel::Error, "two"),
+                ],
+            )
+            .unwrap();
+        transact_entity_operations(&subgraph_store, id.clone(), BLOCKS[2].clone(), vec![]).unwrap();
+        subgraph_store
+            .write_mapping_logs(&id, vec![log(2, MappingLogLevel::Debug, "three")])
+            .unwrap();
+
+        let logs = store
+            .mapping_logs(&id, MappingLogLevel::Debug, None, None)
+            .unwrap();
+        assert_eq!(vec!["one", "two", "three"], messages(logs));
+
+        let logs = store
+            .mapping_logs(&id, MappingLogLevel::Warning, None, None)
+            .unwrap();
+        assert_eq!(vec![log(1, MappingLogLevel::Error, "two")], logs);
+
+        let logs = store
+            .mapping_logs(&id, MappingLogLevel::Debug, Some(2), Some(2))
+            .unwrap();
+        assert_eq!(vec!["three"], messages(logs));
+
+        // Processing block 2 again replaces its messages
+        subgraph_store
+            .write_mapping_logs(&id, vec![log(2, MappingLogLevel::Debug, "four")])
+            .unwrap();
+        let logs = store
+            .mapping_logs(&id, MappingLogLevel::Debug, None, None)
+            .unwrap();
+        assert_eq!(vec!["one", "two", "four"], messages(logs));
+
+        // Reverting block 2 removes its messages
+        subgraph_store
+            .revert_block_operations(id.clone(), BLOCKS[1].clone())
+            .unwrap();
+        let logs = store
+            .mapping_logs(&id, MappingLogLevel::Debug, None, None)
+            .unwrap();
+        assert_eq!(vec!["one", "two"], messages(logs));
+
+        // Trimming keeps the newest messages
+        assert_eq!(0, test_store::trim_mapping_logs(2));
+        assert_eq!(1, test_store::trim_mapping_logs(1));
+        let logs = store
+            .mapping_logs(&id, MappingLogLevel::Debug, None, None)
+            .unwrap();
+        assert_eq!(vec!["two"], messages(logs));
+
+        test_store::remove_subgraphs();
+    })
+}
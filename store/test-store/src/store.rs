@@ -23,7 +23,10 @@ use hex_literal::hex;
 use lazy_static::lazy_static;
 use std::sync::Mutex;
 use std::time::Instant;
-use std::{collections::BTreeSet, env};
+use std::{
+    collections::{BTreeSet, HashMap},
+    env,
+};
 use tokio::runtime::{Builder, Runtime};
 use web3::types::H256;
 
@@ -222,6 +225,7 @@ pub fn transact_errors(
         stopwatch_metrics,
         Vec::new(),
         errs,
+        HashMap::new(),
     )
 }
 
@@ -265,6 +269,7 @@ pub fn transact_entities_and_dynamic_data_sources(
         stopwatch_metrics,
         data_sources,
         Vec::new(),
+        HashMap::new(),
     )
 }
 
@@ -282,6 +287,13 @@ pub fn insert_ens_name(hash: &str, name: &str) {
         .unwrap();
 }
 
+/// Delete mapping logs so that each deployment has at most `limit` of
+/// them; returns the number of deleted messages
+pub fn trim_mapping_logs(limit: i64) -> usize {
+    let conn = PRIMARY_POOL.get().unwrap();
+    graph_store_postgres::layout_for_tests::trim_mapping_logs(&conn, limit).unwrap()
+}
+
 pub fn insert_entities(
     subgraph_id: SubgraphDeploymentId,
     entities: Vec<(EntityType, Entity)>,